@@ -0,0 +1,467 @@
+//! `--anonymize`: a copy of proprietary code that can be shared for outside help.
+//!
+//! Names the code declares (after `fn`, `class`, `def`, `let`, `type`, ...) are replaced
+//! by neutral ones (`Name3`, `name_4`, `NAME_5`), the same in every file and in comments,
+//! so the code still reads consistently. Configured terms, such as company and product
+//! names, are removed: identifiers containing one are renamed too, comments mentioning
+//! one are dropped, and the term is redacted from string literals and paths. Strings
+//! are otherwise left as they are.
+//!
+//! Like `--minify`, this works on the lexer's view of the code (see `minify::spans`),
+//! not a parse tree, so declarations it doesn't recognize keep their names. Keywords and
+//! built-in types of the exported languages are never renamed.
+
+use crate::language_from_path;
+use crate::minify::{spans, Span};
+use crate::types::FileEntry;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// What redacted terms are replaced with.
+const REDACTED: &str = "REDACTED";
+
+/// Words after which an identifier is being declared, across the supported languages.
+const DECLARATION_KEYWORDS: &[&str] = &[
+    "fn",
+    "struct",
+    "enum",
+    "trait",
+    "type",
+    "union",
+    "mod",
+    "const",
+    "static",
+    "let",
+    "mut",
+    "class",
+    "interface",
+    "record",
+    "def",
+    "function",
+    "func",
+    "fun",
+    "var",
+    "val",
+    "object",
+    "module",
+    "namespace",
+];
+
+/// Built-in types in C-family languages, after which a variable or function is declared:
+/// `static int counter`, `const char *name`, `void bump(void)`.
+const C_TYPES: &[&str] = &[
+    "int", "char", "void", "short", "long", "float", "double", "signed", "unsigned", "bool",
+    "size_t", "auto", "String",
+];
+
+/// Languages where `C_TYPES` declare names.
+const C_LIKE: &[&str] = &["c", "cpp", "objectivec", "java", "csharp"];
+
+/// Keywords and built-in types and functions, by language. Renames apply to every file,
+/// so the names of every language in the export are kept everywhere.
+const BUILTIN_NAMES: &[(&[&str], &[&str])] = &[
+    (
+        &["rust"],
+        &[
+            "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+            "f32", "f64", "bool", "char", "str", "String", "Vec", "Option", "Some", "None",
+            "Result", "Ok", "Err", "Box", "Rc", "Arc", "Cell", "RefCell", "HashMap", "HashSet",
+            "BTreeMap", "BTreeSet", "crate", "impl", "pub", "use", "where", "dyn", "ref", "move",
+            "async", "await", "unsafe", "extern", "return", "match", "loop", "while", "for",
+            "else", "true", "false",
+        ],
+    ),
+    (
+        &["c", "cpp", "objectivec"],
+        &[
+            "int",
+            "char",
+            "void",
+            "short",
+            "long",
+            "float",
+            "double",
+            "signed",
+            "unsigned",
+            "bool",
+            "size_t",
+            "ssize_t",
+            "int8_t",
+            "int16_t",
+            "int32_t",
+            "int64_t",
+            "uint8_t",
+            "uint16_t",
+            "uint32_t",
+            "uint64_t",
+            "FILE",
+            "NULL",
+            "auto",
+            "register",
+            "volatile",
+            "extern",
+            "inline",
+            "typedef",
+            "sizeof",
+            "return",
+            "switch",
+            "case",
+            "break",
+            "continue",
+            "while",
+            "for",
+            "else",
+            "goto",
+            "std",
+            "string",
+            "vector",
+            "nullptr",
+            "template",
+            "typename",
+            "public",
+            "private",
+            "protected",
+            "virtual",
+            "using",
+            "delete",
+            "true",
+            "false",
+        ],
+    ),
+    (
+        &["java", "csharp", "kotlin", "scala"],
+        &[
+            "int",
+            "long",
+            "short",
+            "byte",
+            "char",
+            "boolean",
+            "float",
+            "double",
+            "void",
+            "String",
+            "Object",
+            "Integer",
+            "Long",
+            "Boolean",
+            "List",
+            "Map",
+            "Set",
+            "Unit",
+            "Any",
+            "Int",
+            "public",
+            "private",
+            "protected",
+            "final",
+            "abstract",
+            "override",
+            "return",
+            "null",
+            "true",
+            "false",
+            "string",
+            "bool",
+            "var",
+            "void",
+        ],
+    ),
+    (
+        &["python"],
+        &[
+            "None", "True", "False", "cls", "int", "str", "float", "bool", "list", "dict", "set",
+            "tuple", "bytes", "object", "print", "len", "range", "return", "import", "pass",
+            "lambda", "yield", "async", "await", "with", "elif", "while", "for", "else", "not",
+            "and",
+        ],
+    ),
+    (
+        &["javascript", "jsx", "typescript", "tsx", "vue", "svelte"],
+        &[
+            "return",
+            "extends",
+            "import",
+            "export",
+            "async",
+            "await",
+            "null",
+            "undefined",
+            "true",
+            "false",
+            "string",
+            "number",
+            "boolean",
+            "any",
+            "void",
+            "never",
+            "unknown",
+            "Array",
+            "Object",
+            "String",
+            "Number",
+            "Boolean",
+            "Promise",
+            "Map",
+            "Set",
+            "Error",
+            "console",
+            "typeof",
+            "instanceof",
+        ],
+    ),
+    (
+        &["go"],
+        &[
+            "int", "int8", "int16", "int32", "int64", "uint", "uint8", "uint16", "uint32",
+            "uint64", "float32", "float64", "string", "bool", "byte", "rune", "error", "any",
+            "nil", "true", "false", "map", "chan", "return", "package", "import", "defer", "make",
+            "len", "cap", "append",
+        ],
+    ),
+];
+
+/// Declared names that are kept because the language or a framework gives them meaning.
+const KEPT_NAMES: &[&str] = &[
+    "main",
+    "new",
+    "default",
+    "init",
+    "self",
+    "Self",
+    "this",
+    "super",
+    "constructor",
+    "toString",
+    "fmt",
+    "from",
+    "into",
+    "drop",
+    "clone",
+    "hash",
+    "next",
+    "len",
+    "test",
+];
+
+pub struct Anonymizer {
+    /// Lowercase terms to redact
+    terms: Vec<String>,
+    renames: HashMap<String, String>,
+}
+
+impl Anonymizer {
+    /// Pick the new names for everything `files` declare or that contains a term.
+    pub fn new(files: &[FileEntry], terms: &[String]) -> Self {
+        let mut anonymizer = Anonymizer {
+            terms: terms
+                .iter()
+                .map(|t| t.trim().to_ascii_lowercase())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            renames: HashMap::new(),
+        };
+        let languages: HashSet<&str> = files
+            .iter()
+            .map(|file| language_from_path(Path::new(&file.rel_path)))
+            .collect();
+        let builtins: HashSet<&str> = BUILTIN_NAMES
+            .iter()
+            .filter(|(langs, _)| langs.iter().any(|lang| languages.contains(lang)))
+            .flat_map(|(_, names)| names.iter().copied())
+            .collect();
+        for file in files {
+            let lang = language_from_path(Path::new(&file.rel_path));
+            let typed = C_LIKE.contains(&lang);
+            let Some(spans) = spans(&file.content, lang) else {
+                for (_, word) in word_offsets(&file.content) {
+                    if anonymizer.has_term(word) {
+                        anonymizer.rename(word);
+                    }
+                }
+                continue;
+            };
+            // Comments with a term are dropped and strings only redacted, so only code counts.
+            for (_, text) in spans.into_iter().filter(|(kind, _)| *kind == Span::Code) {
+                let mut previous = "";
+                for (word, gap) in words_with_gaps(text) {
+                    // `fn name`, or `int name`/`char *name` in C-family code, on one line.
+                    let after_keyword =
+                        DECLARATION_KEYWORDS.contains(&previous) && gap.trim().is_empty();
+                    let after_type = typed
+                        && C_TYPES.contains(&previous)
+                        && gap.chars().all(|c| matches!(c, ' ' | '\t' | '*' | '&'));
+                    let declared = (after_keyword || after_type)
+                        && !gap.contains('\n')
+                        && !DECLARATION_KEYWORDS.contains(&word)
+                        && !KEPT_NAMES.contains(&word)
+                        && !builtins.contains(word)
+                        && word.len() > 2
+                        && !(word.starts_with("__") && word.ends_with("__"));
+                    if declared || anonymizer.has_term(word) {
+                        anonymizer.rename(word);
+                    }
+                    previous = word;
+                }
+            }
+        }
+        anonymizer
+    }
+
+    fn rename(&mut self, name: &str) {
+        if self.renames.contains_key(name) {
+            return;
+        }
+        let n = self.renames.len() + 1;
+        let bare = name.trim_start_matches('_');
+        let underscores = &name[..name.len() - bare.len()];
+        let alias = if bare.chars().all(|c| !c.is_ascii_lowercase()) && bare.len() > 1 {
+            format!("NAME_{}", n)
+        } else if bare.starts_with(|c: char| c.is_ascii_uppercase()) {
+            format!("Name{}", n)
+        } else {
+            format!("name_{}", n)
+        };
+        self.renames
+            .insert(name.to_string(), format!("{}{}", underscores, alias));
+    }
+
+    fn has_term(&self, text: &str) -> bool {
+        let text = text.to_ascii_lowercase();
+        self.terms.iter().any(|term| text.contains(term.as_str()))
+    }
+
+    /// `text` with every term replaced by `REDACTED`, ignoring ASCII case.
+    pub fn redact(&self, text: &str) -> String {
+        let mut out = text.to_string();
+        for term in &self.terms {
+            let mut redacted = String::with_capacity(out.len());
+            let mut rest = out.as_str();
+            while let Some(at) = rest.to_ascii_lowercase().find(term.as_str()) {
+                redacted.push_str(&rest[..at]);
+                redacted.push_str(REDACTED);
+                rest = &rest[at + term.len()..];
+            }
+            redacted.push_str(rest);
+            out = redacted;
+        }
+        out
+    }
+
+    /// A relative path or input name with its terms redacted.
+    pub fn path(&self, path: &str) -> String {
+        self.redact(path)
+    }
+
+    /// Prose such as a note or comment: renamed identifiers, then redacted terms.
+    pub fn text(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for (start, word) in word_offsets(text) {
+            if let Some(alias) = self.renames.get(word) {
+                out.push_str(&text[last..start]);
+                out.push_str(alias);
+                last = start + word.len();
+            }
+        }
+        out.push_str(&text[last..]);
+        self.redact(&out)
+    }
+
+    /// A file's content in language `lang`, anonymized.
+    pub fn content(&self, content: &str, lang: &str) -> String {
+        let Some(spans) = spans(content, lang) else {
+            return self.text(content);
+        };
+        let mut out = String::with_capacity(content.len());
+        for (kind, text) in spans {
+            match kind {
+                Span::Code => out.push_str(&self.text(text)),
+                Span::Str => out.push_str(&self.redact(text)),
+                // Keep the comment's line breaks so line numbers still match.
+                Span::Comment if self.has_term(text) => {
+                    out.extend(text.chars().filter(|&c| c == '\n'));
+                }
+                Span::Comment => out.push_str(&self.text(text)),
+            }
+        }
+        out
+    }
+}
+
+/// Start offset and text of each identifier-like word in `text`. Runs starting with a
+/// digit (`0x1f`, `2nd`) are numbers, not words.
+fn word_offsets(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut rest = 0;
+    std::iter::from_fn(move || loop {
+        let start = rest + text[rest..].find(is_word)?;
+        let end = text[start..]
+            .find(|c: char| !is_word(c))
+            .map_or(text.len(), |len| start + len);
+        rest = end;
+        if !text[start..].starts_with(|c: char| c.is_ascii_digit()) {
+            return Some((start, &text[start..end]));
+        }
+    })
+}
+
+/// Each word with the text between it and the word before.
+fn words_with_gaps(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut previous_end = 0;
+    word_offsets(text).map(move |(start, word)| {
+        let gap = &text[previous_end..start];
+        previous_end = start + word.len();
+        (word, gap)
+    })
+}
+
+#[test]
+fn test_anonymize() {
+    let files = vec![
+        FileEntry {
+            rel_path: "acme/billing.rs".to_string(),
+            content: "// Acme Corp internal\nstruct Invoice { total: u64 }\n\n/// Bills an Invoice.\nfn charge_card(inv: &Invoice) -> AcmeResult {\n    let mut amount = inv.total;\n    send(\"acme-gw\", amount)\n}\nfn main() {}\n".to_string(),
+            ..Default::default()
+        },
+        FileEntry {
+            rel_path: "app.py".to_string(),
+            content: "from billing import charge_card\n\nclass Checkout:\n    def __init__(self):\n        charge_card(None)  # see ACME docs\n".to_string(),
+            ..Default::default()
+        },
+    ];
+    let anon = Anonymizer::new(&files, &["ACME".to_string()]);
+    assert_eq!(anon.path("acme/billing.rs"), "REDACTED/billing.rs");
+    assert_eq!(
+        anon.content(&files[0].content, "rust"),
+        "\nstruct Name1 { total: u64 }\n\n/// Bills an Name1.\nfn name_2(inv: &Name1) -> Name3 {\n    let mut name_4 = inv.total;\n    send(\"REDACTED-gw\", name_4)\n}\nfn main() {}\n"
+    );
+    assert_eq!(
+        anon.content(&files[1].content, "python"),
+        "from billing import name_2\n\nclass Name5:\n    def __init__(self):\n        name_2(None)  \n"
+    );
+
+    let c = "static int counter = 0;\nconst char *name = \"acme\";\nstatic void bump(void) { counter++; }\nint main(void) { bump(); }\n";
+    let files = vec![FileEntry {
+        rel_path: "count.c".to_string(),
+        content: c.to_string(),
+        ..Default::default()
+    }];
+    let anon = Anonymizer::new(&files, &["acme".to_string()]);
+    assert_eq!(
+        anon.content(c, "c"),
+        "static int name_1 = 0;\nconst char *name_2 = \"REDACTED\";\nstatic void name_3(void) { name_1++; }\nint main(void) { name_3(); }\n"
+    );
+
+    let rust = "fn write_all(out: &mut Vec<u8>, s: &mut String) {}\nstatic LIMIT: usize = 4;\n";
+    let files = vec![FileEntry {
+        rel_path: "io.rs".to_string(),
+        content: rust.to_string(),
+        ..Default::default()
+    }];
+    let anon = Anonymizer::new(&files, &[]);
+    assert_eq!(
+        anon.content(rust, "rust"),
+        "fn name_1(out: &mut Vec<u8>, s: &mut String) {}\nstatic NAME_2: usize = 4;\n"
+    );
+}
@@ -0,0 +1,440 @@
+use crate::error::R2mdError;
+use crate::lfs;
+use crate::notebook;
+use crate::patterns::{normalize_rel_path, PathFilter};
+use crate::sniff::bytes_to_text;
+use crate::types::{CollectOptions, CollectReport, FileEntry, LfsAsset};
+use crate::{
+    has_collectable_name, is_script_candidate, keep_included_lines, BINARY_FILE_EXTENSIONS,
+    DEFAULT_MAX_FILE_SIZE,
+};
+use flate2::read::GzDecoder;
+use rayon::prelude::*;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use tracing::debug;
+
+/// Archive formats accepted as local inputs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+/// Detect a supported archive from its file name.
+pub fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+/// Collect files from a local `.zip`, `.tar`, `.tar.gz` or `.tgz` snapshot.
+pub fn collect_files_from_archive_file(
+    path: &Path,
+    opts: &CollectOptions,
+    report: &mut CollectReport,
+) -> Result<Vec<FileEntry>, Box<dyn Error>> {
+    let kind = archive_kind(path).ok_or_else(|| format!("Not an archive: {}", path.display()))?;
+    debug!("Reading {:?} archive {}", kind, path.display());
+    match kind {
+        ArchiveKind::Zip => collect_files_from_zip(path, opts, report),
+        ArchiveKind::Tar => {
+            collect_files_from_tar(|| Ok(BufReader::new(File::open(path)?)), opts, report)
+        }
+        ArchiveKind::TarGz => collect_files_from_tar(
+            || Ok(GzDecoder::new(BufReader::new(File::open(path)?))),
+            opts,
+            report,
+        ),
+    }
+}
+
+/// What one ZIP entry turned into, so workers can run without touching the shared report.
+enum ZipOutcome {
+    Dir,
+    Skipped,
+    Kept(FileEntry),
+    Lfs(LfsAsset),
+    Failed(R2mdError),
+}
+
+/// Collect files from a ZIP archive on disk, e.g. a downloaded GitHub snapshot. Only the
+/// entries being decoded are held in memory, however big the archive is.
+///
+/// Entries are decompressed and decoded in parallel: each rayon worker opens its own
+/// handle on the file.
+pub fn collect_files_from_zip(
+    path: &Path,
+    opts: &CollectOptions,
+    report: &mut CollectReport,
+) -> Result<Vec<FileEntry>, Box<dyn Error>> {
+    let open = || -> zip::result::ZipResult<zip::ZipArchive<BufReader<File>>> {
+        zip::ZipArchive::new(BufReader::new(File::open(path)?))
+    };
+    let archive = open()?;
+    let top_level = common_top_level(archive.file_names());
+    let filter = PathFilter::new(None, &opts.includes, &opts.excludes)?;
+
+    let outcomes: Vec<ZipOutcome> = (0..archive.len())
+        .into_par_iter()
+        .map_init(
+            || open().map_err(|e| e.to_string()),
+            |handle, i| match handle {
+                Ok(handle) => read_zip_entry(handle, i, top_level.as_deref(), &filter, opts),
+                Err(e) => ZipOutcome::Failed(R2mdError::Unreadable {
+                    path: archive_rel_path(
+                        archive.name_for_index(i).unwrap_or_default(),
+                        top_level.as_deref(),
+                    ),
+                    source: io::Error::other(e.clone()),
+                }),
+            },
+        )
+        .collect();
+
+    let mut file_entries = Vec::new();
+    for outcome in outcomes {
+        match outcome {
+            ZipOutcome::Dir => {}
+            ZipOutcome::Skipped => report.skipped += 1,
+            ZipOutcome::Kept(_) if at_file_limit(file_entries.len(), opts, report) => break,
+            ZipOutcome::Kept(entry) => file_entries.push(entry),
+            ZipOutcome::Lfs(asset) => report.lfs_assets.push(asset),
+            ZipOutcome::Failed(e) => report.record(e),
+        }
+    }
+    keep_included_lines(&mut file_entries, &filter, opts);
+    Ok(file_entries)
+}
+
+fn read_zip_entry(
+    archive: &mut zip::ZipArchive<BufReader<File>>,
+    index: usize,
+    top_level: Option<&str>,
+    filter: &PathFilter,
+    opts: &CollectOptions,
+) -> ZipOutcome {
+    let name = archive
+        .name_for_index(index)
+        .unwrap_or_default()
+        .to_string();
+    let mut file = match archive.by_index(index) {
+        Ok(file) => file,
+        Err(e) => {
+            return ZipOutcome::Failed(R2mdError::Unreadable {
+                path: archive_rel_path(&name, top_level),
+                source: e.into(),
+            });
+        }
+    };
+    if file.is_dir() {
+        return ZipOutcome::Dir;
+    }
+    let rel_path = archive_rel_path(&name, top_level);
+    // An extensionless name is only kept for a shebang, so look at the first bytes.
+    let mut bytes = Vec::new();
+    if is_script_candidate(Path::new(&rel_path)) {
+        let _ = (&mut file).take(2).read_to_end(&mut bytes);
+    }
+    match keep_archive_entry(&rel_path, file.size(), &bytes, filter, opts) {
+        Ok(true) => {}
+        Ok(false) if file.size() <= lfs::MAX_POINTER_SIZE && !opts.only_included => {
+            let _ = file.read_to_end(&mut bytes);
+            return match lfs::pointer_from_bytes(&rel_path, &bytes, opts) {
+                Some(asset) => ZipOutcome::Lfs(asset),
+                None => ZipOutcome::Skipped,
+            };
+        }
+        Ok(false) => return ZipOutcome::Skipped,
+        Err(e) => return ZipOutcome::Failed(e),
+    }
+
+    bytes.reserve(file.size() as usize);
+    if let Err(source) = file.read_to_end(&mut bytes) {
+        debug!(path = %rel_path, reason = "unreadable", error = %source, "Skipping file");
+        return ZipOutcome::Failed(R2mdError::Unreadable {
+            path: rel_path,
+            source,
+        });
+    }
+    match bytes_to_text(bytes, opts.strict_utf8)
+        .and_then(|text| notebook::convert(&rel_path, text, opts.fence_style))
+    {
+        Ok(content) => match lfs::parse_pointer(&rel_path, &content) {
+            Some(asset) => ZipOutcome::Lfs(asset),
+            None => ZipOutcome::Kept(FileEntry {
+                rel_path,
+                content,
+                ..Default::default()
+            }),
+        },
+        Err(reason) => {
+            debug!(path = %rel_path, reason = "undecodable", error = %reason, "Skipping file");
+            ZipOutcome::Failed(R2mdError::Decode {
+                path: rel_path,
+                reason,
+            })
+        }
+    }
+}
+
+/// Collect files from a TAR stream, opened by `open` (decompressing it if need be).
+///
+/// A stream can't be searched, so it is read twice: once for the names, to find the
+/// shared top-level folder, then for the content of the entries that pass the filters.
+/// Only kept files are held in memory.
+pub fn collect_files_from_tar<R: Read>(
+    open: impl Fn() -> io::Result<R>,
+    opts: &CollectOptions,
+    report: &mut CollectReport,
+) -> Result<Vec<FileEntry>, Box<dyn Error>> {
+    let mut names = Vec::new();
+    for entry in tar::Archive::new(open()?).entries()? {
+        let entry = entry?;
+        if entry.header().entry_type().is_file() {
+            names.push(entry.path()?.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    let top_level = common_top_level(names.iter().map(String::as_str));
+    let filter = PathFilter::new(None, &opts.includes, &opts.excludes)?;
+
+    let mut file_entries = Vec::new();
+    let mut archive = tar::Archive::new(open()?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        if at_file_limit(file_entries.len(), opts, report) {
+            break;
+        }
+        let name = entry.path()?.to_string_lossy().replace('\\', "/");
+        let rel_path = archive_rel_path(&name, top_level.as_deref());
+        let size = entry.size();
+        // An extensionless name is only kept for a shebang, so look at the first bytes.
+        let mut bytes = Vec::new();
+        if is_script_candidate(Path::new(&rel_path)) {
+            (&mut entry).take(2).read_to_end(&mut bytes)?;
+        }
+        match keep_archive_entry(&rel_path, size, &bytes, &filter, opts) {
+            Ok(true) => {}
+            Ok(false) => {
+                if size <= lfs::MAX_POINTER_SIZE && !opts.only_included {
+                    entry.read_to_end(&mut bytes)?;
+                    if let Some(asset) = lfs::pointer_from_bytes(&rel_path, &bytes, opts) {
+                        report.lfs_assets.push(asset);
+                        continue;
+                    }
+                }
+                report.skipped += 1;
+                continue;
+            }
+            Err(e) => {
+                report.record(e);
+                continue;
+            }
+        }
+        bytes.reserve(size as usize);
+        entry.read_to_end(&mut bytes)?;
+        match bytes_to_text(bytes, opts.strict_utf8)
+            .and_then(|text| notebook::convert(&rel_path, text, opts.fence_style))
+        {
+            Ok(content) => match lfs::parse_pointer(&rel_path, &content) {
+                Some(asset) => report.lfs_assets.push(asset),
+                None => file_entries.push(FileEntry {
+                    rel_path,
+                    content,
+                    ..Default::default()
+                }),
+            },
+            Err(reason) => {
+                debug!(path = %rel_path, reason = "undecodable", error = %reason, "Skipping file");
+                report.record(R2mdError::Decode {
+                    path: rel_path,
+                    reason,
+                });
+            }
+        }
+    }
+    keep_included_lines(&mut file_entries, &filter, opts);
+    Ok(file_entries)
+}
+
+/// The single top-level folder shared by every entry (as in GitHub/`git archive` snapshots), if any.
+fn common_top_level<'a>(mut names: impl Iterator<Item = &'a str>) -> Option<String> {
+    let first = names.next()?;
+    let (top, _) = first.trim_start_matches("./").split_once('/')?;
+    let prefix = format!("{}/", top);
+    names
+        .all(|n| n.trim_start_matches("./").starts_with(&prefix))
+        .then(|| top.to_string())
+}
+
+fn archive_rel_path(name: &str, top_level: Option<&str>) -> String {
+    let name = normalize_rel_path(name);
+    match top_level {
+        Some(top) => name
+            .strip_prefix(top)
+            .map(|rest| rest.trim_start_matches('/').to_string())
+            .unwrap_or(name),
+        None => name,
+    }
+}
+
+/// Whether `kept` files already reach `--max-files`, noting it in the report if so.
+fn at_file_limit(kept: usize, opts: &CollectOptions, report: &mut CollectReport) -> bool {
+    let hit = opts.max_files.is_some_and(|max| kept >= max);
+    report.file_limit_hit |= hit;
+    hit
+}
+
+/// Same depth/include/exclude/extension/ignore/size checks the directory walker applies;
+/// `head` is the start of the entry's content, for the shebang check. Oversized entries
+/// are an error so they show up in the report.
+fn keep_archive_entry(
+    rel_path: &str,
+    size: u64,
+    head: &[u8],
+    filter: &PathFilter,
+    opts: &CollectOptions,
+) -> Result<bool, R2mdError> {
+    if opts
+        .max_depth
+        .is_some_and(|max| rel_path.split('/').count() > max)
+    {
+        debug!(path = %rel_path, reason = "too_deep", "Skipping file");
+        return Ok(false);
+    }
+    if filter.is_included(rel_path) {
+        // Bypass all checks for included files
+        return Ok(true);
+    }
+    if opts.only_included {
+        debug!(path = %rel_path, reason = "not_included", "Skipping file (see --only)");
+        return Ok(false);
+    }
+    if filter.is_excluded(rel_path, false) {
+        debug!(path = %rel_path, reason = "excluded", "Skipping file");
+        return Ok(false);
+    }
+
+    let ext = Path::new(rel_path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !has_collectable_name(Path::new(rel_path), opts, || head.starts_with(b"#!")) {
+        if BINARY_FILE_EXTENSIONS.contains(&ext.as_str()) {
+            debug!(path = %rel_path, reason = "binary", "Skipping file");
+            return Ok(false);
+        }
+        debug!(path = %rel_path, reason = "extension", "Skipping file");
+        return Ok(false);
+    }
+
+    if opts.user_ignores.matches(rel_path, false) {
+        debug!(path = %rel_path, reason = "ignored", "Skipping file");
+        return Ok(false);
+    }
+
+    if opts.kind_filter.skips_path(rel_path) {
+        debug!(path = %rel_path, reason = "test_or_generated", "Skipping file");
+        return Ok(false);
+    }
+
+    if size > DEFAULT_MAX_FILE_SIZE && opts.truncate.is_none() {
+        debug!(path = %rel_path, reason = "too_large", size, "Skipping file");
+        return Err(R2mdError::TooLarge {
+            path: rel_path.to_string(),
+            size,
+            limit: DEFAULT_MAX_FILE_SIZE,
+        });
+    }
+    Ok(true)
+}
+
+#[test]
+fn test_tar_gz_round_trip() -> Result<(), Box<dyn Error>> {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, body) in [
+        ("proj-1.0/src/main.rs", "fn main() {}"),
+        ("proj-1.0/src/gen/out.rs", "fn generated() {}"),
+        ("proj-1.0/logo.png", "not really a png"),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(body.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, body.as_bytes())?;
+    }
+    let bytes = builder.into_inner()?.finish()?;
+
+    let opts = CollectOptions {
+        excludes: vec!["gen/".into()],
+        ..Default::default()
+    };
+    let mut report = CollectReport::default();
+    let files = collect_files_from_tar(|| Ok(GzDecoder::new(&bytes[..])), &opts, &mut report)?;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].rel_path, "src/main.rs");
+    assert_eq!(files[0].content, "fn main() {}");
+    assert_eq!(report.skipped, 2);
+
+    let opts = CollectOptions {
+        max_files: Some(1),
+        ..Default::default()
+    };
+    let mut report = CollectReport::default();
+    let files = collect_files_from_tar(|| Ok(GzDecoder::new(&bytes[..])), &opts, &mut report)?;
+    assert_eq!(files.len(), 1);
+    assert!(report.file_limit_hit);
+    Ok(())
+}
+
+#[test]
+fn test_zip_file_round_trip() -> Result<(), Box<dyn Error>> {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let archive = tempfile::NamedTempFile::new()?;
+    let mut zip = zip::ZipWriter::new(archive.reopen()?);
+    for i in 0..50 {
+        zip.start_file(
+            format!("repo-main/src/m{:02}.rs", i),
+            SimpleFileOptions::default(),
+        )?;
+        write!(zip, "fn f{}() {{}}", i)?;
+    }
+    zip.start_file("repo-main/logo.png", SimpleFileOptions::default())?;
+    zip.write_all(b"not really a png")?;
+    zip.start_file("repo-main/LICENSE", SimpleFileOptions::default())?;
+    zip.write_all(b"MIT License")?;
+    zip.start_file("repo-main/bin/run", SimpleFileOptions::default())?;
+    zip.write_all(b"#!/bin/sh\nexec cargo run\n")?;
+    zip.finish()?;
+
+    let mut report = CollectReport::default();
+    let mut files =
+        collect_files_from_zip(archive.path(), &CollectOptions::default(), &mut report)?;
+    files.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    assert_eq!(files.len(), 51);
+    assert_eq!(files[0].rel_path, "bin/run");
+    assert_eq!(files[0].content, "#!/bin/sh\nexec cargo run\n");
+    assert_eq!(files[8].rel_path, "src/m07.rs");
+    assert_eq!(files[8].content, "fn f7() {}");
+    assert_eq!(report.skipped, 2);
+    Ok(())
+}
@@ -0,0 +1,73 @@
+//! `--max-output-bytes` / `--max-output-tokens`: a cap on the size of the whole export.
+//!
+//! Files are admitted in order of usefulness rather than path order, so a tight budget
+//! still yields a coherent document: files other files import come first (they are what
+//! the rest is built on), then smaller files before larger ones. A file that doesn't fit
+//! is skipped and the next one tried, so the budget is filled as far as it goes.
+
+use crate::deps::import_edges;
+use crate::types::FileEntry;
+use std::cmp::Reverse;
+
+/// What `--max-output-*` counts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputLimit {
+    /// Bytes of file contents and paths
+    Bytes(usize),
+    /// Estimated tokens of file contents
+    Tokens(usize),
+}
+
+impl OutputLimit {
+    pub fn max(&self) -> usize {
+        match self {
+            OutputLimit::Bytes(n) | OutputLimit::Tokens(n) => *n,
+        }
+    }
+}
+
+/// Which of `files` to keep. `sizes[i]` is what `files[i]` costs in the unit of the limit,
+/// as it will be written (after minifying and trimming).
+pub fn fit_output(files: &[&FileEntry], sizes: &[usize], limit: OutputLimit) -> Vec<bool> {
+    let mut importers = vec![0; files.len()];
+    for (_, imported) in import_edges(files) {
+        importers[imported] += 1;
+    }
+    let mut ranked: Vec<usize> = (0..files.len()).collect();
+    ranked.sort_by_key(|&i| (Reverse(importers[i]), sizes[i]));
+
+    let mut keep = vec![false; files.len()];
+    let mut left = limit.max();
+    for i in ranked {
+        if sizes[i] <= left {
+            left -= sizes[i];
+            keep[i] = true;
+        }
+    }
+    keep
+}
+
+#[test]
+fn test_fit_output_prefers_imported_then_small() {
+    let file = |rel_path: &str, content: &str| FileEntry {
+        rel_path: rel_path.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    };
+    let files = [
+        file("main.py", "import util\nimport models\n"),
+        file("models.py", "import util\nclass Model: pass\n"),
+        file("util.py", "def helper():\n    return 42 * 42 * 42\n"),
+        file("notes.py", "x = 1\n"),
+    ];
+    let refs: Vec<&FileEntry> = files.iter().collect();
+    let sizes: Vec<usize> = files.iter().map(|f| f.content.len()).collect();
+
+    // util.py (two importers), then models.py (one), then the smaller of the rest.
+    let keep = fit_output(&refs, &sizes, OutputLimit::Bytes(sizes[2] + sizes[1] + 6));
+    assert_eq!(keep, [false, true, true, true]);
+    // A file too big for what is left is passed over for a smaller one.
+    let keep = fit_output(&refs, &sizes, OutputLimit::Bytes(sizes[2] + 6));
+    assert_eq!(keep, [false, false, true, true]);
+    assert_eq!(fit_output(&refs, &sizes, OutputLimit::Bytes(0)), [false; 4]);
+}
@@ -0,0 +1,33 @@
+//! Ctrl-C handling: the first interrupt asks the run to wrap up and write what it has,
+//! a second one exits immediately.
+
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::warn;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Exit status for a run cut short by Ctrl-C (128 + SIGINT).
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Run summary warning for an interrupted run.
+pub const WARNING: &str = "interrupted; the output is incomplete";
+
+/// Install the SIGINT handler. Only for export runs; long-lived modes such as the daemon
+/// keep the default behavior.
+pub fn install() {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        eprintln!("\nInterrupted; writing partial output (press Ctrl-C again to abort)");
+    });
+    if let Err(e) = result {
+        warn!("Could not install Ctrl-C handler: {}", e);
+    }
+}
+
+/// True once the user has pressed Ctrl-C.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,799 @@
+//! Heuristic chunking of source files into top-level definitions.
+//!
+//! There is no parser behind this: brace languages are split at top-level `{ ... }`
+//! blocks and indentation languages at column-0 `def`/`class` blocks. C, C++ and
+//! Objective-C also skip preprocessor lines, and Objective-C `@interface`/`@implementation`
+//! blocks run to their `@end`. Vue and Svelte components are chunked by their `<script>`
+//! blocks. That is good enough for size/nesting flags and chunk-level training samples.
+
+use crate::component::{split_component, BlockKind};
+use crate::tokens::estimate_tokens;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::LazyLock;
+
+/// One top-level definition (function, class, impl block, ...).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// Identifier from the first line, if one could be found
+    pub name: Option<String>,
+    /// 1-based first line
+    pub start_line: usize,
+    /// 1-based last line (inclusive)
+    pub end_line: usize,
+    /// 1-based first line after the signature (past `end_line` when the body shares its line)
+    pub body_start: usize,
+    /// Deepest block nesting inside the chunk (0 = flat body)
+    pub max_depth: usize,
+}
+
+impl Chunk {
+    pub fn lines(&self) -> usize {
+        self.end_line - self.start_line + 1
+    }
+
+    pub fn text(&self, content: &str) -> String {
+        content
+            .lines()
+            .skip(self.start_line - 1)
+            .take(self.lines())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+static NAME_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\b(?:fn|def|class|struct|enum|trait|impl|interface|func|function|module|object|record)\s+([A-Za-z_][A-Za-z0-9_]*)",
+    )
+    .unwrap()
+});
+
+/// A C function definition or prototype at column 0, `static int *parse(` or
+/// `Parser::parse(`, or an Objective-C method, `- (void)parse:`.
+static C_FUNCTION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:[-+]\s*\([^)]*\)\s*([A-Za-z_]\w*)|(?:[A-Za-z_][\w\s*&<>,]*?[\s*&])?(?:\w+::)*(~?[A-Za-z_]\w*)\s*\()")
+        .unwrap()
+});
+
+static OBJC_BLOCK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^@(?:interface|implementation|protocol)\s+([A-Za-z_]\w*)").unwrap()
+});
+
+/// A Terraform block and its labels: `resource "aws_instance" "web" {`.
+static HCL_BLOCK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(resource|data|module|variable|output|provider|locals|terraform)((?:\s+"[^"]*")*)\s*\{"#)
+        .unwrap()
+});
+
+static PROTO_NAME_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:message|service|enum|rpc|extend)\s+([A-Za-z_][\w.]*)").unwrap()
+});
+
+/// `CREATE [OR REPLACE] [TEMP|UNIQUE|...] TABLE [IF NOT EXISTS] name` and the like.
+static SQL_CREATE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)^\s*create\s+(?:or\s+replace\s+)?(?:(?:temp|temporary|unique|materialized)\s+)*(?:table|view|index|function|procedure|trigger|type|sequence|schema)\s+(?:if\s+not\s+exists\s+)?([\w."]+)"#,
+    )
+    .unwrap()
+});
+
+/// Languages chunked as C: braces, plus preprocessor lines that are not code.
+fn is_c_family(lang: &str) -> bool {
+    matches!(lang, "c" | "cpp" | "objectivec")
+}
+
+/// Split `content` into top-level chunks, picking the strategy from the language name.
+pub fn chunk_file(content: &str, lang: &str) -> Vec<Chunk> {
+    match lang {
+        "python" | "yaml" => indent_chunks(content),
+        "objectivec" => objc_chunks(content),
+        "vue" | "svelte" => component_chunks(content),
+        "sql" => sql_chunks(content),
+        _ => brace_chunks(content, lang),
+    }
+}
+
+/// The chunks of a Vue or Svelte component's `<script>` blocks; markup and styles have
+/// no definitions to speak of.
+fn component_chunks(content: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    for block in split_component(content) {
+        if block.kind != BlockKind::Script {
+            continue;
+        }
+        let offset = block.first_line - 1;
+        chunks.extend(
+            chunk_file(&block.text, block.lang)
+                .into_iter()
+                .map(|c| Chunk {
+                    start_line: c.start_line + offset,
+                    end_line: c.end_line + offset,
+                    body_start: c.body_start + offset,
+                    ..c
+                }),
+        );
+    }
+    chunks
+}
+
+/// Line ranges a renderer should keep whole when a file has to break across pages or
+/// output parts: each top-level chunk, and between chunks, blocks separated by blank
+/// lines (a blank line stays with the block before it). The ranges are 1-based and
+/// inclusive, in order, and cover every line of `content`.
+pub fn layout_units(content: &str, lang: &str) -> Vec<(usize, usize)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut units: Vec<(usize, usize)> = Vec::new();
+    let gap = |units: &mut Vec<(usize, usize)>, from: usize, to: usize| {
+        let mut start = from;
+        for n in from..=to {
+            if !lines[n - 1].trim().is_empty() {
+                continue;
+            }
+            match units.last_mut() {
+                Some(last) if start == n && last.1 + 1 == n => last.1 = n,
+                _ => units.push((start, n)),
+            }
+            start = n + 1;
+        }
+        if start <= to {
+            units.push((start, to));
+        }
+    };
+    let mut next = 1;
+    for chunk in chunk_file(content, lang) {
+        if chunk.end_line < next {
+            continue;
+        }
+        if chunk.start_line > next {
+            gap(&mut units, next, chunk.start_line - 1);
+        }
+        units.push((chunk.start_line.max(next), chunk.end_line));
+        next = chunk.end_line + 1;
+    }
+    if next <= lines.len() {
+        gap(&mut units, next, lines.len());
+    }
+    units
+}
+
+/// Names of the top-level chunks that look exported: `pub` in Rust, `export` in
+/// JS/TS (and JSX/TSX), a capital letter in Go, no leading underscore in Python, not `static` in C,
+/// `public` elsewhere.
+pub fn public_symbols(content: &str, lang: &str) -> Vec<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    chunk_file(content, lang)
+        .into_iter()
+        .filter_map(|chunk| {
+            let name = chunk.name?;
+            let body = &lines[chunk.start_line - 1..chunk.end_line];
+            // A `typedef struct { ... } Name;` is named by its last line.
+            let signature = body
+                .iter()
+                .map(|l| l.trim_start())
+                .find(|l| chunk_name(l, lang).is_some())
+                .or_else(|| body.first().copied().filter(|_| is_c_family(lang)))?;
+            let public = match lang {
+                "rust" => signature.starts_with("pub "),
+                "c" | "cpp" | "objectivec" => !signature.starts_with("static "),
+                "javascript" | "typescript" | "jsx" | "tsx" | "vue" | "svelte" => {
+                    signature.starts_with("export ")
+                }
+                "go" => name.starts_with(|c: char| c.is_ascii_uppercase()),
+                "hcl" | "protobuf" | "sql" => true,
+                "python" => !name.starts_with('_'),
+                _ => signature.split_whitespace().any(|w| w == "public"),
+            };
+            public.then_some(name)
+        })
+        .collect()
+}
+
+static FUNCTION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:[\w:<>()]+\s+)*(?:fn|def|func|function)\s+([A-Za-z_][A-Za-z0-9_]*)")
+        .unwrap()
+});
+
+/// Every function definition in `content` with a body, at any nesting depth, so methods
+/// inside `impl` blocks and classes are found too. C functions and Objective-C methods
+/// are only looked for at column 0, where they are by convention. Attributes, decorators and comments
+/// right above a function belong to it. Declarations without a body (trait methods,
+/// prototypes) are skipped. `max_depth` is not computed.
+pub fn functions(content: &str, lang: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let indent = |l: &str| l.len() - l.trim_start().len();
+    let mut functions = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let name = match FUNCTION_RE.captures(line) {
+            Some(c) => c[1].to_string(),
+            None if is_c_family(lang) => match c_function_name(line) {
+                Some(name) => name,
+                None => continue,
+            },
+            None => continue,
+        };
+        let end = if lang == "python" {
+            // The body is every following line indented deeper, up to the last non-blank one.
+            let mut end = i;
+            for (j, next) in lines.iter().enumerate().skip(i + 1) {
+                if next.trim().is_empty() {
+                    continue;
+                }
+                if indent(next) <= indent(line) {
+                    break;
+                }
+                end = j;
+            }
+            Some((end, i + 1))
+        } else {
+            brace_body_end(&lines, i)
+        };
+        let Some((end, open)) = end else {
+            continue;
+        };
+        let mut start = i;
+        while start > 0 {
+            let above = lines[start - 1].trim_start();
+            // In Objective-C, `@` starts `@implementation`, not a decorator.
+            let decorator = above.starts_with('@') && lang != "objectivec";
+            if decorator || ["#[", "//", "/*", "*"].iter().any(|p| above.starts_with(p)) {
+                start -= 1;
+            } else {
+                break;
+            }
+        }
+        functions.push(Chunk {
+            name: Some(name),
+            start_line: start + 1,
+            end_line: end + 1,
+            body_start: open + 2,
+            max_depth: 0,
+        });
+    }
+    functions
+}
+
+/// `(last line, line of the opening brace)` of the block that follows the signature at
+/// `start`, or `None` if the signature ends in `;` first.
+fn brace_body_end(lines: &[&str], start: usize) -> Option<(usize, usize)> {
+    let mut depth = 0usize;
+    let mut parens = 0usize;
+    let mut open = None;
+    for (j, line) in lines.iter().enumerate().skip(start) {
+        for c in code_chars(line) {
+            match c {
+                '(' | '[' => parens += 1,
+                ')' | ']' => parens = parens.saturating_sub(1),
+                ';' if open.is_none() && parens == 0 => return None,
+                '{' => {
+                    open.get_or_insert(j);
+                    depth += 1;
+                }
+                '}' if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return open.map(|open| (j, open));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+fn chunk_name(line: &str, lang: &str) -> Option<String> {
+    match lang {
+        "hcl" => return hcl_address(line),
+        "protobuf" => return PROTO_NAME_RE.captures(line).map(|c| c[1].to_string()),
+        "sql" => return SQL_CREATE_RE.captures(line).map(|c| c[1].replace('"', "")),
+        _ => {}
+    }
+    match NAME_RE.captures(line) {
+        Some(c) => Some(c[1].to_string()),
+        None if is_c_family(lang) => c_function_name(line),
+        None => None,
+    }
+}
+
+/// How Terraform addresses a block: `aws_instance.web` for a resource, `data.aws_ami.x`,
+/// `module.vpc`, `var.region`, ... and the bare keyword for `locals`/`terraform`.
+fn hcl_address(line: &str) -> Option<String> {
+    let c = HCL_BLOCK_RE.captures(line)?;
+    let labels = c[2].split('"').skip(1).step_by(2);
+    let prefix = match &c[1] {
+        "resource" => None,
+        "variable" => Some("var"),
+        keyword => Some(keyword),
+    };
+    Some(
+        prefix
+            .into_iter()
+            .chain(labels)
+            .collect::<Vec<_>>()
+            .join("."),
+    )
+}
+
+fn c_function_name(line: &str) -> Option<String> {
+    let c = C_FUNCTION_RE.captures(line)?;
+    let name = c.get(1).or(c.get(2))?.as_str();
+    let keyword = ["if", "for", "while", "switch", "return", "sizeof", "else"].contains(&name);
+    (!keyword).then(|| name.to_string())
+}
+
+/// Top-level `{ ... }` blocks; the chunk starts at the first non-blank line after the
+/// previous chunk so signatures, attributes and doc comments stay attached.
+///
+/// For C-family languages, preprocessor lines (with their `\` continuations) and
+/// `extern "C" {` wrappers are not blocks, and `typedef struct { ... } Name;` is named
+/// after its last line.
+fn brace_chunks(content: &str, lang: &str) -> Vec<Chunk> {
+    let c_family = is_c_family(lang);
+    let mut chunks = Vec::new();
+    let mut depth = 0usize;
+    let mut start: Option<usize> = None;
+    let mut pending_start: Option<usize> = None;
+    let mut max_depth = 0;
+    let mut open_line = 0;
+    let mut continued = false;
+    for (i, line) in content.lines().enumerate() {
+        let line_no = i + 1;
+        if c_family {
+            let trimmed = line.trim();
+            let directive = continued || trimmed.starts_with('#');
+            continued = directive && trimmed.ends_with('\\');
+            let wrapper = trimmed.starts_with("extern \"C\"") && trimmed.ends_with('{');
+            // `@end` and the like close Objective-C blocks, which `objc_chunks` handles.
+            if depth == 0 && (directive || wrapper || trimmed.starts_with('@')) {
+                pending_start = None;
+                continue;
+            }
+        }
+        if depth == 0 && pending_start.is_none() && !line.trim().is_empty() {
+            pending_start = Some(line_no);
+        }
+        for c in code_chars(line) {
+            match c {
+                '{' => {
+                    if depth == 0 {
+                        start = pending_start.or(Some(line_no));
+                        max_depth = 0;
+                        open_line = line_no;
+                    }
+                    depth += 1;
+                    max_depth = max_depth.max(depth);
+                }
+                '}' if depth > 0 => depth -= 1,
+                _ => {}
+            }
+        }
+        if depth == 0 {
+            if let Some(first) = start.take() {
+                let head: String = content
+                    .lines()
+                    .skip(first - 1)
+                    .take(line_no - first + 1)
+                    .find(|l| chunk_name(l, lang).is_some())
+                    .unwrap_or_default()
+                    .to_string();
+                let typedef_name = || {
+                    let last = line.trim().strip_prefix('}')?.trim().strip_suffix(';')?;
+                    let name = last.trim();
+                    (!name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_'))
+                        .then(|| name.to_string())
+                };
+                chunks.push(Chunk {
+                    name: chunk_name(&head, lang).or_else(|| typedef_name().filter(|_| c_family)),
+                    start_line: first,
+                    end_line: line_no,
+                    body_start: open_line + 1,
+                    max_depth: max_depth - 1,
+                });
+                pending_start = None;
+            } else if line.trim().is_empty() || line.trim_end().ends_with(';') {
+                // Statements and blank lines between blocks don't belong to the next one.
+                pending_start = None;
+            }
+        }
+    }
+    chunks
+}
+
+/// The characters of `line` outside string/char literals and `//` comments, so braces in
+/// `"{}"` or `'{'` don't count. Strings spanning lines are not tracked.
+fn code_chars(line: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = line.chars().peekable();
+    let mut quote: Option<char> = None;
+    let mut done = false;
+    std::iter::from_fn(move || loop {
+        if done {
+            return None;
+        }
+        let c = chars.next()?;
+        match quote {
+            Some(q) => {
+                if c == '\\' {
+                    chars.next();
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '`' => quote = Some(c),
+                // Only a char literal if it closes right away (`'{'`, `'\n'`), not a Rust lifetime.
+                '\'' => {
+                    let mut ahead = chars.clone();
+                    let first = ahead.next();
+                    let closes = match first {
+                        Some('\\') => ahead.nth(1) == Some('\''),
+                        Some(_) => ahead.next() == Some('\''),
+                        None => false,
+                    };
+                    if closes {
+                        quote = Some('\'');
+                    }
+                }
+                '/' if chars.peek() == Some(&'/') => done = true,
+                _ => return Some(c),
+            },
+        }
+    })
+}
+
+/// Objective-C `@interface`, `@implementation` and `@protocol` blocks, each running to
+/// its `@end` and named after the class or protocol, with the C functions and types
+/// between them chunked by braces. Comments right above a block belong to it.
+fn objc_chunks(content: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim_end();
+        let name = OBJC_BLOCK_RE.captures(line).map(|c| c[1].to_string());
+        // `@protocol Foo;` and `@class Foo;` only declare.
+        if name.is_none() || line.ends_with(';') {
+            i += 1;
+            continue;
+        }
+        let Some(end) = (i + 1..lines.len()).find(|&j| lines[j].trim() == "@end") else {
+            break;
+        };
+        let mut start = i;
+        while start > 0 {
+            let above = lines[start - 1].trim_start();
+            if ["//", "/*", "*"].iter().any(|p| above.starts_with(p)) {
+                start -= 1;
+            } else {
+                break;
+            }
+        }
+        let mut depth = 0usize;
+        let mut max_depth = 0;
+        for line in &lines[i..=end] {
+            for c in code_chars(line) {
+                match c {
+                    '{' => {
+                        depth += 1;
+                        max_depth = max_depth.max(depth);
+                    }
+                    '}' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+        blocks.push(Chunk {
+            name,
+            start_line: start + 1,
+            end_line: end + 1,
+            body_start: i + 2,
+            max_depth,
+        });
+        i = end + 1;
+    }
+    let mut chunks = brace_chunks(content, "objectivec");
+    chunks.retain(|c| {
+        !blocks
+            .iter()
+            .any(|b| c.start_line <= b.end_line && b.start_line <= c.end_line)
+    });
+    chunks.extend(blocks);
+    chunks.sort_by_key(|c| c.start_line);
+    chunks
+}
+
+/// SQL statements, each running to its closing `;`. Semicolons in strings, `--` comments
+/// and `$$`-quoted function bodies don't count, and comments above a statement belong
+/// to it. `max_depth` is the parenthesis nesting.
+fn sql_chunks(content: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut depth = 0usize;
+    let mut max_depth = 0;
+    let mut dollar_quoted = false;
+    for (i, line) in content.lines().enumerate() {
+        if start.is_none() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            start = Some(i + 1);
+            max_depth = 0;
+        }
+        let mut ended = false;
+        let mut quote: Option<char> = None;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '$') if chars.peek() == Some(&'$') => {
+                    chars.next();
+                    dollar_quoted = !dollar_quoted;
+                }
+                (None, _) if dollar_quoted => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '-') if chars.peek() == Some(&'-') => break,
+                (None, '(') => {
+                    depth += 1;
+                    max_depth = max_depth.max(depth);
+                }
+                (None, ')') => depth = depth.saturating_sub(1),
+                (None, ';') if depth == 0 => ended = true,
+                _ => {}
+            }
+        }
+        if ended {
+            if let Some(first) = start.take() {
+                let lines: Vec<&str> = content
+                    .lines()
+                    .skip(first - 1)
+                    .take(i + 2 - first)
+                    .collect();
+                let head = lines.iter().position(|l| {
+                    let l = l.trim_start();
+                    !l.is_empty() && !l.starts_with("--")
+                });
+                let head = head.unwrap_or(0);
+                chunks.push(Chunk {
+                    name: chunk_name(lines[head], "sql"),
+                    start_line: first,
+                    end_line: i + 1,
+                    body_start: first + head + 1,
+                    max_depth: max_depth.saturating_sub(1),
+                });
+            }
+            depth = 0;
+        }
+    }
+    chunks
+}
+
+/// Column-0 `def`/`class` blocks, ending before the next column-0 line.
+fn indent_chunks(content: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut chunks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let is_def = line.starts_with("def ")
+            || line.starts_with("async def ")
+            || line.starts_with("class ");
+        if !is_def {
+            i += 1;
+            continue;
+        }
+        let mut end = i;
+        let mut max_indent = 0;
+        for (j, next) in lines.iter().enumerate().skip(i + 1) {
+            if next.trim().is_empty() {
+                continue;
+            }
+            let indent = next.len() - next.trim_start().len();
+            if indent == 0 {
+                break;
+            }
+            max_indent = max_indent.max(indent);
+            end = j;
+        }
+        // Signatures can wrap; the body starts after the line ending in `:`.
+        let sig_end = (i..=end)
+            .find(|&j| lines[j].trim_end().ends_with(':'))
+            .unwrap_or(i);
+        chunks.push(Chunk {
+            name: chunk_name(line, "python"),
+            start_line: i + 1,
+            end_line: end + 1,
+            body_start: sig_end + 2,
+            // Assume 4-space indentation; the body itself is level 1.
+            max_depth: (max_indent / 4).saturating_sub(1),
+        });
+        i = end + 1;
+    }
+    chunks
+}
+
+/// Limits past which a chunk gets flagged, from `chunk_flags:` in `r2md.yml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ChunkThresholds {
+    pub lines: usize,
+    pub depth: usize,
+    pub tokens: usize,
+}
+
+impl Default for ChunkThresholds {
+    fn default() -> Self {
+        ChunkThresholds {
+            lines: 200,
+            depth: 6,
+            tokens: 4000,
+        }
+    }
+}
+
+/// A chunk over one of the thresholds.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkFlag {
+    /// `long`, `deep` or `large`
+    pub kind: &'static str,
+    pub chunk: Option<String>,
+    pub line: usize,
+    /// Lines, nesting depth or estimated tokens, depending on `kind`
+    pub value: usize,
+}
+
+impl fmt::Display for ChunkFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match &self.chunk {
+            Some(name) => format!("`{}` (line {})", name, self.line),
+            None => format!("block at line {}", self.line),
+        };
+        match self.kind {
+            "long" => write!(f, "long chunk {}: {} lines", what, self.value),
+            "deep" => write!(f, "deeply nested chunk {}: depth {}", what, self.value),
+            _ => write!(f, "large chunk {}: ~{} tokens", what, self.value),
+        }
+    }
+}
+
+/// Flags for every chunk in `content` that exceeds `limits`.
+pub fn flag_chunks(content: &str, lang: &str, limits: &ChunkThresholds) -> Vec<ChunkFlag> {
+    let mut flags = Vec::new();
+    for chunk in chunk_file(content, lang) {
+        let mut flag = |kind, value| {
+            flags.push(ChunkFlag {
+                kind,
+                chunk: chunk.name.clone(),
+                line: chunk.start_line,
+                value,
+            })
+        };
+        if chunk.lines() > limits.lines {
+            flag("long", chunk.lines());
+        }
+        if chunk.max_depth > limits.depth {
+            flag("deep", chunk.max_depth);
+        }
+        let tokens = estimate_tokens(&chunk.text(content));
+        if tokens > limits.tokens {
+            flag("large", tokens);
+        }
+    }
+    flags
+}
+
+#[test]
+fn test_brace_and_indent_chunks() {
+    let rust = "use std::io;\n\n/// Doc\nfn a() {\n    if x {\n        y('{', \"}\"); // }\n    }\n}\n\nstruct B {\n    c: u8,\n}\n";
+    let chunks = chunk_file(rust, "rust");
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].name.as_deref(), Some("a"));
+    assert_eq!((chunks[0].start_line, chunks[0].end_line), (3, 8));
+    assert_eq!(chunks[0].body_start, 5);
+    assert_eq!(chunks[0].max_depth, 1);
+    assert_eq!(chunks[1].name.as_deref(), Some("B"));
+
+    let py =
+        "import os\n\ndef f(x):\n    if x:\n        return 1\n    return 2\n\nclass C:\n    pass\n";
+    let chunks = chunk_file(py, "python");
+    assert_eq!(chunks.len(), 2);
+    assert_eq!((chunks[0].start_line, chunks[0].end_line), (3, 6));
+    assert_eq!(chunks[0].body_start, 4);
+    assert_eq!(chunks[1].name.as_deref(), Some("C"));
+
+    let limits = ChunkThresholds {
+        lines: 3,
+        ..Default::default()
+    };
+    let flags = flag_chunks(rust, "rust", &limits);
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0].to_string(), "long chunk `a` (line 3): 6 lines");
+
+    let exported = "pub fn a() {\n}\n\nfn b() {\n}\n\n/// Doc\npub struct C {\n}\n";
+    assert_eq!(public_symbols(exported, "rust"), vec!["a", "C"]);
+
+    let methods = "trait T {\n    fn decl(&self);\n}\n\nimpl S {\n    #[inline]\n    pub fn m(&self, a: [u8; 4]) -> u8 {\n        a[0]\n    }\n}\n";
+    let found = functions(methods, "rust");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].name.as_deref(), Some("m"));
+    assert_eq!((found[0].start_line, found[0].end_line), (6, 9));
+
+    let with_blank = "use a;\n\nfn f() {\n    x();\n\n    y();\n}\n\nfn g() {}\n";
+    assert_eq!(
+        layout_units(with_blank, "rust"),
+        vec![(1, 2), (3, 8), (9, 9)]
+    );
+}
+
+#[test]
+fn test_c_and_objc_chunks() {
+    let c = "#include <stdio.h>\n#define INIT { \\\n    0 }\n\n#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n/* Parse it. */\nstatic int parse(const char *s)\n{\n    if (s) {\n        return 1;\n    }\n    return 0;\n}\n\ntypedef struct {\n    int x;\n} Point;\n\nint *make(void) { return 0; }\n\n#ifdef __cplusplus\n}\n#endif\n";
+    let chunks = chunk_file(c, "c");
+    let names: Vec<_> = chunks.iter().map(|c| c.name.as_deref()).collect();
+    assert_eq!(names, [Some("parse"), Some("Point"), Some("make")]);
+    assert_eq!((chunks[0].start_line, chunks[0].end_line), (9, 16));
+    assert_eq!(chunks[0].max_depth, 1);
+    assert_eq!(public_symbols(c, "c"), ["Point", "make"]);
+    let found: Vec<_> = functions(c, "c")
+        .into_iter()
+        .filter_map(|f| f.name)
+        .collect();
+    assert_eq!(found, ["parse", "make"]);
+
+    let objc = "#import <Foundation/Foundation.h>\n\n@protocol Greeter;\n\n// A greeter.\n@interface Hello : NSObject {\n    int count;\n}\n- (void)greet:(NSString *)name;\n@end\n\n@implementation Hello\n- (void)greet:(NSString *)name {\n    if (name) {\n        NSLog(@\"hi %@\", name);\n    }\n}\n@end\n\nint main(void) {\n    return 0;\n}\n";
+    let chunks = chunk_file(objc, "objectivec");
+    let spans: Vec<_> = chunks
+        .iter()
+        .map(|c| (c.name.as_deref(), c.start_line, c.end_line))
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            (Some("Hello"), 5, 10),
+            (Some("Hello"), 12, 18),
+            (Some("main"), 20, 22)
+        ]
+    );
+    assert_eq!(chunks[1].max_depth, 2);
+    let methods = functions(objc, "objectivec");
+    assert_eq!(methods[0].name.as_deref(), Some("greet"));
+    assert_eq!((methods[0].start_line, methods[0].end_line), (13, 17));
+}
+
+#[test]
+fn test_infra_chunks() {
+    let tf = "variable \"region\" {\n  default = \"eu-west-1\"\n}\n\n# The web server\nresource \"aws_instance\" \"web\" {\n  tags = {\n    Name = \"web\"\n  }\n}\n\nlocals {\n  a = 1\n}\n";
+    let names: Vec<_> = chunk_file(tf, "hcl")
+        .into_iter()
+        .map(|c| (c.name, c.start_line, c.end_line))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            (Some("var.region".to_string()), 1, 3),
+            (Some("aws_instance.web".to_string()), 5, 10),
+            (Some("locals".to_string()), 12, 14)
+        ]
+    );
+
+    let proto = "syntax = \"proto3\";\n\nmessage User {\n  string name = 1;\n}\n\nservice Users {\n  rpc Get(User) returns (User);\n}\n";
+    assert_eq!(public_symbols(proto, "protobuf"), ["User", "Users"]);
+
+    let sql = "-- Accounts\nCREATE TABLE IF NOT EXISTS public.users (\n  id int PRIMARY KEY, -- ; not the end\n  name text DEFAULT ';'\n);\n\nINSERT INTO users VALUES (1, 'a');\nCREATE OR REPLACE FUNCTION f() RETURNS int AS $$\nBEGIN\n  RETURN 1;\nEND;\n$$ LANGUAGE plpgsql;\n";
+    let chunks = chunk_file(sql, "sql");
+    let spans: Vec<_> = chunks
+        .iter()
+        .map(|c| (c.name.as_deref(), c.start_line, c.end_line))
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            (Some("public.users"), 1, 5),
+            (None, 7, 7),
+            (Some("f"), 8, 12)
+        ]
+    );
+    assert_eq!(chunks[0].body_start, 3);
+    assert_eq!(public_symbols(sql, "sql"), ["public.users", "f"]);
+}
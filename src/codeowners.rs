@@ -0,0 +1,89 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Where GitHub and GitLab look for the file, in order.
+const CODEOWNERS_LOCATIONS: &[&str] = &["CODEOWNERS", ".github/CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Parsed `CODEOWNERS` rules. Patterns use gitignore syntax and the last matching rule wins.
+pub struct CodeOwners {
+    rules: Vec<(Gitignore, Vec<String>)>,
+}
+
+impl CodeOwners {
+    /// Load the first `CODEOWNERS` file found under `root`, if any.
+    pub fn load(root: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        for location in CODEOWNERS_LOCATIONS {
+            let path = root.join(location);
+            if path.is_file() {
+                let text = fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                return Ok(Some(Self::parse(root, &text)?));
+            }
+        }
+        Ok(None)
+    }
+
+    fn parse(root: &Path, text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut rules = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            // GitLab `[Section]` headers carry no pattern.
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else {
+                continue;
+            };
+            let owners = fields
+                .take_while(|f| !f.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect();
+            let mut builder = GitignoreBuilder::new(root);
+            builder.add_line(None, pattern)?;
+            rules.push((builder.build()?, owners));
+        }
+        Ok(CodeOwners { rules })
+    }
+
+    /// Owners of `rel_path` (lowercased), empty when no rule matches.
+    pub fn owners_of(&self, rel_path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(matcher, _)| {
+                matcher
+                    .matched_path_or_any_parents(rel_path, false)
+                    .is_ignore()
+            })
+            .map(|(_, owners)| owners.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// True if `owner` (`@user`, `@org/team` or an email; case-insensitive) owns `rel_path`.
+    pub fn is_owned_by(&self, rel_path: &str, owner: &str) -> bool {
+        let owner = owner.to_lowercase();
+        let owner = if owner.starts_with('@') || owner.contains('@') {
+            owner
+        } else {
+            format!("@{}", owner)
+        };
+        self.owners_of(rel_path).contains(&owner)
+    }
+}
+
+#[test]
+fn test_last_matching_rule_wins() -> Result<(), Box<dyn Error>> {
+    let owners = CodeOwners::parse(
+        Path::new("."),
+        "# comment\n* @org/everyone\n/src/ @org/Platform\nsrc/ui/ @org/frontend # web\n*.md\n",
+    )?;
+    assert!(owners.is_owned_by("build.rs", "@org/everyone"));
+    assert!(owners.is_owned_by("src/main.rs", "@org/platform"));
+    assert!(owners.is_owned_by("src/ui/app.rs", "org/frontend"));
+    assert!(!owners.is_owned_by("src/ui/app.rs", "@org/platform"));
+    assert!(owners.owners_of("src/README.md").is_empty());
+    Ok(())
+}
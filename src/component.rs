@@ -0,0 +1,144 @@
+//! Vue and Svelte single-file components, which hold markup, a `<script>` and a `<style>`
+//! in one file. Each part is rendered as its own fenced block in its own language, and
+//! only the scripts are chunked.
+
+/// What a part of a component holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockKind {
+    Script,
+    Style,
+    /// Everything outside `<script>` and `<style>`: a Vue `<template>`, Svelte markup
+    Markup,
+}
+
+/// One part of a component.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block<'a> {
+    pub kind: BlockKind,
+    /// Code-fence language, from the tag's `lang` attribute
+    pub lang: &'static str,
+    /// The opening `<script ...>`/`<style ...>` line; empty for markup
+    pub tag: &'a str,
+    /// 1-based line of the first line of `text`
+    pub first_line: usize,
+    pub text: String,
+}
+
+/// Split `content` at its top-level `<script>` and `<style>` elements, which start at
+/// column 0 with the opening tag on a line of its own. Blank markup between them is
+/// dropped; a block missing its closing tag runs to the end of the file.
+pub fn split_component(content: &str) -> Vec<Block<'_>> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut blocks = Vec::new();
+    let mut markup: Vec<&str> = Vec::new();
+    let mut markup_start = 1;
+    let flush = |blocks: &mut Vec<Block>, markup: &mut Vec<&str>, start: usize| {
+        let first = markup.iter().position(|l| !l.trim().is_empty());
+        let last = markup.iter().rposition(|l| !l.trim().is_empty());
+        if let (Some(first), Some(last)) = (first, last) {
+            blocks.push(Block {
+                kind: BlockKind::Markup,
+                lang: "html",
+                tag: "",
+                first_line: start + first,
+                text: markup[first..=last].join("\n"),
+            });
+        }
+        markup.clear();
+    };
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let kind = if line.starts_with("<script") {
+            BlockKind::Script
+        } else if line.starts_with("<style") {
+            BlockKind::Style
+        } else {
+            BlockKind::Markup
+        };
+        if kind == BlockKind::Markup || !line.trim_end().ends_with('>') {
+            if markup.is_empty() {
+                markup_start = i + 1;
+            }
+            markup.push(line);
+            i += 1;
+            continue;
+        }
+        flush(&mut blocks, &mut markup, markup_start);
+        let close = if kind == BlockKind::Script {
+            "</script>"
+        } else {
+            "</style>"
+        };
+        let end = (i + 1..lines.len())
+            .find(|&j| lines[j].trim_start().starts_with(close))
+            .unwrap_or(lines.len());
+        blocks.push(Block {
+            kind,
+            lang: fence_lang(kind, attribute(line, "lang")),
+            tag: line.trim_end(),
+            first_line: i + 2,
+            text: lines[i + 1..end].join("\n"),
+        });
+        i = end + 1;
+    }
+    flush(&mut blocks, &mut markup, markup_start);
+    blocks
+}
+
+/// Value of `name="..."` (or `'...'`) in an opening tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let at = tag.find(&format!(" {}=", name))? + name.len() + 2;
+    let rest = &tag[at..];
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    rest[1..].split(quote).next()
+}
+
+fn fence_lang(kind: BlockKind, lang: Option<&str>) -> &'static str {
+    match (kind, lang) {
+        (BlockKind::Script, Some("ts")) => "typescript",
+        (BlockKind::Script, Some("tsx")) => "tsx",
+        (BlockKind::Script, Some("jsx")) => "jsx",
+        (BlockKind::Script, _) => "javascript",
+        (BlockKind::Style, Some("scss")) => "scss",
+        (BlockKind::Style, Some("sass")) => "sass",
+        (BlockKind::Style, Some("less")) => "less",
+        (BlockKind::Style, Some("stylus")) => "stylus",
+        (BlockKind::Style, _) => "css",
+        (BlockKind::Markup, _) => "html",
+    }
+}
+
+#[test]
+fn test_split_component() {
+    let vue = "<template>\n  <div>{{ msg }}</div>\n</template>\n\n<script setup lang=\"ts\">\nconst msg = 'hi'\n</script>\n\n<style scoped lang='scss'>\n.a { color: red; }\n</style>\n";
+    let blocks = split_component(vue);
+    let summary: Vec<_> = blocks
+        .iter()
+        .map(|b| (b.kind, b.lang, b.first_line, b.text.as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                BlockKind::Markup,
+                "html",
+                1,
+                "<template>\n  <div>{{ msg }}</div>\n</template>"
+            ),
+            (BlockKind::Script, "typescript", 6, "const msg = 'hi'"),
+            (BlockKind::Style, "scss", 10, ".a { color: red; }"),
+        ]
+    );
+    assert_eq!(blocks[1].tag, "<script setup lang=\"ts\">");
+
+    // Svelte: markup after the script, and a script that never closes.
+    let svelte = "<script>\n  let n = 0;\n</script>\n\n<button on:click={() => n++}>{n}</button>\n<script context=\"module\">\nexport const x = 1;\n";
+    let blocks = split_component(svelte);
+    assert_eq!(blocks.len(), 3);
+    assert_eq!(
+        (blocks[1].kind, blocks[1].first_line),
+        (BlockKind::Markup, 5)
+    );
+    assert_eq!(blocks[2].text, "export const x = 1;");
+}
@@ -0,0 +1,136 @@
+//! Transparent compression of output files: a name ending in `.gz` is written gzipped
+//! and one ending in `.zst` with zstd, so `-o r2md_output.md.gz` never puts the plain
+//! export on disk. Reading back (`--update`) decompresses the same way.
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+
+/// zstd level: the library default, a good ratio at well over 100 MB/s.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compression picked from a file name's last extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    Plain,
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    pub fn for_path(path: &str) -> Codec {
+        match path
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase())
+        {
+            Some(ext) if ext == "gz" => Codec::Gzip,
+            Some(ext) if ext == "zst" => Codec::Zstd,
+            _ => Codec::Plain,
+        }
+    }
+
+    /// The extension this codec adds, with its dot; empty for plain files.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Codec::Plain => "",
+            Codec::Gzip => ".gz",
+            Codec::Zstd => ".zst",
+        }
+    }
+}
+
+/// `path` without a `.gz`/`.zst` extension, and that extension: `out.md.gz` -> (`out.md`,
+/// `.gz`). Outputs named after the Markdown file use the first part.
+pub fn split_suffix(path: &str) -> (&str, &'static str) {
+    let codec = Codec::for_path(path);
+    let suffix = codec.suffix();
+    (&path[..path.len() - suffix.len()], suffix)
+}
+
+/// A file being written, compressed as its name says. Call `finish` when done: a
+/// compressed stream dropped without it is missing its trailer.
+pub enum OutputFile {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl OutputFile {
+    pub fn create(path: &str) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match Codec::for_path(path) {
+            Codec::Plain => OutputFile::Plain(file),
+            Codec::Gzip => OutputFile::Gzip(GzEncoder::new(file, Compression::default())),
+            Codec::Zstd => OutputFile::Zstd(zstd::Encoder::new(file, ZSTD_LEVEL)?),
+        })
+    }
+
+    /// End the compressed stream and flush everything to disk.
+    pub fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            OutputFile::Plain(file) => file,
+            OutputFile::Gzip(encoder) => encoder.finish()?,
+            OutputFile::Zstd(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Gzip(encoder) => encoder.write(buf),
+            OutputFile::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Gzip(encoder) => encoder.flush(),
+            OutputFile::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// `fs::write`, compressing as `path`'s name says.
+pub fn write_file(path: &str, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut file = OutputFile::create(path)?;
+    file.write_all(contents.as_ref())?;
+    file.finish()
+}
+
+/// `fs::read_to_string`, decompressing as `path`'s name says.
+pub fn read_to_string(path: &str) -> io::Result<String> {
+    let mut text = String::new();
+    match Codec::for_path(path) {
+        Codec::Plain => return fs::read_to_string(path),
+        Codec::Gzip => MultiGzDecoder::new(File::open(path)?).read_to_string(&mut text)?,
+        Codec::Zstd => zstd::Decoder::new(File::open(path)?)?.read_to_string(&mut text)?,
+    };
+    Ok(text)
+}
+
+#[test]
+fn test_compressed_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let text = "# Export\n\n".repeat(1000);
+    for name in ["out.md", "out.md.gz", "out.md.zst"] {
+        let path = dir.path().join(name).to_string_lossy().into_owned();
+        write_file(&path, &text)?;
+        assert_eq!(read_to_string(&path)?, text);
+    }
+    let gz = fs::read(dir.path().join("out.md.gz"))?;
+    assert_eq!(gz[..2], [0x1f, 0x8b]);
+    assert!(gz.len() < text.len() / 10);
+    let zst = fs::read(dir.path().join("out.md.zst"))?;
+    assert_eq!(zst[..4], [0x28, 0xb5, 0x2f, 0xfd]);
+
+    assert_eq!(split_suffix("dist/out.md.GZ"), ("dist/out.md", ".gz"));
+    assert_eq!(split_suffix("out.md.zst"), ("out.md", ".zst"));
+    assert_eq!(split_suffix("out.md"), ("out.md", ""));
+    Ok(())
+}
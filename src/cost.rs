@@ -0,0 +1,64 @@
+//! `--cost-estimate`: what sending the export as a prompt would cost with a few common
+//! models, from the estimated token count.
+
+use std::collections::HashMap;
+
+/// USD per million input tokens, the providers' list prices at the time of writing.
+/// Prices change; `model_prices` in `r2md.yml` overrides them and adds models.
+pub const DEFAULT_PRICES: [(&str, f64); 8] = [
+    ("gpt-4o", 2.50),
+    ("gpt-4o-mini", 0.15),
+    ("gpt-4.1", 2.00),
+    ("claude-opus", 15.00),
+    ("claude-sonnet", 3.00),
+    ("claude-haiku", 0.80),
+    ("gemini-2.5-pro", 1.25),
+    ("gemini-2.5-flash", 0.30),
+];
+
+/// The prompt cost of `tokens` input tokens per model: the defaults in order, with
+/// `overrides` applied, then models only found in `overrides` by name.
+pub fn estimate_costs(tokens: usize, overrides: &HashMap<String, f64>) -> Vec<(String, f64)> {
+    let mut extra: Vec<(&String, &f64)> = overrides
+        .iter()
+        .filter(|(model, _)| !DEFAULT_PRICES.iter().any(|(name, _)| name == model))
+        .collect();
+    extra.sort_by(|a, b| a.0.cmp(b.0));
+    let defaults = DEFAULT_PRICES
+        .iter()
+        .map(|&(model, price)| (model, *overrides.get(model).unwrap_or(&price)));
+    defaults
+        .chain(
+            extra
+                .into_iter()
+                .map(|(model, &price)| (model.as_str(), price)),
+        )
+        .map(|(model, price)| (model.to_string(), tokens as f64 * price / 1_000_000.0))
+        .collect()
+}
+
+/// `$12.35`, with more digits below a dollar so small exports don't all read `$0.00`.
+pub fn format_usd(amount: f64) -> String {
+    if amount < 1.0 {
+        format!("${:.4}", amount)
+    } else {
+        format!("${:.2}", amount)
+    }
+}
+
+#[test]
+fn test_estimate_costs() {
+    let overrides = HashMap::from([
+        ("gpt-4o".to_string(), 5.0),
+        ("local-llama".to_string(), 0.0),
+        ("acme-large".to_string(), 1.0),
+    ]);
+    let costs = estimate_costs(200_000, &overrides);
+    assert_eq!(costs.len(), DEFAULT_PRICES.len() + 2);
+    assert_eq!(costs[0], ("gpt-4o".to_string(), 1.0));
+    assert_eq!(costs[4], ("claude-sonnet".to_string(), 0.6));
+    assert_eq!(costs[8].0, "acme-large");
+    assert_eq!(costs[9], ("local-llama".to_string(), 0.0));
+    assert_eq!(format_usd(0.6), "$0.6000");
+    assert_eq!(format_usd(12.346), "$12.35");
+}
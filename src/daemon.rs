@@ -0,0 +1,270 @@
+use crate::compress;
+use crate::sniff::bytes_to_text;
+use crate::tokens::estimate_tokens;
+use crate::tree::Tree;
+use crate::types::{CollectOptions, CollectReport};
+use crate::{collect_files_parallel, write_markdown_document};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::warn;
+
+/// Decoded file contents, reused until the file's mtime or size changes. Token counts are
+/// a byte-length estimate and not worth caching; parse trees and the deps graph aren't
+/// kept either, since requests don't use them.
+#[derive(Default)]
+pub struct ReadCache {
+    entries: Mutex<HashMap<PathBuf, CachedRead>>,
+}
+
+struct CachedRead {
+    modified: SystemTime,
+    len: u64,
+    text: Result<String, &'static str>,
+}
+
+impl ReadCache {
+    /// Read and decode `path`, serving it from memory when it hasn't changed on disk.
+    pub fn read(&self, path: &Path, strict_utf8: bool) -> io::Result<Result<String, &'static str>> {
+        let md = fs::metadata(path)?;
+        let modified = md.modified()?;
+        if let Some(hit) = self.entries.lock().unwrap().get(path) {
+            if hit.modified == modified && hit.len == md.len() {
+                return Ok(hit.text.clone());
+            }
+        }
+        let text = bytes_to_text(fs::read(path)?, strict_utf8);
+        self.entries.lock().unwrap().insert(
+            path.to_path_buf(),
+            CachedRead {
+                modified,
+                len: md.len(),
+                text: text.clone(),
+            },
+        );
+        Ok(text)
+    }
+}
+
+/// Default socket location: `$TMPDIR/r2md.sock`.
+pub fn default_socket_path() -> PathBuf {
+    std::env::temp_dir().join("r2md.sock")
+}
+
+/// One request per line, e.g. `{"cmd": "stat", "path": "/repo"}`.
+#[derive(Debug, Deserialize)]
+struct DaemonRequest {
+    /// `stat`, `generate` or `shutdown`.
+    cmd: String,
+    /// Directory to export (default: the daemon's working directory).
+    path: Option<String>,
+    /// For `generate`: write the Markdown here instead of returning it inline.
+    output: Option<String>,
+}
+
+/// Serve `stat`/`generate` requests over a Unix socket, keeping file contents warm in
+/// memory between requests so repeated exports of a large repo only re-read what changed.
+#[cfg(unix)]
+pub fn run_daemon(socket: &Path, opts: &CollectOptions) -> Result<(), Box<dyn Error>> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    clear_stale_socket(socket)?;
+    let listener = UnixListener::bind(socket)?;
+    eprintln!("r2md daemon listening on {}", socket.display());
+    let cache = ReadCache::default();
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Daemon connection error: {}", e);
+                continue;
+            }
+        };
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(l) if l.trim().is_empty() => continue,
+                Ok(l) => l,
+                Err(_) => break,
+            };
+            let (response, shutdown) = match serde_json::from_str::<DaemonRequest>(&line) {
+                Ok(req) if req.cmd == "shutdown" => (json!({ "ok": true }), true),
+                Ok(req) => match handle_request(&req, opts, &cache) {
+                    Ok(v) => (v, false),
+                    Err(e) => (json!({ "ok": false, "error": e.to_string() }), false),
+                },
+                Err(e) => (
+                    json!({ "ok": false, "error": format!("Bad request: {}", e) }),
+                    false,
+                ),
+            };
+            if writeln!(writer, "{}", response).is_err() {
+                break;
+            }
+            if shutdown {
+                let _ = fs::remove_file(socket);
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove a socket left behind by a daemon that is gone. Anything else at `socket`, a
+/// regular file or the socket of a daemon still answering, is an error.
+#[cfg(unix)]
+fn clear_stale_socket(socket: &Path) -> Result<(), Box<dyn Error>> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixStream;
+
+    let Ok(md) = fs::symlink_metadata(socket) else {
+        return Ok(());
+    };
+    if !md.file_type().is_socket() {
+        return Err(format!("{} exists and is not a socket", socket.display()).into());
+    }
+    if UnixStream::connect(socket).is_ok() {
+        return Err(format!(
+            "An r2md daemon is already listening on {}",
+            socket.display()
+        )
+        .into());
+    }
+    fs::remove_file(socket)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn run_daemon(_socket: &Path, _opts: &CollectOptions) -> Result<(), Box<dyn Error>> {
+    Err("r2md daemon requires Unix domain sockets and is not available on this platform".into())
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+fn handle_request(
+    req: &DaemonRequest,
+    opts: &CollectOptions,
+    cache: &ReadCache,
+) -> Result<serde_json::Value, Box<dyn Error>> {
+    let root = PathBuf::from(req.path.as_deref().unwrap_or("."));
+    let mut report = CollectReport::default();
+    let files = collect_files_parallel(&root, opts, &mut report, Some(cache))?;
+    let tokens: usize = files.iter().map(|f| estimate_tokens(&f.content)).sum();
+    let bytes: usize = files.iter().map(|f| f.content.len()).sum();
+
+    match req.cmd.as_str() {
+        "stat" => Ok(json!({
+            "ok": true,
+            "files": files.len(),
+            "bytes": bytes,
+            "tokens": tokens,
+            "skipped": report.skipped,
+            "inaccessible": report.inaccessible.len(),
+        })),
+        "generate" => {
+            let mut doc = Vec::new();
+            write_markdown_document(
+                &mut doc,
+                &[Tree::new(&root, &files)],
+                opts,
+                &[],
+                &files,
+                &report,
+            )?;
+            let markdown = String::from_utf8_lossy(&doc).into_owned();
+            match &req.output {
+                Some(out) => {
+                    compress::write_file(out, &markdown)?;
+                    Ok(json!({ "ok": true, "files": files.len(), "tokens": tokens, "output": out }))
+                }
+                None => Ok(json!({
+                    "ok": true,
+                    "files": files.len(),
+                    "tokens": tokens,
+                    "markdown": markdown,
+                })),
+            }
+        }
+        other => Err(format!("Unknown command: {}", other).into()),
+    }
+}
+
+#[test]
+fn test_read_cache() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("a.rs");
+    fs::write(&path, "fn a() {}")?;
+    let cache = ReadCache::default();
+    assert_eq!(cache.read(&path, false)?, Ok("fn a() {}".to_string()));
+    assert_eq!(cache.entries.lock().unwrap().len(), 1);
+    assert_eq!(cache.read(&path, false)?, Ok("fn a() {}".to_string()));
+
+    // A different size invalidates the entry even within the mtime's resolution.
+    fs::write(&path, "fn a() { 1 }")?;
+    assert_eq!(cache.read(&path, false)?, Ok("fn a() { 1 }".to_string()));
+    fs::write(&path, b"\0\x01binary")?;
+    assert_eq!(cache.read(&path, false)?, Err("binary content"));
+    assert_eq!(cache.entries.lock().unwrap().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_handle_request() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("main.rs"), "fn main() {}\n")?;
+    let opts = CollectOptions::default();
+    let cache = ReadCache::default();
+    let request = |cmd: &str, output: Option<String>| DaemonRequest {
+        cmd: cmd.to_string(),
+        path: Some(dir.path().to_string_lossy().into_owned()),
+        output,
+    };
+
+    let stat = handle_request(&request("stat", None), &opts, &cache)?;
+    assert_eq!(stat["files"], 1);
+    assert_eq!(stat["bytes"], 13);
+    assert_eq!(stat["tokens"], 4);
+
+    let generated = handle_request(&request("generate", None), &opts, &cache)?;
+    assert!(generated["markdown"]
+        .as_str()
+        .is_some_and(|md| md.contains("fn main() {}")));
+    let out = dir.path().join("out.md");
+    let output = Some(out.to_string_lossy().into_owned());
+    handle_request(&request("generate", output), &opts, &cache)?;
+    assert!(fs::read_to_string(&out)?.contains("fn main() {}"));
+
+    assert!(handle_request(&request("nope", None), &opts, &cache).is_err());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_clear_stale_socket() -> Result<(), Box<dyn Error>> {
+    use std::os::unix::net::UnixListener;
+
+    let dir = tempfile::tempdir()?;
+    let socket = dir.path().join("r2md.sock");
+    clear_stale_socket(&socket)?;
+
+    fs::write(&socket, "not a socket")?;
+    assert!(clear_stale_socket(&socket).is_err());
+    assert!(socket.exists());
+    fs::remove_file(&socket)?;
+
+    let listener = UnixListener::bind(&socket)?;
+    assert!(clear_stale_socket(&socket).is_err());
+    assert!(socket.exists());
+
+    drop(listener);
+    clear_stale_socket(&socket)?;
+    assert!(!socket.exists());
+    Ok(())
+}
@@ -0,0 +1,191 @@
+//! Exact and near-duplicate detection for training data.
+//!
+//! Exact copies are caught by a content hash. Near-duplicates (vendored copies with a
+//! changed header, regenerated files) are caught with MinHash over word shingles, with
+//! locality-sensitive hashing so only files sharing a band get compared.
+
+use crate::types::FileEntry;
+use std::collections::HashMap;
+
+const NUM_HASHES: usize = 64;
+const BANDS: usize = 16;
+const ROWS: usize = NUM_HASHES / BANDS;
+const SHINGLE_WORDS: usize = 5;
+
+/// A file left out of the training data, and the earlier file it duplicates.
+#[derive(Debug, Clone)]
+pub struct Removed {
+    pub path: String,
+    pub duplicate_of: String,
+    /// 1.0 for exact copies, estimated Jaccard similarity otherwise
+    pub similarity: f64,
+}
+
+/// Keep the first of each group of duplicates, in input order. Files whose estimated
+/// similarity to a kept file reaches `threshold` are removed.
+pub fn dedup_files(files: &[FileEntry], threshold: f64) -> (Vec<FileEntry>, Vec<Removed>) {
+    let mut kept: Vec<FileEntry> = Vec::new();
+    let mut signatures: Vec<[u64; NUM_HASHES]> = Vec::new();
+    let mut removed = Vec::new();
+    let mut exact: HashMap<u64, usize> = HashMap::new();
+    let mut buckets: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+
+    for file in files {
+        let normalized = normalize(&file.content);
+        if let Some(&i) = exact.get(&fnv1a(normalized.as_bytes())) {
+            removed.push(Removed {
+                path: file.rel_path.clone(),
+                duplicate_of: kept[i].rel_path.clone(),
+                similarity: 1.0,
+            });
+            continue;
+        }
+
+        let signature = minhash(&normalized);
+        let band_keys: Vec<(usize, u64)> = (0..BANDS)
+            .map(|b| {
+                let rows = &signature[b * ROWS..(b + 1) * ROWS];
+                let bytes: Vec<u8> = rows.iter().flat_map(|h| h.to_le_bytes()).collect();
+                (b, fnv1a(&bytes))
+            })
+            .collect();
+        let mut best: Option<(usize, f64)> = None;
+        for key in &band_keys {
+            for &i in buckets.get(key).into_iter().flatten() {
+                let similarity = estimate_similarity(&signature, &signatures[i]);
+                if similarity >= threshold && best.is_none_or(|(_, s)| similarity > s) {
+                    best = Some((i, similarity));
+                }
+            }
+        }
+        if let Some((i, similarity)) = best {
+            removed.push(Removed {
+                path: file.rel_path.clone(),
+                duplicate_of: kept[i].rel_path.clone(),
+                similarity,
+            });
+            continue;
+        }
+
+        let index = kept.len();
+        exact.insert(fnv1a(normalized.as_bytes()), index);
+        for key in band_keys {
+            buckets.entry(key).or_default().push(index);
+        }
+        signatures.push(signature);
+        kept.push(file.clone());
+    }
+    (kept, removed)
+}
+
+/// For each file, the first earlier file with exactly the same content, for
+/// `--dedup-files`. Blank files are never duplicates: an empty `__init__.py` is not a
+/// vendored copy.
+pub fn identical_files(files: &[FileEntry]) -> Vec<Option<usize>> {
+    let mut firsts: HashMap<u64, Vec<usize>> = HashMap::new();
+    files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| {
+            if file.content.trim().is_empty() {
+                return None;
+            }
+            let same_hash = firsts.entry(fnv1a(file.content.as_bytes())).or_default();
+            match same_hash
+                .iter()
+                .find(|&&j| files[j].content == file.content)
+            {
+                Some(&j) => Some(j),
+                None => {
+                    same_hash.push(i);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Whitespace-insensitive form, so reindented or CRLF copies count as exact.
+fn normalize(content: &str) -> String {
+    content.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn minhash(normalized: &str) -> [u64; NUM_HASHES] {
+    let words: Vec<&str> = normalized.split(' ').collect();
+    let mut signature = [u64::MAX; NUM_HASHES];
+    let shingles = words.windows(SHINGLE_WORDS.min(words.len()).max(1));
+    for shingle in shingles {
+        let base = fnv1a(shingle.join(" ").as_bytes());
+        for (seed, slot) in signature.iter_mut().enumerate() {
+            // Cheap independent-enough hash family: remix the base hash per seed.
+            let h = mix(base ^ (seed as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            *slot = (*slot).min(h);
+        }
+    }
+    signature
+}
+
+fn estimate_similarity(a: &[u64; NUM_HASHES], b: &[u64; NUM_HASHES]) -> f64 {
+    a.iter().zip(b).filter(|(x, y)| x == y).count() as f64 / NUM_HASHES as f64
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// splitmix64 finalizer
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[test]
+fn test_dedup_exact_and_near() {
+    let body: String = (0..200)
+        .map(|i| format!("let v{} = compute({});\n", i, i))
+        .collect();
+    let file = |path: &str, content: String| FileEntry {
+        rel_path: path.into(),
+        content,
+        ..Default::default()
+    };
+    let files = vec![
+        file("a.rs", body.clone()),
+        file("vendor/a.rs", body.replace('\n', "\r\n")),
+        file("b.rs", format!("// Generated, do not edit\n{}", body)),
+        file("c.rs", "fn unrelated() { println!(\"hi\"); }".into()),
+    ];
+    let (kept, removed) = dedup_files(&files, 0.85);
+    let kept: Vec<_> = kept.iter().map(|f| f.rel_path.as_str()).collect();
+    assert_eq!(kept, vec!["a.rs", "c.rs"]);
+    assert_eq!(removed[0].similarity, 1.0);
+    assert_eq!(removed[1].path, "b.rs");
+    assert_eq!(removed[1].duplicate_of, "a.rs");
+}
+
+#[test]
+fn test_identical_files() {
+    let file = |path: &str, content: &str| FileEntry {
+        rel_path: path.into(),
+        content: content.into(),
+        ..Default::default()
+    };
+    let files = [
+        file("a/lib.js", "export const x = 1;\n"),
+        file("a/__init__.py", ""),
+        file("b/lib.js", "export const x = 1;\n"),
+        file("b/__init__.py", ""),
+        file("c/lib.js", "export const x = 1;\r\n"),
+        file("d/lib.js", "export const x = 1;\n"),
+    ];
+    assert_eq!(
+        identical_files(&files),
+        [None, None, Some(0), None, None, Some(0)]
+    );
+}
@@ -2,17 +2,17 @@
 use crate::types::FileEntry;
 use anyhow::{anyhow, Result};
 use petgraph::algo::toposort;
-use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::graph::DiGraph;
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 // Extract dependencies from a file based on its language
-fn extract_dependencies(file_path: &Path, content: &str) -> Vec<String> {
+pub fn extract_dependencies(file_path: &Path, content: &str) -> Vec<String> {
     let ext = file_path.extension().and_then(|s| s.to_str()).unwrap_or("");
     match ext {
         "rs" => extract_rust_dependencies(content),
         "py" => extract_python_dependencies(content),
-        "js" | "ts" => extract_js_ts_dependencies(content),
+        "js" | "ts" | "jsx" | "tsx" | "vue" | "svelte" => extract_js_ts_dependencies(content),
         "java" => extract_java_dependencies(content),
         _ => vec![], // Unsupported file types return empty list
     }
@@ -54,7 +54,10 @@ fn extract_js_ts_dependencies(content: &str) -> Vec<String> {
     for line in content.lines() {
         if line.trim().starts_with("import ") {
             if let Some(dep) = line.split(['"', '\'']).nth(1) {
-                let dep_path = if dep.ends_with(".js") || dep.ends_with(".ts") {
+                let dep_path = if [".js", ".ts", ".jsx", ".tsx"]
+                    .iter()
+                    .any(|ext| dep.ends_with(ext))
+                {
                     dep.to_string()
                 } else {
                     dep.to_string() + ".js" // Default to .js if no extension
@@ -81,6 +84,34 @@ fn extract_java_dependencies(content: &str) -> Vec<String> {
     dependencies
 }
 
+/// `(importer, imported)` pairs of indices into `files`. Import targets are guesses, so a
+/// file counts as imported when its stem is a component of the import path.
+pub fn import_edges(files: &[&FileEntry]) -> Vec<(usize, usize)> {
+    let stems: Vec<&str> = files.iter().map(|f| file_stem(&f.rel_path)).collect();
+    let mut edges = HashSet::new();
+    for (i, file) in files.iter().enumerate() {
+        for dep in extract_dependencies(Path::new(&file.rel_path), &file.content) {
+            let dep = dep.rsplit_once('.').map_or(dep.as_str(), |(path, _)| path);
+            let parts: HashSet<&str> = dep.split(['/', '.']).collect();
+            for (j, stem) in stems.iter().enumerate() {
+                if i != j && parts.contains(stem) {
+                    edges.insert((i, j));
+                }
+            }
+        }
+    }
+    let mut edges: Vec<(usize, usize)> = edges.into_iter().collect();
+    edges.sort();
+    edges
+}
+
+pub fn file_stem(rel_path: &str) -> &str {
+    Path::new(rel_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+}
+
 // Build the dependency graph
 fn build_dependency_graph(files: &[FileEntry]) -> Result<DiGraph<PathBuf, ()>> {
     let mut graph = DiGraph::new();
@@ -122,7 +153,7 @@ pub fn sort_files_by_dependency(files: &[FileEntry]) -> Result<Vec<FileEntry>> {
             let path = &graph[index];
             files
                 .iter()
-                .find(|f| PathBuf::from(&f.rel_path) == *path)
+                .find(|f| Path::new(&f.rel_path) == path)
                 .unwrap()
                 .clone()
         })
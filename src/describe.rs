@@ -0,0 +1,201 @@
+//! `--describe-files`: a one-paragraph description under each file heading, taken from
+//! what the file says about itself: a Rust file's `//!` docs, a Python module docstring,
+//! or the comment at the top of a C-like or shell-like file.
+//!
+//! License headers and tool directives (`// eslint-disable`, `//go:build`) are not
+//! descriptions, so they are skipped in favor of the comment after them.
+
+use crate::license::is_license;
+
+/// Longest description kept, in characters; longer ones are cut at a word.
+const MAX_CHARS: usize = 240;
+
+/// The first paragraph of `content`'s header documentation, on one line.
+pub fn file_description(content: &str, lang: &str) -> Option<String> {
+    let text = match lang {
+        "rust" => rust_docs(content),
+        "python" => python_docstring(content),
+        "javascript" | "typescript" | "jsx" | "tsx" | "java" | "c" | "cpp" | "csharp" | "go"
+        | "kotlin" | "scala" | "swift" | "php" | "objectivec" | "protobuf" => {
+            header_comment(content, &["///", "//"], true)
+        }
+        "bash" | "ruby" | "hcl" => header_comment(content, &["#"], false),
+        "sql" => header_comment(content, &["--"], true),
+        _ => None,
+    }?;
+    shorten(&first_paragraph(&text))
+}
+
+/// Leading `//!` lines or a `/*! ... */` block, past plain comments above them.
+fn rust_docs(content: &str) -> Option<String> {
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .skip_while(|l| l.is_empty() || (l.starts_with("//") && !l.starts_with("//!")));
+    let first = lines.next()?;
+    if let Some(rest) = first.strip_prefix("/*!") {
+        return Some(block_text(rest, lines));
+    }
+    let mut text = vec![first.strip_prefix("//!")?];
+    text.extend(lines.map_while(|l| l.strip_prefix("//!")));
+    Some(text.join("\n"))
+}
+
+/// The module docstring: the string literal that is the file's first statement.
+fn python_docstring(content: &str) -> Option<String> {
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .skip_while(|l| l.is_empty() || l.starts_with('#'));
+    let first = lines.next()?;
+    let first = first.trim_start_matches(['r', 'R', 'u', 'U']);
+    let quote = ["\"\"\"", "'''", "\"", "'"]
+        .into_iter()
+        .find(|q| first.starts_with(q))?;
+    let rest = &first[quote.len()..];
+    if let Some(end) = rest.find(quote) {
+        return Some(rest[..end].to_string());
+    }
+    if quote.len() == 1 {
+        return None;
+    }
+    let mut text = vec![rest];
+    for line in lines {
+        match line.find(quote) {
+            Some(end) => {
+                text.push(&line[..end]);
+                return Some(text.join("\n"));
+            }
+            None => text.push(line),
+        }
+    }
+    None
+}
+
+/// The first comment at the top of the file that is neither a license nor directives,
+/// made of `markers` line comments or, with `blocks`, a `/* ... */` block.
+fn header_comment(content: &str, markers: &[&str], blocks: bool) -> Option<String> {
+    let mut lines = content.lines().map(str::trim).peekable();
+    // A shebang or PHP's opening tag come before any header.
+    lines.next_if(|l| l.starts_with("#!") || l.starts_with("<?php"));
+    loop {
+        while lines.next_if(|l| l.is_empty()).is_some() {}
+        let first = lines.next()?;
+        let text = if let Some(rest) = first.strip_prefix("/*").filter(|_| blocks) {
+            block_text(rest.trim_start_matches('*'), &mut lines)
+        } else {
+            let mut text = vec![strip_marker(first, markers)?];
+            while let Some(line) = lines.next_if(|l| strip_marker(l, markers).is_some()) {
+                text.extend(strip_marker(line, markers));
+            }
+            text.join("\n")
+        };
+        if !is_license(&text) && !is_directives(&text) {
+            return Some(text);
+        }
+    }
+}
+
+fn strip_marker<'a>(line: &'a str, markers: &[&str]) -> Option<&'a str> {
+    markers.iter().find_map(|m| line.strip_prefix(m))
+}
+
+/// The text of a block comment from just after its opener, with ` * ` gutters removed
+/// and JSDoc tags other than `@file`/`@fileoverview` left out.
+fn block_text<'a>(first: &'a str, rest: impl Iterator<Item = &'a str>) -> String {
+    let mut text = Vec::new();
+    let mut tagged = false;
+    for line in std::iter::once(first).chain(rest) {
+        let (line, done) = match line.find("*/") {
+            Some(end) => (&line[..end], true),
+            None => (line, false),
+        };
+        let line = line.trim().trim_start_matches('*').trim();
+        let line = ["@fileoverview", "@file"]
+            .iter()
+            .find_map(|tag| line.strip_prefix(tag))
+            .unwrap_or(line);
+        // The description ends at the first other tag; the rest is read to the `*/`.
+        tagged |= line.starts_with('@');
+        if !tagged {
+            text.push(line);
+        }
+        if done {
+            break;
+        }
+    }
+    text.join("\n")
+}
+
+/// Comments meant for tools: `eslint-disable`, `@ts-check`, `go:build`, `-*- coding -*-`, ...
+fn is_directives(text: &str) -> bool {
+    text.lines().map(str::trim).all(|line| {
+        line.is_empty()
+            || line.starts_with('@')
+            || line.starts_with("-*-")
+            || line.starts_with("go:")
+            || line.starts_with("+build")
+            || line.starts_with("eslint")
+            || line.starts_with("prettier-")
+            || line.starts_with("<reference")
+    })
+}
+
+/// The first paragraph of `text` with its lines joined, minus a Markdown heading marker.
+fn first_paragraph(text: &str) -> String {
+    let paragraph: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .skip_while(|l| l.is_empty())
+        .take_while(|l| !l.is_empty())
+        .collect();
+    let joined = paragraph.join(" ");
+    joined.trim_start_matches('#').trim_start().to_string()
+}
+
+/// `text`, or its words up to `MAX_CHARS` followed by `…`; `None` if there is nothing.
+fn shorten(text: &str) -> Option<String> {
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= MAX_CHARS {
+        return Some(text.to_string());
+    }
+    let cut: String = text.chars().take(MAX_CHARS).collect();
+    let cut = cut
+        .rsplit_once(' ')
+        .map_or(cut.as_str(), |(words, _)| words);
+    Some(format!("{}…", cut.trim_end_matches([',', ';', ':'])))
+}
+
+#[test]
+fn test_file_description() {
+    let rust = "// Licensed under MIT\n\n//! Chunking of source files.\n//!\n//! More detail.\n\nuse std::fmt;\n";
+    assert_eq!(
+        file_description(rust, "rust").as_deref(),
+        Some("Chunking of source files.")
+    );
+    let python = "#!/usr/bin/env python\n# -*- coding: utf-8 -*-\n\"\"\"\nFetch the data\nand clean it.\n\nUsage: ...\n\"\"\"\nimport os\n";
+    assert_eq!(
+        file_description(python, "python").as_deref(),
+        Some("Fetch the data and clean it.")
+    );
+    let js = "/*\n * Copyright 2024 Acme\n */\n// eslint-disable no-console\n\n/**\n * @fileoverview Routes for the API,\n * one per resource.\n * @module routes\n */\nexport {};\n";
+    assert_eq!(
+        file_description(js, "javascript").as_deref(),
+        Some("Routes for the API, one per resource.")
+    );
+    let go = "//go:build linux\n\n// Package server runs the HTTP server.\npackage server\n";
+    assert_eq!(
+        file_description(go, "go").as_deref(),
+        Some("Package server runs the HTTP server.")
+    );
+    assert_eq!(file_description("package main\n// late\n", "go"), None);
+    assert_eq!(
+        file_description("x = 1\n\"\"\"not a docstring\"\"\"\n", "python"),
+        None
+    );
+    let long = format!("//! {}\n", "word ".repeat(100));
+    let short = file_description(&long, "rust").unwrap();
+    assert!(short.ends_with("word…") && short.chars().count() <= MAX_CHARS + 1);
+}
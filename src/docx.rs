@@ -0,0 +1,159 @@
+//! `--format docx`: the export as a Word document, for reviewers who only take `.docx`.
+//! Each file is a heading followed by its lines as monospace paragraphs.
+//!
+//! Like the EPUB, the package is written directly with `zip`: a document this plain needs
+//! only five small XML parts.
+
+use crate::epub::escape_xml;
+use crate::metadata::Metadata;
+use crate::types::FileEntry;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+  <Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+  <Default Extension="xml" ContentType="application/xml"/>
+  <Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>
+  <Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/>
+</Types>
+"#;
+
+const PACKAGE_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+  <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+</Relationships>
+"#;
+
+const DOCUMENT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+  <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+</Relationships>
+"#;
+
+const WORD_NS: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+
+/// Write `files` as a `.docx`. `lang` is the document's language tag (`en`, `de`).
+pub fn write_docx_file(
+    files: &[FileEntry],
+    directories: &[PathBuf],
+    output_file_name: &str,
+    lang: &str,
+) -> Result<(), Box<dyn Error>> {
+    let metadata = Metadata::new(directories, files.len(), 0);
+    let title = format!("r2md: {}", metadata.repo);
+
+    let mut body = paragraph("Title", &title);
+    for file in files {
+        body.push_str(&paragraph("Heading1", &file.rel_path));
+        for line in file.content.lines() {
+            body.push_str(&paragraph("Code", line));
+        }
+    }
+
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(output_file_name)?));
+    let deflated = SimpleFileOptions::default();
+    zip.start_file("[Content_Types].xml", deflated)?;
+    zip.write_all(CONTENT_TYPES.as_bytes())?;
+    zip.start_file("_rels/.rels", deflated)?;
+    zip.write_all(PACKAGE_RELS.as_bytes())?;
+    zip.start_file("word/_rels/document.xml.rels", deflated)?;
+    zip.write_all(DOCUMENT_RELS.as_bytes())?;
+    zip.start_file("word/styles.xml", deflated)?;
+    zip.write_all(styles(lang).as_bytes())?;
+    zip.start_file("word/document.xml", deflated)?;
+    write!(
+        zip,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<w:document xmlns:w=\"{}\">\n<w:body>\n{}<w:sectPr/>\n</w:body>\n</w:document>\n",
+        WORD_NS, body
+    )?;
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+/// One paragraph in `style`. Tabs become Word tabs; other whitespace is kept as is.
+fn paragraph(style: &str, text: &str) -> String {
+    let runs: Vec<String> = text
+        .split('\t')
+        .map(|part| format!("<w:t xml:space=\"preserve\">{}</w:t>", escape_xml(part)))
+        .collect();
+    format!(
+        "<w:p><w:pPr><w:pStyle w:val=\"{}\"/></w:pPr><w:r>{}</w:r></w:p>\n",
+        style,
+        runs.join("<w:tab/>")
+    )
+}
+
+/// `styles.xml`: the title, one heading level for paths, and a compact monospace style
+/// for code lines.
+fn styles(lang: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="{ns}">
+  <w:docDefaults>
+    <w:rPrDefault><w:rPr><w:sz w:val="22"/><w:lang w:val="{lang}"/></w:rPr></w:rPrDefault>
+  </w:docDefaults>
+  <w:style w:type="paragraph" w:default="1" w:styleId="Normal">
+    <w:name w:val="Normal"/>
+  </w:style>
+  <w:style w:type="paragraph" w:styleId="Title">
+    <w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/>
+    <w:pPr><w:spacing w:after="240"/></w:pPr>
+    <w:rPr><w:sz w:val="48"/></w:rPr>
+  </w:style>
+  <w:style w:type="paragraph" w:styleId="Heading1">
+    <w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:next w:val="Code"/>
+    <w:pPr><w:keepNext/><w:spacing w:before="360" w:after="120"/><w:outlineLvl w:val="0"/></w:pPr>
+    <w:rPr><w:b/><w:sz w:val="28"/></w:rPr>
+  </w:style>
+  <w:style w:type="paragraph" w:styleId="Code">
+    <w:name w:val="Code"/><w:basedOn w:val="Normal"/>
+    <w:pPr><w:spacing w:before="0" w:after="0" w:line="240" w:lineRule="auto"/></w:pPr>
+    <w:rPr><w:rFonts w:ascii="Consolas" w:hAnsi="Consolas" w:cs="Consolas"/><w:sz w:val="18"/></w:rPr>
+  </w:style>
+</w:styles>
+"#,
+        ns = WORD_NS,
+        lang = escape_xml(lang)
+    )
+}
+
+#[test]
+fn test_docx_layout() -> Result<(), Box<dyn Error>> {
+    use std::io::Read;
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("review.docx");
+    let files = vec![FileEntry {
+        rel_path: "src/a&b.rs".to_string(),
+        content: "fn main() {\n\tif 1 < 2 {}\n}\n".to_string(),
+        ..Default::default()
+    }];
+    write_docx_file(
+        &files,
+        &[dir.path().to_path_buf()],
+        &path.to_string_lossy(),
+        "en",
+    )?;
+
+    let mut archive = zip::ZipArchive::new(File::open(&path)?)?;
+    for part in ["[Content_Types].xml", "_rels/.rels", "word/styles.xml"] {
+        assert!(archive.by_name(part).is_ok(), "{}", part);
+    }
+    let mut document = String::new();
+    archive
+        .by_name("word/document.xml")?
+        .read_to_string(&mut document)?;
+    assert!(document.contains(
+        "<w:pStyle w:val=\"Heading1\"/></w:pPr><w:r><w:t xml:space=\"preserve\">src/a&amp;b.rs</w:t>"
+    ));
+    assert!(document.contains(
+        "<w:t xml:space=\"preserve\"></w:t><w:tab/><w:t xml:space=\"preserve\">if 1 &lt; 2 {}</w:t>"
+    ));
+    assert_eq!(document.matches("w:val=\"Code\"").count(), 3);
+    Ok(())
+}
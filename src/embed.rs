@@ -0,0 +1,158 @@
+//! `--chunks`: the export as token-bounded, overlapping windows in JSONL, ready to embed
+//! for retrieval. Windows break between the units `layout_units` keeps whole (top-level
+//! definitions, blank-line separated blocks); a unit is only split by line when it is
+//! bigger than a window on its own.
+
+use crate::chunks::layout_units;
+use crate::compress::OutputFile;
+use crate::language_from_path;
+use crate::schema::SCHEMA_VERSION;
+use crate::tokens::estimate_tokens;
+use crate::types::FileEntry;
+use serde::Serialize;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+
+/// One JSONL record. Lines are 1-based and inclusive; bytes are offsets into the file's
+/// exported content, end exclusive.
+#[derive(Serialize)]
+struct EmbedChunk<'a> {
+    schema_version: u32,
+    path: &'a str,
+    language: &'a str,
+    /// Position of the chunk within its file, from 0
+    index: usize,
+    start_line: usize,
+    end_line: usize,
+    start_byte: usize,
+    end_byte: usize,
+    tokens: usize,
+    text: &'a str,
+}
+
+/// Write the chunks of `files` to `output_file_name`, one JSON object per line, and return
+/// how many were written. `overlap` tokens of whole lines from the end of each window are
+/// repeated at the start of the next one in the same file.
+pub fn write_chunks(
+    files: &[FileEntry],
+    output_file_name: &str,
+    size: usize,
+    overlap: usize,
+) -> Result<usize, Box<dyn Error>> {
+    let mut out = OutputFile::create(output_file_name)?;
+    let mut count = 0;
+    for file in files {
+        let lang = language_from_path(Path::new(&file.rel_path));
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(file.content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        for (index, (start_line, end_line)) in windows(&file.content, lang, size, overlap)
+            .into_iter()
+            .enumerate()
+        {
+            let start_byte = line_starts[start_line - 1];
+            let end_byte = line_starts
+                .get(end_line)
+                .copied()
+                .unwrap_or(file.content.len());
+            let text = &file.content[start_byte..end_byte];
+            let record = EmbedChunk {
+                schema_version: SCHEMA_VERSION,
+                path: &file.rel_path,
+                language: lang,
+                index,
+                start_line,
+                end_line,
+                start_byte,
+                end_byte,
+                tokens: estimate_tokens(text),
+                text,
+            };
+            writeln!(out, "{}", serde_json::to_string(&record)?)?;
+            count += 1;
+        }
+    }
+    out.finish()?;
+    Ok(count)
+}
+
+/// Line ranges of the windows covering `content`. A single line longer than `size` still
+/// becomes one (oversized) window.
+fn windows(content: &str, lang: &str, size: usize, overlap: usize) -> Vec<(usize, usize)> {
+    // `prefix[n]`: tokens of lines 1..=n.
+    let mut prefix = vec![0];
+    for line in content.split_inclusive('\n') {
+        prefix.push(prefix[prefix.len() - 1] + estimate_tokens(line));
+    }
+    let cost = |start: usize, end: usize| prefix[end] - prefix[start - 1];
+
+    let units = layout_units(content, lang)
+        .into_iter()
+        .flat_map(|(start, end)| {
+            if cost(start, end) > size {
+                (start..=end).map(|n| (n, n)).collect()
+            } else {
+                vec![(start, end)]
+            }
+        });
+
+    let mut windows = Vec::new();
+    let mut window: Option<(usize, usize)> = None;
+    for (start, end) in units {
+        let unit = cost(start, end);
+        if let Some((from, to)) = window {
+            if cost(from, to) + unit <= size {
+                window = Some((from, end));
+                continue;
+            }
+            windows.push((from, to));
+            // Step back over whole lines while they fit the overlap and leave room for the
+            // unit, never repeating the entire previous window.
+            let mut next = to + 1;
+            while next - 1 > from
+                && cost(next - 1, to) <= overlap
+                && cost(next - 1, to) + unit <= size
+            {
+                next -= 1;
+            }
+            window = Some((next, end));
+        } else {
+            window = Some((start, end));
+        }
+    }
+    windows.extend(window);
+    windows
+}
+
+#[test]
+fn test_windows_overlap_and_cover_the_file() -> Result<(), Box<dyn Error>> {
+    // Four functions of 7 tokens, each unit keeping the blank line after it: 8+8+8+7.
+    let content: String = (0..4)
+        .map(|i| format!("fn f{}() {{\n    x();\n}}\n", i))
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert_eq!(windows(&content, "rust", 17, 0), vec![(1, 8), (9, 15)]);
+    // The closing brace and blank line of the previous window are repeated.
+    assert_eq!(windows(&content, "rust", 17, 2), vec![(1, 8), (7, 15)]);
+    // A function bigger than a window is split by line.
+    assert_eq!(windows(&content, "rust", 5, 0)[..2], [(1, 1), (2, 4)]);
+    assert!(windows("", "rust", 10, 2).is_empty());
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("chunks.jsonl");
+    let files = [FileEntry {
+        rel_path: "src/lib.rs".to_string(),
+        content,
+        ..Default::default()
+    }];
+    assert_eq!(write_chunks(&files, &path.to_string_lossy(), 17, 0)?, 2);
+    let text = std::fs::read_to_string(&path)?;
+    let second: serde_json::Value = serde_json::from_str(text.lines().nth(1).unwrap_or(""))?;
+    assert_eq!(second["start_line"], 9);
+    assert_eq!(
+        &files[0].content[second["start_byte"].as_u64().unwrap() as usize..],
+        second["text"].as_str().unwrap()
+    );
+    Ok(())
+}
@@ -0,0 +1,219 @@
+//! `--format epub`: the export as an EPUB 3 book with one chapter per file, for reading
+//! a codebase on a tablet or e-reader.
+
+use crate::metadata::Metadata;
+use crate::types::FileEntry;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use syntect::highlighting::Theme;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+const STYLESHEET: &str = "body { margin: 0 1em; }\n\
+h1 { font-size: 1.1em; word-break: break-all; }\n\
+pre { font-size: 0.75em; white-space: pre-wrap; word-wrap: break-word; padding: 0.5em; }\n";
+
+/// Write `files` as an EPUB. `lang` is the book's language tag (`en`, `de`).
+pub fn write_epub_file(
+    files: &[FileEntry],
+    directories: &[PathBuf],
+    output_file_name: &str,
+    lang: &str,
+    theme: &Theme,
+) -> Result<(), Box<dyn Error>> {
+    let ss = SyntaxSet::load_defaults_newlines();
+    let metadata = Metadata::new(directories, files.len(), 0);
+    let title = format!("r2md: {}", metadata.repo);
+
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(output_file_name)?));
+    // The mimetype entry must come first and be stored uncompressed.
+    zip.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(b"application/epub+zip")?;
+    let deflated = SimpleFileOptions::default();
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER_XML.as_bytes())?;
+    zip.start_file("OEBPS/style.css", deflated)?;
+    zip.write_all(STYLESHEET.as_bytes())?;
+
+    for (i, file) in files.iter().enumerate() {
+        let ext = Path::new(&file.rel_path)
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("");
+        let syntax = ss
+            .find_syntax_by_extension(ext)
+            .unwrap_or_else(|| ss.find_syntax_plain_text());
+        let body = format!(
+            "<h1>{}</h1>\n{}",
+            escape_xml(&file.rel_path),
+            highlighted_html_for_string(&xml_chars(&file.content), &ss, syntax, theme)?
+        );
+        zip.start_file(chapter_name(i), deflated)?;
+        zip.write_all(xhtml_page(&file.rel_path, lang, &body).as_bytes())?;
+    }
+
+    let toc: String = files
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            format!(
+                "  <li><a href=\"{}\">{}</a></li>\n",
+                chapter_file(i),
+                escape_xml(&f.rel_path)
+            )
+        })
+        .collect();
+    let nav = format!(
+        "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{}</ol>\n</nav>",
+        escape_xml(&title),
+        toc
+    );
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(xhtml_page(&title, lang, &nav).as_bytes())?;
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(package_document(files, &metadata, &title, lang).as_bytes())?;
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+fn chapter_file(index: usize) -> String {
+    format!("file-{:05}.xhtml", index + 1)
+}
+
+fn chapter_name(index: usize) -> String {
+    format!("OEBPS/{}", chapter_file(index))
+}
+
+fn xhtml_page(title: &str, lang: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{lang}" lang="{lang}">
+<head>
+  <meta charset="UTF-8"/>
+  <title>{title}</title>
+  <link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        lang = lang,
+        title = escape_xml(title),
+        body = body
+    )
+}
+
+/// `content.opf`: metadata, the manifest of every entry, and the reading order.
+fn package_document(files: &[FileEntry], metadata: &Metadata, title: &str, lang: &str) -> String {
+    // Stable identifier: the same export gives the same book id.
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(file.rel_path.as_bytes());
+        hasher.update(file.content.as_bytes());
+    }
+    let id = format!("urn:r2md:{:x}", hasher.finalize());
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    for i in 0..files.len() {
+        manifest.push_str(&format!(
+            "    <item id=\"f{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            i + 1,
+            chapter_file(i)
+        ));
+        spine.push_str(&format!("    <itemref idref=\"f{}\"/>\n", i + 1));
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{id}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>{lang}</dc:language>
+    <dc:creator>r2md {version}</dc:creator>
+    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="css" href="style.css" media-type="text/css"/>
+{manifest}  </manifest>
+  <spine>
+    <itemref idref="nav"/>
+{spine}  </spine>
+</package>
+"#,
+        id = id,
+        title = escape_xml(title),
+        lang = lang,
+        version = metadata.version,
+        modified = metadata.generated,
+        manifest = manifest,
+        spine = spine
+    )
+}
+
+/// Drop control characters XML 1.0 doesn't allow; e-readers reject the whole chapter.
+fn xml_chars(text: &str) -> String {
+    text.chars()
+        .filter(|&c| c >= ' ' || matches!(c, '\t' | '\n' | '\r'))
+        .collect()
+}
+
+pub fn escape_xml(text: &str) -> String {
+    xml_chars(text)
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn test_epub_layout() -> Result<(), Box<dyn Error>> {
+    use std::io::Read;
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("book.epub");
+    let files = vec![FileEntry {
+        rel_path: "src/a&b.rs".to_string(),
+        content: "fn main() { if 1 < 2 {} }\n".to_string(),
+        ..Default::default()
+    }];
+    write_epub_file(
+        &files,
+        &[dir.path().to_path_buf()],
+        &path.to_string_lossy(),
+        "en",
+        &crate::theme::load_theme(None)?,
+    )?;
+
+    let mut archive = zip::ZipArchive::new(File::open(&path)?)?;
+    let first = archive.by_index(0)?;
+    assert_eq!(first.name(), "mimetype");
+    assert_eq!(first.compression(), CompressionMethod::Stored);
+    drop(first);
+    let mut chapter = String::new();
+    archive
+        .by_name("OEBPS/file-00001.xhtml")?
+        .read_to_string(&mut chapter)?;
+    assert!(chapter.contains("<h1>src/a&amp;b.rs</h1>"));
+    assert!(chapter.contains("&lt;"));
+    assert!(!chapter.contains("1 < 2"));
+    Ok(())
+}
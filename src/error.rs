@@ -0,0 +1,104 @@
+use crate::types::InaccessiblePath;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+/// Why a single path ended up missing from the output.
+#[derive(Debug, Error)]
+pub enum R2mdError {
+    /// The walker could not enter or stat the path.
+    #[error("{}: inaccessible ({})", .0.path, .0.reason)]
+    Inaccessible(InaccessiblePath),
+    #[error("{path}: unreadable ({source})")]
+    Unreadable {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{path}: too large ({size} bytes, limit {limit})")]
+    TooLarge { path: String, size: u64, limit: u64 },
+    #[error("{path}: {reason}")]
+    Decode { path: String, reason: &'static str },
+    /// A `preprocess:` hook failed to run or exited non-zero.
+    #[error("{path}: preprocess hook failed ({message})")]
+    Preprocess { path: String, message: String },
+}
+
+impl R2mdError {
+    pub fn path(&self) -> &str {
+        match self {
+            R2mdError::Inaccessible(p) => &p.path,
+            R2mdError::Unreadable { path, .. }
+            | R2mdError::TooLarge { path, .. }
+            | R2mdError::Decode { path, .. }
+            | R2mdError::Preprocess { path, .. } => path,
+        }
+    }
+
+    /// Stable identifier used in the JSON report.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            R2mdError::Inaccessible(_) => "inaccessible",
+            R2mdError::Unreadable { .. } => "unreadable",
+            R2mdError::TooLarge { .. } => "too_large",
+            R2mdError::Decode { .. } => "decode",
+            R2mdError::Preprocess { .. } => "preprocess",
+        }
+    }
+
+    /// Whether the path failed, as opposed to being left out for its size or content.
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            R2mdError::Inaccessible(_)
+                | R2mdError::Unreadable { .. }
+                | R2mdError::Preprocess { .. }
+        )
+    }
+
+    /// The Markdown `## Inaccessible Paths` entry for failures that are access problems.
+    pub fn as_inaccessible(&self) -> Option<InaccessiblePath> {
+        match self {
+            R2mdError::Inaccessible(p) => Some(p.clone()),
+            R2mdError::Unreadable { path, source } => Some(InaccessiblePath {
+                path: path.clone(),
+                reason: source.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "path": self.path(),
+            "kind": self.kind(),
+            "message": self.to_string(),
+        })
+    }
+}
+
+/// Exit status for any error not listed below: bad arguments or config, unwritable output.
+pub const EXIT_ERROR: i32 = 1;
+/// Exit status when `--fail-if-empty` is given and no file is left to export.
+pub const EXIT_EMPTY: i32 = 3;
+/// Exit status when the export was written but some paths failed (see `is_failure`).
+pub const EXIT_PARTIAL: i32 = 4;
+/// Exit status when a remote input, issue or pull request could not be downloaded.
+pub const EXIT_DOWNLOAD_FAILED: i32 = 5;
+
+/// Why the whole run stopped, for failures with an exit status of their own.
+#[derive(Debug, Error)]
+pub enum RunError {
+    #[error("{0}")]
+    Download(String),
+    #[error("No files to export ({skipped} skipped); check the input paths, --include, --exclude and ignore_patterns")]
+    Empty { skipped: usize },
+}
+
+impl RunError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RunError::Download(_) => EXIT_DOWNLOAD_FAILED,
+            RunError::Empty { .. } => EXIT_EMPTY,
+        }
+    }
+}
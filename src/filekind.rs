@@ -0,0 +1,114 @@
+//! `--no-tests` and `--no-generated`: leave out test code and generated code, recognized
+//! by naming conventions and, for generated files, by the marker generators write near the
+//! top. `test_patterns`, `generated_patterns` and `generated_markers` in `r2md.yml`
+//! replace the built-in lists.
+
+use crate::patterns::PathPatterns;
+
+/// Test files and directories across the common ecosystems, in gitignore syntax.
+pub const TEST_PATTERNS: &[&str] = &[
+    "*_test.go",
+    "test_*.py",
+    "*_test.py",
+    "conftest.py",
+    "*.spec.js",
+    "*.spec.jsx",
+    "*.spec.ts",
+    "*.spec.tsx",
+    "*.test.js",
+    "*.test.jsx",
+    "*.test.ts",
+    "*.test.tsx",
+    "*Test.java",
+    "*Tests.java",
+    "*Tests.cs",
+    "*_spec.rb",
+    "*_test.rb",
+    "__tests__/",
+    "tests/",
+    "test/",
+    "spec/",
+    "testdata/",
+];
+
+/// Output names of common code generators, in gitignore syntax.
+pub const GENERATED_PATTERNS: &[&str] = &[
+    "*.pb.go",
+    "*.pb.cc",
+    "*.pb.h",
+    "*_pb2.py",
+    "*_pb2_grpc.py",
+    "*_generated.go",
+    "*.g.dart",
+    "*.freezed.dart",
+    "*.designer.cs",
+    "*.generated.*",
+];
+
+/// Text that generators put in a file's header.
+pub const GENERATED_MARKERS: &[&str] = &["@generated", "DO NOT EDIT", "<auto-generated"];
+
+/// How far down a file a generated marker counts; further down it is more likely code
+/// that mentions a marker than a header.
+const MARKER_LINES: usize = 10;
+
+/// Which kinds of files to leave out. The defaults leave out nothing.
+#[derive(Debug, Clone, Default)]
+pub struct FileKindFilter {
+    /// Test paths, with `--no-tests`
+    pub tests: Option<PathPatterns>,
+    /// Generated paths, with `--no-generated`
+    pub generated: Option<PathPatterns>,
+    /// Header markers of generated files, with `--no-generated`
+    pub generated_markers: Vec<String>,
+}
+
+impl FileKindFilter {
+    /// Whether `rel_path` is a test or generated file by name alone.
+    pub fn skips_path(&self, rel_path: &str) -> bool {
+        [&self.tests, &self.generated]
+            .into_iter()
+            .flatten()
+            .any(|patterns| patterns.matches(rel_path, false))
+    }
+
+    /// Whether `content` starts with a generated-file marker.
+    pub fn skips_content(&self, content: &str) -> bool {
+        !self.generated_markers.is_empty()
+            && content.lines().take(MARKER_LINES).any(|line| {
+                self.generated_markers
+                    .iter()
+                    .any(|marker| line.contains(marker.as_str()))
+            })
+    }
+}
+
+#[test]
+fn test_default_kinds() -> Result<(), Box<dyn std::error::Error>> {
+    let filter = FileKindFilter {
+        tests: Some(PathPatterns::new(TEST_PATTERNS)?),
+        generated: Some(PathPatterns::new(GENERATED_PATTERNS)?),
+        generated_markers: GENERATED_MARKERS.iter().map(|m| m.to_string()).collect(),
+    };
+    for path in [
+        "pkg/server_test.go",
+        "test_api.py",
+        "web/src/__tests__/App.jsx",
+        "web/src/app.spec.ts",
+        "tests/integration.rs",
+        "api/v1/service.pb.go",
+    ] {
+        assert!(filter.skips_path(path), "{}", path);
+    }
+    for path in ["pkg/server.go", "src/testing.rs", "contest.py", "api/pb.go"] {
+        assert!(!filter.skips_path(path), "{}", path);
+    }
+    assert!(filter.skips_content("// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api\n"));
+    assert!(!filter.skips_content(&format!(
+        "{}// DO NOT EDIT\n",
+        "fn f() {}\n".repeat(MARKER_LINES)
+    )));
+    assert!(!FileKindFilter::default().skips_path("tests/a.rs"));
+    assert!(!FileKindFilter::default().skips_content("// @generated\n"));
+    Ok(())
+}
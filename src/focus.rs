@@ -0,0 +1,332 @@
+//! `--focus path::function`: export only one function and its neighbourhood in the call
+//! graph (the functions it calls and the functions that call it, up to `--focus-depth`
+//! hops), as a minimal context for debugging that function.
+//!
+//! Like `chunks`, there is no parser behind this. A call is an identifier followed by `(`,
+//! matched to function definitions by name; when several files define the name, the
+//! caller's own file wins, then files linked to it by an import.
+
+use crate::chunks::{chunk_file, functions, Chunk};
+use crate::deps::{file_stem, import_edges};
+use crate::language_from_path;
+use crate::types::FileEntry;
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// A call, with what comes before it: a path segment (`Parser::new(`, `parse::run(`) or
+/// `.` for a method call.
+static CALL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(\.|\b[A-Za-z_][A-Za-z0-9_]*::)?\b([A-Za-z_][A-Za-z0-9_]*)\s*\(").unwrap()
+});
+
+/// A parsed `--focus` value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FocusTarget {
+    pub path: String,
+    /// Enclosing type or class (`Parser::parse`), if given
+    pub owner: Option<String>,
+    pub name: String,
+}
+
+impl FocusTarget {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (path, symbol) = value
+            .split_once("::")
+            .filter(|(path, symbol)| !path.is_empty() && !symbol.is_empty())
+            .ok_or_else(|| format!("Invalid --focus '{}', expected PATH::FUNCTION", value))?;
+        let (owner, name) = match symbol.rsplit_once("::") {
+            Some((owner, name)) => (Some(owner.to_string()), name),
+            None => (None, symbol),
+        };
+        Ok(FocusTarget {
+            path: path.trim_start_matches("./").replace('\\', "/"),
+            owner,
+            name: name.to_string(),
+        })
+    }
+
+    /// `rel_path` is the target file, allowing for the path being given from a parent or
+    /// child of the input directory.
+    fn matches_path(&self, rel_path: &str) -> bool {
+        rel_path == self.path
+            || self.path.ends_with(&format!("/{}", rel_path))
+            || rel_path.ends_with(&format!("/{}", self.path))
+    }
+}
+
+/// One function definition: index into the files, and its line range.
+struct Function {
+    file: usize,
+    chunk: Chunk,
+    /// Name of the enclosing top-level block (`impl Parser`, `class Parser`)
+    owner: Option<String>,
+    /// `(qualifier, name)` of each call in the body; see `CALL_RE`
+    calls: HashSet<(Option<String>, String)>,
+}
+
+/// Which lines of each file to keep: `files[i]` keeps the inclusive, 1-based ranges in
+/// `ranges[i]`, in order and without overlaps; files with no ranges are dropped.
+pub fn select_focus(
+    files: &[&FileEntry],
+    target: &FocusTarget,
+    depth: usize,
+) -> Result<Vec<Vec<(usize, usize)>>, String> {
+    let langs: Vec<&str> = files
+        .iter()
+        .map(|f| language_from_path(Path::new(&f.rel_path)))
+        .collect();
+    let mut defs = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        let lines: Vec<&str> = file.content.lines().collect();
+        let blocks = chunk_file(&file.content, langs[i]);
+        for chunk in functions(&file.content, langs[i]) {
+            let owner = blocks
+                .iter()
+                .find(|b| b.start_line <= chunk.start_line && chunk.end_line < b.end_line)
+                .and_then(|b| b.name.clone());
+            // A one-line function's body shares the signature line.
+            let calls = lines[chunk.body_start.min(chunk.end_line) - 1..chunk.end_line]
+                .iter()
+                .flat_map(|line| {
+                    CALL_RE.captures_iter(line).map(|c| {
+                        let qualifier = c.get(1).map(|q| q.as_str().trim_end_matches(':'));
+                        (qualifier.map(str::to_string), c[2].to_string())
+                    })
+                })
+                .collect();
+            defs.push(Function {
+                file: i,
+                chunk,
+                owner,
+                calls,
+            });
+        }
+    }
+
+    let target_file = files
+        .iter()
+        .position(|f| f.rel_path == target.path)
+        .or_else(|| files.iter().position(|f| target.matches_path(&f.rel_path)))
+        .ok_or_else(|| format!("--focus: no exported file matches '{}'", target.path))?;
+    let seeds: Vec<usize> = (0..defs.len())
+        .filter(|&d| {
+            let def = &defs[d];
+            def.file == target_file
+                && def.chunk.name.as_deref() == Some(target.name.as_str())
+                && (target.owner.is_none() || target.owner == def.owner)
+        })
+        .collect();
+    if seeds.is_empty() {
+        return Err(format!(
+            "--focus: no function '{}' in {}",
+            target.name, files[target_file].rel_path
+        ));
+    }
+
+    let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (d, def) in defs.iter().enumerate() {
+        if let Some(name) = &def.chunk.name {
+            by_name.entry(name).or_default().push(d);
+        }
+    }
+    let stems: Vec<&str> = files.iter().map(|f| file_stem(&f.rel_path)).collect();
+    let linked: HashSet<(usize, usize)> = import_edges(files)
+        .into_iter()
+        .flat_map(|(a, b)| [(a, b), (b, a)])
+        .collect();
+    // Callee edges, resolved by name; callers are the same edges reversed.
+    let mut callees: Vec<Vec<usize>> = vec![Vec::new(); defs.len()];
+    let mut callers: Vec<Vec<usize>> = vec![Vec::new(); defs.len()];
+    for (d, def) in defs.iter().enumerate() {
+        for (qualifier, name) in &def.calls {
+            let Some(candidates) = by_name.get(name.as_str()) else {
+                continue;
+            };
+            let pick = |keep: &dyn Fn(&Function) -> bool| -> Vec<usize> {
+                candidates
+                    .iter()
+                    .copied()
+                    .filter(|&c| keep(&defs[c]))
+                    .collect()
+            };
+            // `crate::f(`, `super::f(`, `self::f(` name no type or module: a plain call.
+            let qualifier = qualifier
+                .as_deref()
+                .filter(|q| !matches!(*q, "crate" | "super" | "self"));
+            let resolved = match qualifier {
+                // `Self::new(`, `Parser::new(`: only that type's functions.
+                Some("Self") => pick(&|c| c.file == def.file && c.owner == def.owner),
+                Some(ty) if ty.starts_with(char::is_uppercase) => {
+                    pick(&|c| c.owner.as_deref() == Some(ty))
+                }
+                // `parse::run(`: a function in a module file of that name.
+                Some(module) if stems.contains(&module) => pick(&|c| stems[c.file] == module),
+                _ => {
+                    let mut resolved = pick(&|c| c.file == def.file);
+                    if resolved.is_empty() {
+                        resolved = pick(&|c| linked.contains(&(def.file, c.file)));
+                    }
+                    // A name defined in several unrelated places is too ambiguous to follow,
+                    // and so is a method call, which is mostly on library types.
+                    if resolved.is_empty() && candidates.len() == 1 && qualifier.is_none() {
+                        resolved = candidates.clone();
+                    }
+                    resolved
+                }
+            };
+            for callee in resolved.into_iter().filter(|&c| c != d) {
+                callees[d].push(callee);
+                callers[callee].push(d);
+            }
+        }
+    }
+
+    let mut keep: HashSet<usize> = seeds.iter().copied().collect();
+    for edges in [&callees, &callers] {
+        let mut queue: VecDeque<(usize, usize)> = seeds.iter().map(|&s| (s, 0)).collect();
+        let mut seen: HashSet<usize> = seeds.iter().copied().collect();
+        while let Some((d, hops)) = queue.pop_front() {
+            if hops == depth {
+                continue;
+            }
+            for &next in &edges[d] {
+                if seen.insert(next) {
+                    keep.insert(next);
+                    queue.push_back((next, hops + 1));
+                }
+            }
+        }
+    }
+
+    let mut ranges = vec![Vec::new(); files.len()];
+    let mut kept: Vec<&Function> = keep.iter().map(|&d| &defs[d]).collect();
+    kept.sort_by_key(|def| {
+        (
+            def.file,
+            def.chunk.start_line,
+            std::cmp::Reverse(def.chunk.end_line),
+        )
+    });
+    for def in kept {
+        let file_ranges: &mut Vec<(usize, usize)> = &mut ranges[def.file];
+        // A kept method inside a kept function is already covered.
+        match file_ranges.last() {
+            Some(&(_, end)) if def.chunk.start_line <= end => {}
+            _ => file_ranges.push((def.chunk.start_line, def.chunk.end_line)),
+        }
+    }
+    Ok(ranges)
+}
+
+/// `content` reduced to `ranges`, with `marker` (which may contain `{count}`) standing in
+/// for each run of dropped lines, and the line of `content` each output line was (`None`
+/// for markers).
+pub fn cut_lines(
+    content: &str,
+    ranges: &[(usize, usize)],
+    marker: &str,
+) -> (String, Vec<Option<usize>>) {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut out: Vec<String> = Vec::new();
+    let mut origins = Vec::new();
+    let mut next = 1;
+    for &(start, end) in ranges {
+        if start > next {
+            out.push(marker.replace("{count}", &(start - next).to_string()));
+            origins.push(None);
+        }
+        out.extend(lines[start - 1..end].iter().map(|l| l.to_string()));
+        origins.extend((start..=end).map(Some));
+        next = end + 1;
+    }
+    if next <= lines.len() {
+        out.push(marker.replace("{count}", &(lines.len() + 1 - next).to_string()));
+        origins.push(None);
+    }
+    (out.join("\n"), origins)
+}
+
+/// Reduce `file` to `ranges` (see `cut_lines`), keeping track of its original line numbers.
+pub fn keep_ranges(file: &mut FileEntry, ranges: &[(usize, usize)], marker: &str) {
+    let (content, origins) = cut_lines(&file.content, ranges, marker);
+    file.content = content;
+    file.lines = file.lines.cut(origins);
+}
+
+#[test]
+fn test_focus_follows_calls_both_ways() -> Result<(), String> {
+    let file = |rel_path: &str, content: &str| FileEntry {
+        rel_path: rel_path.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    };
+    let files = [
+        file(
+            "src/main.rs",
+            "use crate::parse;\n\nfn main() {\n    run();\n}\n\nfn run() {\n    parse::parse(\"x\");\n}\n\nfn unrelated() {\n}\n",
+        ),
+        file(
+            "src/parse.rs",
+            "pub fn parse(s: &str) {\n    token(s);\n}\n\nfn token(s: &str) {\n    helper();\n}\n\nfn helper() {}\n",
+        ),
+        file("src/other.rs", "fn parse() {}\n"),
+    ];
+    let refs: Vec<&FileEntry> = files.iter().collect();
+    let target = FocusTarget::parse("parse.rs::parse")?;
+
+    let ranges = select_focus(&refs, &target, 1)?;
+    assert_eq!(ranges[0], vec![(7, 9)]);
+    assert_eq!(ranges[1], vec![(1, 3), (5, 7)]);
+    assert!(ranges[2].is_empty());
+
+    let ranges = select_focus(&refs, &target, 2)?;
+    assert_eq!(ranges[0], vec![(3, 5), (7, 9)]);
+    assert_eq!(ranges[1], vec![(1, 3), (5, 7), (9, 9)]);
+
+    let mut cut = files[1].clone();
+    keep_ranges(&mut cut, &[(5, 7)], "// {count} omitted");
+    assert_eq!(
+        cut.content,
+        "// 4 omitted\nfn token(s: &str) {\n    helper();\n}\n// 2 omitted"
+    );
+    assert_eq!(cut.lines.original(1), None);
+    assert_eq!(cut.lines.original(2), Some(5));
+    keep_ranges(&mut cut, &[(3, 4)], "// {count} omitted");
+    assert_eq!(cut.content, "// 2 omitted\n    helper();\n}\n// 1 omitted");
+    assert_eq!(cut.lines.original(2), Some(6));
+    assert_eq!(cut.lines.original(4), None);
+    assert!(select_focus(&refs, &FocusTarget::parse("src/main.rs::nope")?, 1).is_err());
+
+    // Path-qualified calls from another file are callers too.
+    let files = [
+        file(
+            "src/a.rs",
+            "pub fn target() {}
+",
+        ),
+        file(
+            "src/b.rs",
+            "pub fn other() {
+    crate::target();
+}
+
+fn near() {
+    super::target();
+}
+
+fn skip() {}
+",
+        ),
+    ];
+    let refs: Vec<&FileEntry> = files.iter().collect();
+    let ranges = select_focus(&refs, &FocusTarget::parse("src/a.rs::target")?, 1)?;
+    assert_eq!(ranges[1], vec![(1, 3), (5, 7)]);
+    assert!(FocusTarget::parse("src/main.rs").is_err());
+    assert_eq!(
+        FocusTarget::parse("./a.rs::Parser::run")?.owner.as_deref(),
+        Some("Parser")
+    );
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+
+/// Run `git -C dir <args>` and return stdout, or an error carrying git's stderr.
+pub fn run_git(dir: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "core.quotePath=false"])
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed in {}: {}",
+            args.join(" "),
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Files changed since `since` (tracked changes plus new untracked files), relative to `dir`.
+pub fn changed_files(dir: &Path, since: &str) -> Result<HashSet<String>, Box<dyn Error>> {
+    let tracked = run_git(dir, &["diff", "--name-only", "--relative", since, "--"])?;
+    let untracked = run_git(dir, &["ls-files", "--others", "--exclude-standard"])?;
+    Ok(tracked
+        .lines()
+        .chain(untracked.lines())
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Unified diff of the working tree under `dir` against `since`.
+pub fn diff_since(dir: &Path, since: &str) -> Result<String, Box<dyn Error>> {
+    run_git(dir, &["diff", "--relative", since, "--"])
+}
+
+/// Who touched a file and when, from `git log`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileHistory {
+    /// Date (`YYYY-MM-DD`) of the newest commit touching the file
+    pub last_modified: String,
+    /// Abbreviated SHA of that commit
+    pub commit: String,
+    /// Authors by number of commits touching the file, most first
+    pub authors: Vec<(String, usize)>,
+}
+
+/// History of every file under `dir` (paths relative to `dir`), from a single `git log` pass.
+pub fn file_histories(dir: &Path) -> Result<HashMap<String, FileHistory>, Box<dyn Error>> {
+    let log = run_git(
+        dir,
+        &[
+            "log",
+            "--no-renames",
+            "--relative",
+            "--name-only",
+            "--format=%x1e%h%x1f%ad%x1f%an",
+            "--date=short",
+            "--",
+            ".",
+        ],
+    )?;
+    Ok(parse_file_histories(&log))
+}
+
+fn parse_file_histories(log: &str) -> HashMap<String, FileHistory> {
+    let mut counts: HashMap<String, HashMap<String, usize>> = HashMap::new();
+    let mut histories: HashMap<String, FileHistory> = HashMap::new();
+    for record in log.split('\x1e').filter(|r| !r.trim().is_empty()) {
+        let mut lines = record.lines();
+        let mut header = lines.next().unwrap_or_default().split('\x1f');
+        let (Some(commit), Some(date), Some(author)) =
+            (header.next(), header.next(), header.next())
+        else {
+            continue;
+        };
+        for path in lines.filter(|l| !l.is_empty()) {
+            // Newest commits come first, so the first sighting is the last modification.
+            histories
+                .entry(path.to_string())
+                .or_insert_with(|| FileHistory {
+                    last_modified: date.to_string(),
+                    commit: commit.to_string(),
+                    authors: Vec::new(),
+                });
+            *counts
+                .entry(path.to_string())
+                .or_default()
+                .entry(author.to_string())
+                .or_default() += 1;
+        }
+    }
+    for (path, history) in &mut histories {
+        let mut authors: Vec<(String, usize)> = counts
+            .remove(path)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        authors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        history.authors = authors;
+    }
+    histories
+}
+
+/// One commit for `--history`.
+#[derive(Debug, PartialEq)]
+pub struct Commit {
+    /// Abbreviated SHA
+    pub hash: String,
+    pub author: String,
+    /// `YYYY-MM-DD`
+    pub date: String,
+    /// Full message, subject first
+    pub message: String,
+    /// Unified diff of the commit, with paths relative to the input
+    pub diff: String,
+}
+
+/// The last `count` commits touching `dir`, newest first, each with its diff.
+pub fn recent_commits(dir: &Path, count: usize) -> Result<Vec<Commit>, Box<dyn Error>> {
+    let log = run_git(
+        dir,
+        &[
+            "log",
+            &format!("--max-count={}", count),
+            "--relative",
+            "--patch",
+            "--no-color",
+            "--no-ext-diff",
+            "--format=%x1e%h%x1f%an%x1f%ad%x1f%B%x1f",
+            "--date=short",
+            "--",
+            ".",
+        ],
+    )?;
+    Ok(parse_commits(&log))
+}
+
+fn parse_commits(log: &str) -> Vec<Commit> {
+    log.split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.splitn(5, '\x1f');
+            let (hash, author, date, message) = (
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+            );
+            Some(Commit {
+                hash: hash.to_string(),
+                author: author.to_string(),
+                date: date.to_string(),
+                message: message.trim().to_string(),
+                diff: fields.next().unwrap_or_default().trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_commits() {
+    let log = "\x1eabc1234\x1fAda\x1f2024-05-02\x1fFix parser\n\nIt dropped the last line.\n\x1f\n\ndiff --git a/src/p.rs b/src/p.rs\n-old\n+new\n\x1edef5678\x1fLin\x1f2024-05-01\x1fMerge branch 'x'\n\x1f\n";
+    let commits = parse_commits(log);
+    assert_eq!(commits.len(), 2);
+    assert_eq!(
+        commits[0].message,
+        "Fix parser\n\nIt dropped the last line."
+    );
+    assert_eq!(
+        commits[0].diff,
+        "diff --git a/src/p.rs b/src/p.rs\n-old\n+new"
+    );
+    assert_eq!(
+        (commits[1].author.as_str(), commits[1].diff.as_str()),
+        ("Lin", "")
+    );
+}
+
+#[test]
+fn test_parse_file_histories() {
+    let log = "\x1eabc1234\x1f2024-05-02\x1fAda\n\nsrc/main.rs\n\x1edef5678\x1f2024-04-01\x1fLin\n\nsrc/main.rs\nsrc/lib.rs\n\x1e0001111\x1f2024-03-01\x1fLin\n\nsrc/main.rs\n";
+    let histories = parse_file_histories(log);
+    let main = &histories["src/main.rs"];
+    assert_eq!(main.last_modified, "2024-05-02");
+    assert_eq!(main.commit, "abc1234");
+    assert_eq!(
+        main.authors,
+        vec![("Lin".to_string(), 2), ("Ada".to_string(), 1)]
+    );
+    assert_eq!(histories["src/lib.rs"].commit, "def5678");
+}
@@ -0,0 +1,167 @@
+use crate::error::R2mdError;
+use crate::summary::RunSummary;
+use serde::{Deserialize, Deserializer};
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::process::Command;
+use tracing::warn;
+
+/// `hooks:` section of `r2md.yml`
+#[derive(Debug, Default, Deserialize)]
+pub struct HooksConfig {
+    /// Shell commands run after a successful export.
+    #[serde(default)]
+    pub on_complete: Vec<String>,
+}
+
+/// Run each `--on-complete` / `hooks.on_complete` command through the shell, with the
+/// run's outcome in `R2MD_*` environment variables. A failing hook only warns.
+pub fn run_on_complete(commands: &[String], summary: &RunSummary) -> Result<(), Box<dyn Error>> {
+    for cmd in commands {
+        let mut command = shell(cmd);
+        command
+            // One path per line, so paths with spaces survive.
+            .env("R2MD_OUTPUTS", summary.outputs.join("\n"))
+            .env("R2MD_FILES", summary.files_included.to_string())
+            .env("R2MD_SKIPPED", summary.files_skipped.to_string())
+            .env("R2MD_TOKENS", summary.tokens.to_string())
+            .env("R2MD_ERRORS", summary.errors.len().to_string())
+            // stdout may be carrying the streamed Markdown.
+            .stdout(std::io::stderr());
+        let status = command.status()?;
+        if !status.success() {
+            warn!("On-complete hook `{}` exited with {}", cmd, status);
+        }
+    }
+    Ok(())
+}
+
+/// A `preprocess:` entry from `r2md.yml`: files matching `glob` are replaced by `cmd`'s stdout.
+/// Only files on disk are hooked (local directories and SSH clones), not archive entries.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PreprocessHook {
+    /// Glob matched against the path relative to the walked directory.
+    pub glob: String,
+    /// Shell command reading the file on stdin; `{}` in it stands for the file's path.
+    pub cmd: String,
+}
+
+/// `preprocess:` either as a list of `{glob, cmd}` entries or as a `glob: cmd` map; in both
+/// the order is kept, since the first matching hook wins.
+pub fn deserialize_preprocess<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<PreprocessHook>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Hooks {
+        List(Vec<PreprocessHook>),
+        Map(serde_yaml::Mapping),
+    }
+    match Hooks::deserialize(deserializer)? {
+        Hooks::List(hooks) => Ok(hooks),
+        Hooks::Map(map) => map
+            .into_iter()
+            .map(|(glob, cmd)| match (glob.as_str(), cmd.as_str()) {
+                (Some(glob), Some(cmd)) => Ok(PreprocessHook {
+                    glob: glob.to_string(),
+                    cmd: cmd.to_string(),
+                }),
+                _ => Err(serde::de::Error::custom(
+                    "preprocess: map globs to command strings",
+                )),
+            })
+            .collect(),
+    }
+}
+
+/// The first preprocess hook whose glob matches `rel_path`.
+pub fn preprocess_hook_for<'a>(
+    hooks: &'a [PreprocessHook],
+    rel_path: &str,
+) -> Option<&'a PreprocessHook> {
+    hooks.iter().find(|hook| {
+        glob::Pattern::new(&hook.glob)
+            .map(|p| p.matches(rel_path))
+            .unwrap_or(false)
+    })
+}
+
+/// Run a preprocess hook on `path` and return its stdout as the file's new content. The
+/// file is the command's stdin, so filters like `tr` or `sed` work as they are; a command
+/// that needs the path names it with `{}` (or reads `$R2MD_FILE`).
+pub fn run_preprocess(
+    hook: &PreprocessHook,
+    path: &Path,
+    rel_path: &str,
+) -> Result<Vec<u8>, R2mdError> {
+    // The path travels in an environment variable so it never needs shell quoting.
+    let cmd = if cfg!(windows) {
+        hook.cmd.replace("{}", "\"%R2MD_FILE%\"")
+    } else {
+        hook.cmd.replace("{}", "\"$R2MD_FILE\"")
+    };
+    let failed = |e: std::io::Error| R2mdError::Preprocess {
+        path: rel_path.to_string(),
+        message: e.to_string(),
+    };
+    let output = shell(&cmd)
+        .env("R2MD_FILE", path)
+        .stdin(File::open(path).map_err(failed)?)
+        .output()
+        .map_err(failed)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(R2mdError::Preprocess {
+            path: rel_path.to_string(),
+            message: format!(
+                "`{}` exited with {}: {}",
+                hook.cmd,
+                output.status,
+                stderr.trim()
+            ),
+        });
+    }
+    Ok(output.stdout)
+}
+
+fn shell(cmd: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", cmd]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", cmd]);
+        command
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_preprocess_replaces_content() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("notes.txt");
+    std::fs::write(&path, "hello")?;
+    let hooks = vec![PreprocessHook {
+        glob: "*.txt".into(),
+        cmd: "tr a-z A-Z < {}".into(),
+    }];
+    let hook = preprocess_hook_for(&hooks, "notes.txt").ok_or("no hook")?;
+    assert_eq!(run_preprocess(hook, &path, "notes.txt")?, b"HELLO");
+    assert!(preprocess_hook_for(&hooks, "main.rs").is_none());
+
+    // The map form, with a plain filter that reads the file from stdin.
+    #[derive(Deserialize)]
+    struct Config {
+        #[serde(deserialize_with = "deserialize_preprocess")]
+        preprocess: Vec<PreprocessHook>,
+    }
+    let config: Config =
+        serde_yaml::from_str("preprocess:\n  \"*.txt\": \"tr a-z A-Z\"\n  \"*\": cat\n")?;
+    let hook = preprocess_hook_for(&config.preprocess, "notes.txt").ok_or("no hook")?;
+    assert_eq!(hook.cmd, "tr a-z A-Z");
+    assert_eq!(run_preprocess(hook, &path, "notes.txt")?, b"HELLO");
+    assert_eq!(config.preprocess[1].glob, "*");
+    Ok(())
+}
@@ -0,0 +1,151 @@
+//! `--format html-app`: the export as one self-contained HTML page with a file list and
+//! client-side search over paths and symbols, for sharing a browsable snapshot.
+
+use crate::chunks::chunk_file;
+use crate::language_from_path;
+use crate::metadata::Metadata;
+use crate::types::FileEntry;
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use syntect::highlighting::Theme;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+const STYLE: &str = "body { margin: 0; display: flex; height: 100vh; font-family: system-ui, sans-serif; }
+nav { width: 22em; overflow-y: auto; border-right: 1px solid #ddd; padding: 0.5em; box-sizing: border-box; }
+nav input { width: 100%; padding: 0.4em; box-sizing: border-box; }
+nav ul { list-style: none; padding: 0; font-size: 0.85em; }
+nav li { padding: 0.15em 0; word-break: break-all; }
+nav small { color: #777; }
+main { flex: 1; overflow: auto; padding: 0 1em; }
+main pre { font-size: 0.8em; padding: 0.5em; }
+";
+
+const SCRIPT: &str = "const index = JSON.parse(document.getElementById('index').textContent);
+const list = document.getElementById('files');
+const search = document.getElementById('search');
+function show(id) {
+  document.querySelectorAll('main section').forEach(s => { s.hidden = s.id !== id; });
+}
+function render(query) {
+  const q = query.toLowerCase();
+  list.replaceChildren();
+  index.forEach((file, i) => {
+    const inPath = file.path.toLowerCase().includes(q);
+    const symbol = q ? file.symbols.find(s => s.toLowerCase().includes(q)) : undefined;
+    if (!inPath && !symbol) return;
+    const item = document.createElement('li');
+    const link = document.createElement('a');
+    link.href = '#f' + (i + 1);
+    link.textContent = file.path;
+    item.appendChild(link);
+    if (!inPath) {
+      const note = document.createElement('small');
+      note.textContent = ' ' + symbol;
+      item.appendChild(note);
+    }
+    list.appendChild(item);
+  });
+}
+search.addEventListener('input', () => render(search.value));
+window.addEventListener('hashchange', () => show(location.hash.slice(1)));
+render('');
+show(location.hash.slice(1) || 'f1');
+";
+
+/// One search index entry; its position in the list is the file's section number.
+#[derive(Serialize)]
+struct IndexEntry<'a> {
+    path: &'a str,
+    symbols: Vec<String>,
+}
+
+/// Write `files` as a single HTML page. `lang` is the page's language tag (`en`, `de`).
+pub fn write_html_app(
+    files: &[FileEntry],
+    directories: &[PathBuf],
+    output_file_name: &str,
+    lang: &str,
+    theme: &Theme,
+) -> Result<(), Box<dyn Error>> {
+    let ss = SyntaxSet::load_defaults_newlines();
+    let metadata = Metadata::new(directories, files.len(), 0);
+    let title = format!("r2md: {}", metadata.repo);
+
+    let index: Vec<IndexEntry> = files
+        .iter()
+        .map(|f| IndexEntry {
+            path: &f.rel_path,
+            symbols: chunk_file(&f.content, language_from_path(Path::new(&f.rel_path)))
+                .into_iter()
+                .filter_map(|chunk| chunk.name)
+                .collect(),
+        })
+        .collect();
+    // `</script>` inside a path or symbol must not end the script element.
+    let index_json = serde_json::to_string(&index)?.replace("</", "<\\/");
+
+    let mut out = BufWriter::new(File::create(output_file_name)?);
+    write!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"{lang}\">\n<head>\n<meta charset=\"UTF-8\">\n<title>{title}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n<nav>\n<h1>{title}</h1>\n<input id=\"search\" type=\"search\" placeholder=\"Search files and symbols\">\n<ul id=\"files\"></ul>\n</nav>\n<main>\n",
+        lang = escape_html(lang),
+        title = escape_html(&title),
+    )?;
+    for (i, file) in files.iter().enumerate() {
+        let ext = Path::new(&file.rel_path)
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("");
+        let syntax = ss
+            .find_syntax_by_extension(ext)
+            .unwrap_or_else(|| ss.find_syntax_plain_text());
+        writeln!(
+            out,
+            "<section id=\"f{}\" hidden>\n<h2>{}</h2>\n{}</section>",
+            i + 1,
+            escape_html(&file.rel_path),
+            highlighted_html_for_string(&file.content, &ss, syntax, theme)?
+        )?;
+    }
+    write!(
+        out,
+        "</main>\n<script type=\"application/json\" id=\"index\">{}</script>\n<script>\n{}</script>\n</body>\n</html>\n",
+        index_json, SCRIPT
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn test_html_app_embeds_code_and_index() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("app.html");
+    let files = vec![FileEntry {
+        rel_path: "src/</script>.rs".to_string(),
+        content: "fn parse() {\n    if 1 < 2 {}\n}\n".to_string(),
+        ..Default::default()
+    }];
+    write_html_app(
+        &files,
+        &[dir.path().to_path_buf()],
+        &path.to_string_lossy(),
+        "en",
+        &crate::theme::load_theme(None)?,
+    )?;
+    let html = std::fs::read_to_string(&path)?;
+    assert!(html.contains("<section id=\"f1\" hidden>\n<h2>src/&lt;/script&gt;.rs</h2>"));
+    assert!(html.contains(r#"{"path":"src/<\/script>.rs","symbols":["parse"]}"#));
+    assert!(!html.contains("1 < 2"));
+    Ok(())
+}
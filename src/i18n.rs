@@ -0,0 +1,267 @@
+//! Section titles and fixed phrases of the generated document, so exports can be shared
+//! with teams that don't read English.
+
+use std::collections::HashMap;
+
+/// Every fixed phrase r2md writes into the Markdown.
+#[derive(Debug, Clone)]
+pub struct Labels {
+    pub code: String,
+    /// `{since}` and `{dir}` are filled in
+    pub changes_since: String,
+    /// `{dir}` is filled in
+    pub history: String,
+    /// `{number}` and `{title}` are filled in
+    pub issue: String,
+    /// `{number}` and `{title}` are filled in
+    pub pull_request: String,
+    pub changed_files: String,
+    pub part: String,
+    pub parts: String,
+    pub this_part: String,
+    pub lines: String,
+    /// `{date}`, `{commit}` and `{authors}` are filled in
+    pub authors: String,
+    pub files: String,
+    pub assets: String,
+    pub assets_note: String,
+    pub bytes: String,
+    pub inaccessible: String,
+    pub inaccessible_note: String,
+    pub interrupted: String,
+    /// Replaces the lines cut from a trimmed file; `{count}` is filled in
+    pub omitted: String,
+    /// `{version}` and `{command}` are filled in
+    pub footer: String,
+    pub summaries: String,
+    /// `{files}` and `{tokens}` are filled in
+    pub summary_stats: String,
+    pub languages: String,
+    pub largest: String,
+    pub symbols: String,
+    /// `{count}` is filled in
+    pub over_budget: String,
+    pub over_budget_note: String,
+    /// Stands in for a file that repeats an earlier one; `{path}` is filled in
+    pub identical: String,
+    /// Stands in for a removed license header; `{license}` is filled in
+    pub license_header: String,
+    /// Stands in for a removed copyright header that names no known license
+    pub copyright_header: String,
+    /// Heads the files of a workspace member crate; `{path}` is filled in
+    pub crate_member: String,
+    /// Heads the files of a git submodule; `{path}` is filled in
+    pub submodule: String,
+    pub stats_title: String,
+    pub stats_language: String,
+    pub stats_files: String,
+    pub stats_lines: String,
+    pub stats_code: String,
+    pub stats_comments: String,
+    pub stats_blank: String,
+    pub stats_ratio: String,
+    pub stats_tokens: String,
+    pub stats_total: String,
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Labels::for_lang("en").expect("built-in English labels")
+    }
+}
+
+impl Labels {
+    /// Built-in translations: `en`, `de`.
+    pub fn for_lang(lang: &str) -> Option<Labels> {
+        let labels = match lang {
+            "en" => [
+                "Code",
+                "Changes since {since} in {dir}",
+                "Recent commits in {dir}",
+                "Issue #{number}: {title}",
+                "Pull request #{number}: {title}",
+                "Changed files",
+                "part",
+                "Parts",
+                "this part",
+                "lines",
+                "Last modified {date} in {commit}; top contributors: {authors}",
+                "files",
+                "Assets",
+                "Stored in Git LFS; only pointers are in the repository.",
+                "bytes",
+                "Inaccessible Paths",
+                "The following paths could not be read; the export above is incomplete.",
+                "Output truncated: r2md was interrupted before it finished.",
+                "... {count} lines omitted by r2md ...",
+                "Generated by r2md {version}. Rerun with: {command}",
+                "Directory Summaries",
+                "{files} files, about {tokens} tokens",
+                "Languages",
+                "Largest files",
+                "Public symbols",
+                "Truncated: {count} files omitted",
+                "These files did not fit in the output size limit.",
+                "Identical to {path}",
+                "{license} license header removed",
+                "Copyright header removed",
+                "Crate {path}",
+                "Submodule {path}",
+                "Language Summary",
+                "Language",
+                "Files",
+                "Lines",
+                "Code",
+                "Comments",
+                "Blank",
+                "Comments/code",
+                "Tokens",
+                "Total",
+            ],
+            "de" => [
+                "Code",
+                "Änderungen seit {since} in {dir}",
+                "Letzte Commits in {dir}",
+                "Issue #{number}: {title}",
+                "Pull Request #{number}: {title}",
+                "Geänderte Dateien",
+                "Teil",
+                "Teile",
+                "dieser Teil",
+                "Zeilen",
+                "Zuletzt geändert am {date} in {commit}; Hauptautoren: {authors}",
+                "Dateien",
+                "Assets",
+                "In Git LFS gespeichert; im Repository liegen nur Zeiger.",
+                "Bytes",
+                "Nicht lesbare Pfade",
+                "Die folgenden Pfade konnten nicht gelesen werden; der Export oben ist unvollständig.",
+                "Ausgabe unvollständig: r2md wurde vor dem Ende abgebrochen.",
+                "... {count} Zeilen von r2md ausgelassen ...",
+                "Erzeugt mit r2md {version}. Neu erzeugen mit: {command}",
+                "Verzeichnisübersicht",
+                "{files} Dateien, etwa {tokens} Tokens",
+                "Sprachen",
+                "Größte Dateien",
+                "Öffentliche Symbole",
+                "Gekürzt: {count} Dateien ausgelassen",
+                "Diese Dateien passten nicht in die Größenbegrenzung der Ausgabe.",
+                "Identisch mit {path}",
+                "Lizenzkopf ({license}) entfernt",
+                "Copyright-Kopf entfernt",
+                "Crate {path}",
+                "Submodul {path}",
+                "Sprachübersicht",
+                "Sprache",
+                "Dateien",
+                "Zeilen",
+                "Code",
+                "Kommentare",
+                "Leer",
+                "Kommentare/Code",
+                "Tokens",
+                "Gesamt",
+            ],
+            _ => return None,
+        };
+        let [code, changes_since, history, issue, pull_request, changed_files, part, parts, this_part, lines, authors, files, assets, assets_note, bytes, inaccessible, inaccessible_note, interrupted, omitted, footer, summaries, summary_stats, languages, largest, symbols, over_budget, over_budget_note, identical, license_header, copyright_header, crate_member, submodule, stats_title, stats_language, stats_files, stats_lines, stats_code, stats_comments, stats_blank, stats_ratio, stats_tokens, stats_total] =
+            labels.map(String::from);
+        Some(Labels {
+            code,
+            changes_since,
+            history,
+            issue,
+            pull_request,
+            changed_files,
+            part,
+            parts,
+            this_part,
+            lines,
+            authors,
+            files,
+            assets,
+            assets_note,
+            bytes,
+            inaccessible,
+            inaccessible_note,
+            interrupted,
+            omitted,
+            footer,
+            summaries,
+            summary_stats,
+            languages,
+            largest,
+            symbols,
+            over_budget,
+            over_budget_note,
+            identical,
+            license_header,
+            copyright_header,
+            crate_member,
+            submodule,
+            stats_title,
+            stats_language,
+            stats_files,
+            stats_lines,
+            stats_code,
+            stats_comments,
+            stats_blank,
+            stats_ratio,
+            stats_tokens,
+            stats_total,
+        })
+    }
+
+    /// Apply `ui_labels:` overrides from `r2md.yml`, keyed by field name.
+    pub fn apply_overrides(&mut self, overrides: &HashMap<String, String>) -> Result<(), String> {
+        for (key, value) in overrides {
+            let slot = match key.as_str() {
+                "code" => &mut self.code,
+                "changes_since" => &mut self.changes_since,
+                "history" => &mut self.history,
+                "issue" => &mut self.issue,
+                "pull_request" => &mut self.pull_request,
+                "changed_files" => &mut self.changed_files,
+                "part" => &mut self.part,
+                "parts" => &mut self.parts,
+                "this_part" => &mut self.this_part,
+                "lines" => &mut self.lines,
+                "authors" => &mut self.authors,
+                "files" => &mut self.files,
+                "assets" => &mut self.assets,
+                "assets_note" => &mut self.assets_note,
+                "bytes" => &mut self.bytes,
+                "inaccessible" => &mut self.inaccessible,
+                "inaccessible_note" => &mut self.inaccessible_note,
+                "interrupted" => &mut self.interrupted,
+                "omitted" => &mut self.omitted,
+                "footer" => &mut self.footer,
+                "summaries" => &mut self.summaries,
+                "summary_stats" => &mut self.summary_stats,
+                "languages" => &mut self.languages,
+                "largest" => &mut self.largest,
+                "symbols" => &mut self.symbols,
+                "over_budget" => &mut self.over_budget,
+                "over_budget_note" => &mut self.over_budget_note,
+                "identical" => &mut self.identical,
+                "license_header" => &mut self.license_header,
+                "copyright_header" => &mut self.copyright_header,
+                "crate_member" => &mut self.crate_member,
+                "submodule" => &mut self.submodule,
+                "stats_title" => &mut self.stats_title,
+                "stats_language" => &mut self.stats_language,
+                "stats_files" => &mut self.stats_files,
+                "stats_lines" => &mut self.stats_lines,
+                "stats_code" => &mut self.stats_code,
+                "stats_comments" => &mut self.stats_comments,
+                "stats_blank" => &mut self.stats_blank,
+                "stats_ratio" => &mut self.stats_ratio,
+                "stats_tokens" => &mut self.stats_tokens,
+                "stats_total" => &mut self.stats_total,
+                _ => return Err(format!("Unknown ui_labels key '{}'", key)),
+            };
+            *slot = value.clone();
+        }
+        Ok(())
+    }
+}
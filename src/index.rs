@@ -0,0 +1,206 @@
+//! `r2md index` / `r2md query`: a persistent tantivy index of code chunks, so a context
+//! document for a question can be assembled without re-reading the repo.
+
+use crate::chunks::chunk_file;
+use crate::relevance::terms;
+use crate::sanitize::{fence_for, inline_code};
+use crate::tokens::estimate_tokens;
+use crate::types::{CollectOptions, CollectReport, FileEntry};
+use crate::{collect_files_parallel, language_from_path};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexWriter, TantivyDocument};
+use tracing::warn;
+
+/// Where `r2md index` writes and `r2md query` reads by default.
+pub const DEFAULT_INDEX_DIR: &str = ".r2md-index";
+
+/// Candidates fetched from the index before the token budget is applied.
+const MAX_HITS: usize = 500;
+
+fn schema() -> Schema {
+    let mut builder = Schema::builder();
+    builder.add_text_field("path", STRING | STORED);
+    builder.add_u64_field("start_line", STORED);
+    builder.add_u64_field("end_line", STORED);
+    builder.add_text_field("body", STORED);
+    // Identifier-split, stemmed terms of path and body, matching `--relevant-to`.
+    builder.add_text_field("terms", TEXT);
+    builder.build()
+}
+
+/// Index every chunk of the files under `dirs` into `index_dir`, replacing an earlier index.
+/// Returns the number of chunks indexed.
+pub fn build_index(
+    dirs: &[PathBuf],
+    index_dir: &Path,
+    opts: &CollectOptions,
+) -> Result<usize, Box<dyn Error>> {
+    if index_dir.exists() {
+        // Only ever delete something that is recognizably a tantivy index.
+        if !index_dir.join("meta.json").is_file() {
+            return Err(format!(
+                "{} exists and is not an r2md index; pick another --index-dir",
+                index_dir.display()
+            )
+            .into());
+        }
+        fs::remove_dir_all(index_dir)?;
+    }
+    fs::create_dir_all(index_dir)?;
+    let schema = schema();
+    let field = |name: &str| schema.get_field(name);
+    let (path_f, start_f, end_f, body_f, terms_f) = (
+        field("path")?,
+        field("start_line")?,
+        field("end_line")?,
+        field("body")?,
+        field("terms")?,
+    );
+    let index = Index::create_in_dir(index_dir, schema.clone())?;
+    let mut writer: IndexWriter = index.writer(64 * 1024 * 1024)?;
+
+    let mut report = CollectReport::default();
+    let mut count = 0;
+    for dir in dirs {
+        if !dir.is_dir() {
+            warn!(
+                "r2md index only reads local directories; skipping {}",
+                dir.display()
+            );
+            continue;
+        }
+        for file in collect_files_parallel(dir, opts, &mut report, None)? {
+            for (start, end, body) in file_chunks(&file) {
+                let terms: Vec<String> = terms(&file.rel_path).chain(terms(&body)).collect();
+                writer.add_document(doc!(
+                    path_f => file.rel_path.as_str(),
+                    start_f => start as u64,
+                    end_f => end as u64,
+                    body_f => body,
+                    terms_f => terms.join(" "),
+                ))?;
+                count += 1;
+            }
+        }
+    }
+    writer.commit()?;
+    Ok(count)
+}
+
+/// Top-level chunks of `file` as `(start_line, end_line, text)`; files without any
+/// recognizable chunk are indexed whole.
+fn file_chunks(file: &FileEntry) -> Vec<(usize, usize, String)> {
+    let lang = language_from_path(Path::new(&file.rel_path));
+    let chunks = chunk_file(&file.content, lang);
+    if chunks.is_empty() {
+        let lines = file.content.lines().count().max(1);
+        return vec![(1, lines, file.content.clone())];
+    }
+    chunks
+        .iter()
+        .map(|c| (c.start_line, c.end_line, c.text(&file.content)))
+        .collect()
+}
+
+/// Markdown document of the chunks best matching `text`, up to about `max_tokens`.
+/// Chunks are picked by score and then printed in path and line order.
+pub fn query_index(
+    index_dir: &Path,
+    text: &str,
+    max_tokens: usize,
+    opts: &CollectOptions,
+) -> Result<String, Box<dyn Error>> {
+    let index = Index::open_in_dir(index_dir).map_err(|e| {
+        format!(
+            "Cannot open index in {} ({}); run `r2md index` first",
+            index_dir.display(),
+            e
+        )
+    })?;
+    let schema = index.schema();
+    let field = |name: &str| schema.get_field(name);
+    let (path_f, start_f, end_f, body_f, terms_f) = (
+        field("path")?,
+        field("start_line")?,
+        field("end_line")?,
+        field("body")?,
+        field("terms")?,
+    );
+    let searcher = index.reader()?.searcher();
+    let query_terms: Vec<String> = terms(text).collect();
+    let (query, _) =
+        QueryParser::for_index(&index, vec![terms_f]).parse_query_lenient(&query_terms.join(" "));
+    let hits = searcher.search(&query, &TopDocs::with_limit(MAX_HITS))?;
+
+    let mut picked = Vec::new();
+    let mut tokens_left = max_tokens;
+    for (_, address) in hits {
+        let doc: TantivyDocument = searcher.doc(address)?;
+        let text_of = |f| doc.get_first(f).and_then(|v| v.as_str()).unwrap_or("");
+        let num_of = |f| doc.get_first(f).and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let section = render_chunk(
+            text_of(path_f),
+            num_of(start_f),
+            num_of(end_f),
+            text_of(body_f),
+            opts,
+        );
+        // Skip chunks that don't fit and keep looking for smaller ones.
+        let tokens = estimate_tokens(&section);
+        if tokens > tokens_left {
+            continue;
+        }
+        tokens_left -= tokens;
+        picked.push((text_of(path_f).to_string(), num_of(start_f), section));
+    }
+    picked.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+    let mut out = format!("## {}\n\n", opts.labels.code);
+    for (_, _, section) in picked {
+        out.push_str(&section);
+    }
+    Ok(out)
+}
+
+fn render_chunk(path: &str, start: usize, end: usize, body: &str, opts: &CollectOptions) -> String {
+    let lang = language_from_path(Path::new(path));
+    let fence = fence_for(body, opts.fence_style);
+    format!(
+        "### {} ({} {}-{})\n\n{}{}\n{}\n{}\n\n",
+        inline_code(path),
+        opts.labels.lines,
+        start,
+        end,
+        fence,
+        lang,
+        body,
+        fence
+    )
+}
+
+#[test]
+fn test_index_round_trip() -> Result<(), Box<dyn Error>> {
+    let repo = tempfile::tempdir()?;
+    fs::write(
+        repo.path().join("billing.rs"),
+        "fn schedule_retry() {\n    charge();\n}\n\nfn refund() {\n    undo();\n}\n",
+    )?;
+    fs::write(repo.path().join("ui.rs"), "fn render_button() {}\n")?;
+    let index_dir = repo.path().join("idx");
+    let opts = CollectOptions::default();
+    assert_eq!(
+        build_index(&[repo.path().to_path_buf()], &index_dir, &opts)?,
+        3
+    );
+
+    let doc = query_index(&index_dir, "retries", 8000, &opts)?;
+    assert!(doc.contains("### `billing.rs` (lines 1-3)"));
+    assert!(!doc.contains("refund"));
+    assert!(!doc.contains("render_button"));
+    Ok(())
+}
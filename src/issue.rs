@@ -0,0 +1,338 @@
+use crate::git::run_git;
+use crate::i18n::Labels;
+use crate::remote::{github_token, is_remote_url};
+use crate::remote::{http_client, HttpOptions};
+use crate::sanitize::{fence_for, inline_code, plain_text, FenceStyle};
+use crate::types::FileEntry;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// A GitHub issue or pull request with its conversation.
+#[derive(Debug, Deserialize)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    pub user: User,
+    #[serde(skip)]
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub login: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Comment {
+    pub user: User,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+/// Fetch issue (or PR) `number` of the GitHub repo behind the first input that has one:
+/// a GitHub URL, or a local checkout whose `origin` points at GitHub.
+pub fn fetch_issue(
+    inputs: &[PathBuf],
+    number: u64,
+    http: &HttpOptions,
+) -> Result<Issue, Box<dyn Error>> {
+    let (owner, repo) = inputs
+        .iter()
+        .find_map(|input| github_repo_of(input))
+        .ok_or("--issue needs a GitHub repo URL or a checkout whose origin is on GitHub")?;
+    let client = http_client(http)?;
+    let api = format!(
+        "https://api.github.com/repos/{}/{}/issues/{}",
+        owner, repo, number
+    );
+    let mut issue: Issue = serde_json::from_str(&get(&client, &api)?)?;
+    // The first 100 comments is plenty of context; longer threads get cut off.
+    let comments = get(&client, &format!("{}/comments?per_page=100", api))?;
+    issue.comments = serde_json::from_str(&comments)?;
+    Ok(issue)
+}
+
+/// A pull request with its conversation, review comments and changes.
+#[derive(Debug)]
+pub struct PullRequest {
+    /// Number, title, description, author and conversation
+    pub issue: Issue,
+    pub files: Vec<ChangedFile>,
+    pub review_comments: Vec<ReviewComment>,
+    /// Unified diff of the whole pull request
+    pub diff: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangedFile {
+    pub filename: String,
+    pub status: String,
+    pub additions: u64,
+    pub deletions: u64,
+}
+
+/// A comment on a line of the diff.
+#[derive(Debug, Deserialize)]
+pub struct ReviewComment {
+    pub user: User,
+    pub path: String,
+    #[serde(default)]
+    pub line: Option<u64>,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+/// Fetch the pull request at `url` (`https://github.com/owner/repo/pull/123`) with its
+/// description, comments, review comments, changed files and diff.
+pub fn fetch_pull_request(url: &str, http: &HttpOptions) -> Result<PullRequest, Box<dyn Error>> {
+    let (owner, repo, number) =
+        parse_pr_url(url).ok_or_else(|| format!("--pr: not a GitHub pull request URL: {}", url))?;
+    let client = http_client(http)?;
+    let repo_api = format!("https://api.github.com/repos/{}/{}", owner, repo);
+    let api = format!("{}/pulls/{}", repo_api, number);
+    let mut issue: Issue = serde_json::from_str(&get(&client, &api)?)?;
+    // As for issues, the first 100 of each list is plenty of context.
+    let comments = get(
+        &client,
+        &format!("{}/issues/{}/comments?per_page=100", repo_api, number),
+    )?;
+    issue.comments = serde_json::from_str(&comments)?;
+    let review_comments = get(&client, &format!("{}/comments?per_page=100", api))?;
+    let files = get(&client, &format!("{}/files?per_page=100", api))?;
+    Ok(PullRequest {
+        issue,
+        files: serde_json::from_str(&files)?,
+        review_comments: serde_json::from_str(&review_comments)?,
+        diff: get_as(&client, &api, "application/vnd.github.diff")?,
+    })
+}
+
+/// `(owner, repo, number)` from `https://github.com/o/r/pull/123`, also with a trailing
+/// `/files` or `#discussion_...`.
+fn parse_pr_url(url: &str) -> Option<(String, String, u64)> {
+    let (_, rest) = url.split_once("github.com/")?;
+    let mut segments = rest.split(['/', '#', '?']);
+    let owner = segments.next().filter(|s| !s.is_empty())?;
+    let repo = segments.next().filter(|s| !s.is_empty())?;
+    if segments.next()? != "pull" {
+        return None;
+    }
+    let number = segments.next()?.parse().ok()?;
+    Some((owner.to_string(), repo.to_string(), number))
+}
+
+fn get(client: &Client, url: &str) -> Result<String, Box<dyn Error>> {
+    get_as(client, url, "application/vnd.github+json")
+}
+
+fn get_as(client: &Client, url: &str, accept: &str) -> Result<String, Box<dyn Error>> {
+    let mut request = client
+        .get(url)
+        .header("Accept", accept)
+        .header("User-Agent", concat!("r2md/", env!("CARGO_PKG_VERSION")));
+    if let Some(token) = github_token() {
+        request = request.bearer_auth(token);
+    }
+    let resp = request.send()?;
+    if !resp.status().is_success() {
+        return Err(format!("GitHub API request {} failed: {}", url, resp.status()).into());
+    }
+    Ok(resp.text()?)
+}
+
+fn github_repo_of(input: &Path) -> Option<(String, String)> {
+    let text = input.to_string_lossy();
+    if is_remote_url(&text) {
+        return parse_github_repo(&text);
+    }
+    let origin = run_git(input, &["remote", "get-url", "origin"]).ok()?;
+    parse_github_repo(origin.trim())
+}
+
+/// `owner/repo` from `https://github.com/o/r(.git)`, `git@github.com:o/r.git` or
+/// `ssh://git@github.com/o/r`.
+fn parse_github_repo(url: &str) -> Option<(String, String)> {
+    let rest = url
+        .strip_prefix("git@github.com:")
+        .or_else(|| url.split_once("github.com/").map(|(_, rest)| rest))?;
+    let mut segments = rest.trim_end_matches('/').split('/');
+    let owner = segments.next().filter(|s| !s.is_empty())?;
+    let repo = segments.next()?.trim_end_matches(".git");
+    (!repo.is_empty()).then(|| (owner.to_string(), repo.to_string()))
+}
+
+impl Issue {
+    /// The issue as a Markdown section for ahead of the code. Bodies are block-quoted so
+    /// their headings and fences stay nested under the issue.
+    pub fn render(&self, labels: &Labels) -> String {
+        let title = labels
+            .issue
+            .replace("{number}", &self.number.to_string())
+            .replace("{title}", &plain_text(&self.title));
+        let mut out = format!("## {}\n\n", title);
+        out.push_str(&quote(&self.user.login, self.body.as_deref()));
+        for comment in &self.comments {
+            out.push_str(&quote(&comment.user.login, comment.body.as_deref()));
+        }
+        out
+    }
+
+    /// Whether the issue mentions `file`: its path or file name, or an identifier the
+    /// issue puts in backticks that occurs as a whole word in the file.
+    pub fn mentions(&self, file: &FileEntry) -> bool {
+        let text = self.text();
+        let name = file.rel_path.rsplit('/').next().unwrap_or(&file.rel_path);
+        if text.contains(&file.rel_path) || (name.contains('.') && text.contains(name)) {
+            return true;
+        }
+        let mentioned = code_spans(&text)
+            .flat_map(|span| span.split(|c: char| !(c.is_alphanumeric() || c == '_')))
+            .filter(|ident| ident.len() >= 3)
+            .any(|ident| contains_word(&file.content, ident));
+        mentioned
+    }
+
+    fn text(&self) -> String {
+        let mut text = format!("{}\n{}", self.title, self.body.as_deref().unwrap_or(""));
+        for comment in &self.comments {
+            text.push('\n');
+            text.push_str(comment.body.as_deref().unwrap_or(""));
+        }
+        text
+    }
+}
+
+impl PullRequest {
+    /// The pull request as a Markdown section for ahead of the code: the conversation as
+    /// for issues, then review comments with the line they are on, the changed files and
+    /// the diff.
+    pub fn render(&self, labels: &Labels, fence_style: FenceStyle) -> String {
+        let title = labels
+            .pull_request
+            .replace("{number}", &self.issue.number.to_string())
+            .replace("{title}", &plain_text(&self.issue.title));
+        let mut out = format!("## {}\n\n", title);
+        out.push_str(&quote(&self.issue.user.login, self.issue.body.as_deref()));
+        for comment in &self.issue.comments {
+            out.push_str(&quote(&comment.user.login, comment.body.as_deref()));
+        }
+        for comment in &self.review_comments {
+            let place = match comment.line {
+                Some(line) => format!("{}:{}", comment.path, line),
+                None => comment.path.clone(),
+            };
+            let heading = format!(
+                "**@{}** {}",
+                plain_text(&comment.user.login),
+                inline_code(&place)
+            );
+            out.push_str(&quote_under(&heading, comment.body.as_deref()));
+        }
+        if !self.files.is_empty() {
+            out.push_str(&format!("### {}\n\n", labels.changed_files));
+            for file in &self.files {
+                out.push_str(&format!(
+                    "- {} ({}, +{} -{})\n",
+                    inline_code(&file.filename),
+                    file.status,
+                    file.additions,
+                    file.deletions
+                ));
+            }
+            out.push('\n');
+        }
+        let diff = self.diff.trim_end();
+        if !diff.is_empty() {
+            let fence = fence_for(diff, fence_style);
+            out.push_str(&format!("{}diff\n{}\n{}\n\n", fence, diff, fence));
+        }
+        out
+    }
+}
+
+fn quote(author: &str, body: Option<&str>) -> String {
+    quote_under(&format!("**@{}**", plain_text(author)), body)
+}
+
+/// `body` block-quoted under the Markdown line `heading`.
+fn quote_under(heading: &str, body: Option<&str>) -> String {
+    let mut out = format!("{}\n\n", heading);
+    for line in body.unwrap_or("").trim().lines() {
+        out.push_str("> ");
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push('\n');
+    out
+}
+
+/// Contents of the `inline code` spans in `text`.
+fn code_spans(text: &str) -> impl Iterator<Item = &str> {
+    text.split('`').skip(1).step_by(2)
+}
+
+fn contains_word(haystack: &str, word: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    haystack.match_indices(word).any(|(i, _)| {
+        let before = haystack[..i].chars().next_back();
+        let after = haystack[i + word.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+#[test]
+fn test_issue_mentions() {
+    assert_eq!(
+        parse_github_repo("git@github.com:acme/api.git"),
+        Some(("acme".to_string(), "api".to_string()))
+    );
+    let issue = Issue {
+        number: 7,
+        title: "Retries double-charge".to_string(),
+        body: Some("See `charge_card` in billing.rs".to_string()),
+        user: User {
+            login: "dev".to_string(),
+        },
+        comments: Vec::new(),
+    };
+    let file = |rel_path: &str, content: &str| FileEntry {
+        rel_path: rel_path.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    };
+    assert!(issue.mentions(&file("src/billing.rs", "")));
+    assert!(issue.mentions(&file("src/pay.rs", "fn charge_card() {}")));
+    assert!(!issue.mentions(&file("src/pay.rs", "fn charge_cards() {}")));
+
+    assert_eq!(
+        parse_pr_url("https://github.com/acme/api/pull/12/files#diff-1"),
+        Some(("acme".to_string(), "api".to_string(), 12))
+    );
+    assert_eq!(parse_pr_url("https://github.com/acme/api/issues/12"), None);
+    let pr = PullRequest {
+        issue,
+        files: vec![ChangedFile {
+            filename: "src/billing.rs".to_string(),
+            status: "modified".to_string(),
+            additions: 3,
+            deletions: 1,
+        }],
+        review_comments: vec![ReviewComment {
+            user: User {
+                login: "lin".to_string(),
+            },
+            path: "src/billing.rs".to_string(),
+            line: Some(40),
+            body: Some("Needs a test".to_string()),
+        }],
+        diff: "diff --git a/src/billing.rs b/src/billing.rs\n-a\n+b\n".to_string(),
+    };
+    assert_eq!(
+        pr.render(&Labels::default(), FenceStyle::Backtick),
+        "## Pull request #7: Retries double-charge\n\n**@dev**\n\n> See `charge_card` in billing.rs\n\n**@lin** `src/billing.rs:40`\n\n> Needs a test\n\n### Changed files\n\n- `src/billing.rs` (modified, +3 -1)\n\n```diff\ndiff --git a/src/billing.rs b/src/billing.rs\n-a\n+b\n```\n\n"
+    );
+}
@@ -0,0 +1,97 @@
+use crate::patterns::PathFilter;
+use crate::symlinks::SymlinkGuard;
+use crate::types::CollectOptions;
+use crate::{
+    make_relative, should_skip_folder, BINARY_FILE_EXTENSIONS, DEFAULT_MAX_FILE_SIZE, IGNORE_FILE,
+    RECOGNIZED_EXTENSIONS,
+};
+use ignore::WalkBuilder;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::debug;
+
+/// Extensions r2md doesn't recognize that still make up more than `threshold_pct` percent
+/// of the bytes under `dirs`, largest share first.
+///
+/// This is a metadata-only pre-pass using the same gitignore and folder rules as the
+/// real walk, so niche-language repos (`.zig`, `.odin`, `.gleam`, ...) aren't exported
+/// nearly empty. Content is still sniffed for binary data when the files are read.
+pub fn detect_auto_extensions(
+    dirs: &[&Path],
+    opts: &CollectOptions,
+    threshold_pct: f64,
+) -> Vec<String> {
+    let mut bytes_by_ext: HashMap<String, u64> = HashMap::new();
+    let mut total: u64 = 0;
+    for dir in dirs {
+        // An invalid pattern is reported by the real walk.
+        let filter = PathFilter::new(Some(dir), &[], &opts.excludes).unwrap_or_default();
+        let prune_filter = filter.clone();
+        let root = dir.to_path_buf();
+        let link_guard = opts.follow_symlinks.then(|| SymlinkGuard::new(dir));
+        let walker = WalkBuilder::new(dir)
+            .hidden(false)
+            .follow_links(opts.follow_symlinks)
+            .git_global(false)
+            .git_exclude(false)
+            .require_git(false)
+            .add_custom_ignore_filename(IGNORE_FILE)
+            .filter_entry(move |ent| {
+                link_guard.as_ref().is_none_or(|guard| guard.allows(ent))
+                    && (ent.depth() == 0
+                        || !ent.file_type().is_some_and(|t| t.is_dir())
+                        || !(should_skip_folder(&ent.file_name().to_string_lossy())
+                            || prune_filter.is_excluded(&make_relative(&root, ent.path()), true)))
+            })
+            .build();
+        for ent in walker.flatten() {
+            if !ent.file_type().is_some_and(|t| t.is_file())
+                || filter.is_excluded(&make_relative(dir, ent.path()), false)
+            {
+                continue;
+            }
+            let Some(size) = ent.metadata().ok().map(|m| m.len()) else {
+                continue;
+            };
+            if size > DEFAULT_MAX_FILE_SIZE {
+                continue;
+            }
+            let Some(ext) = ent.path().extension().and_then(|e| e.to_str()) else {
+                continue;
+            };
+            let ext = ext.to_lowercase();
+            // Docs/config files aren't code; `--include-docs` covers them.
+            if BINARY_FILE_EXTENSIONS.contains(&ext.as_str())
+                || opts
+                    .doc_extensions
+                    .iter()
+                    .any(|d| d.eq_ignore_ascii_case(&ext))
+            {
+                continue;
+            }
+            total += size;
+            *bytes_by_ext.entry(ext).or_default() += size;
+        }
+    }
+    if total == 0 {
+        return vec![];
+    }
+
+    let mut picked: Vec<(String, u64)> = bytes_by_ext
+        .into_iter()
+        .filter(|(ext, bytes)| {
+            !RECOGNIZED_EXTENSIONS.contains(&ext.as_str())
+                && *bytes as f64 * 100.0 / total as f64 > threshold_pct
+        })
+        .collect();
+    picked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    for (ext, bytes) in &picked {
+        debug!(
+            "Auto-including .{} ({:.1}% of {} bytes)",
+            ext,
+            *bytes as f64 * 100.0 / total as f64,
+            total
+        );
+    }
+    picked.into_iter().map(|(ext, _)| ext).collect()
+}
@@ -0,0 +1,153 @@
+//! `--summary`: a cloc-style table of the export per language (files, code, comment and
+//! blank lines, and tokens), at the top of the document.
+//!
+//! Comment lines come from the `minify` lexer rather than a parser: a line is code if
+//! anything is left of it once comments and docstrings are stripped. Languages `minify`
+//! doesn't know count every non-blank line as code.
+
+use crate::i18n::Labels;
+use crate::language_from_path;
+use crate::minify::minify;
+use crate::tokens::estimate_tokens;
+use crate::types::FileEntry;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Line and token counts for one language, or for the whole export.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LanguageStats {
+    pub files: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blank: usize,
+    pub tokens: usize,
+}
+
+impl LanguageStats {
+    fn add(&mut self, other: &LanguageStats) {
+        self.files += other.files;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blank += other.blank;
+        self.tokens += other.tokens;
+    }
+
+    fn lines(&self) -> usize {
+        self.code + self.comments + self.blank
+    }
+}
+
+/// Counts for one file of language `lang`.
+pub fn file_stats(content: &str, lang: &str) -> LanguageStats {
+    let blank = content.lines().filter(|l| l.trim().is_empty()).count();
+    let non_blank = content.lines().count() - blank;
+    // Blank lines inside multi-line strings survive minifying; they were counted as blank.
+    let code = match minify(content, lang) {
+        Some(small) => small
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .count()
+            .min(non_blank),
+        None => non_blank,
+    };
+    LanguageStats {
+        files: 1,
+        code,
+        comments: non_blank - code,
+        blank,
+        tokens: estimate_tokens(content),
+    }
+}
+
+/// Per-language counts, most code first.
+pub fn language_stats(files: &[FileEntry]) -> Vec<(&str, LanguageStats)> {
+    let mut by_lang: HashMap<&str, LanguageStats> = HashMap::new();
+    for file in files {
+        let lang = language_from_path(Path::new(&file.rel_path));
+        by_lang
+            .entry(lang)
+            .or_default()
+            .add(&file_stats(&file.content, lang));
+    }
+    let mut rows: Vec<(&str, LanguageStats)> = by_lang.into_iter().collect();
+    rows.sort_by(|(a_lang, a), (b_lang, b)| b.code.cmp(&a.code).then(a_lang.cmp(b_lang)));
+    rows
+}
+
+/// The summary section: one table row per language and a total.
+pub fn render_language_stats(files: &[FileEntry], labels: &Labels) -> String {
+    let rows = language_stats(files);
+    let mut total = LanguageStats::default();
+    for (_, stats) in &rows {
+        total.add(stats);
+    }
+    let mut out = format!("## {}\n\n", labels.stats_title);
+    out.push_str(&format!(
+        "| {} | {} | {} | {} | {} | {} | {} | {} |\n",
+        labels.stats_language,
+        labels.stats_files,
+        labels.stats_lines,
+        labels.stats_code,
+        labels.stats_comments,
+        labels.stats_blank,
+        labels.stats_ratio,
+        labels.stats_tokens
+    ));
+    out.push_str("|---|--:|--:|--:|--:|--:|--:|--:|\n");
+    let row = |name: &str, stats: &LanguageStats| {
+        let ratio = if stats.code == 0 {
+            "-".to_string()
+        } else {
+            format!("{:.2}", stats.comments as f64 / stats.code as f64)
+        };
+        format!(
+            "| {} | {} | {} | {} | {} | {} | {} | {} |\n",
+            name,
+            stats.files,
+            stats.lines(),
+            stats.code,
+            stats.comments,
+            stats.blank,
+            ratio,
+            stats.tokens
+        )
+    };
+    for (lang, stats) in &rows {
+        out.push_str(&row(lang, stats));
+    }
+    out.push_str(&row(&format!("**{}**", labels.stats_total), &total));
+    out.push('\n');
+    out
+}
+
+#[test]
+fn test_language_stats() {
+    let file = |rel_path: &str, content: &str| FileEntry {
+        rel_path: rel_path.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    };
+    let files = [
+        file(
+            "src/lib.rs",
+            "// Parser.\n\n/* Block\n   comment */\nfn parse() {} // trailing\nfn run() {}\n",
+        ),
+        file(
+            "tool.py",
+            "\"\"\"Docs.\"\"\"\n# comment\nx = \"\"\"\n\n\"\"\"\n",
+        ),
+        file("Makefile", "all:\n\n"),
+    ];
+    let stats = language_stats(&files);
+    let get = |lang: &str| stats.iter().find(|(l, _)| *l == lang).unwrap().1;
+    let rust = get("rust");
+    assert_eq!((rust.code, rust.comments, rust.blank), (2, 3, 1));
+    let python = get("python");
+    assert_eq!((python.code, python.comments, python.blank), (2, 2, 1));
+    assert_eq!(get("plaintext").code, 1);
+    assert_eq!(stats[0].0, "python");
+
+    let table = render_language_stats(&files, &Labels::default());
+    assert!(table.contains("| rust | 1 | 6 | 2 | 3 | 1 | 1.50 |"));
+    assert!(table.contains("| **Total** | 3 | 13 | 5 | 5 | 3 |"));
+}
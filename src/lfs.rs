@@ -0,0 +1,63 @@
+use crate::types::{CollectOptions, LfsAsset};
+use std::fs;
+use std::path::Path;
+
+/// Real LFS pointers are ~130 bytes; anything much larger is an actual file.
+pub const MAX_POINTER_SIZE: u64 = 1024;
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// Parse a Git LFS pointer file (`version` / `oid sha256:...` / `size N`).
+pub fn parse_pointer(rel_path: &str, text: &str) -> Option<LfsAsset> {
+    let mut lines = text.lines();
+    if lines.next()?.trim_end() != POINTER_VERSION {
+        return None;
+    }
+    let mut oid = None;
+    let mut size = None;
+    for line in lines {
+        if let Some(rest) = line.strip_prefix("oid ") {
+            oid = Some(rest.trim().to_string());
+        } else if let Some(rest) = line.strip_prefix("size ") {
+            size = rest.trim().parse().ok();
+        }
+    }
+    Some(LfsAsset {
+        path: rel_path.to_string(),
+        oid: oid?,
+        size: size?,
+    })
+}
+
+/// An LFS asset if `bytes` (an archive entry the name filters rejected) is a pointer.
+pub fn pointer_from_bytes(rel_path: &str, bytes: &[u8], opts: &CollectOptions) -> Option<LfsAsset> {
+    if bytes.len() as u64 > MAX_POINTER_SIZE || opts.user_ignores.matches(rel_path, false) {
+        return None;
+    }
+    parse_pointer(rel_path, std::str::from_utf8(bytes).ok()?)
+}
+
+/// Whether a file the name filters rejected (`.png`, `.bin`, ...) is really an LFS pointer
+/// that belongs in the assets inventory. User ignore patterns still apply.
+pub fn is_pointer_file(path: &Path, rel_path: &str, opts: &CollectOptions) -> bool {
+    if opts.user_ignores.matches(rel_path, false) {
+        return false;
+    }
+    match path.metadata() {
+        Ok(md) if md.len() <= MAX_POINTER_SIZE => {}
+        _ => return false,
+    }
+    fs::read(path)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .is_some_and(|text| parse_pointer(rel_path, &text).is_some())
+}
+
+#[test]
+fn test_parse_lfs_pointer() {
+    let text = "version https://git-lfs.github.com/spec/v1\noid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\nsize 12345\n";
+    let asset = parse_pointer("assets/logo.png", text).expect("pointer");
+    assert_eq!(asset.size, 12345);
+    assert!(asset.oid.starts_with("sha256:4d7a"));
+    assert!(parse_pointer("main.rs", "fn main() {}").is_none());
+}
@@ -0,0 +1,170 @@
+//! `--strip-license-headers`: drop the license or copyright comments at the top of a
+//! file, often dozens of lines repeated in every file, and name the license instead.
+
+use crate::language_from_path;
+use crate::minify::{spans, Span};
+use crate::types::FileEntry;
+use std::path::Path;
+
+/// Whether a comment reads like a license or copyright notice.
+pub fn is_license(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    ["copyright", "license", "spdx-", "(c) "]
+        .iter()
+        .any(|word| lower.contains(word))
+}
+
+/// Wording that identifies a license, checked in order, as its SPDX identifier.
+const LICENSE_PHRASES: &[(&str, &str)] = &[
+    ("apache license", "Apache-2.0"),
+    ("permission is hereby granted, free of charge", "MIT"),
+    ("mit license", "MIT"),
+    ("gnu affero general public license", "AGPL-3.0"),
+    ("gnu lesser general public license", "LGPL"),
+    ("gnu general public license", "GPL"),
+    ("mozilla public license", "MPL-2.0"),
+    ("neither the name of", "BSD-3-Clause"),
+    (
+        "redistribution and use in source and binary forms",
+        "BSD-2-Clause",
+    ),
+    (
+        "permission to use, copy, modify, and/or distribute this software",
+        "ISC",
+    ),
+    ("boost software license", "BSL-1.0"),
+    ("eclipse public license", "EPL-2.0"),
+    ("this is free and unencumbered software", "Unlicense"),
+];
+
+/// The license a header names: its `SPDX-License-Identifier`, else one recognized by its
+/// wording. GPL-family versions come from a `version N` in the text.
+pub fn license_name(text: &str) -> Option<String> {
+    if let Some(at) = text.find("SPDX-License-Identifier:") {
+        let id = text[at + "SPDX-License-Identifier:".len()..]
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .trim_end_matches("*/")
+            .trim();
+        if !id.is_empty() {
+            return Some(id.to_string());
+        }
+    }
+    // Lowercase words without comment markers, so phrases match across lines.
+    let words: Vec<String> = text
+        .to_lowercase()
+        .split_whitespace()
+        .map(|w| w.trim_start_matches(['/', '#', '*', '!']).to_string())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let normalized = words.join(" ");
+    let (_, name) = LICENSE_PHRASES
+        .iter()
+        .find(|(phrase, _)| normalized.contains(phrase))?;
+    if name.ends_with("GPL") {
+        for (version, spdx) in [("3", "3.0"), ("2.1", "2.1"), ("2", "2.0")] {
+            if normalized.contains(&format!("version {}", version)) {
+                return Some(format!("{}-{}", name, spdx));
+            }
+        }
+    }
+    Some(name.to_string())
+}
+
+/// Remove the license and copyright comments `file` starts with (after a shebang or
+/// `<?php` line), returning the removed text; `None` if it doesn't start with any. The
+/// lines that stay keep their original numbers in `file.lines`.
+///
+/// The leading comments are taken a paragraph at a time, up to a blank line, and only
+/// paragraphs that mention a copyright or a recognized license go; the first one that
+/// doesn't, such as the file's own docs, ends the header.
+pub fn strip_license_header(file: &mut FileEntry) -> Option<String> {
+    let lang = language_from_path(Path::new(&file.rel_path));
+    let content = file.content.as_str();
+    let top_len = if content.starts_with("#!") || content.starts_with("<?php") {
+        content.find('\n').map_or(content.len(), |i| i + 1)
+    } else {
+        0
+    };
+    let (top, rest) = content.split_at(top_len);
+    let mut removed = String::new();
+    let mut end = 0;
+    let mut paragraph = String::new();
+    let mut offset = 0;
+    for (kind, text) in spans(rest, lang)? {
+        let blank_line = kind == Span::Code && text.matches('\n').count() > 1;
+        if kind == Span::Comment {
+            paragraph.push_str(text);
+            paragraph.push('\n');
+        } else if !(kind == Span::Code && text.trim().is_empty()) {
+            break;
+        }
+        offset += text.len();
+        if blank_line {
+            if !is_header(&paragraph) {
+                break;
+            }
+            removed.push_str(&paragraph);
+            paragraph.clear();
+            end = offset;
+        }
+    }
+    if is_header(&paragraph) {
+        removed.push_str(&paragraph);
+        end = offset;
+    }
+    if removed.is_empty() {
+        return None;
+    }
+    let body = rest[end..].trim_start_matches(['\n', '\r']);
+    let top_lines = top.lines().count();
+    let dropped = content.lines().count() - top_lines - body.lines().count();
+    let origins: Vec<Option<usize>> = (1..=top_lines)
+        .chain((top_lines + 1..).map(|line| line + dropped))
+        .take(top_lines + body.lines().count())
+        .map(Some)
+        .collect();
+    file.content = format!("{}{}", top, body);
+    file.lines = file.lines.cut(origins);
+    Some(removed)
+}
+
+/// Whether a comment paragraph is a copyright or license notice rather than other docs.
+fn is_header(paragraph: &str) -> bool {
+    let lower = paragraph.to_lowercase();
+    lower.contains("copyright") || lower.contains("(c) ") || license_name(paragraph).is_some()
+}
+
+#[test]
+fn test_strip_license_header() {
+    let file = |rel_path: &str, content: &str| FileEntry {
+        rel_path: rel_path.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    };
+    let mut rust = file("lib.rs", "// Copyright 2024 Acme Inc.\n// SPDX-License-Identifier: Apache-2.0\n\n//! Parses license files.\n\nfn main() {}\n");
+    let removed = strip_license_header(&mut rust).unwrap();
+    assert_eq!(rust.content, "//! Parses license files.\n\nfn main() {}\n");
+    assert_eq!(rust.lines.original(1), Some(4));
+    assert_eq!(rust.lines.original(3), Some(6));
+    assert_eq!(license_name(&removed).as_deref(), Some("Apache-2.0"));
+
+    let mut python = file("tool.py", "#!/usr/bin/env python\n# Copyright (c) 2020 Someone\n#\n# This program is free software: you can redistribute it under the terms of the GNU\n# General Public License as published by the Free Software Foundation, either\n# version 3 of the License.\nimport os\n");
+    let removed = strip_license_header(&mut python).unwrap();
+    assert_eq!(python.content, "#!/usr/bin/env python\nimport os\n");
+    assert_eq!(python.lines.original(1), Some(1));
+    assert_eq!(python.lines.original(2), Some(7));
+    assert_eq!(license_name(&removed).as_deref(), Some("GPL-3.0"));
+
+    let mut js = file("a.js", "/*\n * Permission is hereby granted, free of charge, to any person\n * obtaining a copy of this software\n */\nexport const a = 1;\n");
+    let removed = strip_license_header(&mut js).unwrap();
+    assert_eq!(js.content, "export const a = 1;\n");
+    assert_eq!(license_name(&removed).as_deref(), Some("MIT"));
+
+    let mut docs = file("lib.rs", "// License file parsing.\nfn a() {}\n");
+    assert_eq!(strip_license_header(&mut docs), None);
+    assert!(docs.lines.is_whole());
+    assert_eq!(strip_license_header(&mut file("a.rs", "fn a() {}\n")), None);
+}
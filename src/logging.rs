@@ -0,0 +1,123 @@
+//! Diagnostics on stderr through `tracing`: warnings and notes by default, every skip
+//! decision with `--log-level debug` (or `--debug`), and one JSON object per event with
+//! `--log-json` so skip decisions can be filtered and counted by other tools.
+//!
+//! Skip events all carry `path` and a short `reason`, e.g. `binary`, `extension`,
+//! `ignored`, `excluded`, `too_large`, `undecodable` or `unreadable`.
+
+use std::fmt;
+use std::io;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Level names `--log-level` accepts.
+pub const LOG_LEVELS: &[&str] = &["error", "warn", "info", "debug", "trace"];
+
+/// Send r2md's events at `level` and above to stderr, as text or as JSON lines.
+pub fn init(level: LevelFilter, json: bool) {
+    let builder = tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_ansi(atty::is(atty::Stream::Stderr))
+        .with_max_level(LevelFilter::TRACE);
+    // Only fails if a subscriber is already set, which leaves logging working anyway.
+    let _ = if json {
+        builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(false)
+            .finish()
+            .with(targets(level))
+            .try_init()
+    } else {
+        builder
+            .event_format(Plain)
+            .finish()
+            .with(targets(level))
+            .try_init()
+    };
+}
+
+/// `level` for r2md itself; dependencies (tantivy, reqwest, hyper, ...) only get to
+/// print warnings and errors.
+fn targets(level: LevelFilter) -> Targets {
+    Targets::new()
+        .with_target("r2md", level)
+        .with_default(LevelFilter::WARN.min(level))
+}
+
+/// Whether `--log-level debug` or finer is on, for turning off the progress bar.
+pub fn is_verbose() -> bool {
+    LevelFilter::current() >= LevelFilter::DEBUG
+}
+
+/// The text format r2md has always printed: `Warning: ...` and notes as they are, debug
+/// events with their level in front and fields after the message.
+struct Plain;
+
+impl<S, N> FormatEvent<S, N> for Plain
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        match *event.metadata().level() {
+            Level::ERROR => write!(writer, "Error: ")?,
+            Level::WARN => write!(writer, "Warning: ")?,
+            Level::INFO => {}
+            level => write!(writer, "{} ", level)?,
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+#[test]
+fn test_plain_format() {
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+    impl io::Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .with_max_level(LevelFilter::TRACE)
+        .event_format(Plain)
+        .finish()
+        .with(targets(LevelFilter::DEBUG));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(target: "tantivy::indexer", "Preparing commit");
+        tracing::warn!("--owner only applies to local directories");
+        tracing::info!("Loaded config from r2md.yml");
+        tracing::debug!(path = "a.png", reason = "binary", "Skipping file");
+        tracing::trace!("not shown");
+        tracing::warn!(target: "hyper", "connection reset");
+    });
+    let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(
+        text,
+        "Warning: --owner only applies to local directories\nLoaded config from r2md.yml\nDEBUG Skipping file path=\"a.png\" reason=\"binary\"\nWarning: connection reset\n"
+    );
+}
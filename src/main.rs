@@ -1,31 +1,152 @@
+mod anonymize;
+mod archive;
+mod budget;
+mod cancel;
+mod chunks;
+mod codeowners;
+mod component;
+mod compress;
+mod cost;
+mod daemon;
+mod dedup;
 mod deps;
+mod describe;
+mod docx;
+mod embed;
+mod epub;
+mod error;
+mod filekind;
+mod focus;
+mod git;
+mod hooks;
+mod html;
+mod i18n;
+mod index;
+mod issue;
+mod langshare;
+mod langstats;
+mod lfs;
+mod license;
+mod logging;
+mod manifest;
+mod markdown;
+mod markup;
+mod members;
+mod metadata;
+mod minify;
+mod notebook;
+mod order;
+mod overview;
+mod patterns;
+mod pdf;
+mod progress;
+mod relevance;
+mod remote;
+mod resume;
+mod sanitize;
+mod schema;
+mod sniff;
+mod split;
+mod sqlite;
+mod summary;
+mod symlinks;
+mod theme;
+mod tokens;
 mod training; // at the top
+mod tree;
 mod types;
+mod update;
+mod verify;
 
-use atty;
 use clap::{Arg, ArgAction, Command};
-use ignore::WalkBuilder;
+use ignore::{WalkBuilder, WalkState};
 use rayon::prelude::*;
-use reqwest;
 use serde::Deserialize;
-use serde_yaml;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
 use std::error::Error;
 use std::ffi::OsStr;
-use std::fs::{self, File};
+use std::fs;
 use std::io::{self, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
-
-use crate::training::produce_training_json;
-use crate::types::FileEntry;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+use tracing_subscriber::filter::LevelFilter;
+
+use crate::anonymize::Anonymizer;
+use crate::archive::{archive_kind, collect_files_from_archive_file};
+use crate::budget::{fit_output, OutputLimit};
+use crate::chunks::{flag_chunks, ChunkThresholds};
+use crate::codeowners::CodeOwners;
+use crate::component::{split_component, BlockKind};
+use crate::compress::OutputFile;
+use crate::cost::estimate_costs;
+use crate::daemon::{default_socket_path, run_daemon, ReadCache};
+use crate::dedup::identical_files;
+use crate::describe::file_description;
+use crate::docx::write_docx_file;
+use crate::embed::write_chunks;
+use crate::epub::write_epub_file;
+use crate::error::{R2mdError, RunError, EXIT_ERROR, EXIT_PARTIAL};
+use crate::filekind::{FileKindFilter, GENERATED_MARKERS, GENERATED_PATTERNS, TEST_PATTERNS};
+use crate::focus::{cut_lines, keep_ranges, select_focus, FocusTarget};
+use crate::hooks::{
+    deserialize_preprocess, preprocess_hook_for, run_on_complete, run_preprocess, HooksConfig,
+    PreprocessHook,
+};
+use crate::html::write_html_app;
+use crate::i18n::Labels;
+use crate::index::{build_index, query_index, DEFAULT_INDEX_DIR};
+use crate::issue::{fetch_issue, fetch_pull_request};
+use crate::langshare::detect_auto_extensions;
+use crate::langstats::render_language_stats;
+use crate::license::{license_name, strip_license_header};
+use crate::logging::LOG_LEVELS;
+use crate::manifest::{write_manifest, CountingWriter, SectionSpan, DEFAULT_MANIFEST};
+use crate::markdown::{render_inline, RenderStrategy};
+use crate::markup::{write_markup_file, Markup};
+use crate::members::{find_members, is_checked_out, member_of, MemberKind};
+use crate::metadata::{Metadata, MetadataStyle};
+use crate::minify::minify;
+use crate::order::{sort_files, SortOrder};
+use crate::overview::render_overview;
+use crate::patterns::{normalize_rel_path, PathFilter, PathPatterns};
+use crate::pdf::{write_pdf_file, Orientation, PageSize, PdfOptions};
+use crate::progress::Phase;
+use crate::relevance::{select_relevant, Budget};
+use crate::remote::{
+    collect_files_from_git_url, collect_files_from_ssh_url, is_remote_url, is_ssh_url, HttpOptions,
+};
+use crate::resume::{ResumeState, DEFAULT_RESUME_DIR};
+use crate::sanitize::{escape_control, fence_for, inline_code, plain_text, FenceStyle};
+use crate::schema::{json_schema, SCHEMA_NAMES, SCHEMA_VERSION};
+use crate::sniff::bytes_to_text;
+use crate::split::{write_dir_split_markdown, write_split_markdown};
+use crate::sqlite::write_sqlite_file;
+use crate::summary::RunSummary;
+use crate::symlinks::SymlinkGuard;
+use crate::theme::{load_theme, theme_names};
+use crate::tokens::{estimate_tokens, TruncateStrategy, Truncation};
+use crate::training::{
+    produce_training_json, TrainFormat, TrainStyle, TrainingOptions, DEFAULT_TOKENIZER,
+};
+use crate::tree::{input_prefixes, Tree};
+use crate::types::{CollectOptions, CollectReport, FileEntry, InaccessiblePath, LfsAsset, LineMap};
+use crate::update::{update_markdown, Frame};
 
 /// Keep the original ~20 recognized language extensions (focusing on text-based code)
 static RECOGNIZED_EXTENSIONS: &[&str] = &[
     // Rust
     "rs", // Python
     "py", // JavaScript
-    "js", // TypeScript
-    "ts", // C
+    "js", "jsx", // TypeScript
+    "ts", "tsx", // C
     "c", "h", // C++
     "cpp", "hpp", "cc", "cxx", "hh",    // Java
     "java",  // C#
@@ -34,17 +155,76 @@ static RECOGNIZED_EXTENSIONS: &[&str] = &[
     "rb",    // PHP
     "php",   // Swift
     "swift", // Kotlin
-    "kt", "kts", // Objective-C
-    "m",   // Objective-C++
-    "mm",  // Shell scripts
-    "sh",  // Batch
-    "bat", // F#
-    "fs",  // Visual Basic
-    "vb",  // Scala
+    "kt", "kts",   // Objective-C
+    "m",     // Objective-C++
+    "mm",    // Shell scripts
+    "sh",    // Batch
+    "bat",   // F#
+    "fs",    // Visual Basic
+    "vb",    // Scala
+    "scala", // Terraform, SQL and Protobuf
+    "tf", "sql", "proto", // Jupyter notebooks
+    "ipynb", // Vue and Svelte components
+    "vue", "svelte",
+];
+
+/// Every name `language_from_path` can return for a collected file, for `--lang`.
+static LANGUAGE_NAMES: &[&str] = &[
+    "rust",
+    "python",
+    "javascript",
+    "jsx",
+    "typescript",
+    "tsx",
+    "java",
+    "c",
+    "cpp",
+    "csharp",
+    "go",
+    "ruby",
+    "php",
+    "swift",
+    "kotlin",
+    "objectivec",
+    "bash",
+    "bat",
+    "fsharp",
+    "vb",
     "scala",
-    "tf"
+    "markdown",
+    "asciidoc",
+    "toml",
+    "yaml",
+    "json",
+    "hcl",
+    "sql",
+    "protobuf",
+    "dockerfile",
+    "notebook",
+    "vue",
+    "svelte",
+    "plaintext",
+];
+
+/// Docs and config files collected with `--include-docs` (overridable via `doc_extensions`)
+static DOC_EXTENSIONS: &[&str] = &[
+    "md",
+    "markdown",
+    "adoc",
+    "asciidoc",
+    "toml",
+    "yaml",
+    "yml",
+    "json",
+    "dockerfile",
+    "tf",
+    "sql",
+    "proto",
 ];
 
+/// Machine-generated lockfiles that look like docs/config but are pure noise
+static DOC_SKIP_NAMES: &[&str] = &["package-lock.json", "pnpm-lock.yaml", "npm-shrinkwrap.json"];
+
 /// Built-in known "binary" file extensions we skip entirely
 static BINARY_FILE_EXTENSIONS: &[&str] = &[
     "jpg", "jpeg", "png", "gif", "exe", "dll", "so", "dylib", "pdf", "mp4", "mov", "zip", "tar",
@@ -76,9 +256,14 @@ static SKIP_FOLDERS: &[&str] = &[
 
 /// Default maximum file size (5MB) for skipping large files
 const DEFAULT_MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+/// r2md-only exclusions in gitignore syntax, honored at any level of the tree.
+const IGNORE_FILE: &str = ".r2mdignore";
 
 // Helper: determine a language identifier from the file’s extension.
 fn language_from_path(path: &Path) -> &str {
+    if is_dockerfile_name(path) {
+        return "dockerfile";
+    }
     match path
         .extension()
         .and_then(OsStr::to_str)
@@ -89,10 +274,35 @@ fn language_from_path(path: &Path) -> &str {
         "rs" => "rust",
         "py" => "python",
         "js" => "javascript",
+        "jsx" => "jsx",
         "ts" => "typescript",
+        "tsx" => "tsx",
         "java" => "java",
-        "c" => "c",
-        "cpp" => "cpp",
+        "c" | "h" => "c",
+        "cpp" | "hpp" | "cc" | "cxx" | "hh" => "cpp",
+        "cs" => "csharp",
+        "go" => "go",
+        "rb" => "ruby",
+        "php" => "php",
+        "swift" => "swift",
+        "kt" | "kts" => "kotlin",
+        "m" | "mm" => "objectivec",
+        "sh" => "bash",
+        "bat" => "bat",
+        "fs" => "fsharp",
+        "vb" => "vb",
+        "scala" => "scala",
+        "md" | "markdown" => "markdown",
+        "adoc" | "asciidoc" => "asciidoc",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        "json" => "json",
+        "tf" => "hcl",
+        "sql" => "sql",
+        "proto" => "protobuf",
+        "ipynb" => "notebook",
+        "vue" => "vue",
+        "svelte" => "svelte",
         other => {
             // You can add additional mappings here
             if other.is_empty() {
@@ -104,15 +314,113 @@ fn language_from_path(path: &Path) -> &str {
     }
 }
 
+/// `Dockerfile`, `Dockerfile.dev`, `api.dockerfile`, ...
+fn is_dockerfile_name(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .unwrap_or("")
+        .to_lowercase();
+    name == "dockerfile" || name.starts_with("dockerfile.") || name.ends_with(".dockerfile")
+}
+
 /// Config for optional YAML (`r2md.yml` / `r2md.yaml`)
 #[derive(Debug, Deserialize)]
 struct R2mdConfig {
-    /// Additional ignore patterns (substring matches).
+    /// Additional ignore patterns, in gitignore syntax.
+    #[serde(default)]
+    ignore_patterns: Vec<String>,
+    /// Include patterns, added to `--include`; `path:START-END` keeps only those lines.
+    #[serde(default)]
+    include_patterns: Vec<String>,
+    /// Replaces the default docs/config extensions used by `--include-docs`.
+    #[serde(default)]
+    doc_extensions: Option<Vec<String>>,
+    /// Commands to run once the export finishes.
+    #[serde(default)]
+    hooks: HooksConfig,
+    /// Per-file commands whose stdout replaces the file content.
+    #[serde(default, deserialize_with = "deserialize_preprocess")]
+    preprocess: Vec<PreprocessHook>,
+    /// Limits used by `--flag-chunks`.
+    #[serde(default)]
+    chunk_flags: Option<ChunkThresholds>,
+    /// Replaces individual section titles/phrases, on top of `--lang-ui`.
+    #[serde(default)]
+    ui_labels: HashMap<String, String>,
+    /// Page setup and font for `--pdf`.
+    #[serde(default)]
+    pdf: PdfOptions,
+    /// Replaces the test file patterns used by `--no-tests`.
+    #[serde(default)]
+    test_patterns: Option<Vec<String>>,
+    /// Replaces the generated file patterns used by `--no-generated`.
+    #[serde(default)]
+    generated_patterns: Option<Vec<String>>,
+    /// Replaces the header markers used by `--no-generated`.
+    #[serde(default)]
+    generated_markers: Option<Vec<String>>,
+    /// Backtick or tilde code fences, unless `--fence-style` is given.
+    #[serde(default)]
+    fence_style: Option<FenceStyle>,
+    /// Company and product names `--anonymize` removes, on top of `--anonymize-term`.
+    #[serde(default)]
+    anonymize_terms: Vec<String>,
+    /// USD per million input tokens by model, for `--cost-estimate`; adds to and
+    /// overrides the built-in prices.
+    #[serde(default)]
+    model_prices: HashMap<String, f64>,
+    /// Named sets of settings, one of which `--profile` picks.
+    #[serde(default)]
+    profiles: HashMap<String, ProfileConfig>,
+}
+
+/// One entry of `profiles:` in `r2md.yml`. Its patterns add to the top-level ones and
+/// its values stand in for flags that weren't given.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileConfig {
     #[serde(default)]
     ignore_patterns: Vec<String>,
+    #[serde(default)]
+    include_patterns: Vec<String>,
+    /// `--format` values
+    #[serde(default)]
+    format: Vec<String>,
+    /// `--max-output-tokens`
+    max_output_tokens: Option<usize>,
+    /// `--split-tokens`
+    split_tokens: Option<usize>,
+    /// `--output`
+    output: Option<String>,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+/// Values of `--format`, and of `format` in a profile.
+const FORMATS: [&str; 7] = [
+    "pdf", "epub", "docx", "asciidoc", "rst", "html-app", "sqlite",
+];
+
+fn main() {
+    if let Err(e) = run() {
+        if is_broken_pipe(e.as_ref()) {
+            return;
+        }
+        eprintln!("Error: {}", e);
+        let code = e
+            .downcast_ref::<RunError>()
+            .map_or(EXIT_ERROR, RunError::exit_code);
+        process::exit(code);
+    }
+}
+
+/// Whether `e` is stdout closing early, as in `r2md --schema manifest | head`. The reader
+/// has what it wanted, so that ends the run normally.
+fn is_broken_pipe(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
     // (The unchanged CLI/argument parsing and config loading code remains here.)
     let matches = Command::new("r2md")
         .version("0.4.4")
@@ -120,7 +428,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         .about("r2md: merges code from multiple directories, streams or writes Markdown, and can optionally produce PDF.")
         .arg(
             Arg::new("paths")
-                .help("One or more directories or git repo URLs to process")
+                .help("One or more directories, .zip/.tar.gz archives, or git repo URLs (https or ssh) to process")
                 .num_args(0..)
                 .default_value(".")
         )
@@ -135,10 +443,245 @@ fn main() -> Result<(), Box<dyn Error>> {
         .arg(
             Arg::new("include")
                 .long("include")
-                .help("Include only files matching the given pattern (supports glob patterns, e.g., *.tf)")
+                .help("Also include files matching the given pattern (supports glob patterns, e.g., *.tf), even ones other filters skip; PATTERN:START-END keeps only those lines")
                 .action(ArgAction::Append)
                 .required(false)
         )
+        .arg(
+            Arg::new("only")
+                .long("only")
+                .value_name("PATTERN")
+                .help("Like --include, but leave out every file no --include/--only pattern matches")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("strict-utf8")
+                .long("strict-utf8")
+                .help("Skip files that aren't valid UTF-8 instead of transcoding them (Latin-1, UTF-16, ...)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-tests")
+                .long("no-tests")
+                .help("Skip test files and directories (*_test.go, test_*.py, *.spec.ts, __tests__/, tests/, ...)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-generated")
+                .long("no-generated")
+                .help("Skip generated code: *.pb.go, *_pb2.py, ... and files marked @generated or DO NOT EDIT near the top")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("follow-symlinks")
+                .long("follow-symlinks")
+                .help("Follow symlinks that point outside the input, e.g. shared packages in a monorepo (each directory is exported once)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("with-submodules")
+                .long("with-submodules")
+                .help("Also export git submodules that are checked out, each under its own heading (they are skipped by default)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("files-from")
+                .long("files-from")
+                .value_name("FILE")
+                .help("Export exactly the files listed in FILE, one path per line ('-' reads stdin), instead of walking the directory")
+        )
+        .arg(
+            Arg::new("include-docs")
+                .long("include-docs")
+                .help("Also include docs/config files (Markdown, YAML, TOML, JSON, Dockerfile, SQL, Protobuf)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("auto-extensions")
+                .long("auto-extensions")
+                .value_name("PCT")
+                .value_parser(clap::value_parser!(f64))
+                .num_args(0..=1)
+                .default_missing_value("5")
+                .help("Also collect unrecognized extensions that make up more than PCT% (default 5) of the repo's bytes"),
+        )
+        .arg(
+            Arg::new("flag-chunks")
+                .long("flag-chunks")
+                .help("Annotate functions/classes that are too long, deeply nested or token-heavy (limits: chunk_flags in r2md.yml)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("lang-ui")
+                .long("lang-ui")
+                .value_name("LANG")
+                .value_parser(["en", "de"])
+                .default_value("en")
+                .help("Language of the generated section titles (override single titles with ui_labels in r2md.yml)"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Read settings from FILE instead of the nearest r2md.yml (searched from each input up, then the working directory up, then ~/.config/r2md/config.yml)"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help("Use the settings under profiles.NAME in r2md.yml: include/ignore patterns, formats, token budget, split size and output file"),
+        )
+        .arg(
+            Arg::new("metadata")
+                .long("metadata")
+                .value_name("STYLE")
+                .value_parser(["yaml", "comment"])
+                .num_args(0..=1)
+                .default_missing_value("yaml")
+                .help("Start the output with a provenance header (repo, commit, time, version, counts) as YAML front matter or an HTML comment"),
+        )
+        .arg(
+            Arg::new("lang")
+                .long("lang")
+                .value_name("LANGS")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .help("Collect only these languages, e.g. rust,python (names as used for code fences)"),
+        )
+        .arg(
+            Arg::new("with-authors")
+                .long("with-authors")
+                .help("For git working trees, note each file's last change and top contributors under its heading")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("describe-files")
+                .long("describe-files")
+                .help("Put each file's own description (module docs, docstring or header comment) under its heading")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("schema")
+                .long("schema")
+                .value_name("OUTPUT")
+                .value_parser(SCHEMA_NAMES.to_vec())
+                .help("Print the JSON Schema of a machine-readable output (manifest, training, report, chunks) and exit"),
+        )
+        .arg(
+            Arg::new("list-themes")
+                .long("list-themes")
+                .action(ArgAction::SetTrue)
+                .help("Print the built-in syntax highlighting themes and exit"),
+        )
+        .arg(
+            Arg::new("manifest")
+                .long("manifest")
+                .value_name("FILE")
+                .num_args(0..=1)
+                .default_missing_value(DEFAULT_MANIFEST)
+                .help("Also write a JSON manifest of included files with hashes and section byte offsets (default file: r2md_manifest.json)"),
+        )
+        .arg(
+            Arg::new("update")
+                .long("update")
+                .value_name("FILE")
+                .conflicts_with_all(["output", "split-tokens", "split-by"])
+                .help("Rewrite only the changed file sections of an earlier export FILE, keeping anything added to it by hand; needs the manifest written with it (--manifest)"),
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .value_name("DIR")
+                .num_args(0..=1)
+                .default_missing_value(DEFAULT_RESUME_DIR)
+                .help("Record each finished remote input in DIR and reuse it on a re-run, so a failed multi-repo batch picks up where it stopped (default: .r2md-resume)"),
+        )
+        .arg(
+            Arg::new("rate-limit")
+                .long("rate-limit")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .help("Send at most N download requests per minute to any one host"),
+        )
+        .arg(
+            Arg::new("http-timeout")
+                .long("http-timeout")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("30")
+                .help("Give up on a download that connects or sends nothing for SECS seconds (0: wait forever)"),
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("3")
+                .help("Retry dropped connections, timeouts and 429/5xx answers N times with exponential backoff, resuming partial archives"),
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .value_name("URL")
+                .help("Send downloads through this HTTP(S) proxy (HTTPS_PROXY and NO_PROXY are honored without it)"),
+        )
+        .arg(
+            Arg::new("line-numbers")
+                .long("line-numbers")
+                .help("Prefix every code line with its line number in the source file")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fence-style")
+                .long("fence-style")
+                .value_name("STYLE")
+                .value_parser(["backtick", "tilde"])
+                .help("Fence code blocks with backticks or tildes (default: backtick, or fence_style in r2md.yml)"),
+        )
+        .arg(
+            Arg::new("script-only")
+                .long("script-only")
+                .help("Keep only the <script> blocks of Vue and Svelte components, leaving out markup and styles")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("prepend")
+                .long("prepend")
+                .value_name("FILE")
+                .help("Insert this Markdown file at the top of the document (before the directory tree); repeatable")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("summaries")
+                .long("summaries")
+                .help("Start with an overview of each top-level directory: file counts, languages, largest files and public symbols")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("cost-estimate")
+                .long("cost-estimate")
+                .help("Show what the export would cost as a prompt for common models (prices: model_prices in r2md.yml)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("summary")
+                .long("summary")
+                .help("Start with a table of files, code/comment/blank lines and tokens per language, like cloc")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-footer")
+                .long("no-footer")
+                .help("Leave out the closing line with the r2md version and the command to regenerate the document")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("append")
+                .long("append")
+                .value_name("FILE")
+                .help("Insert this Markdown file at the end of the document; repeatable")
+                .action(ArgAction::Append),
+        )
         .arg(
             Arg::new("output")
                 .short('o')
@@ -147,6 +690,19 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .help("Output Markdown file name (default: r2md_output.md if not streaming)")
                 .required(false),
         )
+        .arg(
+            Arg::new("stream")
+                .long("stream")
+                .conflicts_with_all(["output", "no-stream", "split-by", "update"])
+                .help("Write the Markdown to stdout (the default when stdout is not a terminal and --output is not given)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-stream")
+                .long("no-stream")
+                .help("Write the Markdown to a file even when stdout is not a terminal")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("pdf")
                 .short('p')
@@ -154,10 +710,101 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .help("Produce a PDF file as well (default r2md_output.pdf)")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("page-size")
+                .long("page-size")
+                .value_name("SIZE")
+                .value_parser(["a4", "letter"])
+                .help("PDF page size (default a4)"),
+        )
+        .arg(
+            Arg::new("orientation")
+                .long("orientation")
+                .value_parser(["portrait", "landscape"])
+                .help("PDF page orientation (default landscape)"),
+        )
+        .arg(
+            Arg::new("pdf-font-size")
+                .long("pdf-font-size")
+                .value_name("PT")
+                .value_parser(clap::value_parser!(f32))
+                .help("PDF code font size in points (default 8)"),
+        )
+        .arg(
+            Arg::new("pdf-margin")
+                .long("pdf-margin")
+                .value_name("MM")
+                .value_parser(clap::value_parser!(f32))
+                .help("PDF page margin in millimeters (default 10)"),
+        )
+        .arg(
+            Arg::new("pdf-font")
+                .long("pdf-font")
+                .value_name("TTF")
+                .help("Monospace TrueType font to embed in the PDF, for non-ASCII source (default: built-in Courier)"),
+        )
+        .arg(
+            Arg::new("theme")
+                .long("theme")
+                .value_name("NAME|FILE")
+                .help("Syntax highlighting theme for PDF, EPUB and HTML output: a built-in theme (see --list-themes) or a .tmTheme file (default: InspiredGitHub)"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Also write the export as pdf (same as --pdf), epub, docx, asciidoc, rst, html-app (one searchable HTML page) or sqlite (files, chunks, symbols, dependencies), named after the Markdown output")
+                .value_parser(FORMATS)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("chunks")
+                .long("chunks")
+                .value_name("FILE")
+                .help("Also write the export as token-bounded chunks to FILE (JSONL), for embedding"),
+        )
+        .arg(
+            Arg::new("chunk-size")
+                .long("chunk-size")
+                .value_name("TOKENS")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("512")
+                .requires("chunks")
+                .help("With --chunks, maximum estimated tokens per chunk"),
+        )
+        .arg(
+            Arg::new("chunk-overlap")
+                .long("chunk-overlap")
+                .value_name("TOKENS")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("64")
+                .requires("chunks")
+                .help("With --chunks, tokens of whole lines repeated from the end of one chunk at the start of the next"),
+        )
+        .arg(
+            Arg::new("timings")
+                .long("timings")
+                .help("Show how long each phase took (walk, read, parse, tokenize, render) in the run summary")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("debug")
                 .long("debug")
-                .help("Enable debug output")
+                .help("Log every skipped file and download step; same as --log-level debug")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("log-level")
+                .long("log-level")
+                .value_name("LEVEL")
+                .value_parser(LOG_LEVELS.to_vec())
+                .conflicts_with("debug")
+                .help("Least severe diagnostics to print on stderr: error, warn, info (default), debug or trace"),
+        )
+        .arg(
+            Arg::new("log-json")
+                .long("log-json")
+                .help("Print diagnostics on stderr as JSON lines, with fields such as path and reason")
                 .action(ArgAction::SetTrue),
         )
         .arg(
@@ -174,561 +821,2467 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .help("Split ratio for training data (default: 0.8)")
                 .required(false),
         )
-        .get_matches();
-
-    let includes: Vec<String> = matches
-        .get_many::<String>("include")
-        .unwrap_or_default()
-        .map(|s| s.to_string())
-        .collect();
-
-    // (Directory, excludes, streaming and config code unchanged)
-    let directories: Vec<PathBuf> = matches
-        .get_many::<String>("paths")
-        .unwrap_or_default()
-        .map(PathBuf::from)
-        .collect();
-    let excludes: Vec<PathBuf> = matches
-        .get_many::<String>("exclude")
-        .unwrap_or_default()
-        .map(PathBuf::from)
-        .collect();
-    let stdout_is_tty = atty::is(atty::Stream::Stdout);
-    let streaming = !stdout_is_tty;
-    let output_md_file = matches
-        .get_one::<String>("output")
-        .map(|s| s.as_str())
-        .unwrap_or("r2md_output.md");
-    let produce_pdf = matches.get_flag("pdf");
-
-    let config = load_config_file()?;
-    let mut user_ignores = vec![];
-    if let Some(ref c) = config {
-        user_ignores.extend(c.ignore_patterns.clone());
-    }
-    let debug_mode = matches.get_flag("debug");
-
-    let mut all_files = Vec::new();
-    for input in &directories {
-        let input_str = input.to_string_lossy();
-        if input_str.starts_with("http://") || input_str.starts_with("https://") {
-            let git_files =
-                collect_files_from_git_url(&input_str, &user_ignores, &includes, debug_mode)?;
-            all_files.extend(git_files);
-        } else {
-            let collected =
-                collect_files_parallel(input, &user_ignores, &excludes, &includes, debug_mode)?;
-            all_files.extend(collected);
-        }
-    }
-
-    if streaming {
-        stream_markdown(&all_files)?;
-        return Ok(());
-    }
-
-    // Build the Markdown output with proper code fences.
-    if !streaming {
-        let mut f = BufWriter::new(File::create(output_md_file)?);
-        for dir in &directories {
-            f.write_all(b"```\n")?;
-            generate_directory_tree(dir, &user_ignores, &includes, debug_mode, &mut f)?;
-            f.write_all(b"```\n\n")?;
-        }
-        f.write_all(b"## Code\n\n")?;
-        for file in &all_files {
-            let path = Path::new(&file.rel_path);
-            let lang = language_from_path(path);
-            let heading = format!("### `{}`\n\n", file.rel_path);
-            f.write_all(heading.as_bytes())?;
-            f.write_all(format!("```{}\n", lang).as_bytes())?;
-            f.write_all(file.content.as_bytes())?;
-            f.write_all(b"\n```\n\n")?;
-        }
-        f.flush()?;
-        println!("Markdown exported to {}", output_md_file);
-    }
-
-    if produce_pdf {
-        let pdf_name = if output_md_file == "r2md_output.md" {
-            "r2md_output.pdf".to_string()
-        } else {
-            output_md_file.replace(".md", ".pdf")
-        };
-        write_pdf_file(&all_files, &directories, &pdf_name)?;
-        println!("PDF exported to {}", pdf_name);
-    }
-
-    if let Some(json_path) = matches.get_one::<String>("train-json") {
-        let split_ratio = matches
-            .get_one::<f64>("split-ratio")
-            .copied()
-            .unwrap_or(0.8);
-        produce_training_json(&all_files, json_path, split_ratio)?;
-    }
-
-    Ok(())
-}
-
-fn collect_files_from_git_url(
-    url: &str,
-    user_ignores: &[String],
-    includes: &[String],
-    debug: bool,
-) -> Result<Vec<FileEntry>, Box<dyn Error>> {
-    // Remove trailing ".git" if present.
-    let mut base_url = url.to_string();
-    if base_url.ends_with(".git") {
-        base_url = base_url.trim_end_matches(".git").to_string();
-    }
-
-    // Closure that attempts to download the ZIP archive for a given branch.
-    let try_download = |branch: &str| -> Result<reqwest::blocking::Response, Box<dyn Error>> {
-        let download_url = if base_url.ends_with('/') {
-            format!("{}archive/refs/heads/{}.zip", base_url, branch)
-        } else {
-            format!("{}/archive/refs/heads/{}.zip", base_url, branch)
-        };
-        if debug {
-            eprintln!(
-                "Attempting to download repository ZIP from: {}",
-                download_url
-            );
-        }
-        let resp = reqwest::blocking::get(&download_url)?;
-        if resp.status().is_success() {
-            Ok(resp)
-        } else {
-            Err(format!(
-                "Failed to download repository ZIP for branch {}: {}",
-                branch,
-                resp.status()
-            )
-            .into())
-        }
-    };
-
-    // Try the "main" branch first; if that fails, try "master".
-    let response = try_download("main").or_else(|err| {
-        if debug {
-            eprintln!("Main branch download failed: {}", err);
-        }
-        try_download("master")
-    })?;
-
-    // Continue as before: read the ZIP archive from memory.
-    let bytes = response.bytes()?;
-    let reader = std::io::Cursor::new(bytes);
-    let mut archive = zip::ZipArchive::new(reader)?;
-
-    let mut file_entries = Vec::new();
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        if file.is_dir() {
-            continue;
-        }
-        let full_name = file.name();
-        let path = Path::new(full_name);
-        let mut components = path.components();
-        let _ = components.next(); // skip top-level folder
-        let rel_path = components.as_path().to_string_lossy().to_string();
-
-        if !includes.is_empty() {
-            let normalized_path = rel_path.replace('\\', "/");
-            let matches_include = includes.iter().any(|pattern| {
-                glob::Pattern::new(pattern)
-                    .map(|p| p.matches(&normalized_path))
-                    .unwrap_or(false)
-            });
-
-            if matches_include {
-                // Bypass all checks for included files
-                let mut content = String::new();
-                if file.read_to_string(&mut content).is_ok() {
-                    file_entries.push(FileEntry { rel_path, content });
-                }
-                continue;
-            }
-        }
-
-        // (Continue with existing size, extension, and user ignore checks.)
-        if file.size() > DEFAULT_MAX_FILE_SIZE {
-            if debug {
-                eprintln!("Skipping large file from zip: {}", rel_path);
-            }
-            continue;
-        }
-
-        let ext = Path::new(&rel_path)
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_lowercase();
-        if !RECOGNIZED_EXTENSIONS.contains(&ext.as_str()) {
-            if BINARY_FILE_EXTENSIONS.contains(&ext.as_str()) {
-                if debug {
-                    eprintln!("Skipping known binary file from zip: {}", rel_path);
-                }
-                continue;
-            }
-            if debug {
-                eprintln!(
-                    "Skipping unrecognized extension file from zip: {}",
-                    rel_path
-                );
-            }
-            continue;
-        }
-
-        if user_ignores.iter().any(|pat| rel_path.contains(pat)) {
-            if debug {
-                eprintln!(
-                    "Skipping file by user ignore pattern from zip: {}",
-                    rel_path
-                );
+        .arg(
+            Arg::new("train-format")
+                .long("train-format")
+                .value_name("FORMAT")
+                .value_parser(["json", "jsonl"])
+                .default_value("json")
+                .help("Training data layout: one JSON array, or one sample per line (jsonl)"),
+        )
+        .arg(
+            Arg::new("train-style")
+                .long("train-style")
+                .value_name("STYLE")
+                .value_parser(["completion", "fim", "chunks"])
+                .default_value("completion")
+                .help("Training sample shape: prompt/completion split, fill-in-the-middle (fim), or per function/class signature -> body (chunks)"),
+        )
+        .arg(
+            Arg::new("context-tokens")
+                .long("context-tokens")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1024")
+                .help("With --train-style chunks, tokens of preceding code kept before each signature"),
+        )
+        .arg(
+            Arg::new("fim-middle")
+                .long("fim-middle")
+                .value_name("MIN-MAX")
+                .default_value("16-256")
+                .help("Size range of the FIM middle span, in tokens"),
+        )
+        .arg(
+            Arg::new("dedup")
+                .long("dedup")
+                .value_name("SIMILARITY")
+                .value_parser(clap::value_parser!(f64))
+                .num_args(0..=1)
+                .default_missing_value("0.85")
+                .help("Drop exact and near-duplicate files (MinHash similarity >= SIMILARITY, default 0.85) from training data"),
+        )
+        .arg(
+            Arg::new("dedup-files")
+                .long("dedup-files")
+                .help("Write files whose content repeats an earlier file once, with a note pointing to the first copy")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("tokenizer")
+                .long("tokenizer")
+                .value_name("NAME")
+                .help("Tokenizer for training data: a Hugging Face hub id, a local tokenizer.json, or cl100k/o200k (offline)"),
+        )
+        .arg(
+            Arg::new("val-ratio")
+                .long("val-ratio")
+                .value_parser(clap::value_parser!(f64))
+                .help("Hold out this fraction of files into a separate validation file (<name>.val.<ext>)")
+                .required(false),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("42")
+                .help("Seed for the shuffle that picks --val-ratio files"),
+        )
+        .arg(
+            Arg::new("split-tokens")
+                .long("split-tokens")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Write the Markdown as numbered parts (r2md_output.part1.md, ...) of at most ~N tokens each")
+                .required(false),
+        )
+        .arg(
+            Arg::new("split-by")
+                .long("split-by")
+                .value_name("UNIT")
+                .value_parser(["dir"])
+                .conflicts_with("split-tokens")
+                .help("Write one Markdown file per top-level directory (r2md_output/src.md, ...), with r2md_output.md as an index linking them"),
+        )
+        .arg(
+            Arg::new("per-file-max-tokens")
+                .long("per-file-max-tokens")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Trim files over ~N tokens instead of skipping large ones (lifts the 5 MB limit)"),
+        )
+        .arg(
+            Arg::new("truncate-strategy")
+                .long("truncate-strategy")
+                .value_parser(["head", "tail", "head-tail"])
+                .default_value("head-tail")
+                .requires("per-file-max-tokens")
+                .help("Which part of a trimmed file to keep"),
+        )
+        .arg(
+            Arg::new("max-output-bytes")
+                .long("max-output-bytes")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with("max-output-tokens")
+                .help("Leave out files once the export reaches about N bytes, keeping imported and small files first"),
+        )
+        .arg(
+            Arg::new("max-output-tokens")
+                .long("max-output-tokens")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Leave out files once the export reaches about N tokens, keeping imported and small files first"),
+        )
+        .arg(
+            Arg::new("max-depth")
+                .long("max-depth")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .help("Only collect files at most N directories deep; 1 is the input's top level"),
+        )
+        .arg(
+            Arg::new("max-files")
+                .long("max-files")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .help("Stop collecting an input after N files, e.g. when pointed at a home directory by mistake"),
+        )
+        .arg(
+            Arg::new("minify")
+                .long("minify")
+                .help("Drop comments, docstrings, blank lines and insignificant indentation to save tokens")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strip-license-headers")
+                .long("strip-license-headers")
+                .help("Remove the license and copyright comments files start with, noting the license under each heading")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("anonymize")
+                .long("anonymize")
+                .help("Rename declared identifiers consistently and remove --anonymize-term names from comments, strings and paths, to share proprietary code")
+                .conflicts_with_all(["metadata", "with-authors", "with-diff", "history", "issue", "pr"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("anonymize-term")
+                .long("anonymize-term")
+                .value_name("NAME")
+                .requires("anonymize")
+                .help("A company or product name for --anonymize to remove (case-insensitive; repeatable; also anonymize_terms in r2md.yml)")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_parser(["alpha", "size", "deps", "mtime"])
+                .default_value("alpha")
+                .help("Order of the files in the output: by path, largest first, dependencies first, or most recently modified first"),
+        )
+        .arg(
+            Arg::new("diff-since")
+                .long("diff-since")
+                .value_name("REF")
+                .help("Export only files changed since the given git ref (commit, branch or tag)")
+                .required(false),
+        )
+        .arg(
+            Arg::new("issue")
+                .long("issue")
+                .value_name("NUMBER")
+                .value_parser(clap::value_parser!(u64))
+                .help("Include this GitHub issue or PR (title, body, comments) ahead of the code")
+                .required(false),
+        )
+        .arg(
+            Arg::new("pr")
+                .long("pr")
+                .value_name("URL")
+                .help("Include this GitHub pull request (description, comments, review comments, changed files and diff) ahead of the code"),
+        )
+        .arg(
+            Arg::new("issue-files")
+                .long("issue-files")
+                .requires("issue")
+                .help("With --issue, export only files whose paths or `identifiers` the issue mentions")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("focus")
+                .long("focus")
+                .value_name("PATH::FUNCTION")
+                .help("Export only this function, the functions it calls and the functions that call it"),
+        )
+        .arg(
+            Arg::new("focus-depth")
+                .long("focus-depth")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1")
+                .requires("focus")
+                .help("With --focus, how many calls away to follow callers and callees"),
+        )
+        .arg(
+            Arg::new("relevant-to")
+                .long("relevant-to")
+                .value_name("QUERY")
+                .help("Export only the files that best match QUERY (BM25 over identifiers, comments and paths)")
+                .required(false),
+        )
+        .arg(
+            Arg::new("top-k")
+                .long("top-k")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .requires("relevant-to")
+                .help("With --relevant-to, how many files to keep (default 20)"),
+        )
+        .arg(
+            Arg::new("top-tokens")
+                .long("top-tokens")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .requires("relevant-to")
+                .conflicts_with("top-k")
+                .help("With --relevant-to, keep the best files that fit in ~N tokens instead of a fixed count"),
+        )
+        .arg(
+            Arg::new("owner")
+                .long("owner")
+                .value_name("OWNER")
+                .help("Export only files owned by OWNER (e.g. @org/team) according to CODEOWNERS")
+                .required(false),
+        )
+        .arg(
+            Arg::new("with-diff")
+                .long("with-diff")
+                .help("With --diff-since, also include the unified diff in a ```diff block")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("history")
+                .long("history")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("For git working trees, append the last N commits (message, author, date) with their diffs"),
+        )
+        .arg(
+            Arg::new("skip-unreadable")
+                .long("skip-unreadable")
+                .help("Silently skip paths that cannot be read instead of listing them in the output and warnings")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fail-if-empty")
+                .long("fail-if-empty")
+                .help("Exit with status 3 instead of writing an empty export when every file is filtered out")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("report")
+                .long("report")
+                .value_name("FILE")
+                .help("Write every per-file failure (inaccessible, unreadable, too large, not text) to FILE as JSON")
+                .required(false),
+        )
+        .arg(
+            Arg::new("on-complete")
+                .long("on-complete")
+                .value_name("CMD")
+                .help("Run CMD through the shell after a successful export (R2MD_OUTPUTS, R2MD_TOKENS, ... are set); repeatable")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("sudo-hint")
+                .long("sudo-hint")
+                .help("When permission errors occur, print a hint on how to re-run with sufficient privileges")
+                .action(ArgAction::SetTrue),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Regenerate output for golden fixture repos and diff it against the committed expectations")
+                .arg(
+                    Arg::new("golden")
+                        .long("golden")
+                        .value_name("DIR")
+                        .help("Directory of fixtures, each holding an input/ tree and an expected.md")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("update")
+                        .long("update")
+                        .help("Rewrite expected.md files from the current output instead of comparing")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("index")
+                .about("Build an on-disk full-text index of code chunks for `r2md query`")
+                .arg(
+                    Arg::new("paths")
+                        .help("Directories to index (default: .)")
+                        .num_args(0..),
+                )
+                .arg(
+                    Arg::new("index-dir")
+                        .long("index-dir")
+                        .value_name("DIR")
+                        .help("Where to write the index (default: .r2md-index)"),
+                ),
+        )
+        .subcommand(
+            Command::new("query")
+                .about("Print a context document built from the indexed chunks that best match TEXT")
+                .arg(Arg::new("text").required(true).value_name("TEXT"))
+                .arg(
+                    Arg::new("tokens")
+                        .long("tokens")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("8000")
+                        .help("Approximate token budget for the document"),
+                )
+                .arg(
+                    Arg::new("index-dir")
+                        .long("index-dir")
+                        .value_name("DIR")
+                        .help("Index to read (default: .r2md-index)"),
+                ),
+        )
+        .subcommand(
+            Command::new("daemon")
+                .about("Serve stat/generate requests over a local socket, keeping file contents warm between runs")
+                .arg(
+                    Arg::new("socket")
+                        .long("socket")
+                        .value_name("PATH")
+                        .help("Unix socket to listen on (default: $TMPDIR/r2md.sock)")
+                        .required(false),
+                ),
+        )
+        .get_matches();
+
+    let log_level = match matches.get_one::<String>("log-level") {
+        Some(level) => level.parse().unwrap_or(LevelFilter::INFO),
+        None if matches.get_flag("debug") => LevelFilter::DEBUG,
+        None => LevelFilter::INFO,
+    };
+    logging::init(log_level, matches.get_flag("log-json"));
+
+    // (Directory, excludes, streaming and config code unchanged)
+    let directories: Vec<PathBuf> = matches
+        .get_many::<String>("paths")
+        .unwrap_or_default()
+        .map(|p| local_path(Path::new(p)))
+        .collect();
+    let excludes: Vec<PathBuf> = matches
+        .get_many::<String>("exclude")
+        .unwrap_or_default()
+        .map(PathBuf::from)
+        .collect();
+    let mut config = load_config_file(
+        matches.get_one::<String>("config").map(Path::new),
+        &directories,
+    )?;
+    let profile = match matches.get_one::<String>("profile") {
+        Some(name) => {
+            let profiles = config.as_mut().map(|c| &mut c.profiles);
+            let profile = profiles.and_then(|p| p.remove(name)).ok_or_else(|| {
+                format!(
+                    "--profile: no profile '{}' under profiles: in r2md.yml",
+                    name
+                )
+            })?;
+            if let Some(format) = profile
+                .format
+                .iter()
+                .find(|f| !FORMATS.contains(&f.as_str()))
+            {
+                return Err(format!(
+                    "profiles.{}.format in r2md.yml: unknown format '{}' (expected one of {})",
+                    name,
+                    format,
+                    FORMATS.join(", ")
+                )
+                .into());
             }
-            continue;
+            info!("Using profile {}", name);
+            profile
+        }
+        None => ProfileConfig::default(),
+    };
+    // Piping r2md somewhere streams by default; an explicit choice or an output file wins.
+    let streaming = if matches.get_flag("stream") {
+        true
+    } else if matches.get_flag("no-stream")
+        || matches.contains_id("output")
+        || profile.output.is_some()
+        || matches.contains_id("split-by")
+        || matches.contains_id("update")
+    {
+        false
+    } else {
+        !atty::is(atty::Stream::Stdout)
+    };
+    let update_md_file = matches.get_one::<String>("update");
+    let output_md_file = matches
+        .get_one::<String>("output")
+        .or(update_md_file)
+        .or(profile.output.as_ref())
+        .map(|s| s.as_str())
+        .unwrap_or("r2md_output.md");
+    // Other formats are named after the Markdown file, but never compressed.
+    let (output_base, _) = compress::split_suffix(output_md_file);
+    let formats: Vec<&str> = match matches.get_many::<String>("format") {
+        Some(formats) => formats.map(String::as_str).collect(),
+        None => profile.format.iter().map(String::as_str).collect(),
+    };
+    let produce_pdf = matches.get_flag("pdf") || formats.contains(&"pdf");
+    let produce_epub = formats.contains(&"epub");
+    let produce_html = formats.contains(&"html-app");
+    let produce_docx = formats.contains(&"docx");
+    let markups: Vec<Markup> = formats
+        .iter()
+        .filter_map(|format| match *format {
+            "asciidoc" => Some(Markup::AsciiDoc),
+            "rst" => Some(Markup::Rst),
+            _ => None,
+        })
+        .collect();
+    let produce_sqlite = formats.contains(&"sqlite");
+    // Loaded up front so a bad --theme fails before the scan rather than after it.
+    let theme = if produce_pdf || produce_epub || produce_html {
+        Some(load_theme(
+            matches.get_one::<String>("theme").map(String::as_str),
+        )?)
+    } else {
+        None
+    };
+    let split_tokens = matches.get_one::<usize>("split-tokens").copied().or(profile
+        .split_tokens
+        .filter(|_| !matches.contains_id("split-by")));
+    let split_by_dir = matches.contains_id("split-by");
+    let chunk_size = matches
+        .get_one::<usize>("chunk-size")
+        .copied()
+        .unwrap_or(512);
+    let chunk_overlap = matches
+        .get_one::<usize>("chunk-overlap")
+        .copied()
+        .unwrap_or(64);
+    if chunk_overlap >= chunk_size {
+        return Err(format!(
+            "--chunk-overlap ({}) must be smaller than --chunk-size ({})",
+            chunk_overlap, chunk_size
+        )
+        .into());
+    }
+
+    let ignore_patterns: Vec<&String> = config
+        .iter()
+        .flat_map(|c| &c.ignore_patterns)
+        .chain(&profile.ignore_patterns)
+        .collect();
+    let user_ignores = PathPatterns::new(&ignore_patterns)
+        .map_err(|e| format!("ignore_patterns in r2md.yml: {}", e))?;
+    let mut on_complete: Vec<String> = config
+        .as_ref()
+        .map(|c| c.hooks.on_complete.clone())
+        .unwrap_or_default();
+    if let Some(cmds) = matches.get_many::<String>("on-complete") {
+        on_complete.extend(cmds.cloned());
+    }
+    let kind_patterns = |flag: &str, key: &str, configured: Option<&Vec<String>>, defaults| {
+        if !matches.get_flag(flag) {
+            return Ok(None);
         }
+        match configured {
+            Some(patterns) => PathPatterns::new(patterns),
+            None => PathPatterns::new(defaults),
+        }
+        .map(Some)
+        .map_err(|e| format!("{} in r2md.yml: {}", key, e))
+    };
+    let kind_filter = FileKindFilter {
+        tests: kind_patterns(
+            "no-tests",
+            "test_patterns",
+            config.as_ref().and_then(|c| c.test_patterns.as_ref()),
+            TEST_PATTERNS,
+        )?,
+        generated: kind_patterns(
+            "no-generated",
+            "generated_patterns",
+            config.as_ref().and_then(|c| c.generated_patterns.as_ref()),
+            GENERATED_PATTERNS,
+        )?,
+        generated_markers: if matches.get_flag("no-generated") {
+            config
+                .as_ref()
+                .and_then(|c| c.generated_markers.clone())
+                .unwrap_or_else(|| GENERATED_MARKERS.iter().map(|m| m.to_string()).collect())
+        } else {
+            Vec::new()
+        },
+    };
+    let includes: Vec<String> = matches
+        .get_many::<String>("include")
+        .unwrap_or_default()
+        .chain(matches.get_many::<String>("only").unwrap_or_default())
+        .chain(config.iter().flat_map(|c| &c.include_patterns))
+        .chain(&profile.include_patterns)
+        .map(|s| s.to_string())
+        .collect();
+    let mut opts = CollectOptions {
+        user_ignores,
+        kind_filter,
+        excludes,
+        includes,
+        only_included: matches.contains_id("only"),
+        include_docs: matches.get_flag("include-docs"),
+        doc_extensions: config
+            .as_ref()
+            .and_then(|c| c.doc_extensions.clone())
+            .unwrap_or_else(|| DOC_EXTENSIONS.iter().map(|e| e.to_string()).collect()),
+        preprocess: config
+            .as_ref()
+            .map(|c| c.preprocess.clone())
+            .unwrap_or_default(),
+        chunk_flags: matches.get_flag("flag-chunks").then(|| {
+            config
+                .as_ref()
+                .and_then(|c| c.chunk_flags.clone())
+                .unwrap_or_default()
+        }),
+        labels: {
+            let lang = matches.get_one::<String>("lang-ui").map(String::as_str);
+            let mut labels = Labels::for_lang(lang.unwrap_or("en")).unwrap_or_default();
+            if let Some(c) = &config {
+                labels.apply_overrides(&c.ui_labels)?;
+            }
+            labels
+        },
+        languages: parse_languages(matches.get_many::<String>("lang"))?,
+        line_numbers: matches.get_flag("line-numbers"),
+        script_only: matches.get_flag("script-only"),
+        fence_style: match matches.get_one::<String>("fence-style").map(String::as_str) {
+            Some("tilde") => FenceStyle::Tilde,
+            Some(_) => FenceStyle::Backtick,
+            None => config
+                .as_ref()
+                .and_then(|c| c.fence_style)
+                .unwrap_or_default(),
+        },
+        rate_limit: matches.get_one::<u32>("rate-limit").copied(),
+        http: HttpOptions {
+            timeout: matches
+                .get_one::<u64>("http-timeout")
+                .copied()
+                .filter(|&secs| secs > 0)
+                .map(Duration::from_secs),
+            retries: matches.get_one::<u32>("retries").copied().unwrap_or(3),
+            proxy: matches.get_one::<String>("proxy").cloned(),
+        },
+        strict_utf8: matches.get_flag("strict-utf8"),
+        follow_symlinks: matches.get_flag("follow-symlinks"),
+        truncate: matches
+            .get_one::<usize>("per-file-max-tokens")
+            .map(|&max_tokens| Truncation {
+                max_tokens,
+                strategy: match matches
+                    .get_one::<String>("truncate-strategy")
+                    .map(String::as_str)
+                {
+                    Some("head") => TruncateStrategy::Head,
+                    Some("tail") => TruncateStrategy::Tail,
+                    _ => TruncateStrategy::HeadTail,
+                },
+            }),
+        minify: matches.get_flag("minify"),
+        max_depth: matches.get_one::<u32>("max-depth").map(|&n| n as usize),
+        max_files: matches.get_one::<u32>("max-files").map(|&n| n as usize),
+        ..Default::default()
+    };
+    if let Some(threshold) = matches.get_one::<f64>("auto-extensions") {
+        let local_dirs: Vec<&Path> = directories
+            .iter()
+            .filter(|d| !is_remote_url(&d.to_string_lossy()) && d.is_dir())
+            .map(PathBuf::as_path)
+            .collect();
+        opts.extra_extensions = detect_auto_extensions(&local_dirs, &opts, *threshold);
+        if !opts.extra_extensions.is_empty() {
+            info!(
+                "Auto-including extensions: {}",
+                opts.extra_extensions.join(", ")
+            );
+        }
+    }
+    let skip_unreadable = matches.get_flag("skip-unreadable");
+    let sudo_hint = matches.get_flag("sudo-hint");
+    let diff_since = matches.get_one::<String>("diff-since");
+    let owner = matches.get_one::<String>("owner");
+    let report_path = matches.get_one::<String>("report");
+
+    if let Some(name) = matches.get_one::<String>("schema") {
+        let schema = json_schema(name).ok_or_else(|| format!("No schema for '{}'", name))?;
+        writeln!(io::stdout(), "{}", serde_json::to_string_pretty(&schema)?)?;
+        return Ok(());
+    }
+    if matches.get_flag("list-themes") {
+        let mut stdout = io::stdout().lock();
+        for name in theme_names() {
+            writeln!(stdout, "{}", name)?;
+        }
+        return Ok(());
+    }
+    if let Some(("verify", verify_matches)) = matches.subcommand() {
+        let golden = verify_matches
+            .get_one::<String>("golden")
+            .map(|p| local_path(Path::new(p)))
+            .unwrap_or_default();
+        return verify::run_verify(&golden, &opts, verify_matches.get_flag("update"));
+    }
+    if let Some(("index", index_matches)) = matches.subcommand() {
+        let dirs: Vec<PathBuf> = match index_matches.get_many::<String>("paths") {
+            Some(paths) => paths.map(|p| local_path(Path::new(p))).collect(),
+            None => vec![PathBuf::from(".")],
+        };
+        let index_dir = index_matches
+            .get_one::<String>("index-dir")
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_INDEX_DIR);
+        let chunks = build_index(&dirs, Path::new(index_dir), &opts)?;
+        eprintln!("Indexed {} chunks into {}", chunks, index_dir);
+        return Ok(());
+    }
+    if let Some(("query", query_matches)) = matches.subcommand() {
+        let index_dir = query_matches
+            .get_one::<String>("index-dir")
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_INDEX_DIR);
+        let text = query_matches
+            .get_one::<String>("text")
+            .map(String::as_str)
+            .unwrap_or_default();
+        let tokens = query_matches
+            .get_one::<usize>("tokens")
+            .copied()
+            .unwrap_or(8000);
+        let doc = query_index(Path::new(index_dir), text, tokens, &opts)?;
+        io::stdout().write_all(doc.as_bytes())?;
+        return Ok(());
+    }
+    if let Some(("daemon", daemon_matches)) = matches.subcommand() {
+        let socket = daemon_matches
+            .get_one::<String>("socket")
+            .map(PathBuf::from)
+            .unwrap_or_else(default_socket_path);
+        return run_daemon(&socket, &opts);
+    }
+    cancel::install();
+    let run_started = Instant::now();
+    let show_timings = matches.get_flag("timings");
+    if atty::is(atty::Stream::Stderr) && !logging::is_verbose() {
+        progress::enable();
+    }
+    progress::start("Collecting", None);
+    let with_diff = matches.get_flag("with-diff");
+    let resume = match matches.get_one::<String>("resume") {
+        Some(dir) => Some(ResumeState::open(Path::new(dir))?),
+        None => None,
+    };
+    let file_list = match matches.get_one::<String>("files-from") {
+        Some(source) => {
+            if directories.len() != 1 || !directories[0].is_dir() {
+                return Err(
+                    "--files-from needs a single local directory to resolve paths against".into(),
+                );
+            }
+            Some(read_file_list(source)?)
+        }
+        None => None,
+    };
+    // User-authored Markdown spliced in at the top and bottom of the document.
+    let mut prepend = read_user_sections(matches.get_many::<String>("prepend"))?;
+    let mut append = read_user_sections(matches.get_many::<String>("append"))?;
+    let mut footer = String::new();
+    if !matches.get_flag("no-footer") {
+        footer = metadata::footer(&opts.labels);
+        append.push_str(&footer);
+    }
+
+    let mut all_files = Vec::new();
+    let mut trees = Vec::new();
+    // Files per input, kept apart until the tree for each input is built.
+    let mut collected_inputs: Vec<(&PathBuf, &str, Vec<FileEntry>)> = Vec::new();
+    let prefixes = input_prefixes(&directories);
+    let mut report = CollectReport::default();
+    // Extra Markdown sections rendered ahead of the `## Code` section.
+    let mut pre_sections: Vec<String> = Vec::new();
+    let issue = match matches.get_one::<u64>("issue") {
+        Some(&number) => Some(
+            fetch_issue(&directories, number, &opts.http)
+                .map_err(|e| RunError::Download(e.to_string()))?,
+        ),
+        None => None,
+    };
+    if let Some(issue) = &issue {
+        pre_sections.push(issue.render(&opts.labels));
+    }
+    if let Some(url) = matches.get_one::<String>("pr") {
+        let pr =
+            fetch_pull_request(url, &opts.http).map_err(|e| RunError::Download(e.to_string()))?;
+        pre_sections.push(pr.render(&opts.labels, opts.fence_style));
+    }
+    let issue_files = matches.get_flag("issue-files");
+    let with_authors = matches.get_flag("with-authors");
+    let describe_files = matches.get_flag("describe-files");
+    let strip_license_headers = matches.get_flag("strip-license-headers");
+    let history = matches
+        .get_one::<usize>("history")
+        .copied()
+        .filter(|&n| n > 0);
+    // `--history` sections, written after the code.
+    let mut history_sections = String::new();
+    let mut file_notes: HashMap<String, String> = HashMap::new();
+    let with_submodules = matches.get_flag("with-submodules");
+    // Workspace crates and submodules of each local input.
+    let mut input_members = HashMap::new();
+    let mut warned_preprocess = false;
+    for (input, prefix) in directories.iter().zip(&prefixes) {
+        if cancel::interrupted() {
+            break;
+        }
+        let input_str = input.to_string_lossy();
+        let is_local_dir = !is_remote_url(&input_str) && input.is_dir();
+        if diff_since.is_some() && !is_local_dir {
+            warn!(
+                "--diff-since only applies to local git working trees; exporting {} in full",
+                input_str
+            );
+        }
+        if owner.is_some() && !is_local_dir {
+            warn!(
+                "--owner only applies to local directories; exporting {} in full",
+                input_str
+            );
+        }
+        // Hooks run on files on disk: local directories and SSH checkouts.
+        let packed = (is_remote_url(&input_str) && !is_ssh_url(&input_str))
+            || (input.is_file() && archive_kind(input).is_some());
+        if packed && !opts.preprocess.is_empty() && !warned_preprocess {
+            warn!(
+                "preprocess hooks only run on local directories and SSH checkouts; {} is exported without them",
+                input_str
+            );
+            warned_preprocess = true;
+        }
+        // Workspace crates and submodules each get a heading; submodules are only walked
+        // into with --with-submodules.
+        let mut members = if is_local_dir {
+            find_members(input)
+        } else {
+            Vec::new()
+        };
+        let mut walk_opts = Cow::Borrowed(&opts);
+        let submodules = members.iter().filter(|m| m.kind == MemberKind::Submodule);
+        if with_submodules {
+            let mut dirs = Vec::new();
+            for member in submodules {
+                if is_checked_out(input, member) {
+                    dirs.push(member.path.clone());
+                } else {
+                    warn!(
+                        "Submodule {} is not checked out; run `git submodule update --init` to export it",
+                        member.path
+                    );
+                }
+            }
+            walk_opts.to_mut().submodule_dirs = dirs;
+        } else {
+            let skipped: Vec<&str> = submodules
+                .filter(|m| is_checked_out(input, m))
+                .map(|m| m.path.as_str())
+                .collect();
+            if !skipped.is_empty() {
+                info!(
+                    "Skipping {} git submodule(s) in {}: {}; add --with-submodules to export them",
+                    skipped.len(),
+                    input_str,
+                    skipped.join(", ")
+                );
+                let excludes = &mut walk_opts.to_mut().excludes;
+                excludes.extend(skipped.iter().map(|path| input.join(path)));
+            }
+            members.retain(|m| m.kind != MemberKind::Submodule);
+        }
+        let resumed = resume
+            .as_ref()
+            .filter(|_| is_remote_url(&input_str))
+            .and_then(|state| state.load(&input_str));
+        let mut collected = if let Some((files, skipped)) = resumed {
+            info!("Resuming: {} was collected by an earlier run", input_str);
+            report.skipped += skipped;
+            files
+        } else if is_remote_url(&input_str) {
+            let started = Instant::now();
+            let skipped_before = report.skipped;
+            let files = if is_ssh_url(&input_str) {
+                collect_files_from_ssh_url(&input_str, &opts, &mut report)
+            } else {
+                collect_files_from_git_url(&input_str, &opts, &mut report)
+            }
+            .map_err(|e| RunError::Download(format!("{}: {}", input_str, e)))?;
+            progress::add(Phase::Read, started.elapsed());
+            if let Some(state) = &resume {
+                state.save(&input_str, &files, report.skipped - skipped_before)?;
+            }
+            files
+        } else if input.is_file() && archive_kind(input).is_some() {
+            let started = Instant::now();
+            let files = collect_files_from_archive_file(input, &opts, &mut report)?;
+            progress::add(Phase::Read, started.elapsed());
+            files
+        } else {
+            let mut collected = match &file_list {
+                Some(list) => collect_listed_files(input, list, &walk_opts, &mut report)?,
+                None => collect_files_parallel(input, &walk_opts, &mut report, None)?,
+            };
+            if let Some(owner) = owner {
+                let owners = CodeOwners::load(input)?
+                    .ok_or_else(|| format!("--owner: no CODEOWNERS file in {}", input.display()))?;
+                collected.retain(|f| owners.is_owned_by(&f.rel_path, owner));
+            }
+            if let Some(since) = diff_since {
+                let changed = git::changed_files(input, since)?;
+                collected.retain(|f| changed.contains(&f.rel_path));
+                if with_diff {
+                    let patch = git::diff_since(input, since)?;
+                    if !patch.trim().is_empty() {
+                        let patch = patch.trim_end();
+                        let fence = fence_for(patch, opts.fence_style);
+                        let title = opts
+                            .labels
+                            .changes_since
+                            .replace("{since}", &inline_code(since))
+                            .replace("{dir}", &inline_code(&input.display().to_string()));
+                        pre_sections.push(format!(
+                            "## {}\n\n{}diff\n{}\n{}\n\n",
+                            title, fence, patch, fence
+                        ));
+                    }
+                }
+            }
+            collected
+        };
+        if strip_license_headers {
+            for file in &mut collected {
+                let Some(removed) = strip_license_header(file) else {
+                    continue;
+                };
+                let note = match license_name(&removed) {
+                    Some(name) => opts.labels.license_header.replace("{license}", &name),
+                    None => opts.labels.copyright_header.clone(),
+                };
+                file_notes.insert(
+                    format!("{}{}", prefix, file.rel_path),
+                    format!("*{}*", note),
+                );
+            }
+        }
+        if describe_files {
+            for file in &collected {
+                let lang = language_from_path(Path::new(&file.rel_path));
+                if let Some(text) = file_description(&file.content, lang) {
+                    let note = format!("*{}*", plain_text(&text));
+                    file_notes
+                        .entry(format!("{}{}", prefix, file.rel_path))
+                        .and_modify(|notes| {
+                            notes.push_str("\n\n");
+                            notes.push_str(&note);
+                        })
+                        .or_insert(note);
+                }
+            }
+        }
+        if with_authors && is_local_dir {
+            match git::file_histories(input) {
+                Ok(histories) => {
+                    for file in &collected {
+                        if let Some(history) = histories.get(&file.rel_path) {
+                            let note = authors_note(history, &opts.labels);
+                            file_notes
+                                .entry(format!("{}{}", prefix, file.rel_path))
+                                .and_modify(|notes| {
+                                    notes.push_str("\n\n");
+                                    notes.push_str(&note);
+                                })
+                                .or_insert(note);
+                        }
+                    }
+                }
+                Err(e) => warn!("--with-authors skipped for {}: {}", input_str, e),
+            }
+        }
+        if let Some(count) = history {
+            if !is_local_dir {
+                warn!(
+                    "--history only applies to local git working trees; skipping {}",
+                    input_str
+                );
+            } else {
+                match git::recent_commits(input, count) {
+                    Ok(commits) if !commits.is_empty() => {
+                        history_sections.push_str(&render_history(input, &commits, &opts))
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("--history skipped for {}: {}", input_str, e),
+                }
+            }
+        }
+        // Names were checked during collection; generated markers need the content.
+        let before = collected.len();
+        collected.retain(|f| {
+            let generated = opts.kind_filter.skips_content(&f.content);
+            if generated {
+                debug!(path = %f.rel_path, reason = "generated", "Skipping file");
+            }
+            !generated
+        });
+        report.skipped += before - collected.len();
+        if let Some(issue) = issue.as_ref().filter(|_| issue_files) {
+            collected.retain(|f| issue.mentions(f));
+        }
+        input_members.insert(input, members);
+        collected_inputs.push((input, prefix, collected));
+    }
+    append.insert_str(0, &history_sections);
+    let parse_started = Instant::now();
+    progress::start("Processing", None);
+    if let Some(query) = matches.get_one::<String>("relevant-to") {
+        let budget = match matches.get_one::<usize>("top-tokens") {
+            Some(&tokens) => Budget::Tokens(tokens),
+            None => Budget::Files(matches.get_one::<usize>("top-k").copied().unwrap_or(20)),
+        };
+        // Rank across all inputs at once, then drop the losers from each.
+        let refs: Vec<&FileEntry> = collected_inputs.iter().flat_map(|(_, _, f)| f).collect();
+        let mut keep = select_relevant(&refs, query, budget).into_iter();
+        for (_, _, files) in &mut collected_inputs {
+            files.retain(|_| keep.next().unwrap_or(false));
+        }
+    }
+    if let Some(value) = matches.get_one::<String>("focus") {
+        let target = FocusTarget::parse(value)?;
+        let depth = matches
+            .get_one::<usize>("focus-depth")
+            .copied()
+            .unwrap_or(1);
+        let refs: Vec<&FileEntry> = collected_inputs.iter().flat_map(|(_, _, f)| f).collect();
+        let mut ranges = select_focus(&refs, &target, depth)?.into_iter();
+        for (_, _, files) in &mut collected_inputs {
+            files.retain_mut(|file| {
+                let keep = ranges.next().unwrap_or_default();
+                if !keep.is_empty() {
+                    keep_ranges(file, &keep, &opts.labels.omitted);
+                }
+                !keep.is_empty()
+            });
+        }
+    }
+    let anonymize_terms: Option<Vec<String>> = matches.get_flag("anonymize").then(|| {
+        matches
+            .get_many::<String>("anonymize-term")
+            .unwrap_or_default()
+            .chain(config.iter().flat_map(|c| &c.anonymize_terms))
+            .cloned()
+            .collect()
+    });
+    if opts.minify {
+        for (_, _, files) in &mut collected_inputs {
+            for file in files {
+                if let Some(small) =
+                    minify(&file.content, language_from_path(Path::new(&file.rel_path)))
+                {
+                    // Line numbers refer to the minified text.
+                    file.content = small;
+                    file.lines = LineMap::default();
+                }
+            }
+        }
+    }
+    let output_limit = match (
+        matches.get_one::<usize>("max-output-bytes"),
+        matches
+            .get_one::<usize>("max-output-tokens")
+            .or(profile.max_output_tokens.as_ref()),
+    ) {
+        (Some(&bytes), _) => Some(OutputLimit::Bytes(bytes)),
+        (None, Some(&tokens)) => Some(OutputLimit::Tokens(tokens)),
+        (None, None) => None,
+    };
+    if let Some(limit) = output_limit {
+        let refs: Vec<&FileEntry> = collected_inputs.iter().flat_map(|(_, _, f)| f).collect();
+        // Measure each file as it will be written, after --per-file-max-tokens.
+        let sizes: Vec<usize> = refs
+            .iter()
+            .map(|f| {
+                let marker = &opts.labels.omitted;
+                let trimmed = opts
+                    .truncate
+                    .as_ref()
+                    .and_then(|t| t.ranges(&f.content, marker))
+                    .map(|ranges| cut_lines(&f.content, &ranges, marker).0);
+                let content = trimmed.as_deref().unwrap_or(&f.content);
+                match limit {
+                    OutputLimit::Bytes(_) => content.len() + f.rel_path.len(),
+                    OutputLimit::Tokens(_) => estimate_tokens(content),
+                }
+            })
+            .collect();
+        let mut keep = fit_output(&refs, &sizes, limit).into_iter();
+        for (_, prefix, files) in &mut collected_inputs {
+            files.retain(|f| {
+                let keep = keep.next().unwrap_or(false);
+                if !keep {
+                    report.over_budget.push(format!("{}{}", prefix, f.rel_path));
+                }
+                keep
+            });
+        }
+    }
+    opts.file_notes = file_notes;
+    let sort_order = match matches.get_one::<String>("sort").map(String::as_str) {
+        Some("size") => SortOrder::Size,
+        Some("deps") => SortOrder::Deps,
+        Some("mtime") => SortOrder::Mtime,
+        _ => SortOrder::Alpha,
+    };
+    // Where each input's files start in `all_files`, for --summaries.
+    let mut input_starts = Vec::new();
+    for (input, prefix, mut collected) in collected_inputs {
+        sort_files(&mut collected, sort_order, input);
+        // Each member's files stay together, after the files that belong to none.
+        let members = input_members.remove(input).unwrap_or_default();
+        collected.sort_by_key(|f| member_of(&members, &f.rel_path).map_or(0, |i| i + 1));
+        let mut member_starts = Vec::new();
+        let mut previous = None;
+        for (i, file) in collected.iter().enumerate() {
+            let member = member_of(&members, &file.rel_path);
+            if let Some(m) = member.filter(|_| member != previous) {
+                member_starts.push((i, m));
+            }
+            previous = member;
+        }
+        if let Some(limit) = &opts.truncate {
+            for file in &mut collected {
+                if let Some(ranges) = limit.ranges(&file.content, &opts.labels.omitted) {
+                    keep_ranges(file, &ranges, &opts.labels.omitted);
+                }
+            }
+        }
+        trees.push(Tree::new(input, &collected));
+        if !prefix.is_empty() {
+            for file in &mut collected {
+                file.rel_path.insert_str(0, prefix);
+            }
+            if let Some(first) = collected.first() {
+                opts.input_headings.insert(
+                    first.rel_path.clone(),
+                    prefix.trim_end_matches('/').to_string(),
+                );
+            }
+        }
+        for (i, member) in member_starts {
+            let member = &members[member];
+            let label = match member.kind {
+                MemberKind::Crate => &opts.labels.crate_member,
+                MemberKind::Submodule => &opts.labels.submodule,
+            };
+            opts.member_headings.insert(
+                collected[i].rel_path.clone(),
+                label.replace("{path}", &inline_code(&member.path)),
+            );
+        }
+        input_starts.push((prefix, all_files.len()));
+        all_files.extend(collected);
+    }
+    if matches.get_flag("dedup-files") {
+        for (i, first) in identical_files(&all_files).into_iter().enumerate() {
+            if let Some(first) = first {
+                let note = opts
+                    .labels
+                    .identical
+                    .replace("{path}", &inline_code(&all_files[first].rel_path));
+                opts.duplicates
+                    .insert(all_files[i].rel_path.clone(), format!("*{}*", note));
+                all_files[i].content.clear();
+            }
+        }
+    }
+    // Last, so every note, heading and path the document shows is anonymized too.
+    let anonymizer = anonymize_terms.map(|terms| Anonymizer::new(&all_files, &terms));
+    if let Some(anon) = &anonymizer {
+        for file in &mut all_files {
+            let lang = language_from_path(Path::new(&file.rel_path));
+            file.content = anon.content(&file.content, lang);
+            file.rel_path = anon.path(&file.rel_path);
+        }
+        for tree in &mut trees {
+            tree.map_paths(|path| anon.path(path));
+        }
+        for map in [
+            &mut opts.file_notes,
+            &mut opts.duplicates,
+            &mut opts.input_headings,
+            &mut opts.member_headings,
+        ] {
+            *map = map
+                .drain()
+                .map(|(path, note)| (anon.path(&path), anon.text(&note)))
+                .collect();
+        }
+        for path in &mut report.over_budget {
+            *path = anon.path(path);
+        }
+        for asset in &mut report.lfs_assets {
+            asset.path = anon.path(&asset.path);
+        }
+        for inaccessible in &mut report.inaccessible {
+            inaccessible.path = anon.path(&inaccessible.path);
+        }
+        prepend = anon.redact(&prepend);
+        append = anon.redact(&append);
+        footer = anon.redact(&footer);
+    }
+    if matches.get_flag("summaries") {
+        let names: Vec<String> = input_starts
+            .iter()
+            .map(|(prefix, _)| {
+                anonymizer
+                    .as_ref()
+                    .map_or(prefix.to_string(), |a| a.path(prefix))
+            })
+            .collect();
+        let mut inputs = Vec::new();
+        for (i, &(_, start)) in input_starts.iter().enumerate() {
+            let end = input_starts.get(i + 1).map_or(all_files.len(), |&(_, e)| e);
+            inputs.push((names[i].as_str(), &all_files[start..end]));
+        }
+        pre_sections.push(render_overview(&inputs, &opts.labels));
+    }
+    if matches.get_flag("summary") {
+        pre_sections.insert(0, render_language_stats(&all_files, &opts.labels));
+    }
+    progress::add(Phase::Parse, parse_started.elapsed());
+    // The JSON report always lists everything; --skip-unreadable only quiets the terminal and Markdown.
+    if let Some(path) = report_path {
+        write_error_report(path, &all_files, &report)?;
+    }
+    if skip_unreadable {
+        report.inaccessible.clear();
+        report.errors.retain(|e| e.as_inaccessible().is_none());
+    }
+    report_inaccessible(&report.inaccessible, sudo_hint);
+    if all_files.is_empty() && matches.get_flag("fail-if-empty") {
+        return Err(RunError::Empty {
+            skipped: report.skipped,
+        }
+        .into());
+    }
+    let partial = report.errors.iter().any(R2mdError::is_failure);
+    let tokenize_started = Instant::now();
+    let tokens = all_files.iter().map(|f| estimate_tokens(&f.content)).sum();
+    progress::add(Phase::Tokenize, tokenize_started.elapsed());
+    let mut summary = RunSummary {
+        files_included: all_files.len(),
+        files_skipped: report.skipped,
+        tokens,
+        errors: report.errors.iter().map(|e| e.to_string()).collect(),
+        ..Default::default()
+    };
+    if matches.get_flag("cost-estimate") {
+        let prices = config.as_ref().map(|c| &c.model_prices);
+        summary.costs = estimate_costs(tokens, prices.unwrap_or(&HashMap::new()));
+    }
+    if let Some(path) = report_path {
+        summary.outputs.push(path.clone());
+    }
+    if !report.over_budget.is_empty() {
+        summary.warnings.push(format!(
+            "{} file(s) left out to stay within the output size limit",
+            report.over_budget.len()
+        ));
+    }
+    if report.file_limit_hit {
+        summary.warnings.push(format!(
+            "stopped at --max-files {}; the rest of the input was left out",
+            opts.max_files.unwrap_or_default()
+        ));
+    }
+    if all_files.is_empty() {
+        summary
+            .warnings
+            .push("no files to export; --fail-if-empty makes this an error".to_string());
+    }
+    let header = match matches.get_one::<String>("metadata").map(String::as_str) {
+        Some(style) => Metadata::new(&directories, summary.files_included, summary.tokens).render(
+            if style == "comment" {
+                MetadataStyle::Comment
+            } else {
+                MetadataStyle::Yaml
+            },
+        ),
+        None => String::new(),
+    };
+
+    let render_started = Instant::now();
+    progress::start("Writing", Some(all_files.len()));
+    if streaming {
+        if matches.contains_id("manifest") {
+            warn!("--manifest needs a Markdown file; it is not written when streaming to stdout");
+        }
+        let mut out = BufWriter::new(io::stdout().lock());
+        out.write_all(header.as_bytes())?;
+        out.write_all(prepend.as_bytes())?;
+        write_markdown_document(&mut out, &trees, &opts, &pre_sections, &all_files, &report)?;
+        out.write_all(append.as_bytes())?;
+        out.flush()?;
+        drop(out);
+        summary.outputs.insert(0, "stdout".to_string());
+        if cancel::interrupted() {
+            summary.warnings.push(cancel::WARNING.to_string());
+        }
+        finish_run(&mut summary, run_started, render_started, show_timings);
+        summary.print(true)?;
+        if cancel::interrupted() {
+            process::exit(cancel::INTERRUPTED_EXIT_CODE);
+        }
+        if let Some(state) = resume {
+            state.clear(&directories)?;
+        }
+        run_on_complete(&on_complete, &summary)?;
+        if partial {
+            process::exit(EXIT_PARTIAL);
+        }
+        return Ok(());
+    }
+
+    // Build the Markdown output with proper code fences.
+    // `--update` always rewrites the manifest: the offsets moved, and the next update reads them.
+    let manifest_path = matches
+        .get_one::<String>("manifest")
+        .map(String::as_str)
+        .or(update_md_file.map(|_| DEFAULT_MANIFEST));
+    let section_spans;
+    if let (Some(doc), Some(manifest)) = (update_md_file, manifest_path) {
+        let trees = trees
+            .iter()
+            .map(|tree| tree_block(tree, opts.fence_style))
+            .collect::<io::Result<Vec<_>>>()?;
+        let frame = Frame {
+            header: &header,
+            trees: &trees,
+            footer: &footer,
+        };
+        let (counts, spans) = update_markdown(doc, manifest, &frame, &all_files, &opts)?;
+        eprintln!(
+            "Updated {}: {} changed, {} added, {} removed, {} unchanged",
+            doc, counts.updated, counts.added, counts.removed, counts.unchanged
+        );
+        section_spans = vec![(doc.clone(), spans)];
+        summary.outputs.push(doc.clone());
+    } else if split_tokens.is_some() || split_by_dir {
+        let mut preamble = prepend.into_bytes();
+        for tree in &trees {
+            preamble.write_all(tree_block(tree, opts.fence_style)?.as_bytes())?;
+        }
+        for section in &pre_sections {
+            preamble.write_all(section.as_bytes())?;
+        }
+        let mut epilogue = Vec::new();
+        write_report_sections(&mut epilogue, &report, &opts.labels)?;
+        epilogue.write_all(append.as_bytes())?;
+        let preamble = String::from_utf8_lossy(&preamble);
+        let epilogue = String::from_utf8_lossy(&epilogue);
+        let parts = match split_tokens {
+            Some(max_tokens) => write_split_markdown(
+                output_md_file,
+                &header,
+                &preamble,
+                &all_files,
+                &epilogue,
+                max_tokens,
+                &opts,
+            )?,
+            None => write_dir_split_markdown(
+                output_md_file,
+                &header,
+                &preamble,
+                &all_files,
+                &epilogue,
+                &opts,
+            )?,
+        };
+        summary
+            .outputs
+            .extend(parts.iter().map(|(name, _)| name.clone()));
+        section_spans = parts;
+    } else {
+        let mut f = OutputFile::create(output_md_file)?;
+        f.write_all(header.as_bytes())?;
+        f.write_all(prepend.as_bytes())?;
+        let mut spans =
+            write_markdown_document(&mut f, &trees, &opts, &pre_sections, &all_files, &report)?;
+        f.write_all(append.as_bytes())?;
+        f.finish()?;
+        let base = header.len() + prepend.len();
+        for span in &mut spans {
+            span.offset += base;
+        }
+        section_spans = vec![(output_md_file.to_string(), spans)];
+        summary.outputs.push(output_md_file.to_string());
+    }
+    if let Some(path) = manifest_path {
+        write_manifest(path, &all_files, &section_spans)?;
+        summary.outputs.push(path.to_string());
+    }
+    // Interrupted: the Markdown and reports are as complete as they'll get; skip the rest.
+    if cancel::interrupted() {
+        summary.warnings.push(cancel::WARNING.to_string());
+        finish_run(&mut summary, run_started, render_started, show_timings);
+        summary.print(false)?;
+        process::exit(cancel::INTERRUPTED_EXIT_CODE);
+    }
+
+    if let Some(theme) = theme.as_ref().filter(|_| produce_pdf) {
+        let pdf_name = if output_base == "r2md_output.md" {
+            "r2md_output.pdf".to_string()
+        } else {
+            output_base.replace(".md", ".pdf")
+        };
+        let mut pdf_opts = config.as_ref().map(|c| c.pdf.clone()).unwrap_or_default();
+        match matches.get_one::<String>("page-size").map(String::as_str) {
+            Some("letter") => pdf_opts.page_size = PageSize::Letter,
+            Some(_) => pdf_opts.page_size = PageSize::A4,
+            None => {}
+        }
+        match matches.get_one::<String>("orientation").map(String::as_str) {
+            Some("portrait") => pdf_opts.orientation = Orientation::Portrait,
+            Some(_) => pdf_opts.orientation = Orientation::Landscape,
+            None => {}
+        }
+        if let Some(&size) = matches.get_one::<f32>("pdf-font-size") {
+            pdf_opts.font_size = size;
+        }
+        if let Some(&margin) = matches.get_one::<f32>("pdf-margin") {
+            pdf_opts.margin = margin;
+        }
+        if let Some(font) = matches.get_one::<String>("pdf-font") {
+            pdf_opts.font = Some(PathBuf::from(font));
+        }
+        write_pdf_file(&all_files, &directories, &pdf_name, &pdf_opts, theme)?;
+        summary.outputs.push(pdf_name);
+    }
+    if let Some(theme) = theme.as_ref().filter(|_| produce_epub) {
+        let epub_name = match output_base.strip_suffix(".md") {
+            Some(stem) => format!("{}.epub", stem),
+            None => format!("{}.epub", output_base),
+        };
+        let lang = matches.get_one::<String>("lang-ui").map(String::as_str);
+        write_epub_file(
+            &all_files,
+            &directories,
+            &epub_name,
+            lang.unwrap_or("en"),
+            theme,
+        )?;
+        summary.outputs.push(epub_name);
+    }
+    if produce_docx {
+        let docx_name = match output_base.strip_suffix(".md") {
+            Some(stem) => format!("{}.docx", stem),
+            None => format!("{}.docx", output_base),
+        };
+        let lang = matches.get_one::<String>("lang-ui").map(String::as_str);
+        write_docx_file(&all_files, &directories, &docx_name, lang.unwrap_or("en"))?;
+        summary.outputs.push(docx_name);
+    }
+    for &markup in &markups {
+        let markup_name = match output_base.strip_suffix(".md") {
+            Some(stem) => format!("{}.{}", stem, markup.extension()),
+            None => format!("{}.{}", output_base, markup.extension()),
+        };
+        write_markup_file(&all_files, &directories, &markup_name, markup)?;
+        summary.outputs.push(markup_name);
+    }
+    if let Some(theme) = theme.as_ref().filter(|_| produce_html) {
+        let html_name = match output_base.strip_suffix(".md") {
+            Some(stem) => format!("{}.html", stem),
+            None => format!("{}.html", output_base),
+        };
+        let lang = matches.get_one::<String>("lang-ui").map(String::as_str);
+        write_html_app(
+            &all_files,
+            &directories,
+            &html_name,
+            lang.unwrap_or("en"),
+            theme,
+        )?;
+        summary.outputs.push(html_name);
+    }
+    if produce_sqlite {
+        let db_name = match output_base.strip_suffix(".md") {
+            Some(stem) => format!("{}.db", stem),
+            None => format!("{}.db", output_base),
+        };
+        write_sqlite_file(&all_files, &db_name)?;
+        summary.outputs.push(db_name);
+    }
+    if let Some(chunks_path) = matches.get_one::<String>("chunks") {
+        write_chunks(&all_files, chunks_path, chunk_size, chunk_overlap)?;
+        summary.outputs.push(chunks_path.clone());
+    }
+
+    if let Some(json_path) = matches.get_one::<String>("train-json") {
+        let split_ratio = matches
+            .get_one::<f64>("split-ratio")
+            .copied()
+            .unwrap_or(0.8);
+        let fim_middle = matches
+            .get_one::<String>("fim-middle")
+            .map(String::as_str)
+            .unwrap_or("16-256");
+        let fim_middle = fim_middle
+            .split_once('-')
+            .and_then(|(lo, hi)| Some((lo.trim().parse().ok()?, hi.trim().parse().ok()?)))
+            .ok_or_else(|| format!("Invalid --fim-middle '{}', expected MIN-MAX", fim_middle))?;
+        let training_opts = TrainingOptions {
+            style: match matches.get_one::<String>("train-style").map(String::as_str) {
+                Some("fim") => TrainStyle::Fim,
+                Some("chunks") => TrainStyle::Chunks,
+                _ => TrainStyle::Completion,
+            },
+            split_ratio,
+            fim_middle,
+            context_tokens: matches
+                .get_one::<usize>("context-tokens")
+                .copied()
+                .unwrap_or(1024),
+            format: match matches
+                .get_one::<String>("train-format")
+                .map(String::as_str)
+            {
+                Some("jsonl") => TrainFormat::Jsonl,
+                _ => TrainFormat::Json,
+            },
+            val_ratio: matches.get_one::<f64>("val-ratio").copied(),
+            seed: matches.get_one::<u64>("seed").copied().unwrap_or(42),
+            tokenizer: matches
+                .get_one::<String>("tokenizer")
+                .cloned()
+                .unwrap_or_else(|| DEFAULT_TOKENIZER.to_string()),
+            chunk_flags: opts.chunk_flags.clone(),
+            dedup: matches.get_one::<f64>("dedup").copied(),
+        };
+        let written = produce_training_json(&all_files, json_path, &training_opts)?;
+        summary.outputs.extend(written);
+    }
+
+    if let Some(state) = resume {
+        state.clear(&directories)?;
+    }
+    finish_run(&mut summary, run_started, render_started, show_timings);
+    summary.print(false)?;
+    run_on_complete(&on_complete, &summary)?;
+    if partial {
+        process::exit(EXIT_PARTIAL);
+    }
+    Ok(())
+}
 
-        let mut content = String::new();
-        if let Err(e) = file.read_to_string(&mut content) {
-            if debug {
-                eprintln!("Skipping unreadable file {}: {}", rel_path, e);
-            }
-            continue;
+/// Lowercase and check `--lang` names against the fence names r2md knows.
+fn parse_languages<'a>(
+    names: Option<impl Iterator<Item = &'a String>>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut languages = Vec::new();
+    for name in names.into_iter().flatten() {
+        let name = name.trim().to_lowercase();
+        if !LANGUAGE_NAMES.contains(&name.as_str()) {
+            return Err(format!(
+                "Unknown --lang '{}'; known languages: {}",
+                name,
+                LANGUAGE_NAMES.join(", ")
+            )
+            .into());
         }
+        languages.push(name);
+    }
+    Ok(languages)
+}
 
-        file_entries.push(FileEntry { rel_path, content });
+/// Concatenate `--prepend`/`--append` files, each ending in a blank line so the
+/// following section starts cleanly.
+fn read_user_sections<'a>(
+    paths: Option<impl Iterator<Item = &'a String>>,
+) -> Result<String, Box<dyn Error>> {
+    let mut out = String::new();
+    for path in paths.into_iter().flatten() {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read section file {}: {}", path, e))?;
+        out.push_str(text.trim_end());
+        out.push_str("\n\n");
     }
-    Ok(file_entries)
+    Ok(out)
 }
 
-fn stream_markdown(files: &[FileEntry]) -> io::Result<()> {
-    let stdout = io::stdout();
-    let mut handle = stdout.lock();
+/// The directory tree in a fence of its own.
+fn tree_block(tree: &Tree, style: FenceStyle) -> io::Result<String> {
+    let mut text = Vec::new();
+    tree.write(&mut text)?;
+    let text = String::from_utf8_lossy(&text);
+    let fence = fence_for(&text, style);
+    Ok(format!("{}\n{}{}\n\n", fence, text, fence))
+}
 
-    writeln!(handle, "# r2md Streaming Output\n")?;
+/// Write the full Markdown document: directory trees, extra sections, code, then the
+/// assets and inaccessible paths from `report`. Returns where each file section landed,
+/// as byte offsets from the start of `writer`.
+fn write_markdown_document<W: Write>(
+    writer: &mut W,
+    trees: &[Tree],
+    opts: &CollectOptions,
+    pre_sections: &[String],
+    files: &[FileEntry],
+    report: &CollectReport,
+) -> Result<Vec<SectionSpan>, Box<dyn Error>> {
+    let mut writer = CountingWriter::new(writer, 0);
+    for tree in trees {
+        writer.write_all(tree_block(tree, opts.fence_style)?.as_bytes())?;
+    }
+    for section in pre_sections {
+        writer.write_all(section.as_bytes())?;
+    }
+    if opts.input_headings.is_empty() {
+        writeln!(writer, "## {}\n", opts.labels.code)?;
+    }
+    let mut spans = Vec::with_capacity(files.len());
     for file in files {
-        let path = Path::new(&file.rel_path);
-        let lang = language_from_path(path);
-        writeln!(handle, "### `{}`\n", file.rel_path)?;
-        writeln!(handle, "```{}", lang)?;
-        writeln!(handle, "{}", file.content)?;
-        writeln!(handle, "```")?;
-        writeln!(handle)?;
+        progress::inc();
+        if let Some(heading) = input_heading(&file.rel_path, opts) {
+            writer.write_all(heading.as_bytes())?;
+        }
+        let section =
+            render_file_section(&file.rel_path, &file.content, None, 1, &file.lines, opts);
+        spans.push(SectionSpan {
+            rel_path: file.rel_path.clone(),
+            offset: writer.count,
+            length: section.len(),
+        });
+        writer.write_all(section.as_bytes())?;
     }
-    handle.flush()
+    write_report_sections(&mut writer, report, &opts.labels)?;
+    Ok(spans)
 }
 
-fn write_pdf_file(
-    files: &[FileEntry],
-    directories: &[PathBuf],
-    output_file_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    use printpdf::{BuiltinFont, Color, Mm, PdfDocument, Rgb};
-    use syntect::easy::HighlightLines;
-    use syntect::highlighting::ThemeSet;
-    use syntect::parsing::SyntaxSet;
-
-    // Create a new PDF document.
-    let (doc, page1, layer1) = PdfDocument::new("r2md PDF", Mm(297.0), Mm(210.0), "Layer 1");
-    let font = doc.add_builtin_font(BuiltinFont::Courier)?;
-    let mut current_layer = doc.get_page(page1).get_layer(layer1);
-    let mut current_y = 210.0_f32 - 10_f32;
-
-    // Prepare syntect’s syntax and theme sets.
-    let ss = SyntaxSet::load_defaults_newlines();
-    let ts = ThemeSet::load_defaults();
-    // Choose a theme – here we use "InspiredGitHub".
-    let theme = &ts.themes["InspiredGitHub"];
-
-    // Print directory headers.
-    for d in directories {
-        if current_y < 20.0 {
-            let (p, l) = doc.add_page(Mm(297.0), Mm(210.0), "Layer next");
-            current_layer = doc.get_page(p).get_layer(l);
-            current_y = 210.0;
-        }
-        let text = format!("Directory: {}\n", d.display());
-        current_layer.use_text(text, 12.0, Mm(10.0), Mm(current_y), &font);
-        current_y -= 10.0;
-    }
-
-    // For each file...
-    for file in files {
-        if current_y < 20.0 {
-            let (p, l) = doc.add_page(Mm(297.0), Mm(210.0), "Layer next");
-            current_layer = doc.get_page(p).get_layer(l);
-            current_y = 210.0;
-        }
-        let heading = format!("File: {}\n", file.rel_path);
-        current_layer.use_text(heading, 10.0, Mm(10.0), Mm(current_y), &font);
-        current_y -= 6.0;
-
-        // Determine syntax for highlighting.
-        let path = std::path::Path::new(&file.rel_path);
-        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
-        let syntax = ss
-            .find_syntax_by_extension(ext)
-            .unwrap_or_else(|| ss.find_syntax_plain_text());
-        let mut highlighter = HighlightLines::new(syntax, theme);
-
-        // Print file content line by line with token-level highlighting.
-        for line in file.content.lines() {
-            if current_y < 10.0 {
-                let (p, l) = doc.add_page(Mm(297.0), Mm(210.0), "Layer next");
-                current_layer = doc.get_page(p).get_layer(l);
-                current_y = 210.0;
-            }
-            let regions = highlighter
-                .highlight_line(line, &ss)
-                .map_err(|e| format!("Highlighting error: {}", e))?;
-            let mut x = Mm(10.0);
-            // For each highlighted region, set the fill color and draw the text.
-            for (style, text) in regions {
-                let r = style.foreground.r as f32 / 255.0;
-                let g = style.foreground.g as f32 / 255.0;
-                let b = style.foreground.b as f32 / 255.0;
-                current_layer.set_fill_color(Color::Rgb(Rgb::new(r, g, b, None)));
-                current_layer.use_text(text, 8.0, x, Mm(current_y), &font);
-                // Estimate width per token (using Courier: ~4.0 mm per character).
-                let token_width = 1.7_f32 * (text.len() as f32);
-                x += Mm(token_width);
-            }
-            current_y -= 4.0;
-        }
-        current_y -= 4.0; // extra gap between files
-    }
-
-    // Save the PDF document.
-    doc.save(&mut std::io::BufWriter::new(std::fs::File::create(
-        output_file_name,
-    )?))?;
-    Ok(())
+/// `# name` and the code heading in front of the first file of each input, when there are
+/// several inputs, then the heading of the workspace crate or submodule the file starts.
+fn input_heading(rel_path: &str, opts: &CollectOptions) -> Option<String> {
+    let input = opts
+        .input_headings
+        .get(rel_path)
+        .map(|name| format!("# {}\n\n## {}\n\n", escape_control(name), opts.labels.code));
+    let member = opts
+        .member_headings
+        .get(rel_path)
+        .map(|title| format!("## {}\n\n", title));
+    match (input, member) {
+        (None, None) => None,
+        (input, member) => Some(input.unwrap_or_default() + &member.unwrap_or_default()),
+    }
 }
 
-/// Attempt to load config from r2md.yml or r2md.yaml, returning None if not found.
-fn load_config_file() -> Result<Option<R2mdConfig>, Box<dyn Error>> {
-    for candidate in &["r2md.yml", "r2md.yaml"] {
-        if Path::new(candidate).exists() {
-            let text = fs::read_to_string(candidate)?;
-            let config: R2mdConfig = serde_yaml::from_str(&text)?;
-            eprintln!("Loaded config from {}", candidate);
-            return Ok(Some(config));
+/// Render one file as a `###` heading plus a fenced code block, or for Markdown-like docs
+/// (see `RenderStrategy`) the content inline with its headings demoted.
+///
+/// `part` labels a piece of a file that had to be split across outputs, e.g. `"2/3"`,
+/// starting at the file's `first_line`; `lines` maps the file's lines to the ones on disk.
+/// With `--flag-chunks`, chunks over the limits get an HTML comment between heading and code.
+fn render_file_section(
+    rel_path: &str,
+    content: &str,
+    part: Option<&str>,
+    first_line: usize,
+    lines: &LineMap,
+    opts: &CollectOptions,
+) -> String {
+    let lang = language_from_path(Path::new(rel_path));
+    let strategy = RenderStrategy::for_language(lang);
+    let line_count = content.lines().count().max(1);
+    let originals =
+        || (first_line..first_line + line_count).filter_map(|line| lines.original(line));
+    let mut heading = match part {
+        Some(part) if opts.line_numbers => format!(
+            "### {} ({} {}, {} {}-{})\n\n",
+            inline_code(rel_path),
+            opts.labels.part,
+            part,
+            opts.labels.lines,
+            originals().next().unwrap_or(first_line),
+            originals().next_back().unwrap_or(first_line)
+        ),
+        Some(part) => format!(
+            "### {} ({} {})\n\n",
+            inline_code(rel_path),
+            opts.labels.part,
+            part
+        ),
+        None => format!("### {}\n\n", inline_code(rel_path)),
+    };
+    if let Some(note) = opts.file_notes.get(rel_path) {
+        heading.push_str(note);
+        heading.push_str("\n\n");
+    }
+    if let Some(note) = opts.duplicates.get(rel_path) {
+        return format!("{}{}\n\n", heading, note);
+    }
+    if let Some(limits) = opts
+        .chunk_flags
+        .as_ref()
+        .filter(|_| matches!(strategy, RenderStrategy::Fenced | RenderStrategy::Component))
+    {
+        let mut found = flag_chunks(content, lang, limits);
+        for flag in &mut found {
+            let line = first_line + flag.line - 1;
+            flag.line = lines.original(line).unwrap_or(line);
+            heading.push_str(&format!("<!-- {} -->\n", flag));
+        }
+        if !found.is_empty() {
+            heading.push('\n');
         }
     }
-    Ok(None)
+    if strategy == RenderStrategy::Component {
+        return format!(
+            "{}{}",
+            heading,
+            render_component(content, first_line, lines, opts)
+        );
+    }
+    let numbered;
+    let content = if opts.line_numbers && strategy == RenderStrategy::Fenced {
+        numbered = number_lines(content, first_line, lines);
+        &numbered
+    } else {
+        content
+    };
+    if strategy != RenderStrategy::Fenced {
+        return format!("{}{}\n\n", heading, render_inline(content, strategy));
+    }
+    let fence = fence_for(content, opts.fence_style);
+    format!("{}{}{}\n{}\n{}\n\n", heading, fence, lang, content, fence)
 }
 
-fn generate_directory_tree<W: Write>(
-    dir: &Path,
-    user_ignores: &[String],
-    includes: &[String],
-    debug: bool,
-    writer: &mut W,
-) -> Result<(), Box<dyn Error>> {
-    let canonical = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
-    let root_name = canonical
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or(".");
-    writeln!(writer, "- {}/", root_name)?;
-
-    for entry in WalkDir::new(&canonical).min_depth(1) {
-        let entry = match entry {
-            Ok(e) => e,
-            Err(_) => continue,
-        };
-        let depth = entry.depth();
-        let path = entry.path();
-        let rel_path = match path.strip_prefix(&canonical) {
-            Ok(p) => p.to_string_lossy().replace('\\', "/"),
-            Err(_) => path.to_string_lossy().replace('\\', "/"),
-        };
-        if should_skip_folder(path)
-            || (!path.is_dir() && should_skip_file(path, &rel_path, user_ignores, includes, debug))
-        {
+/// A Vue or Svelte component as one code fence per part, in the part's own language, each
+/// under its opening tag; with `--script-only` just the scripts.
+fn render_component(
+    content: &str,
+    first_line: usize,
+    lines: &LineMap,
+    opts: &CollectOptions,
+) -> String {
+    let mut out = String::new();
+    for block in split_component(content) {
+        if opts.script_only && block.kind != BlockKind::Script {
             continue;
         }
-        let indent = "  ".repeat(depth);
-        if entry.file_type().is_dir() {
-            writeln!(writer, "{}- {}/", indent, rel_path)?;
+        if !block.tag.is_empty() {
+            out.push_str(&format!("{}\n\n", inline_code(block.tag)));
+        }
+        let numbered;
+        let text = if opts.line_numbers {
+            numbered = number_lines(&block.text, first_line + block.first_line - 1, lines);
+            &numbered
         } else {
-            writeln!(writer, "{}- {}", indent, rel_path)?;
+            &block.text
+        };
+        let fence = fence_for(text, opts.fence_style);
+        out.push_str(&format!("{}{}\n{}\n{}\n\n", fence, block.lang, text, fence));
+    }
+    out
+}
+
+/// The `--history` section of one input: each commit under a heading with its subject,
+/// then its author, date, the rest of its message and its diff.
+fn render_history(dir: &Path, commits: &[git::Commit], opts: &CollectOptions) -> String {
+    let title = opts
+        .labels
+        .history
+        .replace("{dir}", &inline_code(&dir.display().to_string()));
+    let mut out = format!("## {}\n\n", title);
+    for commit in commits {
+        let (subject, body) = commit
+            .message
+            .split_once('\n')
+            .unwrap_or((&commit.message, ""));
+        out.push_str(&format!(
+            "### {} {}\n\n*{}, {}*\n\n",
+            inline_code(&commit.hash),
+            plain_text(subject),
+            plain_text(&commit.author),
+            commit.date
+        ));
+        let body = body.trim();
+        if !body.is_empty() {
+            let lines: Vec<String> = body.lines().map(plain_text).collect();
+            out.push_str(&format!("{}\n\n", lines.join("\n")));
+        }
+        if !commit.diff.is_empty() {
+            let fence = fence_for(&commit.diff, opts.fence_style);
+            out.push_str(&format!("{}diff\n{}\n{}\n\n", fence, commit.diff, fence));
         }
     }
-    Ok(())
+    out
 }
 
-/// Determine if folder should be skipped (hidden or in SKIP_FOLDERS)
-fn should_skip_folder(path: &Path) -> bool {
-    // Check every component in the path.
-    for component in path.components() {
-        if let Some(name) = component.as_os_str().to_str() {
-            // Skip hidden folders (names starting with a dot)
-            if name.starts_with('.') {
-                return true;
+/// The `--with-authors` line under a file heading; lists the top three contributors.
+fn authors_note(history: &git::FileHistory, labels: &Labels) -> String {
+    let authors = history
+        .authors
+        .iter()
+        .take(3)
+        .map(|(name, commits)| format!("{} ({})", plain_text(name), commits))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let note = labels
+        .authors
+        .replace("{date}", &history.last_modified)
+        .replace("{commit}", &inline_code(&history.commit))
+        .replace("{authors}", &authors);
+    format!("*{}*", note)
+}
+
+/// `content`, which starts at line `first` of its file, with each line prefixed by its
+/// right-aligned number on disk (see `LineMap`). Omission markers get no number.
+fn number_lines(content: &str, first: usize, lines: &LineMap) -> String {
+    let numbers: Vec<Option<usize>> = (first..first + content.lines().count())
+        .map(|line| lines.original(line))
+        .collect();
+    let width = numbers
+        .iter()
+        .flatten()
+        .max()
+        .unwrap_or(&first)
+        .to_string()
+        .len();
+    content
+        .lines()
+        .zip(numbers)
+        .map(|(line, number)| {
+            let number = number.map(|n| n.to_string()).unwrap_or_default();
+            match line {
+                "" => format!("{:>width$} |", number, width = width),
+                _ => format!("{:>width$} | {}", number, line, width = width),
             }
-            // If any component matches one of our skip folder names, skip the folder.
-            if SKIP_FOLDERS.contains(&name) {
-                return true;
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// End of an export: clear the progress display and, with `--timings`, put the time per
+/// phase and in total into `summary`.
+fn finish_run(
+    summary: &mut RunSummary,
+    run_started: Instant,
+    render_started: Instant,
+    show_timings: bool,
+) {
+    progress::add(Phase::Render, render_started.elapsed());
+    progress::finish();
+    if show_timings {
+        summary.timings = progress::timings();
+        summary.timings.push(("total", run_started.elapsed()));
+    }
+}
+
+/// Sections after the code: a truncation marker if the run was interrupted, the files left
+/// out by the output size limit, the assets inventory, then inaccessible paths.
+fn write_report_sections<W: Write>(
+    writer: &mut W,
+    report: &CollectReport,
+    labels: &Labels,
+) -> io::Result<()> {
+    if cancel::interrupted() {
+        writeln!(writer, "> **{}**\n", labels.interrupted)?;
+    }
+    write_over_budget_section(writer, &report.over_budget, labels)?;
+    write_assets_section(writer, &report.lfs_assets, labels)?;
+    write_inaccessible_section(writer, &report.inaccessible, labels)
+}
+
+/// List the files `--max-output-*` left out, so a capped export says what it is missing.
+fn write_over_budget_section<W: Write>(
+    writer: &mut W,
+    paths: &[String],
+    labels: &Labels,
+) -> io::Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let title = labels
+        .over_budget
+        .replace("{count}", &paths.len().to_string());
+    writeln!(writer, "## {}\n", title)?;
+    writeln!(writer, "{}\n", labels.over_budget_note)?;
+    for path in paths {
+        writeln!(writer, "- {}", inline_code(path))?;
+    }
+    writeln!(writer)
+}
+
+/// List Git LFS pointers by the real object they stand for, instead of exporting pointer text.
+fn write_assets_section<W: Write>(
+    writer: &mut W,
+    assets: &[LfsAsset],
+    labels: &Labels,
+) -> io::Result<()> {
+    if assets.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "## {}\n", labels.assets)?;
+    writeln!(writer, "{}\n", labels.assets_note)?;
+    for asset in assets {
+        writeln!(
+            writer,
+            "- {}: {} {}, oid {}",
+            inline_code(&asset.path),
+            asset.size,
+            labels.bytes,
+            inline_code(&asset.oid)
+        )?;
+    }
+    writeln!(writer)
+}
+
+/// List paths that could not be walked or read, so a partial export is visibly partial.
+fn write_inaccessible_section<W: Write>(
+    writer: &mut W,
+    inaccessible: &[InaccessiblePath],
+    labels: &Labels,
+) -> io::Result<()> {
+    if inaccessible.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "## {}\n", labels.inaccessible)?;
+    writeln!(writer, "{}\n", labels.inaccessible_note)?;
+    for item in inaccessible {
+        writeln!(
+            writer,
+            "- {}: {}",
+            inline_code(&item.path),
+            plain_text(&item.reason)
+        )?;
+    }
+    writeln!(writer)
+}
+
+/// Print a warning for every inaccessible path, plus an optional privileges hint.
+fn report_inaccessible(inaccessible: &[InaccessiblePath], sudo_hint: bool) {
+    if inaccessible.is_empty() {
+        return;
+    }
+    eprintln!(
+        "Warning: {} path(s) could not be read and were skipped:",
+        inaccessible.len()
+    );
+    for item in inaccessible {
+        eprintln!("  {}: {}", item.path, item.reason);
+    }
+    if sudo_hint {
+        if cfg!(windows) {
+            eprintln!("Hint: re-run r2md from an elevated (Administrator) prompt, or grant read access to the paths above.");
+        } else {
+            eprintln!("Hint: re-run with `sudo r2md ...`, or grant read access to the paths above (e.g. `chmod -R u+rX <dir>`).");
+        }
+    } else {
+        eprintln!("Pass --skip-unreadable to silence this list, or --sudo-hint for help fixing permissions.");
+    }
+}
+
+/// Write the `--report` JSON: counts plus one record per failed path.
+fn write_error_report(
+    path: &str,
+    files: &[FileEntry],
+    report: &CollectReport,
+) -> Result<(), Box<dyn Error>> {
+    let value = serde_json::json!({
+        "schema_version": SCHEMA_VERSION,
+        "files_included": files.len(),
+        "files_skipped": report.skipped,
+        "errors": report.errors.iter().map(R2mdError::to_json).collect::<Vec<_>>(),
+    });
+    fs::write(path, serde_json::to_string_pretty(&value)?)?;
+    Ok(())
+}
+
+/// Turn a walker error into an `InaccessiblePath`, keeping the offending path when known.
+fn inaccessible_from_walk_error(err: &ignore::Error, fallback: &Path) -> InaccessiblePath {
+    fn error_path(err: &ignore::Error) -> Option<&Path> {
+        match err {
+            ignore::Error::WithPath { path, .. } => Some(path),
+            ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+                error_path(err)
             }
+            _ => None,
         }
     }
-    false
+    let path = error_path(err).unwrap_or(fallback);
+    InaccessiblePath {
+        path: path.to_string_lossy().replace('\\', "/"),
+        reason: err.to_string(),
+    }
 }
 
-fn should_skip_file(
-    path: &Path,
-    rel_path: &str,
-    user_ignores: &[String],
-    includes: &[String],
-    debug: bool,
-) -> bool {
-    // (1) If the file matches an `--include` pattern, do NOT skip it.
-    if !includes.is_empty() {
-        let matches_include = includes.iter().any(|pattern| {
-            glob::Pattern::new(pattern)
-                .map(|p| p.matches(rel_path))
-                .unwrap_or(false)
-        });
-        if matches_include {
-            if debug {
-                eprintln!("File {} matches include => not skipping", path.display());
+/// Config file names looked for in each directory, in order.
+const CONFIG_NAMES: [&str; 2] = ["r2md.yml", "r2md.yaml"];
+
+/// Load the config from `explicit` (`--config`), or else from the first file
+/// `find_config_file` turns up, returning None if there is none.
+fn load_config_file(
+    explicit: Option<&Path>,
+    inputs: &[PathBuf],
+) -> Result<Option<R2mdConfig>, Box<dyn Error>> {
+    let Some(path) = explicit
+        .map(Path::to_path_buf)
+        .or_else(|| find_config_file(inputs))
+    else {
+        return Ok(None);
+    };
+    let text = fs::read_to_string(&path)
+        .map_err(|e| format!("Cannot read config {}: {}", path.display(), e))?;
+    let config: R2mdConfig =
+        serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    info!("Loaded config from {}", path.display());
+    Ok(Some(config))
+}
+
+/// The nearest `r2md.yml`/`r2md.yaml` in an input's directory or one of its parents (in
+/// input order), then in the working directory or one of its parents, then the user-wide
+/// `r2md/config.yml` under `$XDG_CONFIG_HOME` (`~/.config` if unset).
+fn find_config_file(inputs: &[PathBuf]) -> Option<PathBuf> {
+    let input_dirs = inputs.iter().filter_map(|input| {
+        let dir = if input.is_dir() {
+            input.as_path()
+        } else {
+            input.parent()?
+        };
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        dunce::canonicalize(dir).ok()
+    });
+    for start in input_dirs.chain(env::current_dir().ok()) {
+        for dir in start.ancestors() {
+            if let Some(found) = CONFIG_NAMES
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|candidate| candidate.is_file())
+            {
+                return Some(found);
             }
-            return false;
         }
     }
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(config_home.join("r2md").join("config.yml")).filter(|path| path.is_file())
+}
+
+/// Determine if folder should be skipped (hidden or in SKIP_FOLDERS), by its name
+fn should_skip_folder(name: &str) -> bool {
+    // Skip hidden folders (names starting with a dot)
+    name.starts_with('.') || SKIP_FOLDERS.contains(&name)
+}
 
-    // (2) Otherwise, do usual checks...
+/// Whether a walked file is out of scope by name: extension, ignore patterns, test or
+/// generated code. `--include` matches are decided before this is asked.
+fn should_skip_file(path: &Path, rel_path: &str, opts: &CollectOptions) -> bool {
     let ext = path
         .extension()
         .and_then(OsStr::to_str)
         .unwrap_or("")
         .to_lowercase();
 
-    if !RECOGNIZED_EXTENSIONS.contains(&ext.as_str()) {
+    // Preprocessed files become text whatever their extension.
+    if !has_collectable_name(path, opts, || file_has_shebang(path))
+        && preprocess_hook_for(&opts.preprocess, rel_path).is_none()
+    {
         if BINARY_FILE_EXTENSIONS.contains(&ext.as_str()) {
-            if debug {
-                eprintln!("Skipping known-binary file: {}", path.display());
-            }
+            debug!(path = %rel_path, reason = "binary", "Skipping file");
             return true;
         }
-        if debug {
-            eprintln!("Skipping unrecognized extension: {}", path.display());
-        }
+        debug!(path = %rel_path, reason = "extension", "Skipping file");
         return true;
     }
 
     // User ignore check using relative path
-    for pat in user_ignores {
-        if rel_path.contains(pat) {
-            if debug {
-                eprintln!("Skipping file by user ignore pattern: {}", path.display());
-            }
-            return true;
-        }
+    if opts.user_ignores.matches(rel_path, false) {
+        debug!(path = %rel_path, reason = "ignored", "Skipping file");
+        return true;
     }
 
-    // Size check
-    if let Ok(md) = path.metadata() {
-        if md.len() > DEFAULT_MAX_FILE_SIZE {
-            if debug {
-                eprintln!("Skipping large file: {} (>5MB)", path.display());
-            }
-            return true;
-        }
+    if opts.kind_filter.skips_path(rel_path) {
+        debug!(path = %rel_path, reason = "test_or_generated", "Skipping file");
+        return true;
     }
 
     false
 }
 
-fn is_excluded_path(path: &Path, excludes: &[PathBuf]) -> bool {
-    // We’ll do a canonicalize on the `path` so that comparisons are consistent:
-    let path_canonical = match path.canonicalize() {
-        Ok(p) => p,
-        Err(_) => return false, // If we can't canonicalize, skip trying to exclude
-    };
+/// The file's size if it is over `DEFAULT_MAX_FILE_SIZE`.
+fn oversized(path: &Path, opts: &CollectOptions) -> Option<u64> {
+    // Truncated files can be any size; they get cut down after reading.
+    if opts.truncate.is_some() {
+        return None;
+    }
+    let len = path.metadata().ok()?.len();
+    if len <= DEFAULT_MAX_FILE_SIZE {
+        return None;
+    }
+    debug!(path = %path.display(), reason = "too_large", size = len, "Skipping file");
+    Some(len)
+}
 
-    for exc in excludes {
-        // canonicalize each exclude as well (you might do it once ahead of time)
-        if let Ok(exc_canon) = exc.canonicalize() {
-            // If path is inside exc_canon, i.e. path starts with exc_canon
-            if path_canonical.starts_with(&exc_canon) {
-                return true;
-            }
-        }
+/// Whether a file's name puts it in scope: a recognized code extension, a known build file
+/// (`Makefile`, `Dockerfile`, ...), a docs/config file when `--include-docs` is on, an
+/// `--auto-extensions` pick, or an extensionless script, which `has_shebang` is only asked
+/// about then. With `--lang`, its language must also be one of those.
+fn has_collectable_name(
+    path: &Path,
+    opts: &CollectOptions,
+    has_shebang: impl FnOnce() -> bool,
+) -> bool {
+    if !opts.languages.is_empty()
+        && !opts
+            .languages
+            .iter()
+            .any(|lang| lang == language_from_path(path))
+    {
+        return false;
     }
-    false
+    let ext = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .unwrap_or("")
+        .to_lowercase();
+    if RECOGNIZED_EXTENSIONS.contains(&ext.as_str())
+        || is_build_file_name(path)
+        || opts.extra_extensions.contains(&ext)
+        || (is_script_candidate(path) && has_shebang())
+    {
+        return true;
+    }
+    if !opts.include_docs {
+        return false;
+    }
+    let name = path.file_name().and_then(OsStr::to_str).unwrap_or("");
+    if DOC_SKIP_NAMES.contains(&name) {
+        return false;
+    }
+    is_dockerfile_name(path)
+        || opts
+            .doc_extensions
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(&ext))
+}
+
+/// Extensionless build files collected like code, compared case-insensitively.
+const BUILD_FILE_NAMES: &[&str] = &[
+    "makefile",
+    "gnumakefile",
+    "dockerfile",
+    "containerfile",
+    "jenkinsfile",
+    "vagrantfile",
+    "rakefile",
+    "gemfile",
+    "podfile",
+    "brewfile",
+    "procfile",
+    "justfile",
+    "build",
+    "workspace",
+];
+
+fn is_build_file_name(path: &Path) -> bool {
+    path.extension().is_none()
+        && path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .is_some_and(|name| BUILD_FILE_NAMES.contains(&name.to_lowercase().as_str()))
+}
+
+/// Any other extensionless, non-hidden file (`LICENSE`, `CHANGELOG`, `bin/deploy`) is only
+/// collected when it starts with a shebang.
+pub(crate) fn is_script_candidate(path: &Path) -> bool {
+    path.extension().is_none()
+        && path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .is_some_and(|name| !name.starts_with('.'))
+        && !is_build_file_name(path)
+}
+
+/// Whether the file at `path` starts with `#!`.
+fn file_has_shebang(path: &Path) -> bool {
+    let mut head = [0; 2];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut head))
+        .is_ok()
+        && &head == b"#!"
 }
 
 fn collect_files_parallel(
     dir: &Path,
-    user_ignores: &[String],
-    excludes: &[PathBuf],
-    includes: &[String],
-    debug: bool,
+    opts: &CollectOptions,
+    report: &mut CollectReport,
+    cache: Option<&ReadCache>,
 ) -> Result<Vec<FileEntry>, Box<dyn Error>> {
     if !dir.is_dir() {
         return Ok(vec![]);
     }
-    let walker = WalkBuilder::new(dir)
+    let filter = PathFilter::new(Some(dir), &opts.includes, &opts.excludes)?;
+    // Prune skipped and excluded directories in the walker itself, so their subtrees
+    // (giant node_modules/target trees) are never descended into.
+    let prune_root = dir.to_path_buf();
+    let prune_filter = filter.clone();
+    let submodule_dirs = opts.submodule_dirs.clone();
+    let link_guard = opts.follow_symlinks.then(|| SymlinkGuard::new(dir));
+    let mut builder = WalkBuilder::new(dir);
+    builder
         .hidden(false)
-        .follow_links(false)
-        .git_ignore(if includes.is_empty() { true } else { false }) // Disable gitignore when --include is used
+        .follow_links(opts.follow_symlinks)
+        .max_depth(opts.max_depth)
+        .git_ignore(!filter.has_includes()) // Disable gitignore when --include is used
         .git_global(false)
         .git_exclude(false)
-        .build();
-
-    let paths: Vec<PathBuf> = walker
-        .filter_map(|entry| {
-            let ent = entry.ok()?;
-            let path = ent.path();
-            let rel_path = match path.strip_prefix(dir) {
-                Ok(p) => p.to_string_lossy().replace('\\', "/"),
-                Err(_) => path.to_string_lossy().replace('\\', "/"),
-            };
+        // Nested .gitignore files count in plain directories and unpacked checkouts too.
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .filter_entry(move |ent| {
+            if link_guard.as_ref().is_some_and(|guard| !guard.allows(ent)) {
+                return false;
+            }
+            if ent.depth() == 0 || !ent.file_type().is_some_and(|t| t.is_dir()) {
+                return true;
+            }
+            let rel_dir = make_relative(&prune_root, ent.path());
+            if prune_filter.reaches_into(&rel_dir)
+                || submodule_dirs
+                    .iter()
+                    .any(|d| Path::new(d).starts_with(&rel_dir))
+            {
+                return true;
+            }
+            !(should_skip_folder(&ent.file_name().to_string_lossy())
+                || prune_filter.is_excluded(&rel_dir, true))
+        });
+    // With a file limit, walk in name order on one thread, so the same files are picked on
+    // every run and every machine; the parallel walker doesn't sort.
+    if opts.max_files.is_some() {
+        builder.sort_by_file_name(|a, b| a.cmp(b));
+    }
 
-            if !includes.is_empty() {
-                let matches_include = includes.iter().any(|pattern| {
-                    glob::Pattern::new(pattern)
-                        .map(|p| p.matches(&rel_path))
-                        .unwrap_or(false)
-                });
-                if matches_include {
-                    return Some(path.to_path_buf());
+    // Walker threads classify entries and send the keepers through a channel; rayon
+    // workers read them as they arrive, so traversal overlaps with file reading.
+    let (tx, rx) = mpsc::channel::<Result<PathBuf, R2mdError>>();
+    let filter = &filter;
+    let skipped = &AtomicUsize::new(0);
+    let found = &AtomicUsize::new(0);
+    let limit_hit = &AtomicBool::new(false);
+    let lfs_assets = &Mutex::new(Vec::new());
+    let visit = |entry: Result<ignore::DirEntry, ignore::Error>,
+                 tx: &mpsc::Sender<Result<PathBuf, R2mdError>>| {
+        if cancel::interrupted() {
+            return WalkState::Quit;
+        }
+        let walked = match entry {
+            Ok(ent) => {
+                let walked = walk_entry(&ent, dir, opts, filter);
+                if walked.is_none() && ent.file_type().is_some_and(|t| t.is_file()) {
+                    skipped.fetch_add(1, Ordering::Relaxed);
                 }
+                walked
             }
-
-            if is_excluded_path(path, excludes)
-                || (path.is_dir() && should_skip_folder(path))
-                || (!path.is_dir()
-                    && should_skip_file(path, &rel_path, user_ignores, includes, debug))
+            Err(err) if symlinks::is_loop(&err) => {
+                debug!(error = %err, reason = "symlink_loop", "Skipping path");
+                None
+            }
+            Err(err) => {
+                debug!(error = %err, "Walk error");
+                Some(Err(R2mdError::Inaccessible(inaccessible_from_walk_error(
+                    &err, dir,
+                ))))
+            }
+        };
+        if let Some(walked) = walked {
+            if walked.is_ok()
+                && opts
+                    .max_files
+                    .is_some_and(|max| found.fetch_add(1, Ordering::Relaxed) >= max)
             {
-                return None;
+                limit_hit.store(true, Ordering::Relaxed);
+                return WalkState::Quit;
             }
-            Some(path.to_path_buf())
-        })
-        .collect();
+            if tx.send(walked).is_err() {
+                return WalkState::Quit;
+            }
+        }
+        WalkState::Continue
+    };
+    let visit = &visit;
+    let results: Vec<Result<FileEntry, R2mdError>> = thread::scope(|scope| {
+        scope.spawn(move || {
+            let started = Instant::now();
+            if opts.max_files.is_some() {
+                for entry in builder.build() {
+                    if visit(entry, &tx) == WalkState::Quit {
+                        break;
+                    }
+                }
+            } else {
+                builder.build_parallel().run(|| {
+                    let tx = tx.clone();
+                    Box::new(move |entry| visit(entry, &tx))
+                });
+            }
+            progress::add(Phase::Walk, started.elapsed());
+        });
 
-    let file_entries: Vec<FileEntry> = paths
-        .par_iter()
-        .filter_map(|path| match fs::read_to_string(path) {
-            Ok(content) => Some(FileEntry {
-                rel_path: make_relative(dir, path),
-                content,
-            }),
-            Err(e) => {
-                if debug {
-                    eprintln!("Skipping unreadable file {}: {}", path.display(), e);
+        rx.into_iter()
+            .par_bridge()
+            .filter_map(|walked| {
+                if cancel::interrupted() {
+                    return None;
                 }
-                None
+                let path = match walked {
+                    Ok(path) => path,
+                    Err(failure) => return Some(Err(failure)),
+                };
+                let rel_path = make_relative(dir, &path);
+                let started = Instant::now();
+                let read = read_file_entry(&path, rel_path, opts, cache, lfs_assets);
+                progress::add(Phase::Read, started.elapsed());
+                progress::inc();
+                read
+            })
+            .collect()
+    });
+
+    report.skipped += skipped.load(Ordering::Relaxed);
+    report.file_limit_hit |= limit_hit.load(Ordering::Relaxed);
+    let mut files = finish_collect(results, lfs_assets, report);
+    keep_included_lines(&mut files, filter, opts);
+    Ok(files)
+}
+
+/// Read exactly the files listed in `--files-from`, relative to `dir` unless absolute.
+/// Name and ignore filters don't apply, since the list already says what to export;
+/// excludes, the size limit and the text check still do. Listed paths that are gone
+/// (e.g. deleted files from `git diff --name-only`) are counted as skipped.
+fn collect_listed_files(
+    dir: &Path,
+    list: &[String],
+    opts: &CollectOptions,
+    report: &mut CollectReport,
+) -> Result<Vec<FileEntry>, Box<dyn Error>> {
+    let filter = PathFilter::new(Some(dir), &[], &opts.excludes)?;
+    let lfs_assets = &Mutex::new(Vec::new());
+    let mut paths = Vec::new();
+    for listed in list {
+        let path = dir.join(local_path(Path::new(listed)));
+        let rel_path = normalize_rel_path(&make_relative(dir, &path));
+        if !path.is_file() || filter.is_excluded(&rel_path, false) {
+            debug!(path = %rel_path, reason = "not_listable", "Skipping file");
+            report.skipped += 1;
+            continue;
+        }
+        paths.push((path, rel_path));
+    }
+    let results: Vec<Result<FileEntry, R2mdError>> = paths
+        .into_par_iter()
+        .filter_map(|(path, rel_path)| {
+            if let Some(size) = oversized(&path, opts) {
+                return Some(Err(R2mdError::TooLarge {
+                    path: rel_path,
+                    size,
+                    limit: DEFAULT_MAX_FILE_SIZE,
+                }));
             }
+            let started = Instant::now();
+            let read = read_file_entry(&path, rel_path, opts, None, lfs_assets);
+            progress::add(Phase::Read, started.elapsed());
+            progress::inc();
+            read
         })
         .collect();
+    Ok(finish_collect(results, lfs_assets, report))
+}
+
+/// One path per line from `source`, or from stdin for `-`. Blank lines are ignored.
+fn read_file_list(source: &str) -> io::Result<Vec<String>> {
+    let text = if source == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(source)?
+    };
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Read one file that passed the filters. `None` means it was a Git LFS pointer, now
+/// recorded in `lfs_assets` instead.
+fn read_file_entry(
+    path: &Path,
+    rel_path: String,
+    opts: &CollectOptions,
+    cache: Option<&ReadCache>,
+    lfs_assets: &Mutex<Vec<LfsAsset>>,
+) -> Option<Result<FileEntry, R2mdError>> {
+    let read = match preprocess_hook_for(&opts.preprocess, &rel_path) {
+        Some(hook) => match run_preprocess(hook, path, &rel_path) {
+            Ok(stdout) => Ok(bytes_to_text(stdout, opts.strict_utf8)),
+            Err(failure) => return Some(Err(failure)),
+        },
+        None => match cache {
+            Some(cache) => cache.read(path, opts.strict_utf8),
+            None => fs::read(path).map(|bytes| bytes_to_text(bytes, opts.strict_utf8)),
+        }
+        .map(|text| text.and_then(|text| notebook::convert(&rel_path, text, opts.fence_style))),
+    };
+    match read {
+        Ok(Ok(content)) => match lfs::parse_pointer(&rel_path, &content) {
+            Some(asset) => {
+                lfs_assets.lock().unwrap().push(asset);
+                None
+            }
+            None => Some(Ok(FileEntry {
+                rel_path,
+                content,
+                ..Default::default()
+            })),
+        },
+        // Binary or non-UTF-8 content is a format issue, not an access failure.
+        Ok(Err(reason)) => {
+            debug!(path = %rel_path, reason = "undecodable", error = %reason, "Skipping file");
+            Some(Err(R2mdError::Decode {
+                path: rel_path,
+                reason,
+            }))
+        }
+        Err(source) => {
+            debug!(path = %rel_path, reason = "unreadable", error = %source, "Skipping file");
+            Some(Err(R2mdError::Unreadable {
+                path: rel_path,
+                source,
+            }))
+        }
+    }
+}
+
+/// Sort collected files and failures into `report`, in a stable order.
+fn finish_collect(
+    results: Vec<Result<FileEntry, R2mdError>>,
+    lfs_assets: &Mutex<Vec<LfsAsset>>,
+    report: &mut CollectReport,
+) -> Vec<FileEntry> {
+    let mut file_entries = Vec::with_capacity(results.len());
+    for result in results {
+        match result {
+            Ok(entry) => file_entries.push(entry),
+            Err(failure) => report.record(failure),
+        }
+    }
+    let mut assets = std::mem::take(&mut *lfs_assets.lock().unwrap());
+    assets.sort_by(|a, b| a.path.cmp(&b.path));
+    report.lfs_assets.extend(assets);
+    // Files are read in parallel, in a nondeterministic order.
+    file_entries.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    report.inaccessible.sort_by(|a, b| a.path.cmp(&b.path));
+    report.errors.sort_by(|a, b| a.path().cmp(b.path()));
+    file_entries
+}
+
+/// Cut the files an `--include path:START-END` matches down to those lines.
+fn keep_included_lines(files: &mut [FileEntry], filter: &PathFilter, opts: &CollectOptions) {
+    for file in files {
+        let line_count = file.content.lines().count();
+        if let Some(ranges) = filter.line_ranges(&file.rel_path, line_count) {
+            keep_ranges(file, &ranges, &opts.labels.omitted);
+        }
+    }
+}
+
+/// Decide whether a walked entry is a file to read, returning its path if so.
+/// Oversized files come back as an error so they show up in the report.
+fn walk_entry(
+    ent: &ignore::DirEntry,
+    dir: &Path,
+    opts: &CollectOptions,
+    filter: &PathFilter,
+) -> Option<Result<PathBuf, R2mdError>> {
+    if ent.file_type().is_some_and(|t| t.is_dir()) {
+        return None;
+    }
+    let path = ent.path();
+    let rel_path = make_relative(dir, path);
+    // Unfollowed links to directories aren't files to read.
+    if ent.path_is_symlink() && path.is_dir() {
+        debug!(path = %rel_path, reason = "symlinked_dir", "Skipping file (see --follow-symlinks)");
+        return None;
+    }
+
+    if filter.is_included(&rel_path) {
+        return Some(Ok(path.to_path_buf()));
+    }
+    if opts.only_included {
+        debug!(path = %rel_path, reason = "not_included", "Skipping file (see --only)");
+        return None;
+    }
+    if filter.is_excluded(&rel_path, false) {
+        return None;
+    }
+    if should_skip_file(path, &rel_path, opts) {
+        // LFS pointers hide behind asset extensions (`.png`, `.bin`); read them so they
+        // can be listed as assets.
+        return lfs::is_pointer_file(path, &rel_path, opts).then(|| Ok(path.to_path_buf()));
+    }
+    if let Some(size) = oversized(path, opts) {
+        return Some(Err(R2mdError::TooLarge {
+            path: rel_path,
+            size,
+            limit: DEFAULT_MAX_FILE_SIZE,
+        }));
+    }
+    Some(Ok(path.to_path_buf()))
+}
 
-    Ok(file_entries)
+/// A path from the command line in the form the rest of r2md expects. On Windows,
+/// `\\?\C:\repo` and `\\?\UNC\server\share\repo` become `C:\repo` and
+/// `\\server\share\repo` when that names the same file, so relative paths, `git -C` and
+/// displayed paths all agree; `std::fs` adds the prefix back for paths over 260
+/// characters. Elsewhere, paths are unchanged.
+fn local_path(path: &Path) -> PathBuf {
+    dunce::simplified(path).to_path_buf()
 }
 
 /// Convert path->string relative to `base`, always using forward slashes
@@ -749,20 +3302,230 @@ fn test_path_utilities() {
         make_relative(Path::new("/base"), Path::new("/other/file.txt")),
         "/other/file.txt"
     );
+    #[cfg(windows)]
+    {
+        assert_eq!(
+            local_path(Path::new(r"\\?\UNC\server\share\repo")),
+            Path::new(r"\\server\share\repo")
+        );
+        let long = format!(r"\\?\C:\{}", "deep\\".repeat(60));
+        assert_eq!(local_path(Path::new(&long)), Path::new(&long));
+        assert_eq!(
+            make_relative(
+                &local_path(Path::new(r"\\?\C:\repo")),
+                Path::new(r"C:\repo\src\main.rs")
+            ),
+            "src/main.rs"
+        );
+    }
+}
+
+#[test]
+fn test_per_file_errors_are_reported() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("ok.rs"), "fn main() {}")?;
+    fs::write(dir.path().join("bin.rs"), b"\0\x01\x02")?;
+    fs::write(
+        dir.path().join("big.rs"),
+        vec![b'a'; DEFAULT_MAX_FILE_SIZE as usize + 1],
+    )?;
+
+    let mut report = CollectReport::default();
+    let files = collect_files_parallel(dir.path(), &CollectOptions::default(), &mut report, None)?;
+    assert_eq!(files.len(), 1);
+    let kinds: Vec<_> = report.errors.iter().map(|e| (e.path(), e.kind())).collect();
+    assert_eq!(kinds, vec![("big.rs", "too_large"), ("bin.rs", "decode")]);
+    assert_eq!(report.skipped, 2);
+    Ok(())
+}
+
+#[test]
+fn test_listed_files_skip_walk_filters() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join("node_modules"))?;
+    fs::write(dir.path().join("node_modules/a.js"), "let a;")?;
+    fs::write(dir.path().join("Makefile.am"), "all:")?;
+    fs::write(dir.path().join("bin.rs"), b"\0\x01")?;
+
+    let list: Vec<String> = ["./node_modules/a.js", "Makefile.am", "bin.rs", "deleted.rs"]
+        .map(String::from)
+        .to_vec();
+    let mut report = CollectReport::default();
+    let files = collect_listed_files(dir.path(), &list, &CollectOptions::default(), &mut report)?;
+    let paths: Vec<_> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    assert_eq!(paths, vec!["Makefile.am", "node_modules/a.js"]);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.skipped, 2);
+    Ok(())
+}
+
+#[test]
+fn test_nested_ignore_files() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir_all(dir.path().join("src/gen"))?;
+    fs::write(dir.path().join("src/lib.rs"), "fn a() {}")?;
+    fs::write(dir.path().join("src/gen/out.rs"), "fn b() {}")?;
+    fs::write(dir.path().join("src/gen/keep.rs"), "fn c() {}")?;
+    fs::write(dir.path().join("src/.gitignore"), "gen/out.rs\n")?;
+    fs::write(dir.path().join("src/gen/.r2mdignore"), "*.rs\n!keep.rs\n")?;
+    fs::write(dir.path().join("notes.rs"), "fn d() {}")?;
+    fs::write(dir.path().join(IGNORE_FILE), "notes.rs\n")?;
+
+    let mut report = CollectReport::default();
+    let files = collect_files_parallel(dir.path(), &CollectOptions::default(), &mut report, None)?;
+    let mut paths: Vec<_> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    paths.sort();
+    assert_eq!(paths, vec!["src/gen/keep.rs", "src/lib.rs"]);
+    Ok(())
+}
+
+#[test]
+fn test_traversal_limits() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir_all(dir.path().join("src/deep"))?;
+    fs::write(dir.path().join("main.rs"), "fn a() {}")?;
+    fs::write(dir.path().join("src/lib.rs"), "fn b() {}")?;
+    fs::write(dir.path().join("src/deep/c.rs"), "fn c() {}")?;
+
+    let opts = CollectOptions {
+        max_depth: Some(2),
+        ..Default::default()
+    };
+    let mut report = CollectReport::default();
+    let files = collect_files_parallel(dir.path(), &opts, &mut report, None)?;
+    let paths: Vec<_> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    assert_eq!(paths, vec!["main.rs", "src/lib.rs"]);
+    assert!(!report.file_limit_hit);
+
+    let opts = CollectOptions {
+        max_files: Some(2),
+        ..Default::default()
+    };
+    let mut report = CollectReport::default();
+    let files = collect_files_parallel(dir.path(), &opts, &mut report, None)?;
+    let paths: Vec<_> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    // The walk goes in name order, whatever order the filesystem lists entries in.
+    assert_eq!(paths, vec!["main.rs", "src/deep/c.rs"]);
+    assert!(report.file_limit_hit);
+    Ok(())
+}
+
+#[test]
+fn test_only_restricts_to_includes() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir_all(dir.path().join("infra"))?;
+    fs::write(dir.path().join("infra/main.tf"), "resource \"a\" \"b\" {}")?;
+    fs::write(dir.path().join("main.rs"), "fn a() {}")?;
+
+    let mut opts = CollectOptions {
+        includes: vec!["*.tf".to_string()],
+        ..Default::default()
+    };
+    let mut report = CollectReport::default();
+    let files = collect_files_parallel(dir.path(), &opts, &mut report, None)?;
+    assert_eq!(files.len(), 2);
+
+    opts.only_included = true;
+    let files = collect_files_parallel(dir.path(), &opts, &mut report, None)?;
+    let paths: Vec<_> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    assert_eq!(paths, vec!["infra/main.tf"]);
+    Ok(())
+}
+
+#[test]
+fn test_line_numbers_after_cuts() {
+    let opts = CollectOptions {
+        line_numbers: true,
+        ..Default::default()
+    };
+    let marker = "// {count} lines omitted";
+    let mut file = FileEntry {
+        rel_path: "util.rs".to_string(),
+        content: "fn main() {\n    let a = 1;\n    let b = 2;\n    let c = 3;\n    let d = 4;\n    let e = 5;\n    let f = 6;\n}\n".to_string(),
+        ..Default::default()
+    };
+    let original = file.clone();
+
+    let limit = Truncation {
+        max_tokens: 20,
+        strategy: TruncateStrategy::HeadTail,
+    };
+    let ranges = limit.ranges(&file.content, marker).unwrap();
+    keep_ranges(&mut file, &ranges, marker);
+    let section = render_file_section(&file.rel_path, &file.content, None, 1, &file.lines, &opts);
+    assert!(section.contains("2 |     let a = 1;\n  | // 5 lines omitted\n8 | }"));
+
+    // An include range first, then truncation of what is left.
+    let mut file = original;
+    keep_ranges(&mut file, &[(3, 8)], marker);
+    let ranges = limit.ranges(&file.content, marker).unwrap();
+    keep_ranges(&mut file, &ranges, marker);
+    let section = render_file_section(&file.rel_path, &file.content, None, 1, &file.lines, &opts);
+    assert!(section
+        .contains("  | // 2 lines omitted\n3 |     let b = 2;\n  | // 4 lines omitted\n8 | }"));
 }
 
 #[test]
-fn test_pdf_generation() -> Result<(), Box<dyn std::error::Error>> {
-    let files = vec![FileEntry {
-        rel_path: "test.rs".into(),
-        content: "fn main() {}".into(),
-    }];
+fn test_react_files_are_collected() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    let app = "import { Button } from './Button';\n\nexport function App() {\n  return <div className=\"app\">{`hi`}<Button /></div>;\n}\n";
+    fs::write(dir.path().join("App.tsx"), app)?;
+    fs::write(
+        dir.path().join("Button.jsx"),
+        "export const Button = () => <button>Don't</button>;\n",
+    )?;
+    let mut report = CollectReport::default();
+    let files = collect_files_parallel(dir.path(), &CollectOptions::default(), &mut report, None)?;
+    let paths: Vec<&str> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    assert_eq!(paths, ["App.tsx", "Button.jsx"]);
+    assert_eq!(language_from_path(Path::new("App.tsx")), "tsx");
+    assert_eq!(language_from_path(Path::new("Button.jsx")), "jsx");
+    assert_eq!(chunks::public_symbols(app, "tsx"), ["App"]);
+    Ok(())
+}
 
-    let temp_file = tempfile::NamedTempFile::new()?;
-    let path = temp_file.path().to_str().unwrap();
+#[test]
+fn test_broken_pipe_is_not_an_error() {
+    let closed: Box<dyn Error> = io::Error::from(io::ErrorKind::BrokenPipe).into();
+    assert!(is_broken_pipe(closed.as_ref()));
+    let other: Box<dyn Error> = io::Error::from(io::ErrorKind::NotFound).into();
+    assert!(!is_broken_pipe(other.as_ref()));
+    assert!(!is_broken_pipe(
+        Box::<dyn Error>::from("Broken pipe").as_ref()
+    ));
+}
 
-    write_pdf_file(&files, &[PathBuf::from(".")], path)?;
-    assert!(Path::new(path).exists());
+#[test]
+fn test_extensionless_files() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("Makefile"), "all:\n\tcargo build\n")?;
+    fs::write(dir.path().join("deploy"), "#!/bin/sh\necho hi\n")?;
+    fs::write(dir.path().join("LICENSE"), "MIT License\n")?;
+    fs::write(dir.path().join("CHANGELOG"), "# 1.0\n")?;
+    let mut report = CollectReport::default();
+    let files = collect_files_parallel(dir.path(), &CollectOptions::default(), &mut report, None)?;
+    let mut paths: Vec<&str> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    paths.sort();
+    assert_eq!(paths, ["Makefile", "deploy"]);
+    Ok(())
+}
 
+#[test]
+fn test_config_found_in_input_ancestors() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = dunce::canonicalize(dir.path())?;
+    let nested = root.join("repo/crates/core");
+    fs::create_dir_all(&nested)?;
+    fs::write(root.join("repo/r2md.yaml"), "ignore_patterns: [\"a\"]\n")?;
+    fs::write(nested.join("lib.rs"), "")?;
+    let found = find_config_file(&[root.join("missing.zip"), nested.join("lib.rs")]);
+    assert_eq!(found, Some(root.join("repo/r2md.yaml")));
+
+    fs::write(root.join("repo/crates/r2md.yml"), "profiles: {}\n")?;
+    let config = load_config_file(None, std::slice::from_ref(&nested))?.unwrap();
+    assert!(config.ignore_patterns.is_empty());
+    let explicit = root.join("repo/r2md.yaml");
+    let config = load_config_file(Some(&explicit), &[nested])?.unwrap();
+    assert_eq!(config.ignore_patterns, ["a"]);
     Ok(())
 }
@@ -0,0 +1,175 @@
+use crate::compress::{read_to_string, write_file};
+use crate::language_from_path;
+use crate::schema::SCHEMA_VERSION;
+use crate::tokens::estimate_tokens;
+use crate::types::FileEntry;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Default file name for `--manifest`.
+pub const DEFAULT_MANIFEST: &str = "r2md_manifest.json";
+
+/// Where one file's section landed in the Markdown output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionSpan {
+    pub rel_path: String,
+    /// Byte offset of the `### ` heading within the output file
+    pub offset: usize,
+    /// Section length in bytes, through the closing fence and blank line
+    pub length: usize,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    schema_version: u32,
+    files: Vec<ManifestEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct ManifestEntry<'a> {
+    path: &'a str,
+    size: usize,
+    tokens: usize,
+    language: &'a str,
+    sha256: String,
+    /// One per output section; split files have several
+    sections: Vec<ManifestSection>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestSection {
+    output: String,
+    offset: usize,
+    length: usize,
+}
+
+/// Write the manifest: every included file with its size, token estimate, language,
+/// content hash and the byte ranges of its section(s). `spans` pairs each output file
+/// name with the sections written to it.
+pub fn write_manifest(
+    path: &str,
+    files: &[FileEntry],
+    spans: &[(String, Vec<SectionSpan>)],
+) -> io::Result<()> {
+    let mut sections: HashMap<&str, Vec<ManifestSection>> = HashMap::new();
+    for (output, output_spans) in spans {
+        for span in output_spans {
+            sections
+                .entry(span.rel_path.as_str())
+                .or_default()
+                .push(ManifestSection {
+                    output: output.clone(),
+                    offset: span.offset,
+                    length: span.length,
+                });
+        }
+    }
+    let manifest = Manifest {
+        schema_version: SCHEMA_VERSION,
+        files: files
+            .iter()
+            .map(|f| ManifestEntry {
+                path: &f.rel_path,
+                size: f.content.len(),
+                tokens: estimate_tokens(&f.content),
+                language: language_from_path(Path::new(&f.rel_path)),
+                sha256: format!("{:x}", Sha256::digest(f.content.as_bytes())),
+                sections: sections.remove(f.rel_path.as_str()).unwrap_or_default(),
+            })
+            .collect(),
+    };
+    write_file(path, serde_json::to_string_pretty(&manifest)?)
+}
+
+#[derive(Deserialize)]
+struct ManifestSpans {
+    files: Vec<EntrySpans>,
+}
+
+#[derive(Deserialize)]
+struct EntrySpans {
+    path: String,
+    sections: Vec<ManifestSection>,
+}
+
+/// The sections a manifest at `path` records in the output named like `output`, in
+/// document order.
+pub fn read_manifest_spans(path: &str, output: &str) -> io::Result<Vec<SectionSpan>> {
+    let manifest: ManifestSpans = serde_json::from_str(&read_to_string(path)?)?;
+    let name = Path::new(output).file_name();
+    let mut spans: Vec<SectionSpan> = manifest
+        .files
+        .into_iter()
+        .flat_map(|entry| {
+            let path = entry.path;
+            entry
+                .sections
+                .into_iter()
+                .filter(|section| Path::new(&section.output).file_name() == name)
+                .map(move |section| SectionSpan {
+                    rel_path: path.clone(),
+                    offset: section.offset,
+                    length: section.length,
+                })
+        })
+        .collect();
+    spans.sort_by_key(|span| span.offset);
+    Ok(spans)
+}
+
+/// A writer that counts the bytes passed through, so section offsets can be recorded
+/// while the document is streamed out.
+pub struct CountingWriter<W> {
+    inner: W,
+    pub count: usize,
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub fn new(inner: W, start: usize) -> Self {
+        CountingWriter {
+            inner,
+            count: start,
+        }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn test_manifest_lists_sections() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("manifest.json");
+    let files = vec![FileEntry {
+        rel_path: "src/main.rs".to_string(),
+        content: "fn main() {}".to_string(),
+        ..Default::default()
+    }];
+    let spans = vec![(
+        "out.md".to_string(),
+        vec![SectionSpan {
+            rel_path: "src/main.rs".to_string(),
+            offset: 10,
+            length: 42,
+        }],
+    )];
+    write_manifest(&path.to_string_lossy(), &files, &spans)?;
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    let entry = &json["files"][0];
+    assert_eq!(entry["language"], "rust");
+    assert_eq!(entry["sections"][0]["offset"], 10);
+    assert_eq!(entry["sha256"].as_str().map(str::len), Some(64));
+    Ok(())
+}
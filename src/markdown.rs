@@ -0,0 +1,150 @@
+//! Doc files that are written inline instead of fenced: Markdown (and notebooks, which
+//! become Markdown) and AsciiDoc. Their headings are demoted to sit under the file's
+//! `###` heading, so they don't break the document outline.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Shallowest heading level a file's own headings may use, one below its `###` heading.
+const TOP_LEVEL: usize = 4;
+/// Markdown has no headings deeper than this.
+const MAX_LEVEL: usize = 6;
+
+/// How a file's content goes into the document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderStrategy {
+    /// In a code fence tagged with the language
+    Fenced,
+    /// Inline, with headings demoted
+    Markdown,
+    /// Inline, with `=` section titles turned into demoted Markdown headings
+    AsciiDoc,
+    /// One code fence per part of a Vue or Svelte component (see `component`)
+    Component,
+}
+
+impl RenderStrategy {
+    pub fn for_language(lang: &str) -> Self {
+        match lang {
+            "markdown" | "notebook" => RenderStrategy::Markdown,
+            "asciidoc" => RenderStrategy::AsciiDoc,
+            "vue" | "svelte" => RenderStrategy::Component,
+            _ => RenderStrategy::Fenced,
+        }
+    }
+}
+
+static ATX_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^ {0,3}(#{1,6})(\s.*|)$").unwrap());
+static ADOC_TITLE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(={1,6})\s+(.*)$").unwrap());
+static SETEXT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^ {0,3}(=+|-+)\s*$").unwrap());
+static FENCE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^ {0,3}(`{3,}|~{3,})").unwrap());
+
+/// `content` ready to inline under a file heading.
+pub fn render_inline(content: &str, strategy: RenderStrategy) -> String {
+    let headings = match strategy {
+        RenderStrategy::AsciiDoc => asciidoc_headings(content),
+        _ => markdown_headings(content),
+    };
+    let shallowest = headings.iter().map(|&(_, level, _)| level).min();
+    let shift = shallowest.map_or(0, |level| TOP_LEVEL.saturating_sub(level));
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    // Setext underlines are dropped, so go bottom-up to keep indices valid.
+    for &(index, level, underline) in headings.iter().rev() {
+        let title = match strategy {
+            RenderStrategy::AsciiDoc => ADOC_TITLE_RE
+                .captures(&lines[index])
+                .map(|c| c[2].to_string()),
+            _ if underline => Some(lines[index].trim().to_string()),
+            _ => ATX_RE
+                .captures(&lines[index])
+                .map(|c| c[2].trim().to_string()),
+        }
+        .unwrap_or_default();
+        lines[index] = format!("{} {}", "#".repeat((level + shift).min(MAX_LEVEL)), title);
+        if underline {
+            lines.remove(index + 1);
+        }
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+/// `(line index, level, setext)` of each Markdown heading outside code fences.
+fn markdown_headings(content: &str) -> Vec<(usize, usize, bool)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut headings = Vec::new();
+    let mut fence: Option<String> = None;
+    // YAML front matter: its closing `---` is not a setext underline.
+    let front_matter = match lines.first() {
+        Some(&"---") => lines
+            .iter()
+            .skip(1)
+            .position(|l| *l == "---")
+            .map_or(0, |end| end + 2),
+        _ => 0,
+    };
+    for (i, line) in lines.iter().enumerate().skip(front_matter) {
+        if let Some(open) = FENCE_RE.captures(line) {
+            let marker = &open[1];
+            match &fence {
+                None => fence = Some(marker.to_string()),
+                Some(f) if marker.starts_with(f.as_str()) && line.trim() == marker => fence = None,
+                _ => {}
+            }
+            continue;
+        }
+        if fence.is_some() {
+            continue;
+        }
+        if let Some(c) = ATX_RE.captures(line) {
+            headings.push((i, c[1].len(), false));
+        } else if !line.trim().is_empty()
+            && !line.starts_with(['-', '*', '+', '>', '|', ' '])
+            && (i == front_matter || lines[i - 1].trim().is_empty())
+        {
+            if let Some(c) = lines.get(i + 1).and_then(|next| SETEXT_RE.captures(next)) {
+                headings.push((i, if c[1].starts_with('=') { 1 } else { 2 }, true));
+            }
+        }
+    }
+    headings
+}
+
+/// `(line index, level, false)` of each AsciiDoc `=` section title outside `----` blocks.
+fn asciidoc_headings(content: &str) -> Vec<(usize, usize, bool)> {
+    let mut headings = Vec::new();
+    let mut delimiter: Option<&str> = None;
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_end();
+        if trimmed.len() >= 4
+            && (trimmed.chars().all(|c| c == '-') || trimmed.chars().all(|c| c == '.'))
+        {
+            delimiter = match delimiter {
+                Some(d) if d == trimmed => None,
+                None => Some(trimmed),
+                other => other,
+            };
+            continue;
+        }
+        if delimiter.is_none() {
+            if let Some(c) = ADOC_TITLE_RE.captures(line) {
+                headings.push((i, c[1].len(), false));
+            }
+        }
+    }
+    headings
+}
+
+#[test]
+fn test_headings_are_demoted() {
+    let md = "---\ntitle: x\n---\n# Guide\n\nIntro\n\n```sh\n# not a heading\n```\n\nSetup\n-----\n\n### Deep\n";
+    assert_eq!(
+        render_inline(md, RenderStrategy::Markdown),
+        "---\ntitle: x\n---\n#### Guide\n\nIntro\n\n```sh\n# not a heading\n```\n\n##### Setup\n\n###### Deep"
+    );
+    let adoc = "= Title\n\n== Install\n\n----\n= literal\n----\n";
+    assert_eq!(
+        render_inline(adoc, RenderStrategy::AsciiDoc),
+        "#### Title\n\n##### Install\n\n----\n= literal\n----"
+    );
+    assert_eq!(RenderStrategy::for_language("rust"), RenderStrategy::Fenced);
+}
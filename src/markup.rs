@@ -0,0 +1,131 @@
+//! `--format asciidoc` and `--format rst`: the export as an AsciiDoc or reStructuredText
+//! document, so it can go straight into an Antora or Sphinx site. Each file is a section
+//! titled with its path, holding the file as a source block.
+
+use crate::language_from_path;
+use crate::metadata::Metadata;
+use crate::types::FileEntry;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A lightweight markup language other than Markdown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Markup {
+    AsciiDoc,
+    Rst,
+}
+
+impl Markup {
+    /// Extension of the output file.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Markup::AsciiDoc => "adoc",
+            Markup::Rst => "rst",
+        }
+    }
+}
+
+/// Write `files` as one `markup` document.
+pub fn write_markup_file(
+    files: &[FileEntry],
+    directories: &[PathBuf],
+    output_file_name: &str,
+    markup: Markup,
+) -> Result<(), Box<dyn Error>> {
+    let metadata = Metadata::new(directories, files.len(), 0);
+    let title = format!("r2md: {}", metadata.repo);
+    let mut out = BufWriter::new(File::create(output_file_name)?);
+    match markup {
+        Markup::AsciiDoc => writeln!(out, "= {}\n", title)?,
+        Markup::Rst => {
+            let rule = "=".repeat(title.chars().count());
+            writeln!(out, "{}\n{}\n{}\n", rule, title, rule)?
+        }
+    }
+    for file in files {
+        let section = match markup {
+            Markup::AsciiDoc => asciidoc_section(file),
+            Markup::Rst => rst_section(file),
+        };
+        out.write_all(section.as_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Lexer name for a source block. Both Rouge (Asciidoctor) and Pygments (Sphinx) know the
+/// names `language_from_path` gives, except for the catch-alls.
+fn lexer(rel_path: &str) -> &str {
+    match language_from_path(Path::new(rel_path)) {
+        "notebook" => "json",
+        "plaintext" | "unknwon" => "text",
+        lang => lang,
+    }
+}
+
+fn asciidoc_section(file: &FileEntry) -> String {
+    let mut section = format!("== `+{}+`\n\n", file.rel_path);
+    if file.content.trim().is_empty() {
+        return section;
+    }
+    // A delimiter line ends the block, so use one longer than any dash line in the file.
+    let longest = file
+        .content
+        .lines()
+        .filter(|line| !line.is_empty() && line.chars().all(|c| c == '-'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let delimiter = "-".repeat(longest.max(3) + 1);
+    section.push_str(&format!(
+        "[source,{}]\n{}\n{}\n{}\n\n",
+        lexer(&file.rel_path),
+        delimiter,
+        file.content.trim_end_matches('\n'),
+        delimiter
+    ));
+    section
+}
+
+fn rst_section(file: &FileEntry) -> String {
+    let title = format!("``{}``", file.rel_path);
+    let mut section = format!("{}\n{}\n\n", title, "=".repeat(title.chars().count()));
+    if file.content.trim().is_empty() {
+        return section;
+    }
+    section.push_str(&format!(".. code-block:: {}\n\n", lexer(&file.rel_path)));
+    for line in file.content.lines() {
+        if line.is_empty() {
+            section.push('\n');
+        } else {
+            section.push_str(&format!("   {}\n", line));
+        }
+    }
+    section.push('\n');
+    section
+}
+
+#[test]
+fn test_markup_sections() {
+    let file = FileEntry {
+        rel_path: "src/a_b.rs".to_string(),
+        content: "fn main() {}\n\n// ----\n----\n".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(
+        asciidoc_section(&file),
+        "== `+src/a_b.rs+`\n\n[source,rust]\n-----\nfn main() {}\n\n// ----\n----\n-----\n\n"
+    );
+    assert_eq!(
+        rst_section(&file),
+        "``src/a_b.rs``\n==============\n\n.. code-block:: rust\n\n   fn main() {}\n\n   // ----\n   ----\n\n"
+    );
+    let empty = FileEntry {
+        rel_path: "notes.txt".to_string(),
+        content: "\n".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(rst_section(&empty), "``notes.txt``\n=============\n\n");
+}
@@ -0,0 +1,197 @@
+//! Parts of a repo that are projects of their own: the member crates of a Cargo
+//! workspace and git submodules. Their files are grouped under a heading per member, and
+//! submodules are left out unless `--with-submodules` is given.
+
+use crate::patterns::normalize_rel_path;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemberKind {
+    Crate,
+    Submodule,
+}
+
+/// A member directory, relative to the input root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub kind: MemberKind,
+    pub path: String,
+}
+
+/// Workspace members from `Cargo.toml` and submodules from `.gitmodules` under `root`,
+/// sorted by path. A submodule that is also a workspace member counts as a submodule.
+pub fn find_members(root: &Path) -> Vec<Member> {
+    let mut members: Vec<Member> = submodule_paths(root)
+        .into_iter()
+        .map(|path| Member {
+            kind: MemberKind::Submodule,
+            path,
+        })
+        .collect();
+    for path in workspace_members(root) {
+        if !members.iter().any(|m| m.path == path) {
+            members.push(Member {
+                kind: MemberKind::Crate,
+                path,
+            });
+        }
+    }
+    members.sort_by(|a, b| a.path.cmp(&b.path));
+    members
+}
+
+/// Index of the innermost member whose directory holds `rel_path`.
+pub fn member_of(members: &[Member], rel_path: &str) -> Option<usize> {
+    members
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| {
+            rel_path
+                .strip_prefix(&m.path)
+                .is_some_and(|rest| rest.starts_with('/'))
+        })
+        .max_by_key(|(_, m)| m.path.len())
+        .map(|(i, _)| i)
+}
+
+/// Whether a submodule's directory has anything in it, i.e. it was checked out.
+pub fn is_checked_out(root: &Path, member: &Member) -> bool {
+    fs::read_dir(root.join(&member.path)).is_ok_and(|mut entries| entries.next().is_some())
+}
+
+/// `path = ...` of every `[submodule]` in `.gitmodules`.
+fn submodule_paths(root: &Path) -> Vec<String> {
+    let Ok(text) = fs::read_to_string(root.join(".gitmodules")) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "path").then(|| normalize_rel_path(value.trim().trim_matches('"')))
+        })
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+/// `[workspace] members` of `Cargo.toml`, with globs expanded to the directories that hold
+/// a `Cargo.toml`, minus `exclude`d ones and the root itself.
+fn workspace_members(root: &Path) -> Vec<String> {
+    let Ok(text) = fs::read_to_string(root.join("Cargo.toml")) else {
+        return Vec::new();
+    };
+    let excluded: Vec<String> = string_array(&text, "workspace", "exclude")
+        .iter()
+        .map(|path| normalize_rel_path(path))
+        .collect();
+    // Globbing `./crates/*` yields `crates/...`, so work from the absolute path.
+    let Ok(root) = root.canonicalize() else {
+        return Vec::new();
+    };
+    let base = glob::Pattern::escape(&root.to_string_lossy());
+    let mut members = Vec::new();
+    for pattern in string_array(&text, "workspace", "members") {
+        let Ok(paths) = glob::glob(&format!("{}/{}", base, pattern)) else {
+            continue;
+        };
+        for dir in paths.flatten() {
+            let Ok(rel) = dir.strip_prefix(&root) else {
+                continue;
+            };
+            let rel = normalize_rel_path(&rel.to_string_lossy());
+            if !rel.is_empty()
+                && dir.join("Cargo.toml").is_file()
+                && !excluded.contains(&rel)
+                && !members.contains(&rel)
+            {
+                members.push(rel);
+            }
+        }
+    }
+    members
+}
+
+/// The strings of `key = [...]` in `[table]` of a TOML file. That much TOML, spread over
+/// any number of lines and with comments, is all a workspace manifest needs.
+fn string_array(toml: &str, table: &str, key: &str) -> Vec<String> {
+    let header = format!("[{}]", table);
+    let mut in_table = false;
+    let mut lines = toml.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_table = line.split('#').next().unwrap_or_default().trim() == header;
+            continue;
+        }
+        let value = line
+            .strip_prefix(key)
+            .map(str::trim_start)
+            .and_then(|rest| rest.strip_prefix('='));
+        let Some(value) = value.filter(|_| in_table) else {
+            continue;
+        };
+        let mut strings = Vec::new();
+        let mut text = value;
+        while !scan_strings(text, &mut strings) {
+            match lines.next() {
+                Some(next) => text = next,
+                None => break,
+            }
+        }
+        return strings;
+    }
+    Vec::new()
+}
+
+/// Push the quoted strings on one line of an array to `strings`; true once the array's
+/// closing `]` is reached.
+fn scan_strings(line: &str, strings: &mut Vec<String>) -> bool {
+    let mut quote = None;
+    let mut current = String::new();
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => {
+                strings.push(std::mem::take(&mut current));
+                quote = None;
+            }
+            Some(_) => current.push(c),
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '#' => return false,
+                ']' => return true,
+                _ => {}
+            },
+        }
+    }
+    false
+}
+
+#[test]
+fn test_find_members() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    fs::write(
+        root.join("Cargo.toml"),
+        "[package]\nname = \"app\"\nmembers = [\"ignored\"]\n\n[workspace]\nmembers = [\n    \"crates/*\", # every crate\n    'tools/gen',\n]\nexclude = [\"crates/old\"]\n",
+    )?;
+    for krate in ["crates/core", "crates/old", "tools/gen"] {
+        fs::create_dir_all(root.join(krate))?;
+        fs::write(root.join(krate).join("Cargo.toml"), "[package]\n")?;
+    }
+    fs::create_dir_all(root.join("crates/notes"))?;
+    fs::write(
+        root.join(".gitmodules"),
+        "[submodule \"vendor/lib\"]\n\tpath = vendor/lib\n\turl = https://example.com/lib.git\n",
+    )?;
+
+    let members = find_members(root);
+    let paths: Vec<&str> = members.iter().map(|m| m.path.as_str()).collect();
+    assert_eq!(paths, ["crates/core", "tools/gen", "vendor/lib"]);
+    assert_eq!(members[2].kind, MemberKind::Submodule);
+    assert!(!is_checked_out(root, &members[2]));
+
+    assert_eq!(member_of(&members, "crates/core/src/lib.rs"), Some(0));
+    assert_eq!(member_of(&members, "crates/core-extra/lib.rs"), None);
+    assert_eq!(member_of(&members, "src/main.rs"), None);
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use crate::git::run_git;
+use crate::i18n::Labels;
+use crate::sanitize::inline_code;
+use crate::tree::input_name;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How `--metadata` renders the provenance header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetadataStyle {
+    /// `---` delimited YAML front matter, read by static site generators and most Markdown tooling.
+    Yaml,
+    /// An HTML comment, invisible when the Markdown is rendered.
+    Comment,
+}
+
+/// Provenance of a generated document.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub repo: String,
+    pub commit: Option<String>,
+    pub generated: String,
+    pub version: &'static str,
+    pub files: usize,
+    pub tokens: usize,
+}
+
+impl Metadata {
+    /// Describe an export of `inputs`. The commit is taken from the first local git checkout.
+    pub fn new(inputs: &[PathBuf], files: usize, tokens: usize) -> Self {
+        let repo = inputs
+            .iter()
+            .map(|p| input_name(p))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let commit = inputs
+            .iter()
+            .filter(|p| p.is_dir())
+            .find_map(|p| run_git(p, &["rev-parse", "HEAD"]).ok())
+            .map(|sha| sha.trim().to_string());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Metadata {
+            repo,
+            commit,
+            generated: format_utc(now),
+            version: env!("CARGO_PKG_VERSION"),
+            files,
+            tokens,
+        }
+    }
+
+    /// Render the header block, ending in a blank line.
+    pub fn render(&self, style: MetadataStyle) -> String {
+        // JSON strings are valid YAML scalars, and keep odd repo names from breaking the block.
+        let quote = |s: &str| serde_json::to_string(s).unwrap_or_default();
+        let mut fields = vec![format!("repo: {}", quote(&self.repo))];
+        if let Some(commit) = &self.commit {
+            fields.push(format!("commit: {}", quote(commit)));
+        }
+        fields.push(format!("generated: {}", quote(&self.generated)));
+        fields.push(format!("r2md_version: {}", quote(self.version)));
+        fields.push(format!("files: {}", self.files));
+        fields.push(format!("tokens: {}", self.tokens));
+        let body = fields.join("\n");
+        match style {
+            MetadataStyle::Yaml => format!("---\n{}\n---\n\n", body),
+            MetadataStyle::Comment => {
+                format!("<!-- r2md\n{}\n-->\n\n", body.replace("--", "-\\u002d"))
+            }
+        }
+    }
+}
+
+/// Closing line naming the r2md version and the command line that produced the document,
+/// so a reader can regenerate it.
+pub fn footer(labels: &Labels) -> String {
+    let command: Vec<String> = std::iter::once("r2md".to_string())
+        .chain(std::env::args().skip(1).map(|arg| shell_quote(&arg)))
+        .collect();
+    let text = labels
+        .footer
+        .replace("{version}", env!("CARGO_PKG_VERSION"))
+        .replace("{command}", &inline_code(&command.join(" ")));
+    format!("\n---\n\n{}\n", text)
+}
+
+/// Quote `arg` for a POSIX shell, leaving plain words alone.
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,@+%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// RFC 3339 UTC timestamp for seconds since the Unix epoch.
+fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil-from-days (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[test]
+fn test_metadata_rendering() {
+    assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_utc(1_709_251_199), "2024-02-29T23:59:59Z");
+    let meta = Metadata {
+        repo: "r2md".to_string(),
+        commit: None,
+        generated: format_utc(0),
+        version: "0.1.0",
+        files: 3,
+        tokens: 120,
+    };
+    assert_eq!(
+        meta.render(MetadataStyle::Yaml),
+        "---\nrepo: \"r2md\"\ngenerated: \"1970-01-01T00:00:00Z\"\nr2md_version: \"0.1.0\"\nfiles: 3\ntokens: 120\n---\n\n"
+    );
+    assert!(meta
+        .render(MetadataStyle::Comment)
+        .starts_with("<!-- r2md\n"));
+    assert_eq!(shell_quote("--split-tokens"), "--split-tokens");
+    assert_eq!(shell_quote("payment retries"), "'payment retries'");
+    assert_eq!(shell_quote("it's"), "'it'\\''s'");
+}
@@ -0,0 +1,583 @@
+//! `--minify`: drop comments, docstrings, blank lines and (where it carries no meaning)
+//! indentation, to fit more code into a token budget.
+//!
+//! Like `chunks`, this is a lexer-level heuristic rather than a parser: it knows each
+//! language's comment and string syntax, so comment markers inside strings survive and
+//! multi-line strings are kept verbatim.
+
+/// Comment and string syntax of one language family.
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Rust block comments nest
+    nested_blocks: bool,
+    quotes: &'static str,
+    /// `"""` strings (Python, Kotlin, Swift, ...)
+    triple_quotes: bool,
+    /// Quotes whose strings have no backslash escapes (Go's backtick)
+    raw_quotes: &'static str,
+    /// Line comments only count when they start the line (YAML, Dockerfile)
+    whole_line_comments: bool,
+    /// Leading whitespace carries no meaning, so it can go
+    strip_indent: bool,
+    /// `/.../` regex literals (JavaScript, TypeScript)
+    regex_literals: bool,
+}
+
+const C_LIKE: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_blocks: false,
+    quotes: "\"'",
+    triple_quotes: false,
+    raw_quotes: "",
+    whole_line_comments: false,
+    strip_indent: true,
+    regex_literals: false,
+};
+
+const HASH: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comment: None,
+    nested_blocks: false,
+    quotes: "\"'",
+    triple_quotes: false,
+    raw_quotes: "",
+    whole_line_comments: false,
+    strip_indent: false,
+    regex_literals: false,
+};
+
+fn syntax_for(lang: &str) -> Option<Syntax> {
+    Some(match lang {
+        "rust" => Syntax {
+            nested_blocks: true,
+            // `'` is handled separately: lifetimes look like unclosed char literals.
+            quotes: "\"",
+            ..C_LIKE
+        },
+        "javascript" | "typescript" | "jsx" | "tsx" => Syntax {
+            quotes: "\"'`",
+            regex_literals: true,
+            ..C_LIKE
+        },
+        "go" => Syntax {
+            quotes: "\"'`",
+            raw_quotes: "`",
+            ..C_LIKE
+        },
+        "java" | "csharp" | "kotlin" | "scala" | "swift" => Syntax {
+            triple_quotes: true,
+            ..C_LIKE
+        },
+        "c" | "cpp" | "objectivec" | "php" | "protobuf" => C_LIKE,
+        "sql" => Syntax {
+            line_comments: &["--"],
+            ..C_LIKE
+        },
+        "json" => Syntax {
+            line_comments: &[],
+            block_comment: None,
+            quotes: "\"",
+            ..C_LIKE
+        },
+        "python" => Syntax {
+            triple_quotes: true,
+            ..HASH
+        },
+        "ruby" | "bash" => HASH,
+        "toml" => Syntax {
+            triple_quotes: true,
+            ..HASH
+        },
+        "hcl" => Syntax {
+            line_comments: &["#", "//"],
+            block_comment: Some(("/*", "*/")),
+            quotes: "\"",
+            ..HASH
+        },
+        "yaml" | "dockerfile" => Syntax {
+            quotes: "",
+            whole_line_comments: true,
+            ..HASH
+        },
+        _ => return None,
+    })
+}
+
+/// Lexer state between characters.
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Code,
+    /// Inside a string opened by this quote; `triple` for `"""`/`'''`
+    Str {
+        quote: char,
+        triple: bool,
+    },
+    /// Inside a raw string closed by `"` and this many `#` (Rust `r#"..."#`)
+    RawStr {
+        hashes: usize,
+    },
+    /// Inside a block comment, at this nesting depth
+    Block {
+        depth: usize,
+    },
+    /// Inside a docstring that is being dropped
+    Docstring {
+        quote: char,
+    },
+}
+
+/// `content` without comments, docstrings, blank lines and insignificant indentation.
+/// `None` for languages it doesn't know, and when the content ends inside a block
+/// comment or string (the lexer misread it); those are exported unchanged.
+pub fn minify(content: &str, lang: &str) -> Option<String> {
+    let syntax = syntax_for(lang)?;
+    let chars: Vec<char> = content.chars().collect();
+    let at = |i: usize, s: &str| {
+        s.chars()
+            .enumerate()
+            .all(|(k, c)| chars.get(i + k) == Some(&c))
+    };
+
+    let mut out = String::with_capacity(content.len());
+    let mut line = String::new();
+    let mut state = State::Code;
+    // Whether the current line began inside a string, so its indentation is content.
+    let mut line_in_string = false;
+    // Python: the next statement may be a docstring (start of file, or after a `:` line).
+    let mut expect_docstring = lang == "python";
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            let in_string = matches!(state, State::Str { .. } | State::RawStr { .. });
+            let mut text = line.as_str();
+            if !in_string {
+                text = text.trim_end();
+            }
+            if !line_in_string && syntax.strip_indent {
+                text = text.trim_start();
+            }
+            if !text.is_empty() || line_in_string || in_string {
+                out.push_str(text);
+                out.push('\n');
+            }
+            if lang == "python" && state == State::Code && !text.is_empty() {
+                expect_docstring = depth == 0 && text.ends_with(':');
+            }
+            line.clear();
+            line_in_string = in_string;
+            i += 1;
+            continue;
+        }
+        match state {
+            State::Code => {
+                if let Some(q) = syntax.quotes.chars().find(|&q| q == c) {
+                    let triple = syntax.triple_quotes && at(i, &q.to_string().repeat(3));
+                    let prefix = line.trim_start();
+                    if triple
+                        && expect_docstring
+                        && prefix.chars().all(|p| "rRuUbB".contains(p))
+                        && prefix.len() <= 2
+                    {
+                        line.truncate(line.len() - prefix.len());
+                        state = State::Docstring { quote: q };
+                        i += 3;
+                        continue;
+                    }
+                    state = State::Str { quote: q, triple };
+                    let len = if triple { 3 } else { 1 };
+                    line.extend(&chars[i..i + len]);
+                    i += len;
+                    continue;
+                }
+                if lang == "rust" {
+                    if let Some(len) = rust_char_literal(&chars[i..]) {
+                        line.extend(&chars[i..i + len]);
+                        i += len;
+                        continue;
+                    }
+                    if let Some(hashes) = rust_raw_string_start(&chars, i) {
+                        let len = hashes + 2;
+                        line.extend(&chars[i..i + len]);
+                        state = State::RawStr { hashes };
+                        i += len;
+                        continue;
+                    }
+                }
+                if let Some(len) = syntax
+                    .regex_literals
+                    .then(|| regex_literal(&chars, i, &line))
+                    .flatten()
+                {
+                    line.extend(&chars[i..i + len]);
+                    i += len;
+                    continue;
+                }
+                if let Some((open, _)) = syntax.block_comment.filter(|(open, _)| at(i, open)) {
+                    state = State::Block { depth: 1 };
+                    i += open.len();
+                    continue;
+                }
+                let line_comment = syntax.line_comments.iter().any(|m| {
+                    at(i, m)
+                        && if syntax.whole_line_comments {
+                            line.trim().is_empty()
+                        } else if *m == "#" {
+                            // `$#`, `${#x}`, `a#b`: only a comment after whitespace.
+                            line.chars().last().is_none_or(char::is_whitespace)
+                                && !(i == 0 && at(i, "#!"))
+                        } else {
+                            true
+                        }
+                });
+                if line_comment {
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                    continue;
+                }
+                match c {
+                    '(' | '[' | '{' => depth += 1,
+                    ')' | ']' | '}' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                line.push(c);
+                i += 1;
+            }
+            State::Str { quote, triple } => {
+                if c == '\\' && !syntax.raw_quotes.contains(quote) {
+                    line.extend(chars.get(i..(i + 2).min(chars.len())).unwrap_or(&[]));
+                    i += 2;
+                    continue;
+                }
+                let close = if triple {
+                    quote.to_string().repeat(3)
+                } else {
+                    quote.to_string()
+                };
+                if at(i, &close) {
+                    line.push_str(&close);
+                    i += close.chars().count();
+                    state = State::Code;
+                    continue;
+                }
+                line.push(c);
+                i += 1;
+            }
+            State::RawStr { hashes } => {
+                if c == '"'
+                    && chars[i + 1..]
+                        .iter()
+                        .take(hashes)
+                        .filter(|&&h| h == '#')
+                        .count()
+                        == hashes
+                {
+                    line.extend(&chars[i..i + 1 + hashes]);
+                    i += 1 + hashes;
+                    state = State::Code;
+                    continue;
+                }
+                line.push(c);
+                i += 1;
+            }
+            State::Block { depth: nesting } => {
+                let (open, close) = syntax.block_comment.unwrap_or(("/*", "*/"));
+                if at(i, close) {
+                    i += close.len();
+                    state = if nesting > 1 {
+                        State::Block { depth: nesting - 1 }
+                    } else {
+                        // `a/**/b` must not become `ab`.
+                        if !line.ends_with(char::is_whitespace) && !line.is_empty() {
+                            line.push(' ');
+                        }
+                        State::Code
+                    };
+                } else if syntax.nested_blocks && at(i, open) {
+                    i += open.len();
+                    state = State::Block { depth: nesting + 1 };
+                } else {
+                    i += 1;
+                }
+            }
+            State::Docstring { quote } => {
+                if c == '\\' {
+                    i += 2;
+                } else if at(i, &quote.to_string().repeat(3)) {
+                    i += 3;
+                    state = State::Code;
+                } else {
+                    i += 1;
+                }
+            }
+        }
+    }
+    if state != State::Code {
+        return None;
+    }
+    if !line.trim().is_empty() || line_in_string {
+        let text = if syntax.strip_indent && !line_in_string {
+            line.trim()
+        } else {
+            line.trim_end()
+        };
+        out.push_str(text);
+        out.push('\n');
+    }
+    Some(out)
+}
+
+/// What a stretch of source text is, as far as the lexer can tell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Span {
+    Code,
+    /// A string or char literal, quotes included
+    Str,
+    /// A comment, markers included; a line comment stops before its newline
+    Comment,
+}
+
+/// `content` cut into code, literal and comment stretches, in order, which join back to
+/// `content`. Uses the same syntax tables as `minify`; `None` for other languages.
+pub fn spans<'a>(content: &'a str, lang: &str) -> Option<Vec<(Span, &'a str)>> {
+    let syntax = syntax_for(lang)?;
+    let chars: Vec<char> = content.chars().collect();
+    let offsets: Vec<usize> = content
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain([content.len()])
+        .collect();
+    let at = |i: usize, s: &str| {
+        s.chars()
+            .enumerate()
+            .all(|(k, c)| chars.get(i + k) == Some(&c))
+    };
+    // First index at or after `from` where `close` starts, or the end.
+    let find = |from: usize, close: &str| (from..chars.len()).find(|&j| at(j, close));
+
+    let mut spans = Vec::new();
+    let mut push = |kind: Span, from: usize, to: usize| {
+        if to > from {
+            spans.push((kind, &content[offsets[from]..offsets[to]]));
+        }
+    };
+    let mut start = 0;
+    let mut line_start = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let literal = if let Some(q) = syntax.quotes.chars().find(|&q| q == c) {
+            let triple = syntax.triple_quotes && at(i, &q.to_string().repeat(3));
+            let close = if triple {
+                q.to_string().repeat(3)
+            } else {
+                q.to_string()
+            };
+            let mut j = i + close.len();
+            while j < chars.len() && !at(j, &close) {
+                // An unclosed one-line string ends at the newline.
+                if chars[j] == '\n' && !triple && !syntax.raw_quotes.contains(q) {
+                    break;
+                }
+                j += if chars[j] == '\\' && !syntax.raw_quotes.contains(q) {
+                    2
+                } else {
+                    1
+                };
+            }
+            let end = if at(j, &close) { j + close.len() } else { j };
+            Some((Span::Str, end.min(chars.len())))
+        } else if let Some(len) = (lang == "rust")
+            .then(|| rust_char_literal(&chars[i..]))
+            .flatten()
+        {
+            Some((Span::Str, i + len))
+        } else if let Some(hashes) = (lang == "rust")
+            .then(|| rust_raw_string_start(&chars, i))
+            .flatten()
+        {
+            let close = format!("\"{}", "#".repeat(hashes));
+            let end = find(i + hashes + 2, &close).map_or(chars.len(), |j| j + close.len());
+            Some((Span::Str, end))
+        } else if let Some(len) = syntax
+            .regex_literals
+            .then(|| regex_literal(&chars, i, &chars[line_start..i].iter().collect::<String>()))
+            .flatten()
+        {
+            Some((Span::Str, i + len))
+        } else if let Some((open, close)) = syntax.block_comment.filter(|(open, _)| at(i, open)) {
+            let mut depth = 1;
+            let mut j = i + open.len();
+            while j < chars.len() && depth > 0 {
+                if at(j, close) {
+                    depth -= 1;
+                    j += close.len();
+                } else if syntax.nested_blocks && at(j, open) {
+                    depth += 1;
+                    j += open.len();
+                } else {
+                    j += 1;
+                }
+            }
+            Some((Span::Comment, j))
+        } else if syntax.line_comments.iter().any(|m| {
+            at(i, m)
+                && if syntax.whole_line_comments {
+                    chars[line_start..i].iter().all(|p| p.is_whitespace())
+                } else if *m == "#" {
+                    (i == line_start || chars[i - 1].is_whitespace()) && !(i == 0 && at(i, "#!"))
+                } else {
+                    true
+                }
+        }) {
+            Some((Span::Comment, find(i, "\n").unwrap_or(chars.len())))
+        } else {
+            None
+        };
+        match literal {
+            Some((kind, end)) => {
+                push(Span::Code, start, i);
+                push(kind, i, end);
+                if let Some(newline) = chars[i..end].iter().rposition(|&p| p == '\n') {
+                    line_start = i + newline + 1;
+                }
+                start = end;
+                i = end;
+            }
+            None => {
+                if c == '\n' {
+                    line_start = i + 1;
+                }
+                i += 1;
+            }
+        }
+    }
+    push(Span::Code, start, chars.len());
+    Some(spans)
+}
+
+/// Length of a JavaScript regex literal at `i` (`/a\/*b/`, flags not included), when
+/// `before`, the code ahead of it on its line, leaves it where an expression starts.
+/// `None` for division, comments and a `/` not closed on the same line.
+fn regex_literal(chars: &[char], i: usize, before: &str) -> Option<usize> {
+    if chars[i] != '/' || matches!(chars.get(i + 1), None | Some('/' | '*')) {
+        return None;
+    }
+    let before = before.trim_end();
+    let expression = before.is_empty()
+        || before.ends_with(|c| "(=,:[!&|?{};".contains(c))
+        || before.strip_suffix("return").is_some_and(|rest| {
+            !rest.ends_with(|c: char| c.is_alphanumeric() || c == '_' || c == '$')
+        });
+    if !expression {
+        return None;
+    }
+    let mut in_class = false;
+    let mut j = i + 1;
+    while let Some(&c) = chars.get(j) {
+        match c {
+            '\n' => return None,
+            '\\' => j += 1,
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '/' if !in_class => return Some(j + 1 - i),
+            _ => {}
+        }
+        j += 1;
+    }
+    None
+}
+
+/// Length of a Rust char literal at the start of `chars` (`'a'`, `'\n'`, `'\u{1F600}'`);
+/// `None` for a lifetime or anything else.
+fn rust_char_literal(chars: &[char]) -> Option<usize> {
+    if chars.first() != Some(&'\'') {
+        return None;
+    }
+    let len = match chars.get(1)? {
+        '\\' => chars.iter().skip(2).take(10).position(|&c| c == '\'')? + 3,
+        '\'' | '\n' => return None,
+        _ => 3,
+    };
+    (chars.get(len - 1) == Some(&'\'')).then_some(len)
+}
+
+/// Number of `#` in a Rust raw string opener (`r"`, `r#"`, `br##"`) at `i`.
+fn rust_raw_string_start(chars: &[char], i: usize) -> Option<usize> {
+    if chars[i] != 'r'
+        || (i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_') && chars[i - 1] != 'b')
+    {
+        return None;
+    }
+    let hashes = chars[i + 1..].iter().take_while(|&&c| c == '#').count();
+    (chars.get(i + 1 + hashes) == Some(&'"')).then_some(hashes)
+}
+
+#[test]
+fn test_minify_keeps_strings_and_drops_comments() {
+    let rust = "/// Adds.\nfn add(a: i32) -> i32 {\n    // note\n    let s = \"// not a comment\"; /* gone */\n    let l: &'static str = r#\"a /* b\"#;\n\n    a + '{' as i32\n}\n";
+    assert_eq!(
+        minify(rust, "rust").unwrap(),
+        "fn add(a: i32) -> i32 {\nlet s = \"// not a comment\";\nlet l: &'static str = r#\"a /* b\"#;\na + '{' as i32\n}\n"
+    );
+
+    let python = "\"\"\"Module doc.\"\"\"\n\ndef f(x):\n    \"\"\"Doc\n    more.\"\"\"\n    s = \"\"\"keep\n\n  this\"\"\"  # why\n    return x  # done\n";
+    assert_eq!(
+        minify(python, "python").unwrap(),
+        "def f(x):\n    s = \"\"\"keep\n\n  this\"\"\"\n    return x\n"
+    );
+
+    assert_eq!(
+        minify("echo $# ${#a} # count\n", "bash").unwrap(),
+        "echo $# ${#a}\n"
+    );
+    assert!(minify("# Title", "markdown").is_none());
+}
+
+#[test]
+fn test_minify_regex_literals() {
+    let js = "const re = /a\\/*b/; // re\nconst t = re.test(s) ? 1 / 2 : /[/*]/g; /* c */\nreturn /x\\//;\n";
+    assert_eq!(
+        minify(js, "javascript").unwrap(),
+        "const re = /a\\/*b/;\nconst t = re.test(s) ? 1 / 2 : /[/*]/g;\nreturn /x\\//;\n"
+    );
+    let cut = spans("f(/\\/*/) /* c */", "typescript").unwrap();
+    assert_eq!(cut[1], (Span::Str, "/\\/*/"));
+
+    // Unterminated block comments and strings keep the file as it is.
+    assert!(minify("int a; /* open\nint b;\n", "c").is_none());
+    assert!(minify("s = \"\"\"open\nx = 1\n", "python").is_none());
+}
+
+#[test]
+fn test_spans() {
+    let rust = "let s = \"a // b\"; // note\nlet c = '\"'; /* x /* y */ */ f(r#\"q\"#)";
+    let cut = spans(rust, "rust").unwrap();
+    assert_eq!(cut.iter().map(|(_, text)| *text).collect::<String>(), rust);
+    let kinds: Vec<(Span, &str)> = cut.into_iter().filter(|(k, _)| *k != Span::Code).collect();
+    assert_eq!(
+        kinds,
+        [
+            (Span::Str, "\"a // b\""),
+            (Span::Comment, "// note"),
+            (Span::Str, "'\"'"),
+            (Span::Comment, "/* x /* y */ */"),
+            (Span::Str, "r#\"q\"#"),
+        ]
+    );
+    let python = "x = '''it's''' # c\n";
+    assert_eq!(
+        spans(python, "python").unwrap(),
+        [
+            (Span::Code, "x = "),
+            (Span::Str, "'''it's'''"),
+            (Span::Code, " "),
+            (Span::Comment, "# c"),
+            (Span::Code, "\n"),
+        ]
+    );
+}
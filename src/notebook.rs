@@ -0,0 +1,138 @@
+//! Jupyter notebooks (`.ipynb`): exported as their cells in order, Markdown cells as
+//! Markdown and code cells as fenced blocks. Outputs are dropped, so rendered plots and
+//! base64 images don't end up in the export.
+
+use crate::sanitize::{fence_for, FenceStyle};
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::LazyLock;
+
+#[derive(Deserialize)]
+struct Notebook {
+    #[serde(default)]
+    cells: Vec<Cell>,
+    /// nbformat 3 keeps cells in worksheets
+    #[serde(default)]
+    worksheets: Vec<Worksheet>,
+    #[serde(default)]
+    metadata: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct Worksheet {
+    #[serde(default)]
+    cells: Vec<Cell>,
+}
+
+#[derive(Deserialize)]
+struct Cell {
+    cell_type: String,
+    /// nbformat 4
+    #[serde(default)]
+    source: Source,
+    /// nbformat 3 code cells
+    #[serde(default)]
+    input: Source,
+}
+
+/// Cell text, stored either as one string or as a list of lines.
+#[derive(Deserialize, Default)]
+#[serde(untagged)]
+enum Source {
+    #[default]
+    Empty,
+    Text(String),
+    Lines(Vec<String>),
+}
+
+impl Source {
+    fn text(&self) -> String {
+        match self {
+            Source::Empty => String::new(),
+            Source::Text(text) => text.clone(),
+            Source::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+/// Inline images in Markdown cells (`![plot](data:image/png;base64,...)`).
+static DATA_IMAGE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!\[([^\]]*)\]\((?:data:|attachment:)[^)]*\)").unwrap());
+
+pub fn is_notebook(rel_path: &str) -> bool {
+    rel_path.to_lowercase().ends_with(".ipynb")
+}
+
+/// The text to export for a file just read: notebooks become their cells, anything else
+/// passes through.
+pub fn convert(
+    rel_path: &str,
+    content: String,
+    fence_style: FenceStyle,
+) -> Result<String, &'static str> {
+    if is_notebook(rel_path) {
+        render_cells(&content, fence_style)
+    } else {
+        Ok(content)
+    }
+}
+
+fn render_cells(json: &str, fence_style: FenceStyle) -> Result<String, &'static str> {
+    let notebook: Notebook = serde_json::from_str(json).map_err(|_| "invalid notebook JSON")?;
+    let lang = notebook
+        .metadata
+        .pointer("/kernelspec/language")
+        .or_else(|| notebook.metadata.pointer("/language_info/name"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("python")
+        .to_lowercase();
+    let cells = notebook
+        .cells
+        .iter()
+        .chain(notebook.worksheets.iter().flat_map(|w| &w.cells));
+
+    let mut out = Vec::new();
+    for cell in cells {
+        let source = match &cell.source {
+            Source::Empty => cell.input.text(),
+            source => source.text(),
+        };
+        let source = source.trim_end();
+        if source.trim().is_empty() {
+            continue;
+        }
+        out.push(match cell.cell_type.as_str() {
+            "markdown" => DATA_IMAGE_RE
+                .replace_all(source, "[image: $1]")
+                .into_owned(),
+            kind => {
+                let fence = fence_for(source, fence_style);
+                let tag = if kind == "code" { lang.as_str() } else { "" };
+                format!("{}{}\n{}\n{}", fence, tag, source, fence)
+            }
+        });
+    }
+    Ok(out.join("\n\n"))
+}
+
+#[test]
+fn test_notebook_cells() {
+    let json = r##"{
+        "metadata": {"kernelspec": {"language": "python"}},
+        "cells": [
+            {"cell_type": "markdown", "source": ["# Load\n", "![plot](data:image/png;base64,iVBOR)"]},
+            {"cell_type": "code", "source": "df = load()\ndf.head()", "outputs": [{"data": {"image/png": "iVBOR"}}]},
+            {"cell_type": "code", "source": []}
+        ],
+        "nbformat": 4
+    }"##;
+    assert_eq!(
+        convert("analysis.ipynb", json.to_string(), FenceStyle::Backtick).unwrap(),
+        "# Load\n[image: plot]\n\n```python\ndf = load()\ndf.head()\n```"
+    );
+    assert_eq!(
+        convert("a.py", "x".to_string(), FenceStyle::Backtick).unwrap(),
+        "x"
+    );
+    assert!(convert("a.ipynb", "{".to_string(), FenceStyle::Backtick).is_err());
+}
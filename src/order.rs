@@ -0,0 +1,72 @@
+//! `--sort`: the order files appear in the code sections.
+
+use crate::deps::sort_files_by_dependency;
+use crate::types::FileEntry;
+use std::cmp::Reverse;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    /// By path; the same on every platform
+    Alpha,
+    /// Largest first
+    Size,
+    /// Dependencies before the files that use them
+    Deps,
+    /// Most recently modified first
+    Mtime,
+}
+
+/// Reorder one input's files, which arrive sorted by path. Ties keep path order, so
+/// the result is stable between runs.
+pub fn sort_files(files: &mut Vec<FileEntry>, order: SortOrder, root: &Path) {
+    match order {
+        SortOrder::Alpha => {}
+        SortOrder::Size => files.sort_by_key(|f| Reverse(f.content.len())),
+        SortOrder::Deps => match sort_files_by_dependency(files) {
+            Ok(mut sorted) => {
+                sorted.reverse();
+                *files = sorted;
+            }
+            Err(e) => warn!("--sort deps: {}; keeping path order", e),
+        },
+        SortOrder::Mtime => {
+            // Archive and remote inputs have no files on disk; they keep path order.
+            files.sort_by_cached_key(|f| {
+                Reverse(
+                    fs::metadata(root.join(&f.rel_path))
+                        .and_then(|md| md.modified())
+                        .unwrap_or(SystemTime::UNIX_EPOCH),
+                )
+            })
+        }
+    }
+}
+
+#[test]
+fn test_sort_orders() {
+    let file = |rel_path: &str, content: &str| FileEntry {
+        rel_path: rel_path.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    };
+    let files = vec![
+        file("a.rs", "use b;\nfn a() {}"),
+        file("b.rs", "fn b() {}"),
+        file("c.rs", "fn c() { /* the longest one */ }"),
+    ];
+    let paths = |order| {
+        let mut sorted = files.clone();
+        sort_files(&mut sorted, order, Path::new("/no/such/root"));
+        sorted.into_iter().map(|f| f.rel_path).collect::<Vec<_>>()
+    };
+    assert_eq!(paths(SortOrder::Alpha), ["a.rs", "b.rs", "c.rs"]);
+    assert_eq!(paths(SortOrder::Size), ["c.rs", "a.rs", "b.rs"]);
+    assert_eq!(paths(SortOrder::Mtime), ["a.rs", "b.rs", "c.rs"]);
+    let deps = paths(SortOrder::Deps);
+    let pos = |p: &str| deps.iter().position(|d| d == p).unwrap();
+    assert!(pos("b.rs") < pos("a.rs"));
+}
@@ -0,0 +1,129 @@
+//! `--summaries`: a short overview of each top-level directory, ahead of the code, so
+//! readers can find their way around a large export.
+
+use crate::chunks::public_symbols;
+use crate::i18n::Labels;
+use crate::language_from_path;
+use crate::sanitize::inline_code;
+use crate::tokens::estimate_tokens;
+use crate::types::FileEntry;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Largest files listed per directory.
+const MAX_LARGEST: usize = 3;
+/// Public symbols listed per directory.
+const MAX_SYMBOLS: usize = 15;
+
+/// The overview section for `inputs`, each given with the prefix its paths carry (empty
+/// for a single input). Files directly in an input's root are grouped under `./`, or
+/// under the prefix.
+pub fn render_overview(inputs: &[(&str, &[FileEntry])], labels: &Labels) -> String {
+    let mut out = format!("## {}\n\n", labels.summaries);
+    for (prefix, files) in inputs {
+        let mut dirs: BTreeMap<&str, Vec<&FileEntry>> = BTreeMap::new();
+        for file in files.iter() {
+            let rel_path = file
+                .rel_path
+                .strip_prefix(*prefix)
+                .unwrap_or(&file.rel_path);
+            let dir = match rel_path.split_once('/') {
+                Some((dir, _)) => dir,
+                None => ".",
+            };
+            dirs.entry(dir).or_default().push(file);
+        }
+        for (dir, files) in dirs {
+            let name = if dir == "." && !prefix.is_empty() {
+                prefix.to_string()
+            } else {
+                format!("{}{}/", prefix, dir)
+            };
+            out.push_str(&render_directory(&name, &files, labels));
+        }
+    }
+    out
+}
+
+fn render_directory(name: &str, files: &[&FileEntry], labels: &Labels) -> String {
+    let tokens: usize = files.iter().map(|f| estimate_tokens(&f.content)).sum();
+    let mut languages: BTreeMap<&str, usize> = BTreeMap::new();
+    for file in files {
+        *languages
+            .entry(language_from_path(Path::new(&file.rel_path)))
+            .or_default() += 1;
+    }
+    let mut languages: Vec<(&str, usize)> = languages.into_iter().collect();
+    languages.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+    let languages: Vec<String> = languages
+        .iter()
+        .map(|(lang, n)| format!("{} ({})", lang, n))
+        .collect();
+
+    let mut by_size: Vec<&&FileEntry> = files.iter().collect();
+    by_size.sort_by_key(|f| std::cmp::Reverse(f.content.len()));
+    let largest: Vec<String> = by_size
+        .iter()
+        .take(MAX_LARGEST)
+        .map(|f| {
+            format!(
+                "{} ({} KB)",
+                inline_code(&f.rel_path),
+                f.content.len().div_ceil(1024)
+            )
+        })
+        .collect();
+
+    let symbols: Vec<String> = files
+        .iter()
+        .flat_map(|f| public_symbols(&f.content, language_from_path(Path::new(&f.rel_path))))
+        .collect();
+    let mut listed: Vec<String> = symbols
+        .iter()
+        .take(MAX_SYMBOLS)
+        .map(|s| inline_code(s))
+        .collect();
+    if symbols.len() > MAX_SYMBOLS {
+        listed.push(format!("+{}", symbols.len() - MAX_SYMBOLS));
+    }
+
+    let mut out = format!("### {}\n\n", inline_code(name));
+    out.push_str(&format!(
+        "- {}\n",
+        labels
+            .summary_stats
+            .replace("{files}", &files.len().to_string())
+            .replace("{tokens}", &tokens.to_string())
+    ));
+    out.push_str(&format!(
+        "- {}: {}\n",
+        labels.languages,
+        languages.join(", ")
+    ));
+    out.push_str(&format!("- {}: {}\n", labels.largest, largest.join(", ")));
+    if !listed.is_empty() {
+        out.push_str(&format!("- {}: {}\n", labels.symbols, listed.join(", ")));
+    }
+    out.push('\n');
+    out
+}
+
+#[test]
+fn test_overview_groups_top_level_dirs() {
+    let file = |rel_path: &str, content: &str| FileEntry {
+        rel_path: rel_path.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    };
+    let files = [
+        file("build.sh", "echo hi"),
+        file("src/lib.rs", "pub fn parse() {\n}\n\nfn helper() {\n}\n"),
+        file("src/util/io.rs", "pub struct Reader {\n}\n"),
+    ];
+    let overview = render_overview(&[("", &files[..])], &Labels::default());
+    assert!(overview.contains("### `./`\n\n- 1 files"));
+    assert!(overview.contains("### `src/`\n\n- 2 files"));
+    assert!(overview.contains("- Languages: rust (2)\n"));
+    assert!(overview.contains("`parse`, `Reader`"));
+    assert!(!overview.contains("helper"));
+}
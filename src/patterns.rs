@@ -0,0 +1,301 @@
+//! gitignore-style path patterns for `--exclude` and `ignore_patterns` in `r2md.yml`, and
+//! `PathFilter`, which applies `--include` and `--exclude` the same way to every kind of
+//! input.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Patterns in gitignore syntax, matched against paths relative to a walk root: `*` and
+/// `**` globs, a leading `/` to anchor at the root, a trailing `/` for directories only,
+/// and `!` to re-include. The last matching pattern wins.
+#[derive(Debug, Clone)]
+pub struct PathPatterns {
+    matcher: Gitignore,
+}
+
+impl Default for PathPatterns {
+    fn default() -> Self {
+        PathPatterns {
+            matcher: Gitignore::empty(),
+        }
+    }
+}
+
+impl PathPatterns {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, Box<dyn Error>> {
+        let mut builder = GitignoreBuilder::new("");
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            builder
+                .add_line(None, pattern)
+                .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+        }
+        Ok(PathPatterns {
+            matcher: builder.build()?,
+        })
+    }
+
+    /// Whether `rel_path` or one of its parent directories matches, and wasn't re-included.
+    pub fn matches(&self, rel_path: &str, is_dir: bool) -> bool {
+        !self.matcher.is_empty()
+            && self
+                .matcher
+                .matched_path_or_any_parents(rel_path, is_dir)
+                .is_ignore()
+    }
+}
+
+/// 1-based first and last line, inclusive.
+pub type LineRange = (usize, usize);
+
+/// `--include` globs and `--exclude` patterns, resolved for one input. Every check takes
+/// a path relative to the input root; directory walks, file lists and archive entries all
+/// normalize it the same way first (see `normalize_rel_path`).
+///
+/// An include can end in a line range, `src/big.rs:100-400`, to export only those lines
+/// of the files it matches.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    includes: Vec<glob::Pattern>,
+    /// Line range of each include, if it has one
+    include_lines: Vec<Option<LineRange>>,
+    /// The literal leading directories of each include (`build/` for `build/**/*.rs`)
+    include_dirs: Vec<String>,
+    excludes: PathPatterns,
+}
+
+impl PathFilter {
+    /// `root` is the input directory on disk, where `--exclude` values naming existing
+    /// paths are resolved; `None` for archives, whose entries have no path to resolve.
+    pub fn new(
+        root: Option<&Path>,
+        includes: &[String],
+        excludes: &[PathBuf],
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut patterns = Vec::new();
+        let mut include_lines = Vec::new();
+        let mut include_dirs = Vec::new();
+        for include in includes {
+            let (include, lines) = split_line_range(include)?;
+            include_lines.push(lines);
+            let include = normalize_rel_path(include);
+            patterns.push(
+                glob::Pattern::new(&include)
+                    .map_err(|e| format!("--include: invalid pattern '{}': {}", include, e))?,
+            );
+            let literal: String = include
+                .chars()
+                .take_while(|c| !matches!(c, '*' | '?' | '[' | '{'))
+                .collect();
+            if let Some((dirs, _)) = literal.rsplit_once('/') {
+                include_dirs.push(format!("{}/", dirs));
+            }
+        }
+        Ok(PathFilter {
+            includes: patterns,
+            include_lines,
+            include_dirs,
+            excludes: resolve_excludes(root, excludes)?,
+        })
+    }
+
+    pub fn has_includes(&self) -> bool {
+        !self.includes.is_empty()
+    }
+
+    /// Whether an `--include` pattern matches `rel_path`; such files skip every other filter.
+    pub fn is_included(&self, rel_path: &str) -> bool {
+        self.includes.iter().any(|p| p.matches(rel_path))
+    }
+
+    /// The lines to keep of a file with `line_count` lines, merged and clamped, or `None`
+    /// to keep it whole because no include with a range matches it, or one without does.
+    pub fn line_ranges(&self, rel_path: &str, line_count: usize) -> Option<Vec<LineRange>> {
+        let mut ranges = Vec::new();
+        for (pattern, lines) in self.includes.iter().zip(&self.include_lines) {
+            if pattern.matches(rel_path) {
+                ranges.push((*lines)?);
+            }
+        }
+        if ranges.is_empty() {
+            return None;
+        }
+        ranges.sort();
+        let mut merged: Vec<LineRange> = Vec::new();
+        for (start, end) in ranges {
+            let end = end.min(line_count);
+            if start > end {
+                continue;
+            }
+            match merged.last_mut() {
+                Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        Some(merged)
+    }
+
+    /// Whether `--exclude` covers `rel_path` or one of its parent directories.
+    pub fn is_excluded(&self, rel_path: &str, is_dir: bool) -> bool {
+        self.excludes.matches(rel_path, is_dir)
+    }
+
+    /// Whether an include pattern explicitly reaches into `rel_dir` (`build/**/*.rs` for
+    /// `build`), so the directory must not be pruned even if it would be skipped.
+    pub fn reaches_into(&self, rel_dir: &str) -> bool {
+        let dir_prefix = format!("{}/", rel_dir);
+        self.include_dirs.iter().any(|d| d.starts_with(&dir_prefix))
+    }
+}
+
+/// `src/big.rs:100-400` as the pattern and its 1-based, inclusive line range. A value
+/// not ending in `:START-END` is all pattern.
+fn split_line_range(include: &str) -> Result<(&str, Option<LineRange>), String> {
+    let Some((pattern, range)) = include.rsplit_once(':') else {
+        return Ok((include, None));
+    };
+    let Some((start, end)) = range.split_once('-') else {
+        return Ok((include, None));
+    };
+    let digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if !digits(start) || !digits(end) {
+        return Ok((include, None));
+    }
+    match (start.parse(), end.parse()) {
+        (Ok(start), Ok(end)) if 1 <= start && start <= end => Ok((pattern, Some((start, end)))),
+        _ => Err(format!(
+            "--include: invalid line range '{}' in '{}' (expected START-END, counting from 1)",
+            range, include
+        )),
+    }
+}
+
+/// Resolve `--exclude` values once per input into gitignore patterns relative to `root`.
+///
+/// A value that names an existing path (relative to the working directory) is excluded
+/// at exactly that place: both sides are canonicalized here, once, rather than for every
+/// visited file. Values starting with `./` or the root are anchored the same way; anything
+/// else is a gitignore pattern (`*.snap`, `fixtures/`, `!keep.rs`).
+fn resolve_excludes(
+    root: Option<&Path>,
+    excludes: &[PathBuf],
+) -> Result<PathPatterns, Box<dyn Error>> {
+    let root_canonical = root.and_then(|r| r.canonicalize().ok());
+    let mut patterns = Vec::new();
+    for exc in excludes {
+        let resolved = match (&root_canonical, exc.canonicalize()) {
+            (Some(root_c), Ok(exc_c)) => match exc_c.strip_prefix(root_c) {
+                Ok(rel) => Some(rel.to_path_buf()),
+                // Outside this root; it can never match here.
+                Err(_) => continue,
+            },
+            _ => root
+                .and_then(|root| exc.strip_prefix(root).ok())
+                .map(Path::to_path_buf),
+        };
+        let text = exc.to_string_lossy().replace('\\', "/");
+        patterns.push(match resolved {
+            Some(rel) => anchored(&rel.to_string_lossy(), text.ends_with('/')),
+            None => match text.strip_prefix("./") {
+                Some(rest) => anchored(rest, text.ends_with('/')),
+                None => text,
+            },
+        });
+    }
+    PathPatterns::new(&patterns).map_err(|e| format!("--exclude: {}", e).into())
+}
+
+/// `rel` as a pattern that only matches at the root; the root itself excludes everything.
+fn anchored(rel: &str, dir_only: bool) -> String {
+    let rel = normalize_rel_path(rel);
+    if rel.is_empty() {
+        "*".to_string()
+    } else if dir_only {
+        format!("/{}/", rel)
+    } else {
+        format!("/{}", rel)
+    }
+}
+
+/// Forward slashes, no leading `./`, no trailing `/`.
+pub fn normalize_rel_path(path: &str) -> String {
+    let mut normalized = path.replace('\\', "/");
+    while let Some(rest) = normalized.strip_prefix("./") {
+        normalized = rest.to_string();
+    }
+    if normalized == "." {
+        normalized.clear();
+    }
+    normalized.trim_end_matches('/').to_string()
+}
+
+#[test]
+fn test_gitignore_semantics() -> Result<(), Box<dyn Error>> {
+    let patterns = PathPatterns::new(&["/build", "*.snap", "fixtures/", "!keep.snap"])?;
+    assert!(patterns.matches("build/a/b.rs", false));
+    assert!(!patterns.matches("src/build/x.rs", false));
+    assert!(patterns.matches("tests/ui/out.snap", false));
+    assert!(!patterns.matches("tests/keep.snap", false));
+    assert!(patterns.matches("src/fixtures/a.rs", false));
+    assert!(!patterns.matches("fixtures", false));
+    assert!(!PathPatterns::default().matches("anything.rs", false));
+    Ok(())
+}
+
+#[test]
+fn test_path_filter() -> Result<(), Box<dyn Error>> {
+    let filter = PathFilter::new(
+        Some(Path::new("/no/such/root")),
+        &["*.tf".to_string(), "./build/**/*.rs".to_string()],
+        &[
+            PathBuf::from("./gen/"),
+            PathBuf::from("/no/such/root/build"),
+            PathBuf::from("*.snap"),
+            PathBuf::from("!keep.snap"),
+        ],
+    )?;
+    assert!(filter.is_excluded("gen", true));
+    assert!(filter.is_excluded("gen/out.rs", false));
+    assert!(filter.is_excluded("build/a/b.rs", false));
+    assert!(!filter.is_excluded("generated.rs", false));
+    assert!(!filter.is_excluded("src/gen/x.rs", false));
+    assert!(filter.is_excluded("tests/ui/a.snap", false));
+    assert!(!filter.is_excluded("tests/keep.snap", false));
+
+    assert!(filter.is_included("main.tf"));
+    assert!(filter.is_included("infra/modules/vpc.tf"));
+    assert!(filter.is_included("build/gen/a.rs"));
+    assert!(!filter.is_included("src/a.rs"));
+    assert!(filter.reaches_into("build"));
+    assert!(!filter.reaches_into("infra"));
+
+    // Archives have no root on disk: path-like values are still anchored patterns.
+    let archive = PathFilter::new(None, &[], &[PathBuf::from("./gen"), PathBuf::from("docs/")])?;
+    assert!(archive.is_excluded("gen/a.rs", false));
+    assert!(!archive.is_excluded("src/gen/a.rs", false));
+    assert!(archive.is_excluded("src/docs/a.md", false));
+    assert!(PathFilter::new(None, &["[".to_string()], &[]).is_err());
+
+    let ranged = PathFilter::new(
+        None,
+        &[
+            "gen/big.rs:100-400".to_string(),
+            "gen/big.rs:350-500".to_string(),
+            "gen/big.rs:900-950".to_string(),
+            "src/*.rs".to_string(),
+            "src/main.rs:1-10".to_string(),
+        ],
+        &[],
+    )?;
+    assert!(ranged.is_included("gen/big.rs"));
+    assert_eq!(
+        ranged.line_ranges("gen/big.rs", 800),
+        Some(vec![(100, 500)])
+    );
+    assert_eq!(ranged.line_ranges("src/main.rs", 800), None);
+    assert_eq!(ranged.line_ranges("src/lib.rs", 800), None);
+    assert!(PathFilter::new(None, &["a.rs:400-100".to_string()], &[]).is_err());
+    assert!(PathFilter::new(None, &["c:/x".to_string()], &[])?.is_included("c:/x"));
+    Ok(())
+}
@@ -0,0 +1,327 @@
+//! PDF output (`-p`): syntax-highlighted code on pages of a configurable size.
+
+use crate::chunks::layout_units;
+use crate::language_from_path;
+use crate::types::FileEntry;
+use printpdf::{
+    BuiltinFont, Color, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+    Rgb,
+};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+
+/// Millimeters per typographic point.
+const MM_PER_PT: f32 = 0.3528;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    A4,
+    Letter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+/// Page setup and font, from `pdf:` in `r2md.yml` and the `--page-size`, `--orientation`,
+/// `--pdf-font-size`, `--pdf-margin` and `--pdf-font` flags.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PdfOptions {
+    pub page_size: PageSize,
+    pub orientation: Orientation,
+    /// Code font size in points; headings are a little larger
+    pub font_size: f32,
+    /// Page margin in millimeters
+    pub margin: f32,
+    /// Monospace TrueType font to embed, so non-ASCII source renders; built-in Courier
+    /// (Latin-1 only) when unset
+    pub font: Option<PathBuf>,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            page_size: PageSize::A4,
+            orientation: Orientation::Landscape,
+            font_size: 8.0,
+            margin: 10.0,
+            font: None,
+        }
+    }
+}
+
+impl PdfOptions {
+    /// Page width and height in millimeters.
+    fn page_mm(&self) -> (f32, f32) {
+        let (short, long) = match self.page_size {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::Letter => (215.9, 279.4),
+        };
+        match self.orientation {
+            Orientation::Portrait => (short, long),
+            Orientation::Landscape => (long, short),
+        }
+    }
+
+    fn line_height(&self) -> f32 {
+        self.font_size * 0.5
+    }
+}
+
+/// The page being written, and where the next line goes.
+struct Pages<'a> {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    y: f32,
+    opts: &'a PdfOptions,
+}
+
+impl Pages<'_> {
+    fn top(&self) -> f32 {
+        self.opts.page_mm().1 - self.opts.margin
+    }
+
+    /// Whether `height` mm more fits above the bottom margin.
+    fn fits(&self, height: f32) -> bool {
+        self.y - height >= self.opts.margin
+    }
+
+    fn new_page(&mut self) {
+        let (width, height) = self.opts.page_mm();
+        let (page, layer) = self.doc.add_page(Mm(width), Mm(height), "Layer next");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = self.top();
+    }
+
+    fn ensure(&mut self, height: f32) {
+        if !self.fits(height) {
+            self.new_page();
+        }
+    }
+}
+
+/// A run of same-colored text within a line.
+struct Run {
+    text: String,
+    rgb: (f32, f32, f32),
+    /// Distance from the left edge of the page, in mm
+    x: f32,
+}
+
+/// One file highlighted and measured, so that placing it on pages is only arithmetic.
+struct LaidOutFile<'a> {
+    rel_path: &'a str,
+    lines: Vec<Vec<Run>>,
+    /// Lines in the function or block starting at each (1-based) line, so one that fits
+    /// on a page but not in the space left can start on a new page
+    unit_starts: HashMap<usize, usize>,
+}
+
+/// Highlight `file` and work out where each run goes on its line. This is the slow part
+/// of a PDF export, so files go through it in parallel; the syntax and theme sets are
+/// loaded once and shared by the workers.
+fn lay_out_file<'a>(
+    file: &'a FileEntry,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    margin: f32,
+    char_width: f32,
+) -> Result<LaidOutFile<'a>, String> {
+    let path = Path::new(&file.rel_path);
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+    let syntax = ss
+        .find_syntax_by_extension(ext)
+        .unwrap_or_else(|| ss.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut lines = Vec::new();
+    for line in file.content.lines() {
+        let regions = highlighter
+            .highlight_line(line, ss)
+            .map_err(|e| format!("Highlighting error: {}", e))?;
+        let mut x = margin;
+        let mut runs = Vec::with_capacity(regions.len());
+        for (style, text) in regions {
+            let color = style.foreground;
+            runs.push(Run {
+                text: text.to_string(),
+                rgb: (
+                    color.r as f32 / 255.0,
+                    color.g as f32 / 255.0,
+                    color.b as f32 / 255.0,
+                ),
+                x,
+            });
+            x += char_width * display_width(text) as f32;
+        }
+        lines.push(runs);
+    }
+    let unit_starts = layout_units(&file.content, language_from_path(path))
+        .into_iter()
+        .map(|(start, end)| (start, end - start + 1))
+        .collect();
+    Ok(LaidOutFile {
+        rel_path: &file.rel_path,
+        lines,
+        unit_starts,
+    })
+}
+
+pub fn write_pdf_file(
+    files: &[FileEntry],
+    directories: &[PathBuf],
+    output_file_name: &str,
+    opts: &PdfOptions,
+    theme: &Theme,
+) -> Result<(), Box<dyn Error>> {
+    let (width, height) = opts.page_mm();
+    let (doc, page1, layer1) = PdfDocument::new("r2md PDF", Mm(width), Mm(height), "Layer 1");
+    let font: IndirectFontRef = match &opts.font {
+        Some(path) => doc
+            .add_external_font(
+                File::open(path)
+                    .map_err(|e| format!("Cannot open PDF font {}: {}", path.display(), e))?,
+            )
+            .map_err(|e| format!("Cannot load PDF font {}: {}", path.display(), e))?,
+        None => doc.add_builtin_font(BuiltinFont::Courier)?,
+    };
+    let layer = doc.get_page(page1).get_layer(layer1);
+    let mut pages = Pages {
+        doc,
+        layer,
+        y: 0.0,
+        opts,
+    };
+    pages.y = pages.top();
+
+    let ss = SyntaxSet::load_defaults_newlines();
+    let line_height = opts.line_height();
+    // Monospace glyphs are about 0.6 em wide.
+    let char_width = opts.font_size * 0.6 * MM_PER_PT;
+    let usable_height = height - 2.0 * opts.margin;
+
+    let laid_out = files
+        .par_iter()
+        .map(|file| lay_out_file(file, &ss, theme, opts.margin, char_width))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for d in directories {
+        pages.ensure(line_height * 2.5);
+        let text = format!("Directory: {}\n", d.display());
+        pages.layer.use_text(
+            text,
+            opts.font_size + 4.0,
+            Mm(opts.margin),
+            Mm(pages.y),
+            &font,
+        );
+        pages.y -= line_height * 2.5;
+    }
+
+    // Page composition is sequential: where a line goes depends on every line before it.
+    for file in &laid_out {
+        pages.ensure(line_height * 2.5);
+        let heading = format!("File: {}\n", file.rel_path);
+        pages.layer.use_text(
+            heading,
+            opts.font_size + 2.0,
+            Mm(opts.margin),
+            Mm(pages.y),
+            &font,
+        );
+        pages.y -= line_height * 1.5;
+
+        for (i, runs) in file.lines.iter().enumerate() {
+            let unit_height = file
+                .unit_starts
+                .get(&(i + 1))
+                .map_or(0.0, |&n| n as f32 * line_height);
+            if !pages.fits(0.0) || (!pages.fits(unit_height) && unit_height <= usable_height) {
+                pages.new_page();
+            }
+            for run in runs {
+                let (r, g, b) = run.rgb;
+                pages
+                    .layer
+                    .set_fill_color(Color::Rgb(Rgb::new(r, g, b, None)));
+                pages.layer.use_text(
+                    run.text.as_str(),
+                    opts.font_size,
+                    Mm(run.x),
+                    Mm(pages.y),
+                    &font,
+                );
+            }
+            pages.y -= line_height;
+        }
+        pages.y -= line_height; // extra gap between files
+    }
+
+    pages
+        .doc
+        .save(&mut BufWriter::new(File::create(output_file_name)?))?;
+    Ok(())
+}
+
+/// Width in monospace cells: CJK and other wide characters take two.
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x1100..=0x115F
+            | 0x2E80..=0x303E
+            | 0x3041..=0x33FF
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xA000..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x20000..=0x3FFFD => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+#[test]
+fn test_pdf_generation() -> Result<(), Box<dyn Error>> {
+    let files = vec![FileEntry {
+        rel_path: "test.rs".into(),
+        content: "fn main() {}".into(),
+        ..Default::default()
+    }];
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let path = temp_file.path().to_str().unwrap();
+
+    let opts = PdfOptions {
+        page_size: PageSize::Letter,
+        orientation: Orientation::Portrait,
+        ..Default::default()
+    };
+    assert_eq!(opts.page_mm(), (215.9, 279.4));
+    write_pdf_file(
+        &files,
+        &[PathBuf::from(".")],
+        path,
+        &opts,
+        &crate::theme::load_theme(None)?,
+    )?;
+    assert!(Path::new(path).exists());
+    assert_eq!(display_width("let 名前 = 1;"), 13);
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+//! Progress display for long runs, and per-phase timings for `--timings`.
+//!
+//! Both are process-wide, like `cancel`: the phases run in different functions and on
+//! walker and reader threads, and a handle passed through all of them would touch every
+//! collection and rendering signature.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A stage of an export run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// Directory traversal and name/ignore filtering
+    Walk,
+    /// Reading and decoding files (downloads for remote inputs)
+    Read,
+    /// Filtering and reshaping the collected files: relevance, focus, minify, sort, tree
+    Parse,
+    /// Token estimates
+    Tokenize,
+    /// Writing every output
+    Render,
+}
+
+const PHASE_NAMES: [&str; 5] = ["walk", "read", "parse", "tokenize", "render"];
+
+static NANOS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Count `elapsed` towards `phase`. Reads on several threads add up, so `read` can be
+/// longer than the run itself.
+pub fn add(phase: Phase, elapsed: Duration) {
+    NANOS[phase as usize].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Time recorded per phase so far, in run order.
+pub fn timings() -> Vec<(&'static str, Duration)> {
+    PHASE_NAMES
+        .iter()
+        .zip(&NANOS)
+        .map(|(name, nanos)| (*name, Duration::from_nanos(nanos.load(Ordering::Relaxed))))
+        .collect()
+}
+
+/// Show progress from now on. Only worth it on an interactive terminal, and not with
+/// `--log-level debug`, whose per-file lines would tear through the bar.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Replace the current display: a spinner counting files when `len` is unknown,
+/// otherwise a bar to `len`.
+pub fn start(message: &str, len: Option<usize>) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let bar = match len {
+        Some(len) => ProgressBar::new(len as u64).with_style(
+            ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len} files ({eta})")
+                .unwrap()
+                .progress_chars("=> "),
+        ),
+        None => ProgressBar::new_spinner().with_style(
+            ProgressStyle::with_template("{spinner} {msg} {pos} files ({elapsed})").unwrap(),
+        ),
+    };
+    bar.set_message(message.to_string());
+    bar.enable_steady_tick(Duration::from_millis(100));
+    if let Some(old) = BAR.lock().unwrap().replace(bar) {
+        old.finish_and_clear();
+    }
+}
+
+/// One more file done.
+pub fn inc() {
+    if let Some(bar) = BAR.lock().unwrap().as_ref() {
+        bar.inc(1);
+    }
+}
+
+/// Clear the display, before the run summary is printed.
+pub fn finish() {
+    if let Some(bar) = BAR.lock().unwrap().take() {
+        bar.finish_and_clear();
+    }
+}
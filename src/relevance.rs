@@ -0,0 +1,150 @@
+use crate::tokens::estimate_tokens;
+use crate::types::FileEntry;
+use std::collections::{HashMap, HashSet};
+
+/// How much of the ranking `--relevant-to` keeps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Budget {
+    /// The K best files.
+    Files(usize),
+    /// The best files that fit in roughly N tokens together.
+    Tokens(usize),
+}
+
+// Standard BM25 parameters.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+/// Path terms count this many times; a query word in a file name is a strong signal.
+const PATH_WEIGHT: usize = 3;
+
+/// Which of `files` to keep for `query`: the best BM25 matches over identifiers (split at
+/// camelCase and snake_case), comments and paths, within `budget`. Files that match no
+/// query term are never kept.
+pub fn select_relevant(files: &[&FileEntry], query: &str, budget: Budget) -> Vec<bool> {
+    let scores = bm25_scores(files, query);
+    let mut ranked: Vec<usize> = (0..files.len()).filter(|&i| scores[i] > 0.0).collect();
+    ranked.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+    let mut keep = vec![false; files.len()];
+    let mut tokens_left = match budget {
+        Budget::Tokens(n) => n,
+        Budget::Files(_) => usize::MAX,
+    };
+    let mut kept = 0;
+    for i in ranked {
+        if matches!(budget, Budget::Files(k) if kept >= k) {
+            break;
+        }
+        // Under a token budget, skip files that don't fit and try smaller ones.
+        let tokens = estimate_tokens(&files[i].content);
+        if tokens > tokens_left {
+            continue;
+        }
+        tokens_left -= tokens;
+        keep[i] = true;
+        kept += 1;
+    }
+    keep
+}
+
+fn bm25_scores(files: &[&FileEntry], query: &str) -> Vec<f64> {
+    let query: HashSet<String> = terms(query).collect();
+    let docs: Vec<HashMap<String, usize>> = files
+        .iter()
+        .map(|f| {
+            let mut tf: HashMap<String, usize> = HashMap::new();
+            for term in terms(&f.content) {
+                *tf.entry(term).or_default() += 1;
+            }
+            for term in terms(&f.rel_path) {
+                *tf.entry(term).or_default() += PATH_WEIGHT;
+            }
+            tf
+        })
+        .collect();
+    let lengths: Vec<usize> = docs.iter().map(|d| d.values().sum()).collect();
+    let avg_len = lengths.iter().sum::<usize>() as f64 / files.len().max(1) as f64;
+    let n = files.len() as f64;
+    let idf: HashMap<&str, f64> = query
+        .iter()
+        .map(|q| {
+            let df = docs.iter().filter(|d| d.contains_key(q)).count() as f64;
+            (q.as_str(), ((n - df + 0.5) / (df + 0.5) + 1.0).ln())
+        })
+        .collect();
+
+    docs.iter()
+        .zip(&lengths)
+        .map(|(tf, &len)| {
+            let norm = K1 * (1.0 - B + B * len as f64 / avg_len.max(1.0));
+            query
+                .iter()
+                .filter_map(|q| {
+                    let f = *tf.get(q)? as f64;
+                    Some(idf[q.as_str()] * f * (K1 + 1.0) / (f + norm))
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Lowercased, lightly stemmed words of `text`, with identifiers split into their parts
+/// (`retryPayment`, `retry_payment` -> `retry`, `payment`).
+pub fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .flat_map(split_camel_case)
+        .filter(|w| w.len() >= 2 && !w.chars().all(|c| c.is_ascii_digit()))
+        .map(|w| stem(&w.to_lowercase()))
+}
+
+fn split_camel_case(word: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    for pair in chars.windows(2) {
+        let ((_, prev), (i, c)) = (pair[0], pair[1]);
+        if prev.is_lowercase() && c.is_uppercase() {
+            parts.push(&word[start..i]);
+            start = i;
+        }
+    }
+    parts.push(&word[start..]);
+    parts
+}
+
+/// Just enough stemming to match plurals: `retries` -> `retry`, `payments` -> `payment`.
+fn stem(word: &str) -> String {
+    if let Some(base) = word.strip_suffix("ies").filter(|b| b.len() >= 2) {
+        return format!("{}y", base);
+    }
+    match word.strip_suffix('s') {
+        Some(base) if base.len() >= 3 && !base.ends_with('s') => base.to_string(),
+        _ => word.to_string(),
+    }
+}
+
+#[test]
+fn test_relevance_ranking() {
+    let file = |rel_path: &str, content: &str| FileEntry {
+        rel_path: rel_path.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    };
+    let files = [
+        file(
+            "src/billing/retry.rs",
+            "fn schedule_retry(payment: &Payment) {}",
+        ),
+        file("src/ui/button.rs", "fn render_button() {}"),
+        file("src/payments.rs", "struct Payment; // captured payments"),
+    ];
+    let refs: Vec<&FileEntry> = files.iter().collect();
+    assert_eq!(
+        select_relevant(&refs, "payment retries", Budget::Files(1)),
+        vec![true, false, false]
+    );
+    assert_eq!(
+        select_relevant(&refs, "payment retries", Budget::Files(5)),
+        vec![true, false, true]
+    );
+}
@@ -0,0 +1,530 @@
+use crate::archive::collect_files_from_zip;
+use crate::collect_files_parallel;
+use crate::types::{CollectOptions, CollectReport, FileEntry};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::RANGE;
+use reqwest::{Proxy, StatusCode, Url};
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
+use tracing::debug;
+
+/// Earliest time the next request to each host may go out, for `--rate-limit`.
+static NEXT_REQUEST: Mutex<Vec<(String, Instant)>> = Mutex::new(Vec::new());
+
+/// How downloads cope with slow and flaky networks.
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    /// Longest wait for a connection or the next bytes of a response; `None` waits forever
+    pub timeout: Option<Duration>,
+    /// Extra attempts after a transient failure, with exponential backoff
+    pub retries: u32,
+    /// Proxy for every request, on top of the `HTTPS_PROXY`-style variables reqwest reads
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            timeout: Some(Duration::from_secs(30)),
+            retries: 3,
+            proxy: None,
+        }
+    }
+}
+
+/// A client with `http`'s timeout and proxy.
+pub fn http_client(http: &HttpOptions) -> Result<Client, Box<dyn Error>> {
+    let mut builder = Client::builder().timeout(http.timeout);
+    if let Some(proxy) = &http.proxy {
+        builder = builder
+            .proxy(Proxy::all(proxy).map_err(|e| format!("Invalid proxy {}: {}", proxy, e))?);
+    }
+    Ok(builder.build()?)
+}
+
+/// True for inputs that are fetched over the network instead of walked locally.
+pub fn is_remote_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://") || is_ssh_url(input)
+}
+
+/// `git@host:org/repo.git` and `ssh://...` URLs are cloned with the local `git`.
+pub fn is_ssh_url(input: &str) -> bool {
+    input.starts_with("ssh://") || (input.starts_with("git@") && input.contains(':'))
+}
+
+/// Code hosts whose archive endpoints and auth headers we know about.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Host {
+    GitHub,
+    GitLab,
+    /// Bitbucket Cloud
+    Bitbucket,
+    /// Gitea and Forgejo, including Codeberg
+    Gitea,
+    Other,
+}
+
+fn detect_host(url: &Url) -> Host {
+    match url.host_str() {
+        Some("github.com") | Some("www.github.com") => Host::GitHub,
+        Some("bitbucket.org") | Some("www.bitbucket.org") => Host::Bitbucket,
+        Some("codeberg.org") => Host::Gitea,
+        Some(h) if h.contains("gitlab") => Host::GitLab,
+        Some(h) if h.contains("gitea") || h.contains("forgejo") => Host::Gitea,
+        _ => Host::Other,
+    }
+}
+
+/// Read the access token for a host from `GITHUB_TOKEN` / `GITLAB_TOKEN` /
+/// `BITBUCKET_TOKEN` / `GITEA_TOKEN`.
+fn token_for(host: Host) -> Option<String> {
+    let var = match host {
+        Host::GitHub => "GITHUB_TOKEN",
+        Host::GitLab => "GITLAB_TOKEN",
+        Host::Bitbucket => "BITBUCKET_TOKEN",
+        Host::Gitea => "GITEA_TOKEN",
+        Host::Other => return None,
+    };
+    std::env::var(var).ok().filter(|t| !t.trim().is_empty())
+}
+
+/// `GITHUB_TOKEN`, for other GitHub API calls.
+pub fn github_token() -> Option<String> {
+    token_for(Host::GitHub)
+}
+
+/// Split `https://host/owner/repo` into `("owner", "repo")`.
+fn owner_and_repo(url: &Url) -> Option<(String, String)> {
+    let mut segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    let repo = segments.pop()?.to_string();
+    if segments.is_empty() {
+        return None;
+    }
+    Some((segments.join("/"), repo))
+}
+
+/// Build the archive request for `branch`, authenticated when there is a `token` (see
+/// `token_for`).
+///
+/// Private repos can't be fetched from the public `/archive/` links, so with a token
+/// we go through each host's API instead.
+fn archive_request(
+    client: &Client,
+    base_url: &str,
+    branch: &str,
+    token: Option<&str>,
+) -> Result<RequestBuilder, Box<dyn Error>> {
+    let url = Url::parse(base_url)?;
+    let host = detect_host(&url);
+    let base = base_url.trim_end_matches('/');
+
+    let request = match (host, token) {
+        (Host::GitHub, Some(token)) => {
+            let (owner, repo) = owner_and_repo(&url).ok_or("Cannot parse owner/repo from URL")?;
+            client
+                .get(format!(
+                    "https://api.github.com/repos/{}/{}/zipball/refs/heads/{}",
+                    owner, repo, branch
+                ))
+                .bearer_auth(token)
+                .header("Accept", "application/vnd.github+json")
+        }
+        (Host::GitLab, Some(token)) => {
+            let (owner, repo) = owner_and_repo(&url).ok_or("Cannot parse owner/repo from URL")?;
+            let project = format!("{}/{}", owner, repo).replace('/', "%2F");
+            let origin = url.origin().ascii_serialization();
+            client
+                .get(format!(
+                    "{}/api/v4/projects/{}/repository/archive.zip?sha={}",
+                    origin, project, branch
+                ))
+                .header("PRIVATE-TOKEN", token)
+        }
+        (Host::GitLab, None) => {
+            let (_, repo) = owner_and_repo(&url).ok_or("Cannot parse owner/repo from URL")?;
+            client.get(format!(
+                "{}/-/archive/{}/{}-{}.zip",
+                base, branch, repo, branch
+            ))
+        }
+        (Host::Bitbucket, token) => {
+            let request = client.get(format!("{}/get/{}.zip", base, branch));
+            match token {
+                Some(token) => request.basic_auth("x-token-auth", Some(token)),
+                None => request,
+            }
+        }
+        (Host::Gitea, Some(token)) => {
+            let (owner, repo) = owner_and_repo(&url).ok_or("Cannot parse owner/repo from URL")?;
+            let origin = url.origin().ascii_serialization();
+            client
+                .get(format!(
+                    "{}/api/v1/repos/{}/{}/archive/{}.zip",
+                    origin, owner, repo, branch
+                ))
+                .header("Authorization", format!("token {}", token))
+        }
+        (Host::Gitea, None) => client.get(format!("{}/archive/{}.zip", base, branch)),
+        _ => client.get(format!("{}/archive/refs/heads/{}.zip", base, branch)),
+    };
+    Ok(request.header("User-Agent", concat!("r2md/", env!("CARGO_PKG_VERSION"))))
+}
+
+/// The repository's default branch from the host's API, or `None` when the host has no
+/// such API or the call fails (then the usual `main`/`master` guess applies).
+fn default_branch(client: &Client, base_url: &str, opts: &CollectOptions) -> Option<String> {
+    let url = Url::parse(base_url).ok()?;
+    let host = detect_host(&url);
+    let token = token_for(host);
+    let (owner, repo) = owner_and_repo(&url)?;
+    let origin = url.origin().ascii_serialization();
+    let (request, field): (RequestBuilder, &[&str]) = match host {
+        Host::GitHub => {
+            let request = client
+                .get(format!("https://api.github.com/repos/{}/{}", owner, repo))
+                .header("Accept", "application/vnd.github+json");
+            let request = match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            };
+            (request, &["default_branch"])
+        }
+        Host::GitLab => {
+            let project = format!("{}/{}", owner, repo).replace('/', "%2F");
+            let request = client.get(format!("{}/api/v4/projects/{}", origin, project));
+            let request = match token {
+                Some(token) => request.header("PRIVATE-TOKEN", token),
+                None => request,
+            };
+            (request, &["default_branch"])
+        }
+        Host::Bitbucket => {
+            let request = client.get(format!(
+                "https://api.bitbucket.org/2.0/repositories/{}/{}",
+                owner, repo
+            ));
+            let request = match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            };
+            (request, &["mainbranch", "name"])
+        }
+        Host::Gitea => {
+            let request = client.get(format!("{}/api/v1/repos/{}/{}", origin, owner, repo));
+            let request = match token {
+                Some(token) => request.header("Authorization", format!("token {}", token)),
+                None => request,
+            };
+            (request, &["default_branch"])
+        }
+        Host::Other => return None,
+    };
+    let request = request.header("User-Agent", concat!("r2md/", env!("CARGO_PKG_VERSION")));
+    if let Some(req) = request.try_clone().and_then(|r| r.build().ok()) {
+        debug!("Looking up the default branch at: {}", req.url());
+        throttle(req.url(), opts.rate_limit);
+    }
+    let response = request.send().ok()?.error_for_status().ok()?;
+    let mut value: serde_json::Value = serde_json::from_slice(&response.bytes().ok()?).ok()?;
+    for key in field {
+        value = value.get_mut(*key)?.take();
+    }
+    value.as_str().map(str::to_string)
+}
+
+/// Why a download attempt failed: worth another try, or not.
+enum Failure {
+    Transient(String),
+    Permanent(Box<dyn Error>),
+}
+
+/// Send `request` and stream the body into `out`, so memory use doesn't grow with the
+/// download. Dropped connections, timeouts, `429` and `5xx` answers are retried up to
+/// `--retries` times with exponential backoff; a body cut off part way continues with a
+/// `Range` request where the server supports it.
+fn fetch(
+    request: &RequestBuilder,
+    opts: &CollectOptions,
+    out: &mut File,
+) -> Result<(), Box<dyn Error>> {
+    let mut received = 0;
+    let mut attempt = 0;
+    loop {
+        let error = match fetch_attempt(request, out, &mut received, opts) {
+            Ok(()) => return Ok(()),
+            Err(Failure::Permanent(e)) => return Err(e),
+            Err(Failure::Transient(e)) => e,
+        };
+        if attempt >= opts.http.retries {
+            return match attempt {
+                0 => Err(error.into()),
+                _ => Err(format!("{} (after {} retries)", error, attempt).into()),
+            };
+        }
+        let delay = Duration::from_secs(1 << attempt.min(6));
+        debug!("{}; retrying in {}s", error, delay.as_secs());
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// One try at `fetch`, appending to the `received` bytes earlier tries wrote to `out`.
+fn fetch_attempt(
+    request: &RequestBuilder,
+    out: &mut File,
+    received: &mut u64,
+    opts: &CollectOptions,
+) -> Result<(), Failure> {
+    let mut request = request
+        .try_clone()
+        .ok_or_else(|| Failure::Permanent("Request cannot be retried".into()))?;
+    if *received > 0 {
+        request = request.header(RANGE, format!("bytes={}-", received));
+    }
+    if let Some(req) = request.try_clone().and_then(|r| r.build().ok()) {
+        throttle(req.url(), opts.rate_limit);
+    }
+    let mut response = request.send().map_err(|e| {
+        if e.is_builder() {
+            Failure::Permanent(e.into())
+        } else {
+            Failure::Transient(e.to_string())
+        }
+    })?;
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        return Err(Failure::Transient(format!("Server answered {}", status)));
+    }
+    if !status.is_success() {
+        return Err(Failure::Permanent(status.to_string().into()));
+    }
+    // Without `206 Partial Content` the server sent everything again.
+    if status != StatusCode::PARTIAL_CONTENT && *received > 0 {
+        *received = 0;
+        out.set_len(0)
+            .and_then(|_| out.rewind())
+            .map_err(|e| Failure::Permanent(e.into()))?;
+    }
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = match response.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) => {
+                return Err(Failure::Transient(format!(
+                    "Download cut off after {} bytes: {}",
+                    received, e
+                )))
+            }
+        };
+        out.write_all(&buf[..n])
+            .map_err(|e| Failure::Permanent(e.into()))?;
+        *received += n as u64;
+    }
+}
+
+/// Wait until another request to `url`'s host fits in `per_minute` requests per minute.
+fn throttle(url: &Url, per_minute: Option<u32>) {
+    let (Some(per_minute), Some(host)) = (per_minute, url.host_str()) else {
+        return;
+    };
+    let interval = Duration::from_secs(60) / per_minute.max(1);
+    let wait = {
+        let mut next = NEXT_REQUEST.lock().unwrap();
+        let now = Instant::now();
+        let i = match next.iter().position(|(h, _)| h == host) {
+            Some(i) => i,
+            None => {
+                next.push((host.to_string(), now));
+                next.len() - 1
+            }
+        };
+        let start = next[i].1.max(now);
+        next[i].1 = start + interval;
+        start - now
+    };
+    if !wait.is_zero() {
+        thread::sleep(wait);
+    }
+}
+
+pub fn collect_files_from_git_url(
+    url: &str,
+    opts: &CollectOptions,
+    report: &mut CollectReport,
+) -> Result<Vec<FileEntry>, Box<dyn Error>> {
+    // Remove trailing ".git" if present.
+    let mut base_url = url.to_string();
+    if base_url.ends_with(".git") {
+        base_url = base_url.trim_end_matches(".git").to_string();
+    }
+    let client = http_client(&opts.http)?;
+    let token = Url::parse(&base_url)
+        .ok()
+        .and_then(|url| token_for(detect_host(&url)));
+
+    // Closure that attempts to download the ZIP archive for a given branch.
+    let try_download = |branch: &str| -> Result<NamedTempFile, Box<dyn Error>> {
+        let request = archive_request(&client, &base_url, branch, token.as_deref())?;
+        if let Some(req) = request.try_clone().and_then(|r| r.build().ok()) {
+            debug!("Attempting to download repository ZIP from: {}", req.url());
+        }
+        let mut archive = NamedTempFile::new()?;
+        fetch(&request, opts, archive.as_file_mut()).map_err(|e| {
+            format!(
+                "Failed to download repository ZIP for branch {}: {}",
+                branch, e
+            )
+        })?;
+        Ok(archive)
+    };
+
+    // The default branch when the host says what it is, else "main" and then "master".
+    let mut branches = vec!["main".to_string(), "master".to_string()];
+    if let Some(branch) = default_branch(&client, &base_url, opts) {
+        branches.retain(|b| *b != branch);
+        branches.insert(0, branch);
+    }
+    let mut response = Err("No branch to download".into());
+    for branch in &branches {
+        response = try_download(branch);
+        match &response {
+            Ok(_) => break,
+            Err(err) => debug!("Download of branch {} failed: {}", branch, err),
+        }
+    }
+    let archive = response?;
+    collect_files_from_zip(archive.path(), opts, report)
+}
+
+/// Shallow-clone an SSH URL with the user's own git/ssh-agent setup, then walk it locally.
+pub fn collect_files_from_ssh_url(
+    url: &str,
+    opts: &CollectOptions,
+    report: &mut CollectReport,
+) -> Result<Vec<FileEntry>, Box<dyn Error>> {
+    let checkout = tempfile::tempdir()?;
+    debug!("Cloning {} into {}", url, checkout.path().display());
+    let status = Command::new("git")
+        .args(["clone", "--depth", "1", "--quiet", url])
+        .arg(checkout.path())
+        .status()
+        .map_err(|e| format!("Failed to run git for {}: {}", url, e))?;
+    if !status.success() {
+        return Err(format!("git clone of {} failed ({})", url, status).into());
+    }
+    collect_files_parallel(checkout.path(), opts, report, None)
+}
+
+#[test]
+fn test_archive_urls() -> Result<(), Box<dyn Error>> {
+    let client = Client::new();
+    for (base, expected) in [
+        (
+            "https://gitlab.com/group/sub/tool",
+            "https://gitlab.com/group/sub/tool/-/archive/dev/tool-dev.zip",
+        ),
+        (
+            "https://bitbucket.org/team/tool",
+            "https://bitbucket.org/team/tool/get/dev.zip",
+        ),
+        (
+            "https://codeberg.org/team/tool",
+            "https://codeberg.org/team/tool/archive/dev.zip",
+        ),
+        (
+            "https://gitea.example.com/team/tool",
+            "https://gitea.example.com/team/tool/archive/dev.zip",
+        ),
+    ] {
+        let request = archive_request(&client, base, "dev", None)?.build()?;
+        assert_eq!(request.url().as_str(), expected);
+        assert!(request.headers().get("Authorization").is_none());
+    }
+
+    // With a token, the request goes through the host API instead.
+    for (base, expected, header, value) in [
+        (
+            "https://github.com/org/tool",
+            "https://api.github.com/repos/org/tool/zipball/refs/heads/dev",
+            "Authorization",
+            "Bearer secret",
+        ),
+        (
+            "https://gitlab.com/group/sub/tool",
+            "https://gitlab.com/api/v4/projects/group%2Fsub%2Ftool/repository/archive.zip?sha=dev",
+            "PRIVATE-TOKEN",
+            "secret",
+        ),
+        (
+            "https://bitbucket.org/team/tool",
+            "https://bitbucket.org/team/tool/get/dev.zip",
+            "Authorization",
+            "Basic eC10b2tlbi1hdXRoOnNlY3JldA==",
+        ),
+        (
+            "https://codeberg.org/team/tool",
+            "https://codeberg.org/api/v1/repos/team/tool/archive/dev.zip",
+            "Authorization",
+            "token secret",
+        ),
+    ] {
+        let request = archive_request(&client, base, "dev", Some("secret"))?.build()?;
+        assert_eq!(request.url().as_str(), expected);
+        assert_eq!(request.headers()[header], value);
+    }
+    assert_eq!(
+        detect_host(&Url::parse("https://forgejo.example.org/a/b")?),
+        Host::Gitea
+    );
+    assert_eq!(
+        detect_host(&Url::parse("https://example.org/a/b")?),
+        Host::Other
+    );
+    Ok(())
+}
+
+#[test]
+fn test_fetch_retries_and_resumes() -> Result<(), Box<dyn Error>> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/repo.zip", listener.local_addr()?);
+    let server = thread::spawn(move || {
+        let mut ranges = Vec::new();
+        let answers = [
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+            // Promises ten bytes, sends five and hangs up.
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n01234",
+            "HTTP/1.1 206 Partial Content\r\nContent-Length: 5\r\n\r\n56789",
+        ];
+        for answer in answers {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                if let Some(range) = line.to_ascii_lowercase().strip_prefix("range: ") {
+                    ranges.push(range.trim().to_string());
+                }
+                line.clear();
+            }
+            stream.write_all(answer.as_bytes()).unwrap();
+        }
+        ranges
+    });
+
+    let opts = CollectOptions::default();
+    let client = http_client(&opts.http)?;
+    let mut out = NamedTempFile::new()?;
+    fetch(&client.get(&url), &opts, out.as_file_mut())?;
+    assert_eq!(std::fs::read(out.path())?, b"0123456789");
+    assert_eq!(server.join().unwrap(), ["bytes=5-"]);
+    Ok(())
+}
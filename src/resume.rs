@@ -0,0 +1,130 @@
+//! `--resume`: remember which remote inputs of a multi-repo run are already collected, so
+//! a batch that fails halfway can be re-run without downloading everything again.
+
+use crate::schema::SCHEMA_VERSION;
+use crate::types::{FileEntry, LineMap};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Default state directory for `--resume`.
+pub const DEFAULT_RESUME_DIR: &str = ".r2md-resume";
+
+/// Per-input completion records in a state directory, one JSON file per finished input.
+pub struct ResumeState {
+    dir: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Completed {
+    schema_version: u32,
+    input: String,
+    /// Files the input's collection pass skipped, so the summary stays accurate
+    skipped: usize,
+    files: Vec<StoredFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredFile {
+    path: String,
+    content: String,
+    #[serde(default, skip_serializing_if = "LineMap::is_whole")]
+    lines: LineMap,
+}
+
+impl ResumeState {
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(ResumeState {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    fn record_path(&self, input: &str) -> PathBuf {
+        let digest = format!("{:x}", Sha256::digest(input.as_bytes()));
+        self.dir.join(format!("{}.json", &digest[..16]))
+    }
+
+    /// Files and skip count of an input finished by an earlier run. Unreadable or
+    /// out-of-date records count as not done.
+    pub fn load(&self, input: &str) -> Option<(Vec<FileEntry>, usize)> {
+        let text = fs::read_to_string(self.record_path(input)).ok()?;
+        let done: Completed = serde_json::from_str(&text).ok()?;
+        if done.schema_version != SCHEMA_VERSION || done.input != input {
+            return None;
+        }
+        let files = done
+            .files
+            .into_iter()
+            .map(|f| FileEntry {
+                rel_path: f.path,
+                content: f.content,
+                lines: f.lines,
+            })
+            .collect();
+        Some((files, done.skipped))
+    }
+
+    /// Mark `input` as done. Written to a temporary file first, so a crash mid-write
+    /// never leaves a truncated record behind.
+    pub fn save(&self, input: &str, files: &[FileEntry], skipped: usize) -> io::Result<()> {
+        let done = Completed {
+            schema_version: SCHEMA_VERSION,
+            input: input.to_string(),
+            skipped,
+            files: files
+                .iter()
+                .map(|f| StoredFile {
+                    path: f.rel_path.clone(),
+                    content: f.content.clone(),
+                    lines: f.lines.clone(),
+                })
+                .collect(),
+        };
+        let path = self.record_path(input);
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_string(&done)?)?;
+        fs::rename(partial, path)
+    }
+
+    /// Forget every input once the whole run has succeeded. Only r2md's own records are
+    /// deleted; the directory goes too if nothing else is in it.
+    pub fn clear(self, inputs: &[PathBuf]) -> io::Result<()> {
+        for input in inputs {
+            let path = self.record_path(&input.to_string_lossy());
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        if fs::read_dir(&self.dir)?.next().is_none() {
+            fs::remove_dir(&self.dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_resume_round_trip() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let state_dir = dir.path().join("state");
+    let state = ResumeState::open(&state_dir)?;
+    let url = "https://github.com/org/repo";
+    assert!(state.load(url).is_none());
+
+    let files = [FileEntry {
+        rel_path: "src/lib.rs".to_string(),
+        content: "pub fn a() {}".to_string(),
+        ..Default::default()
+    }];
+    state.save(url, &files, 3)?;
+    let (loaded, skipped) = state.load(url).expect("saved input");
+    assert_eq!(loaded[0].rel_path, "src/lib.rs");
+    assert_eq!(skipped, 3);
+    assert!(state.load("https://github.com/org/other").is_none());
+
+    state.clear(&[PathBuf::from(url)])?;
+    assert!(!state_dir.exists());
+    Ok(())
+}
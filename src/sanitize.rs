@@ -0,0 +1,108 @@
+//! Keep file paths and contents from breaking or spoofing the generated Markdown.
+//!
+//! Paths and error messages come from the filesystem and contents come from arbitrary
+//! repositories, so none of them may close a fence, open a heading or inject HTML.
+
+use serde::Deserialize;
+
+/// What code fences are made of (`fence_style:` in `r2md.yml`, `--fence-style`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FenceStyle {
+    /// ```` ``` ````
+    #[default]
+    Backtick,
+    /// `~~~`
+    Tilde,
+}
+
+impl FenceStyle {
+    fn marker(self) -> char {
+        match self {
+            FenceStyle::Backtick => '`',
+            FenceStyle::Tilde => '~',
+        }
+    }
+}
+
+/// A fence longer than any run of its character in `content` (at least three), so the
+/// content can never close the block early.
+pub fn fence_for(content: &str, style: FenceStyle) -> String {
+    let marker = style.marker();
+    marker
+        .to_string()
+        .repeat((longest_run(content, marker) + 1).max(3))
+}
+
+/// `text` as an inline code span that stays on one line and can't be closed from inside.
+pub fn inline_code(text: &str) -> String {
+    let text = escape_control(text);
+    let ticks = "`".repeat(longest_run(&text, '`') + 1);
+    // CommonMark strips one space on each side, so padding keeps edge backticks literal.
+    if text.starts_with('`') || text.ends_with('`') {
+        format!("{} {} {}", ticks, text, ticks)
+    } else {
+        format!("{}{}{}", ticks, text, ticks)
+    }
+}
+
+/// `text` as inert inline Markdown: one line, Markdown punctuation and HTML escaped.
+pub fn plain_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in escape_control(text).chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '\\' | '`' | '*' | '_' | '[' | ']' | '#' | '|' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Newlines and other control characters as visible escapes (`\n`, `\u{1b}`, ...).
+pub fn escape_control(text: &str) -> String {
+    if !text.chars().any(char::is_control) {
+        return text.to_string();
+    }
+    text.chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+fn longest_run(text: &str, marker: char) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        if c == marker {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    longest
+}
+
+#[test]
+fn test_sanitize_guards_markdown() {
+    assert_eq!(fence_for("fn main() {}", FenceStyle::Backtick), "```");
+    assert_eq!(fence_for("```rust\n```", FenceStyle::Backtick), "````");
+    assert_eq!(fence_for("```rust\n```", FenceStyle::Tilde), "~~~");
+    assert_eq!(fence_for("a ~~~~ b", FenceStyle::Tilde), "~~~~~");
+    assert_eq!(inline_code("src/main.rs"), "`src/main.rs`");
+    assert_eq!(inline_code("a`b"), "``a`b``");
+    assert_eq!(inline_code("`x"), "`` `x ``");
+    assert_eq!(inline_code("a\n# b"), "`a\\n# b`");
+    assert_eq!(plain_text("<!-- x --> *y*"), "&lt;!-- x --&gt; \\*y\\*");
+}
@@ -0,0 +1,183 @@
+//! Versioning and JSON Schemas for r2md's machine-readable outputs.
+//!
+//! Every JSON object r2md writes for other tools carries `schema_version`. Bump it on any
+//! change that could break a consumer (renamed or removed fields, changed meaning); new
+//! optional fields don't need a bump.
+
+use serde_json::{json, Value};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Outputs `--schema` can describe.
+pub const SCHEMA_NAMES: &[&str] = &["manifest", "training", "report", "chunks"];
+
+/// JSON Schema (draft 2020-12) for one of `SCHEMA_NAMES`.
+pub fn json_schema(name: &str) -> Option<Value> {
+    let version = json!({ "const": SCHEMA_VERSION });
+    let mut schema = match name {
+        "manifest" => json!({
+            "title": "r2md manifest (--manifest)",
+            "type": "object",
+            "required": ["schema_version", "files"],
+            "properties": {
+                "schema_version": version,
+                "files": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["path", "size", "tokens", "language", "sha256", "sections"],
+                        "properties": {
+                            "path": { "type": "string" },
+                            "size": { "type": "integer", "description": "Content size in bytes" },
+                            "tokens": { "type": "integer", "description": "Estimated tokens" },
+                            "language": { "type": "string" },
+                            "sha256": { "type": "string", "pattern": "^[0-9a-f]{64}$" },
+                            "sections": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "required": ["output", "offset", "length"],
+                                    "properties": {
+                                        "output": { "type": "string" },
+                                        "offset": { "type": "integer" },
+                                        "length": { "type": "integer" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }),
+        "training" => {
+            let flags = json!({
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["kind", "line", "value"],
+                    "properties": {
+                        "kind": { "enum": ["long", "deep", "large"] },
+                        "chunk": { "type": ["string", "null"] },
+                        "line": { "type": "integer" },
+                        "value": { "type": "integer" }
+                    }
+                }
+            });
+            json!({
+                "title": "r2md training sample (--train-json; one JSONL line or JSON array element)",
+                "oneOf": [
+                    {
+                        "type": "object",
+                        "required": ["schema_version", "prompt", "completion", "prompt_tokens", "completion_tokens", "tokenizer"],
+                        "properties": {
+                            "schema_version": version,
+                            "prompt": { "type": "string" },
+                            "completion": { "type": "string" },
+                            "prompt_tokens": { "type": "integer" },
+                            "completion_tokens": { "type": "integer" },
+                            "tokenizer": { "type": "string" },
+                            "chunk": { "type": "string", "description": "Source chunk, for --train-style chunks" },
+                            "flags": flags
+                        }
+                    },
+                    {
+                        "type": "object",
+                        "required": ["schema_version", "prefix", "middle", "suffix", "prefix_tokens", "middle_tokens", "suffix_tokens", "tokenizer"],
+                        "properties": {
+                            "schema_version": version,
+                            "prefix": { "type": "string" },
+                            "middle": { "type": "string" },
+                            "suffix": { "type": "string" },
+                            "prefix_tokens": { "type": "integer" },
+                            "middle_tokens": { "type": "integer" },
+                            "suffix_tokens": { "type": "integer" },
+                            "tokenizer": { "type": "string" },
+                            "flags": flags
+                        }
+                    }
+                ]
+            })
+        }
+        "report" => json!({
+            "title": "r2md error report (--report)",
+            "type": "object",
+            "required": ["schema_version", "files_included", "files_skipped", "errors"],
+            "properties": {
+                "schema_version": version,
+                "files_included": { "type": "integer" },
+                "files_skipped": { "type": "integer" },
+                "errors": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["path", "kind", "message"],
+                        "properties": {
+                            "path": { "type": "string" },
+                            "kind": { "type": "string" },
+                            "message": { "type": "string" }
+                        }
+                    }
+                }
+            }
+        }),
+        "chunks" => json!({
+            "title": "r2md chunk (--chunks; one JSONL line)",
+            "type": "object",
+            "required": ["schema_version", "path", "language", "index", "start_line", "end_line", "start_byte", "end_byte", "tokens", "text"],
+            "properties": {
+                "schema_version": version,
+                "path": { "type": "string" },
+                "language": { "type": "string" },
+                "index": { "type": "integer", "description": "Position of the chunk within its file, from 0" },
+                "start_line": { "type": "integer", "description": "1-based, inclusive" },
+                "end_line": { "type": "integer", "description": "1-based, inclusive" },
+                "start_byte": { "type": "integer", "description": "Offset into the exported file content" },
+                "end_byte": { "type": "integer", "description": "Offset into the exported file content, exclusive" },
+                "tokens": { "type": "integer", "description": "Estimated tokens" },
+                "text": { "type": "string" }
+            }
+        }),
+        _ => return None,
+    };
+    schema["$schema"] = json!("https://json-schema.org/draft/2020-12/schema");
+    Some(schema)
+}
+
+#[test]
+fn test_manifest_matches_schema() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::types::FileEntry;
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("manifest.json");
+    let files = [FileEntry {
+        rel_path: "a.rs".to_string(),
+        content: "fn a() {}".to_string(),
+        ..Default::default()
+    }];
+    crate::manifest::write_manifest(&path.to_string_lossy(), &files, &[])?;
+    let manifest: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    let schema = json_schema("manifest").ok_or("no manifest schema")?;
+
+    assert_eq!(manifest["schema_version"], SCHEMA_VERSION);
+    let required = |v: &Value| -> Vec<String> {
+        v["required"]
+            .as_array()
+            .map(|r| {
+                r.iter()
+                    .filter_map(|k| k.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    for key in required(&schema) {
+        assert!(manifest.get(&key).is_some(), "manifest lacks {}", key);
+    }
+    for key in required(&schema["properties"]["files"]["items"]) {
+        assert!(
+            manifest["files"][0].get(&key).is_some(),
+            "file entry lacks {}",
+            key
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::Encoding;
+
+/// How much of a file we look at when deciding whether it's binary.
+const SNIFF_LEN: usize = 8192;
+
+/// Content-based binary detection, independent of the file name.
+///
+/// Like `content_inspector`: a NUL byte in the first few KB means binary, as does a high
+/// share of control bytes that never appear in text (anything below 0x20 except tab,
+/// newline, carriage return, form feed and escape).
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(SNIFF_LEN)];
+    if head.is_empty() {
+        return false;
+    }
+    if head.contains(&0) {
+        return true;
+    }
+    let suspicious = head
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    suspicious * 10 > head.len()
+}
+
+/// Turn raw file bytes into text, rejecting binary content whatever the file is called.
+///
+/// Files with a byte order mark (UTF-16 included, which would otherwise look binary) are
+/// decoded by it. Other non-UTF-8 text, such as Latin-1 or Shift_JIS, is transcoded with
+/// a guessed encoding; bytes that don't map become U+FFFD. With `strict`, only BOM-less
+/// UTF-8 is accepted.
+pub fn bytes_to_text(bytes: Vec<u8>, strict: bool) -> Result<String, &'static str> {
+    if !strict {
+        if let Some((encoding, bom_len)) = Encoding::for_bom(&bytes) {
+            let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+            if looks_binary(text.as_bytes()) {
+                return Err("binary content");
+            }
+            return Ok(text.into_owned());
+        }
+    }
+    if looks_binary(&bytes) {
+        return Err("binary content");
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(text),
+        Err(_) if strict => Err("invalid UTF-8"),
+        Err(e) => {
+            let bytes = e.into_bytes();
+            let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+            detector.feed(&bytes, true);
+            let (text, _) = detector
+                .guess(None, Utf8Detection::Deny)
+                .decode_without_bom_handling(&bytes);
+            Ok(text.into_owned())
+        }
+    }
+}
+
+#[test]
+fn test_looks_binary() {
+    assert!(!looks_binary(b"#!/bin/sh\necho hi\n"));
+    assert!(!looks_binary(
+        "fn main() { println!(\"héllo\"); }".as_bytes()
+    ));
+    assert!(looks_binary(b"\x7fELF\x02\x01\x01\x00\x00\x00"));
+    assert!(looks_binary(&[0x01, 0x02, 0x03, 0x04, b'a']));
+}
+
+#[test]
+fn test_non_utf8_text() {
+    let latin1 = b"// Gr\xfc\xdfe\n".to_vec();
+    assert_eq!(
+        bytes_to_text(latin1.clone(), false).as_deref(),
+        Ok("// Grüße\n")
+    );
+    assert_eq!(bytes_to_text(latin1, true), Err("invalid UTF-8"));
+
+    let utf16: Vec<u8> = [0xFF, 0xFE]
+        .into_iter()
+        .chain("let x = 1;".encode_utf16().flat_map(u16::to_le_bytes))
+        .collect();
+    assert_eq!(
+        bytes_to_text(utf16.clone(), false).as_deref(),
+        Ok("let x = 1;")
+    );
+    assert_eq!(bytes_to_text(utf16, true), Err("binary content"));
+}
@@ -0,0 +1,521 @@
+use crate::chunks::layout_units;
+use crate::compress::{split_suffix, write_file};
+use crate::i18n::Labels;
+use crate::language_from_path;
+use crate::manifest::SectionSpan;
+use crate::progress;
+use crate::sanitize::inline_code;
+use crate::tokens::estimate_tokens;
+use crate::types::{CollectOptions, FileEntry, LineMap};
+use crate::{input_heading, render_file_section};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// One output part: rendered sections (with the file each one shows, if any) plus the
+/// files they came from, for the index.
+struct Part {
+    sections: Vec<(Option<String>, String)>,
+    files: Vec<String>,
+    tokens: usize,
+}
+
+/// Name of the `index`th (1-based) part, e.g. `r2md_output.md` -> `r2md_output.part2.md`.
+/// Parts of a compressed output are compressed too: `out.md.gz` -> `out.part2.md.gz`.
+pub fn part_file_name(output: &str, index: usize) -> String {
+    let (output, compressed) = split_suffix(output);
+    let stem = output.strip_suffix(".md").unwrap_or(output);
+    format!("{}.part{}.md{}", stem, index, compressed)
+}
+
+/// Write the export as several Markdown parts, each estimated to stay under `max_tokens`.
+///
+/// Files are never broken in the middle of a function or other top-level chunk; a file
+/// only spans parts when it alone exceeds the budget. Every part starts with `header` (the
+/// `--metadata` block, possibly empty) and an index of all parts. `preamble` (the directory
+/// tree) goes in the first part and `epilogue` in the last. Returns the names of the
+/// written files, each with the byte ranges of the file sections it holds, or an
+/// `InvalidInput` error when `max_tokens` can't fit the index and a file heading.
+pub fn write_split_markdown(
+    output: &str,
+    header: &str,
+    preamble: &str,
+    files: &[FileEntry],
+    epilogue: &str,
+    max_tokens: usize,
+    opts: &CollectOptions,
+) -> io::Result<Vec<(String, Vec<SectionSpan>)>> {
+    // Every part needs room for the index plus a file heading, its fences and some code.
+    let section_overhead = files
+        .iter()
+        .map(|file| {
+            estimate_tokens(&render_file_section(
+                &file.rel_path,
+                "",
+                Some("99/99"),
+                1,
+                &LineMap::default(),
+                opts,
+            ))
+        })
+        .max()
+        .unwrap_or(0);
+    let too_small = |index_tokens: usize| {
+        let needed = index_tokens + section_overhead + 1;
+        (max_tokens < needed).then(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "--split-tokens {} is too small: the index of parts and a file heading \
+                     would take about {} tokens in every part",
+                    max_tokens,
+                    needed - 1
+                ),
+            )
+        })
+    };
+    // Header and index (with the current part marked) as they would top each of `parts`.
+    let index_size = |parts: &[Part]| {
+        estimate_tokens(header)
+            + estimate_tokens(&render_index(output, parts, 0, &opts.labels))
+            + estimate_tokens(&format!(" ({})", opts.labels.this_part))
+    };
+
+    // The index sits on top of each part, and its size depends on how many parts there
+    // are. Start from a packing without it and repack until the index fits; it only ever
+    // grows, so this ends within `max_tokens`.
+    let mut index_tokens = index_size(&pack_parts(
+        preamble,
+        files,
+        epilogue,
+        max_tokens.saturating_sub(estimate_tokens(header)).max(1),
+        opts,
+    ));
+    let parts = loop {
+        if let Some(error) = too_small(index_tokens) {
+            return Err(error);
+        }
+        let parts = pack_parts(preamble, files, epilogue, max_tokens - index_tokens, opts);
+        let needed = index_size(&parts);
+        if needed <= index_tokens {
+            break parts;
+        }
+        index_tokens = needed;
+    };
+
+    let mut written = Vec::with_capacity(parts.len());
+    for (i, part) in parts.iter().enumerate() {
+        let name = part_file_name(output, i + 1);
+        let mut body = header.to_string();
+        body.push_str(&render_index(output, &parts, i + 1, &opts.labels));
+        let mut spans = Vec::new();
+        for (file, section) in &part.sections {
+            if let Some(file) = file {
+                spans.push(SectionSpan {
+                    rel_path: file.clone(),
+                    offset: body.len(),
+                    length: section.len(),
+                });
+            }
+            body.push_str(section);
+        }
+        write_file(&name, body)?;
+        written.push((name, spans));
+    }
+    Ok(written)
+}
+
+/// Directory `--split-by dir` writes its parts to, next to the index: `r2md_output.md`
+/// -> `r2md_output/`.
+pub fn dir_parts_dir(output: &str) -> String {
+    let (output, _) = split_suffix(output);
+    match output.strip_suffix(".md") {
+        Some(stem) => stem.to_string(),
+        None => format!("{}.d", output),
+    }
+}
+
+/// Write the export as one Markdown file per top-level directory (`r2md_output/src.md`,
+/// ...), with `output` as the index: `header`, `preamble`, links to the parts, the files
+/// at the top level and `epilogue`. Each part starts with `header` and a link back to the
+/// index. Returns the written files like `write_split_markdown`, index first.
+pub fn write_dir_split_markdown(
+    output: &str,
+    header: &str,
+    preamble: &str,
+    files: &[FileEntry],
+    epilogue: &str,
+    opts: &CollectOptions,
+) -> io::Result<Vec<(String, Vec<SectionSpan>)>> {
+    let mut groups: Vec<(&str, Vec<&FileEntry>)> = Vec::new();
+    let mut top_level = Vec::new();
+    for file in files {
+        match file.rel_path.split_once('/') {
+            Some((dir, _)) => match groups.iter_mut().find(|(name, _)| *name == dir) {
+                Some((_, group)) => group.push(file),
+                None => groups.push((dir, vec![file])),
+            },
+            None => top_level.push(file),
+        }
+    }
+
+    let parts_dir = dir_parts_dir(output);
+    let (_, compressed) = split_suffix(output);
+    let file_name = |path: &str| path.rsplit(['/', '\\']).next().unwrap_or(path).to_string();
+    let index_name = file_name(output);
+    if !groups.is_empty() {
+        fs::create_dir_all(&parts_dir)?;
+    }
+    let mut index = format!("{}{}## {}\n\n", header, preamble, opts.labels.parts);
+    let mut written = Vec::with_capacity(groups.len() + 1);
+    for (dir, group) in &groups {
+        let name = format!("{}/{}.md{}", parts_dir, dir, compressed);
+        index.push_str(&format!(
+            "- [{}]({}/{}.md{}): {} {}\n",
+            inline_code(&format!("{}/", dir)),
+            file_name(&parts_dir).replace(' ', "%20"),
+            dir.replace(' ', "%20"),
+            compressed,
+            group.len(),
+            opts.labels.files
+        ));
+        let mut body = format!(
+            "{}[{}](../{})\n\n",
+            header,
+            index_name,
+            index_name.replace(' ', "%20")
+        );
+        let spans = push_sections(&mut body, group, opts);
+        write_file(&name, body)?;
+        written.push((name, spans));
+    }
+    index.push('\n');
+    let spans = if top_level.is_empty() {
+        Vec::new()
+    } else {
+        push_sections(&mut index, &top_level, opts)
+    };
+    index.push_str(epilogue);
+    write_file(output, index)?;
+    written.insert(0, (output.to_string(), spans));
+    Ok(written)
+}
+
+/// Append the code heading and `files` to `body`, returning where each file's section is.
+fn push_sections(
+    body: &mut String,
+    files: &[&FileEntry],
+    opts: &CollectOptions,
+) -> Vec<SectionSpan> {
+    if opts.input_headings.is_empty() {
+        body.push_str(&format!("## {}\n\n", opts.labels.code));
+    }
+    let mut spans = Vec::with_capacity(files.len());
+    for file in files {
+        progress::inc();
+        if let Some(heading) = input_heading(&file.rel_path, opts) {
+            body.push_str(&heading);
+        }
+        let section =
+            render_file_section(&file.rel_path, &file.content, None, 1, &file.lines, opts);
+        spans.push(SectionSpan {
+            rel_path: file.rel_path.clone(),
+            offset: body.len(),
+            length: section.len(),
+        });
+        body.push_str(&section);
+    }
+    spans
+}
+
+fn pack_parts(
+    preamble: &str,
+    files: &[FileEntry],
+    epilogue: &str,
+    budget: usize,
+    opts: &CollectOptions,
+) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut current = Part {
+        sections: Vec::new(),
+        files: Vec::new(),
+        tokens: 0,
+    };
+    let mut push = |current: &mut Part, section: String, file: Option<&str>| {
+        let tokens = estimate_tokens(&section);
+        if current.tokens + tokens > budget && !current.sections.is_empty() {
+            parts.push(std::mem::replace(
+                current,
+                Part {
+                    sections: Vec::new(),
+                    files: Vec::new(),
+                    tokens: 0,
+                },
+            ));
+        }
+        current.tokens += tokens;
+        current.sections.push((file.map(str::to_string), section));
+        if let Some(file) = file {
+            if current.files.last().map(String::as_str) != Some(file) {
+                current.files.push(file.to_string());
+            }
+        }
+    };
+
+    if !preamble.is_empty() {
+        push(&mut current, preamble.to_string(), None);
+    }
+    if opts.input_headings.is_empty() {
+        push(&mut current, format!("## {}\n\n", opts.labels.code), None);
+    }
+    for file in files {
+        progress::inc();
+        if let Some(heading) = input_heading(&file.rel_path, opts) {
+            push(&mut current, heading, None);
+        }
+        let whole = render_file_section(&file.rel_path, &file.content, None, 1, &file.lines, opts);
+        if estimate_tokens(&whole) <= budget {
+            push(&mut current, whole, Some(&file.rel_path));
+            continue;
+        }
+        let overhead = estimate_tokens(&render_file_section(
+            &file.rel_path,
+            "",
+            Some("99/99"),
+            1,
+            &LineMap::default(),
+            opts,
+        ));
+        // `--line-numbers` adds a `1234 | ` prefix to every line.
+        let line_prefix = if opts.line_numbers {
+            file.content.lines().count().to_string().len() + 3
+        } else {
+            0
+        };
+        let pieces = split_content(
+            &file.content,
+            language_from_path(Path::new(&file.rel_path)),
+            budget.saturating_sub(overhead).max(1),
+            line_prefix,
+        );
+        let total = pieces.len();
+        for (i, (first_line, piece)) in pieces.iter().enumerate() {
+            let note = format!("{}/{}", i + 1, total);
+            let section = render_file_section(
+                &file.rel_path,
+                piece,
+                Some(&note),
+                *first_line,
+                &file.lines,
+                opts,
+            );
+            push(&mut current, section, Some(&file.rel_path));
+        }
+    }
+    if !epilogue.is_empty() {
+        push(&mut current, epilogue.to_string(), None);
+    }
+    parts.push(current);
+    parts
+}
+
+/// Break content into pieces under `budget` tokens, cutting only between layout units
+/// (top-level chunks and blank-line separated blocks, see `layout_units`). Each piece
+/// comes with the line number it starts at. `line_prefix` bytes per line are added on
+/// rendering and count against the budget. A unit that is itself too large falls back
+/// to line boundaries.
+fn split_content(
+    content: &str,
+    lang: &str,
+    budget: usize,
+    line_prefix: usize,
+) -> Vec<(usize, String)> {
+    let cost = |text: &str| estimate_tokens(text) + text.lines().count() * line_prefix / 4;
+    let lines: Vec<&str> = content.lines().collect();
+    let chunks = layout_units(content, lang).into_iter().map(|(start, end)| {
+        let mut text = lines[start - 1..end].join("\n");
+        text.push('\n');
+        (start, text)
+    });
+
+    let mut pieces = Vec::new();
+    let mut piece = (1, String::new());
+    for (start, chunk) in chunks {
+        let units: Vec<(usize, String)> = if cost(&chunk) > budget {
+            chunk
+                .split_inclusive('\n')
+                .enumerate()
+                .map(|(i, line)| (start + i, line.to_string()))
+                .collect()
+        } else {
+            vec![(start, chunk)]
+        };
+        for (start, unit) in units {
+            if !piece.1.is_empty() && cost(&piece.1) + cost(&unit) > budget {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            if piece.1.is_empty() {
+                piece.0 = start;
+            }
+            piece.1.push_str(&unit);
+        }
+    }
+    if !piece.1.is_empty() {
+        pieces.push(piece);
+    }
+    pieces
+        .into_iter()
+        .map(|(start, p)| (start, p.trim_end_matches('\n').to_string()))
+        .collect()
+}
+
+/// Cross-part index; `current` (1-based) is marked, 0 marks none.
+fn render_index(output: &str, parts: &[Part], current: usize, labels: &Labels) -> String {
+    let mut index = format!("## {}\n\n", labels.parts);
+    for (i, part) in parts.iter().enumerate() {
+        let name = part_file_name(output, i + 1);
+        let link = name.rsplit(['/', '\\']).next().unwrap_or(&name);
+        let marker = if i + 1 == current {
+            format!(" ({})", labels.this_part)
+        } else {
+            String::new()
+        };
+        let range = match (part.files.first(), part.files.last()) {
+            (Some(first), Some(last)) if first == last => format!(": {}", inline_code(first)),
+            (Some(first), Some(last)) => format!(
+                ": {} … {} ({} {})",
+                inline_code(first),
+                inline_code(last),
+                part.files.len(),
+                labels.files
+            ),
+            _ => String::new(),
+        };
+        index.push_str(&format!(
+            "- [{} {}]({}){}{}\n",
+            capitalize(&labels.part),
+            i + 1,
+            link,
+            marker,
+            range
+        ));
+    }
+    index.push('\n');
+    index
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[test]
+fn test_split_content_respects_chunks() {
+    let content = "fn a() {\n    1;\n\n    2;\n}\n\nfn b() {\n    3\n}\n";
+    let pieces = split_content(content, "rust", 8, 0);
+    assert_eq!(
+        pieces,
+        vec![
+            (1, "fn a() {\n    1;\n\n    2;\n}".to_string()),
+            (7, "fn b() {\n    3\n}".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_split_by_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let output = dir.path().join("out.md").to_string_lossy().to_string();
+    let file = |rel_path: &str| FileEntry {
+        rel_path: rel_path.to_string(),
+        content: "x\n".to_string(),
+        ..Default::default()
+    };
+    let files = [
+        file("README.md"),
+        file("src/a.rs"),
+        file("tests/t.rs"),
+        file("src/b/c.rs"),
+    ];
+    let written =
+        write_dir_split_markdown(&output, "", "", &files, "", &CollectOptions::default())?;
+    let names: Vec<&str> = written.iter().map(|(name, _)| name.as_str()).collect();
+    let src = dir.path().join("out/src.md").to_string_lossy().to_string();
+    assert_eq!(names[0], output);
+    assert_eq!(names[1], src);
+    assert_eq!(written[1].1.len(), 2);
+
+    let index = fs::read_to_string(&output)?;
+    assert!(
+        index.contains("- [`src/`](out/src.md): 2 files\n- [`tests/`](out/tests.md): 1 files\n")
+    );
+    assert!(index.contains("### `README.md`"));
+    let part = fs::read_to_string(&src)?;
+    assert!(part.starts_with("[out.md](../out.md)\n\n"));
+    assert!(part.contains("### `src/b/c.rs`"));
+    assert!(!part.contains("README"));
+    Ok(())
+}
+
+#[test]
+fn test_split_tokens_too_small() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let output = dir.path().join("out.md").to_string_lossy().to_string();
+    let files = [FileEntry {
+        rel_path: "src/lib.rs".to_string(),
+        content: "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n".repeat(12),
+        ..Default::default()
+    }];
+    let opts = CollectOptions::default();
+    let error = write_split_markdown(&output, "", "", &files, "", 20, &opts).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    assert!(!Path::new(&part_file_name(&output, 1)).exists());
+
+    let written = write_split_markdown(&output, "", "", &files, "", 100, &opts)?;
+    assert!(written.len() > 1);
+    for (name, _) in &written {
+        let part = fs::read_to_string(name)?;
+        assert!(part.contains("fn "), "{} has no code", name);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_split_parts_stay_under_budget() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let output = dir.path().join("out.md").to_string_lossy().to_string();
+    let files: Vec<FileEntry> = (0..40)
+        .map(|i| FileEntry {
+            rel_path: format!("src/m{}.rs", i),
+            content: format!("fn f{}() {{\n    {}\n}}\n", i, i).repeat(10),
+            ..Default::default()
+        })
+        .collect();
+    let header = "- Generated: today\n\n";
+    for max_tokens in [600, 900, 1500] {
+        let written = write_split_markdown(
+            &output,
+            header,
+            "",
+            &files,
+            "",
+            max_tokens,
+            &CollectOptions::default(),
+        )?;
+        assert!(written.len() > 1);
+        for (name, _) in &written {
+            let tokens = estimate_tokens(&fs::read_to_string(name)?);
+            assert!(
+                tokens <= max_tokens,
+                "{}: {} > {}",
+                name,
+                tokens,
+                max_tokens
+            );
+        }
+    }
+    Ok(())
+}
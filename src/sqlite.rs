@@ -0,0 +1,187 @@
+//! `--format sqlite`: files, chunks, symbols and dependencies as a SQLite database, for
+//! code-search and RAG pipelines that ingest r2md output.
+
+use crate::chunks::{chunk_file, public_symbols};
+use crate::deps::extract_dependencies;
+use crate::language_from_path;
+use crate::schema::SCHEMA_VERSION;
+use crate::tokens::estimate_tokens;
+use crate::types::FileEntry;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE files (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    language TEXT NOT NULL,
+    bytes INTEGER NOT NULL,
+    tokens INTEGER NOT NULL,
+    content TEXT NOT NULL
+);
+CREATE TABLE chunks (
+    id INTEGER PRIMARY KEY,
+    file_id INTEGER NOT NULL REFERENCES files(id),
+    name TEXT,
+    language TEXT NOT NULL,
+    start_line INTEGER NOT NULL,
+    end_line INTEGER NOT NULL,
+    start_byte INTEGER NOT NULL,
+    end_byte INTEGER NOT NULL,
+    tokens INTEGER NOT NULL
+);
+CREATE TABLE symbols (
+    id INTEGER PRIMARY KEY,
+    file_id INTEGER NOT NULL REFERENCES files(id),
+    chunk_id INTEGER NOT NULL REFERENCES chunks(id),
+    name TEXT NOT NULL,
+    line INTEGER NOT NULL,
+    public INTEGER NOT NULL
+);
+CREATE TABLE dependencies (
+    file_id INTEGER NOT NULL REFERENCES files(id),
+    target TEXT NOT NULL,
+    target_file_id INTEGER REFERENCES files(id)
+);
+CREATE INDEX chunks_file ON chunks(file_id);
+CREATE INDEX symbols_name ON symbols(name);
+CREATE INDEX dependencies_file ON dependencies(file_id);
+";
+
+/// Write `files` to a new database at `output_file_name`, replacing any existing file.
+/// Byte ranges are offsets into `files.content`; lines are 1-based.
+pub fn write_sqlite_file(
+    files: &[FileEntry],
+    output_file_name: &str,
+) -> Result<(), Box<dyn Error>> {
+    if Path::new(output_file_name).exists() {
+        fs::remove_file(output_file_name)?;
+    }
+    let mut conn = Connection::open(output_file_name)?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+    tx.execute(
+        "INSERT INTO meta (key, value) VALUES ('schema_version', ?1), ('r2md_version', ?2)",
+        params![SCHEMA_VERSION.to_string(), env!("CARGO_PKG_VERSION")],
+    )?;
+
+    let mut file_ids = HashMap::new();
+    for file in files {
+        let lang = language_from_path(Path::new(&file.rel_path));
+        tx.execute(
+            "INSERT INTO files (path, language, bytes, tokens, content) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                file.rel_path,
+                lang,
+                file.content.len(),
+                estimate_tokens(&file.content),
+                file.content
+            ],
+        )?;
+        file_ids.insert(file.rel_path.as_str(), tx.last_insert_rowid());
+    }
+
+    for file in files {
+        let file_id = file_ids[file.rel_path.as_str()];
+        let lang = language_from_path(Path::new(&file.rel_path));
+        let line_starts = line_starts(&file.content);
+        let public = public_symbols(&file.content, lang);
+        for chunk in chunk_file(&file.content, lang) {
+            let start_byte = line_starts[chunk.start_line - 1];
+            let end_byte = line_starts
+                .get(chunk.end_line)
+                .copied()
+                .unwrap_or(file.content.len());
+            tx.execute(
+                "INSERT INTO chunks (file_id, name, language, start_line, end_line, start_byte, end_byte, tokens)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    file_id,
+                    chunk.name,
+                    lang,
+                    chunk.start_line,
+                    chunk.end_line,
+                    start_byte,
+                    end_byte,
+                    estimate_tokens(&file.content[start_byte..end_byte])
+                ],
+            )?;
+            if let Some(name) = &chunk.name {
+                tx.execute(
+                    "INSERT INTO symbols (file_id, chunk_id, name, line, public) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        file_id,
+                        tx.last_insert_rowid(),
+                        name,
+                        chunk.start_line,
+                        public.contains(name)
+                    ],
+                )?;
+            }
+        }
+        for target in extract_dependencies(Path::new(&file.rel_path), &file.content) {
+            // Import paths are guesses relative to some source root, so match by suffix.
+            let target_id = files
+                .iter()
+                .find(|f| f.rel_path == target || f.rel_path.ends_with(&format!("/{}", target)))
+                .map(|f| file_ids[f.rel_path.as_str()]);
+            tx.execute(
+                "INSERT INTO dependencies (file_id, target, target_file_id) VALUES (?1, ?2, ?3)",
+                params![file_id, target, target_id],
+            )?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Byte offset where each line starts.
+fn line_starts(content: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+#[test]
+fn test_sqlite_export() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("out.db");
+    let files = vec![
+        FileEntry {
+            rel_path: "src/main.rs".to_string(),
+            content: "use util;\n\npub fn main() {\n    util::run();\n}\n".to_string(),
+            ..Default::default()
+        },
+        FileEntry {
+            rel_path: "src/util.rs".to_string(),
+            content: "fn run() {}\n".to_string(),
+            ..Default::default()
+        },
+    ];
+    write_sqlite_file(&files, &path.to_string_lossy())?;
+
+    let conn = Connection::open(&path)?;
+    let (start, end): (usize, usize) = conn.query_row(
+        "SELECT start_byte, end_byte FROM chunks JOIN files ON files.id = file_id WHERE name = 'main'",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    assert!(files[0].content[start..end].contains("pub fn main() {"));
+    let public: bool = conn.query_row(
+        "SELECT public FROM symbols WHERE name = 'main'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert!(public);
+    let target: String = conn.query_row(
+        "SELECT files.path FROM dependencies JOIN files ON files.id = target_file_id",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(target, "src/util.rs");
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use crate::cost::format_usd;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// End-of-run numbers shown to the user once all outputs are written.
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    pub files_included: usize,
+    pub files_skipped: usize,
+    pub tokens: usize,
+    /// Prompt cost per model, for `--cost-estimate`
+    pub costs: Vec<(String, f64)>,
+    pub outputs: Vec<String>,
+    pub warnings: Vec<String>,
+    /// One line per failed path
+    pub errors: Vec<String>,
+    /// Time per phase, for `--timings`
+    pub timings: Vec<(&'static str, Duration)>,
+}
+
+/// Per-file errors listed before the rest are folded into a count.
+const MAX_LISTED_ERRORS: usize = 20;
+
+/// ANSI styles used in the summary.
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+
+/// Colors only on an interactive terminal, and never when `NO_COLOR` is set (https://no-color.org).
+pub fn colors_enabled(stream: atty::Stream) -> bool {
+    atty::is(stream) && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// `1234567` -> `1,234,567`
+fn with_separators(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+impl RunSummary {
+    pub fn render(&self, color: bool) -> String {
+        let row = |label: &str, value: String| {
+            format!(
+                "  {} {}\n",
+                paint(&format!("{:<16}", label), DIM, color),
+                value
+            )
+        };
+        let mut out = format!(
+            "{}\n",
+            paint("r2md finished", &format!("{};{}", BOLD, GREEN), color)
+        );
+        out += &row(
+            "Files included",
+            paint(&with_separators(self.files_included), BOLD, color),
+        );
+        out += &row("Files skipped", with_separators(self.files_skipped));
+        out += &row(
+            "Tokens (est.)",
+            paint(&with_separators(self.tokens), BOLD, color),
+        );
+        for (i, (model, cost)) in self.costs.iter().enumerate() {
+            let label = if i == 0 { "Cost (est.)" } else { "" };
+            out += &row(label, format!("{:<16} {}", model, format_usd(*cost)));
+        }
+        for (i, output) in self.outputs.iter().enumerate() {
+            let label = if i == 0 { "Output" } else { "" };
+            out += &row(label, output.clone());
+        }
+        if !self.errors.is_empty() {
+            out += &row(
+                "Errors",
+                paint(&with_separators(self.errors.len()), YELLOW, color),
+            );
+            for error in self.errors.iter().take(MAX_LISTED_ERRORS) {
+                out += &format!("    {}\n", error);
+            }
+            if self.errors.len() > MAX_LISTED_ERRORS {
+                out += &format!(
+                    "    ... and {} more (see --report FILE)\n",
+                    self.errors.len() - MAX_LISTED_ERRORS
+                );
+            }
+        }
+        for (i, (phase, elapsed)) in self.timings.iter().enumerate() {
+            let label = if i == 0 { "Timings" } else { "" };
+            out += &row(label, format!("{:<9} {:.2}s", phase, elapsed.as_secs_f64()));
+        }
+        for warning in &self.warnings {
+            out += &format!(
+                "  {} {}\n",
+                paint("warning:", &format!("{};{}", BOLD, YELLOW), color),
+                warning
+            );
+        }
+        out
+    }
+
+    /// Print to stderr when stdout carries the document itself, otherwise to stdout.
+    pub fn print(&self, to_stderr: bool) -> io::Result<()> {
+        if to_stderr {
+            let color = colors_enabled(atty::Stream::Stderr);
+            io::stderr().lock().write_all(self.render(color).as_bytes())
+        } else {
+            let color = colors_enabled(atty::Stream::Stdout);
+            io::stdout().lock().write_all(self.render(color).as_bytes())
+        }
+    }
+}
+
+#[test]
+fn test_summary_plain_render() {
+    let summary = RunSummary {
+        files_included: 1234,
+        files_skipped: 5,
+        tokens: 1_000_000,
+        costs: vec![("gpt-4o".into(), 2.5), ("gpt-4o-mini".into(), 0.15)],
+        outputs: vec!["r2md_output.md".into()],
+        warnings: vec!["1 path(s) could not be read".into()],
+        errors: vec!["big.rs: too large (6000000 bytes, limit 5242880)".into()],
+        timings: vec![
+            ("walk", Duration::from_millis(1250)),
+            ("read", Duration::from_millis(40)),
+        ],
+    };
+    let text = summary.render(false);
+    assert!(!text.contains('\x1b'));
+    assert!(text.contains("1,234"));
+    assert!(text.contains("1,000,000"));
+    assert!(text.contains("Cost (est.)      gpt-4o           $2.50\n"));
+    assert!(text.contains("                 gpt-4o-mini      $0.1500\n"));
+    assert!(text.contains("warning: 1 path(s) could not be read"));
+    assert!(text.contains("    big.rs: too large"));
+    assert!(text.contains("Timings          walk      1.25s\n"));
+    assert!(text.contains("                 read      0.04s\n"));
+}
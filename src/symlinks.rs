@@ -0,0 +1,119 @@
+//! `--follow-symlinks`: which symlinks the walker may follow.
+//!
+//! Monorepos often symlink shared packages in from elsewhere. Following every link would
+//! export a package once per link, export files inside the input twice (under the link
+//! and the real path), and never finish on a link back to an ancestor. So a link whose
+//! target is inside the input is skipped (the real path is exported), and each directory
+//! is entered once, tracked by its inode.
+
+use ignore::DirEntry;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// What makes two paths the same directory.
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+fn dir_id(path: &Path) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+/// Windows has no inode in `std`; the canonical path serves the same purpose.
+#[cfg(not(unix))]
+fn dir_id(path: &Path) -> Option<DirId> {
+    fs::canonicalize(path).ok()
+}
+
+/// Shared by the walker threads of one input.
+pub struct SymlinkGuard {
+    root: PathBuf,
+    visited: Mutex<HashSet<DirId>>,
+}
+
+impl SymlinkGuard {
+    pub fn new(root: &Path) -> Self {
+        SymlinkGuard {
+            root: fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf()),
+            visited: Mutex::new(dir_id(root).into_iter().collect()),
+        }
+    }
+
+    /// Whether the walker should keep `ent`; for a directory, whether to enter it.
+    pub fn allows(&self, ent: &DirEntry) -> bool {
+        if ent.depth() == 0 {
+            return true;
+        }
+        if ent.path_is_symlink() {
+            match fs::canonicalize(ent.path()) {
+                Ok(target) if !target.starts_with(&self.root) => {}
+                // Inside the input, or dangling.
+                _ => return false,
+            }
+        }
+        if !ent.file_type().is_some_and(|t| t.is_dir()) {
+            return true;
+        }
+        match dir_id(ent.path()) {
+            Some(id) => self.visited.lock().unwrap().insert(id),
+            None => true,
+        }
+    }
+}
+
+/// Whether a walk error is the walker refusing a symlink back to an ancestor directory,
+/// which is already being exported.
+pub fn is_loop(err: &ignore::Error) -> bool {
+    match err {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => is_loop(err),
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_links_are_followed_once() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::symlink;
+
+    let dir = tempfile::tempdir()?;
+    let repo = dir.path().join("repo");
+    let shared = dir.path().join("shared");
+    fs::create_dir_all(repo.join("src"))?;
+    fs::create_dir_all(&shared)?;
+    fs::write(repo.join("src/main.rs"), "")?;
+    fs::write(shared.join("lib.rs"), "")?;
+    symlink(&shared, repo.join("shared"))?;
+    symlink(&shared, repo.join("shared-again"))?;
+    symlink(repo.join("src"), repo.join("src-link"))?;
+    symlink(&repo, repo.join("src/up"))?;
+
+    let guard = SymlinkGuard::new(&repo);
+    let mut seen = Vec::new();
+    for entry in ignore::WalkBuilder::new(&repo)
+        .follow_links(true)
+        .filter_entry(move |ent| guard.allows(ent))
+        .build()
+    {
+        match entry {
+            Ok(ent) if ent.file_type().is_some_and(|t| t.is_file()) => seen.push(
+                ent.path()
+                    .strip_prefix(&repo)?
+                    .to_string_lossy()
+                    .replace("shared-again", "shared"),
+            ),
+            Ok(_) => {}
+            Err(err) => assert!(is_loop(&err), "{}", err),
+        }
+    }
+    seen.sort();
+    assert_eq!(seen, ["shared/lib.rs", "src/main.rs"]);
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! `--theme`: the syntect color theme used by every highlighted output (PDF, EPUB and
+//! the HTML app), either a built-in theme by name or a `.tmTheme` file.
+
+use std::path::Path;
+use syntect::highlighting::{Theme, ThemeSet};
+
+/// The theme used when `--theme` isn't given.
+pub const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// Names of the built-in themes, sorted.
+pub fn theme_names() -> Vec<String> {
+    ThemeSet::load_defaults().themes.into_keys().collect()
+}
+
+/// The built-in theme called `spec`, or else the `.tmTheme` file at `spec`.
+pub fn load_theme(spec: Option<&str>) -> Result<Theme, String> {
+    let spec = spec.unwrap_or(DEFAULT_THEME);
+    if let Some(theme) = ThemeSet::load_defaults().themes.remove(spec) {
+        return Ok(theme);
+    }
+    let path = Path::new(spec);
+    if !path.is_file() {
+        return Err(format!(
+            "Unknown theme '{}': not a built-in theme (see --list-themes) or a .tmTheme file",
+            spec
+        ));
+    }
+    ThemeSet::get_theme(path).map_err(|e| format!("Cannot load theme {}: {}", spec, e))
+}
+
+#[test]
+fn test_load_theme() -> Result<(), Box<dyn std::error::Error>> {
+    assert!(theme_names().iter().any(|name| name == DEFAULT_THEME));
+    assert_eq!(load_theme(None)?.name.as_deref(), Some("GitHub"));
+    assert!(load_theme(Some("base16-ocean.dark")).is_ok());
+    assert!(load_theme(Some("no-such-theme")).is_err());
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("plain.tmTheme");
+    std::fs::write(
+        &path,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+    <key>name</key><string>Plain</string>
+    <key>settings</key>
+    <array>
+        <dict><key>settings</key><dict><key>foreground</key><string>#112233</string></dict></dict>
+    </array>
+</dict>
+</plist>
+"#,
+    )?;
+    let theme = load_theme(path.to_str())?;
+    assert_eq!(theme.name.as_deref(), Some("Plain"));
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use crate::patterns::LineRange;
+
+/// Rough token estimate for budgeting output without loading a tokenizer.
+///
+/// BPE tokenizers average about four bytes per token on source code; this errs slightly
+/// high for dense ASCII, which is the safe direction for a budget.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Which end of an over-long file `--truncate-strategy` keeps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TruncateStrategy {
+    Head,
+    Tail,
+    /// Mostly head, plus the last quarter of the budget from the end
+    HeadTail,
+}
+
+/// `--per-file-max-tokens`: files over the limit are trimmed instead of dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Truncation {
+    pub max_tokens: usize,
+    pub strategy: TruncateStrategy,
+}
+
+impl Truncation {
+    /// The lines of `content` to keep, as 1-based inclusive ranges, to cut it at line
+    /// boundaries to about `max_tokens` once `marker` stands in for the lines removed (see
+    /// `focus::keep_ranges`). `None` if the content already fits.
+    pub fn ranges(&self, content: &str, marker: &str) -> Option<Vec<LineRange>> {
+        if estimate_tokens(content) <= self.max_tokens {
+            return None;
+        }
+        let lines: Vec<&str> = content.lines().collect();
+        let marker_tokens = estimate_tokens(marker) + 2;
+        let budget = self.max_tokens.saturating_sub(marker_tokens) * 4;
+        let head_budget = match self.strategy {
+            TruncateStrategy::Head => budget,
+            TruncateStrategy::Tail => 0,
+            TruncateStrategy::HeadTail => budget * 3 / 4,
+        };
+        let fit = |lines: &mut dyn Iterator<Item = &&str>, bytes: usize| {
+            let mut used = 0;
+            lines
+                .take_while(|line| {
+                    used += line.len() + 1;
+                    used <= bytes
+                })
+                .count()
+        };
+        let head = fit(&mut lines.iter(), head_budget);
+        let tail = fit(&mut lines[head..].iter().rev(), budget - head_budget);
+        let omitted = lines.len() - head - tail;
+        if omitted == 0 {
+            return None;
+        }
+        let ranges = [(1, head), (lines.len() - tail + 1, lines.len())];
+        Some(
+            ranges
+                .into_iter()
+                .filter(|(start, end)| start <= end)
+                .collect(),
+        )
+    }
+}
+
+#[test]
+fn test_truncation_keeps_head_and_tail() {
+    use crate::focus::cut_lines;
+
+    let content: String = (1..=100).map(|i| format!("line {:03}\n", i)).collect();
+    let limit = Truncation {
+        max_tokens: 30,
+        strategy: TruncateStrategy::HeadTail,
+    };
+    let marker = "// {count} lines omitted";
+    let (cut, origins) = cut_lines(&content, &limit.ranges(&content, marker).unwrap(), marker);
+    let lines: Vec<&str> = cut.lines().collect();
+    assert_eq!(lines.first(), Some(&"line 001"));
+    assert_eq!(lines.last(), Some(&"line 100"));
+    assert!(cut.contains("lines omitted"));
+    assert!(estimate_tokens(&cut) <= 30);
+    assert_eq!(origins.last(), Some(&Some(100)));
+    assert!(origins.contains(&None));
+
+    let head = Truncation {
+        strategy: TruncateStrategy::Head,
+        ..limit
+    };
+    let kept = head.ranges(&content, "…").unwrap();
+    assert_eq!(kept.len(), 1);
+    assert_eq!(kept[0].0, 1);
+    assert_eq!(limit.ranges("short", "…"), None);
+}
@@ -1,11 +1,20 @@
+use crate::chunks::{chunk_file, flag_chunks, Chunk, ChunkFlag, ChunkThresholds};
+use crate::compress::{split_suffix, OutputFile};
+use crate::dedup::dedup_files;
 use crate::deps::sort_files_by_dependency;
+use crate::language_from_path;
+use crate::schema::SCHEMA_VERSION;
 use crate::types::FileEntry;
 use anyhow::Result;
 use serde::Serialize;
-use std::fs::File;
-use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use tiktoken_rs::CoreBPE;
 use tokenizers::Tokenizer;
 
+/// Tokenizer used when `--tokenizer` isn't given.
+pub const DEFAULT_TOKENIZER: &str = "deepseek-ai/DeepSeek-R1-Distill-Llama-70B";
+
 #[derive(Serialize)]
 struct TrainingSample {
     prompt: String,
@@ -13,52 +22,451 @@ struct TrainingSample {
     prompt_tokens: usize,
     completion_tokens: usize,
     tokenizer: String,
+    /// Source chunk name, for `--train-style chunks`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chunk: Option<String>,
+    /// Chunks of the source file over the `--flag-chunks` limits
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    flags: Vec<ChunkFlag>,
+}
+
+/// Fill-in-the-middle sample: the model sees `prefix` and `suffix` and predicts `middle`.
+#[derive(Serialize)]
+struct FimSample {
+    prefix: String,
+    middle: String,
+    suffix: String,
+    prefix_tokens: usize,
+    middle_tokens: usize,
+    suffix_tokens: usize,
+    tokenizer: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    flags: Vec<ChunkFlag>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Sample {
+    Completion(TrainingSample),
+    Fim(FimSample),
+}
+
+/// A sample as written: stamped with the output schema version.
+#[derive(Serialize)]
+struct Versioned<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    sample: &'a Sample,
+}
+
+/// Layout of the training data file(s).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrainFormat {
+    /// A single JSON array
+    Json,
+    /// One sample per line
+    Jsonl,
+}
+
+/// Shape of each training sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrainStyle {
+    /// Prompt/completion split at `split_ratio`
+    Completion,
+    /// Prefix/middle/suffix triples with a random middle span
+    Fim,
+    /// One sample per function/class: preceding code plus signature -> body
+    Chunks,
 }
 
-pub fn produce_training_json(files: &[FileEntry], out_path: &str, split_ratio: f64) -> Result<()> {
+#[derive(Debug, Clone)]
+pub struct TrainingOptions {
+    pub style: TrainStyle,
+    /// Fraction of each file's tokens that goes into the prompt
+    pub split_ratio: f64,
+    /// Inclusive bounds on the FIM middle span, in tokens
+    pub fim_middle: (usize, usize),
+    /// Tokens of preceding code kept ahead of the signature in chunk samples
+    pub context_tokens: usize,
+    pub format: TrainFormat,
+    /// Fraction of files held out into a separate validation file
+    pub val_ratio: Option<f64>,
+    /// Seed for the shuffle that picks validation files and for FIM spans
+    pub seed: u64,
+    /// `--tokenizer`: a Hugging Face hub id, a local `tokenizer.json`, or `cl100k`/`o200k`
+    pub tokenizer: String,
+    /// Record chunks over these limits in each sample's `flags`
+    pub chunk_flags: Option<ChunkThresholds>,
+    /// Drop exact and near-duplicate files at this similarity (`--dedup`)
+    pub dedup: Option<f64>,
+}
+
+/// Write prompt/completion samples, streaming them to disk as they are produced.
+///
+/// With `val_ratio`, files are shuffled deterministically by `seed` and split into
+/// `<name>.train.<ext>` and `<name>.val.<ext>`. Returns the paths written.
+pub fn produce_training_json(
+    files: &[FileEntry],
+    out_path: &str,
+    opts: &TrainingOptions,
+) -> Result<Vec<String>> {
     // Validate split ratio
-    if split_ratio <= 0.0 || split_ratio >= 1.0 {
+    if opts.split_ratio <= 0.0 || opts.split_ratio >= 1.0 {
         return Err(anyhow::anyhow!("Split ratio must be between 0 and 1"));
     }
+    let (middle_min, middle_max) = opts.fim_middle;
+    if middle_min == 0 || middle_min > middle_max {
+        return Err(anyhow::anyhow!(
+            "FIM middle span must satisfy 0 < min <= max"
+        ));
+    }
+    if let Some(val_ratio) = opts.val_ratio {
+        if val_ratio <= 0.0 || val_ratio >= 1.0 {
+            return Err(anyhow::anyhow!("Validation ratio must be between 0 and 1"));
+        }
+    }
 
     // Sort files by dependency
-    let sorted_files = sort_files_by_dependency(files)?;
-    let bpe = cl100k_base()?;
+    let mut sorted_files = sort_files_by_dependency(files)?;
+    if let Some(threshold) = opts.dedup {
+        let (kept, removed) = dedup_files(&sorted_files, threshold);
+        if !removed.is_empty() {
+            eprintln!(
+                "Dedup: dropped {} of {} file(s) from training data:",
+                removed.len(),
+                sorted_files.len()
+            );
+            for r in &removed {
+                eprintln!(
+                    "  {} (duplicate of {}, similarity {:.2})",
+                    r.path, r.duplicate_of, r.similarity
+                );
+            }
+        }
+        sorted_files = kept;
+    }
+    let bpe = SampleTokenizer::load(&opts.tokenizer)?;
+    let mut rng = SplitMix64(opts.seed);
+
+    let Some(val_ratio) = opts.val_ratio else {
+        let mut sink = SampleSink::create(out_path, opts.format)?;
+        for file in &sorted_files {
+            for sample in make_samples(&bpe, file, opts, &mut rng) {
+                sink.push(&sample)?;
+            }
+        }
+        sink.finish()?;
+        return Ok(vec![out_path.to_string()]);
+    };
+
+    let mut order: Vec<usize> = (0..sorted_files.len()).collect();
+    shuffle(&mut order, opts.seed);
+    let val_count = (sorted_files.len() as f64 * val_ratio).round() as usize;
+    let mut is_val = vec![false; sorted_files.len()];
+    for &i in &order[..val_count] {
+        is_val[i] = true;
+    }
+
+    let train_path = split_file_name(out_path, "train");
+    let val_path = split_file_name(out_path, "val");
+    let mut train = SampleSink::create(&train_path, opts.format)?;
+    let mut val = SampleSink::create(&val_path, opts.format)?;
+    // Keep dependency order within each split; the shuffle only decides membership.
+    for (file, held_out) in sorted_files.iter().zip(is_val) {
+        for sample in make_samples(&bpe, file, opts, &mut rng) {
+            if held_out {
+                val.push(&sample)?;
+            } else {
+                train.push(&sample)?;
+            }
+        }
+    }
+    train.finish()?;
+    val.finish()?;
+    Ok(vec![train_path, val_path])
+}
+
+fn make_samples(
+    bpe: &SampleTokenizer,
+    file: &FileEntry,
+    opts: &TrainingOptions,
+    rng: &mut SplitMix64,
+) -> Vec<Sample> {
+    let tokens = bpe.encode(&file.content);
+    let tokens = &tokens[..];
+    let total = tokens.len();
+    if total < 2 {
+        return vec![]; // Skip files that are too small
+    }
+    let decode = |ids: &[u32]| bpe.decode(ids);
+    let tokenizer = opts.tokenizer.clone();
+    let flags = opts
+        .chunk_flags
+        .as_ref()
+        .map(|limits| {
+            let lang = language_from_path(Path::new(&file.rel_path));
+            flag_chunks(&file.content, lang, limits)
+        })
+        .unwrap_or_default();
+    match opts.style {
+        TrainStyle::Completion => {
+            let prompt_end = (total as f64 * opts.split_ratio).ceil() as usize;
+            let prompt_ids = &tokens[..prompt_end];
+            let completion_ids = &tokens[prompt_end..];
+            vec![Sample::Completion(TrainingSample {
+                prompt: decode(prompt_ids),
+                completion: decode(completion_ids),
+                prompt_tokens: prompt_ids.len(),
+                completion_tokens: completion_ids.len(),
+                tokenizer,
+                chunk: None,
+                flags,
+            })]
+        }
+        TrainStyle::Fim => {
+            let (start, end) = fim_span(total, opts.fim_middle, rng);
+            vec![Sample::Fim(FimSample {
+                prefix: decode(&tokens[..start]),
+                middle: decode(&tokens[start..end]),
+                suffix: decode(&tokens[end..]),
+                prefix_tokens: start,
+                middle_tokens: end - start,
+                suffix_tokens: total - end,
+                tokenizer,
+                flags,
+            })]
+        }
+        TrainStyle::Chunks => {
+            let lang = language_from_path(Path::new(&file.rel_path));
+            chunk_file(&file.content, lang)
+                .iter()
+                .filter_map(|chunk| {
+                    let mut sample = chunk_sample(bpe, &file.content, chunk, opts.context_tokens)?;
+                    sample.tokenizer = tokenizer.clone();
+                    sample.flags = flags
+                        .iter()
+                        .filter(|f| f.line == chunk.start_line)
+                        .cloned()
+                        .collect();
+                    Some(Sample::Completion(sample))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Prompt: up to `context_tokens` of the code before `chunk`, then its signature.
+/// Completion: the body. `None` for chunks without a separate body (`struct A {}`).
+fn chunk_sample(
+    bpe: &SampleTokenizer,
+    content: &str,
+    chunk: &Chunk,
+    context_tokens: usize,
+) -> Option<TrainingSample> {
+    if chunk.body_start > chunk.end_line {
+        return None;
+    }
+    let lines: Vec<&str> = content.lines().collect();
+    let before = lines[..chunk.start_line - 1].join("\n");
+    let signature = lines[chunk.start_line - 1..chunk.body_start - 1].join("\n");
+    let body = lines[chunk.body_start - 1..chunk.end_line].join("\n");
 
-    let mut samples = Vec::new();
+    let before_ids = bpe.encode(&before);
+    let context = &before_ids[before_ids.len().saturating_sub(context_tokens)..];
+    let prompt = if context.is_empty() {
+        signature
+    } else {
+        format!("{}\n{}", bpe.decode(context), signature)
+    };
+    let prompt_tokens = bpe.encode(&prompt).len();
+    let completion_tokens = bpe.encode(&body).len();
+    if completion_tokens < 2 {
+        return None;
+    }
+    Some(TrainingSample {
+        prompt,
+        completion: body,
+        prompt_tokens,
+        completion_tokens,
+        tokenizer: String::new(),
+        chunk: chunk.name.clone(),
+        flags: vec![],
+    })
+}
 
-    for file in &sorted_files {
-        let encoding = bpe.encode(file.content.as_str(), true).unwrap();
+/// Pick the middle span `start..end` of a `total`-token file, never the whole file.
+fn fim_span(total: usize, (min, max): (usize, usize), rng: &mut SplitMix64) -> (usize, usize) {
+    let longest = total - 1;
+    let len = rng.range(min.min(longest), max.min(longest));
+    let start = rng.range(0, total - len);
+    (start, start + len)
+}
+
+/// Writes samples one at a time, so no format needs the whole dataset in memory.
+struct SampleSink {
+    format: TrainFormat,
+    writer: OutputFile,
+    count: usize,
+}
 
-        let tokens = encoding.get_ids();
-        let total = tokens.len();
-        if total < 2 {
-            continue; // Skip files that are too small
+impl SampleSink {
+    fn create(path: &str, format: TrainFormat) -> Result<Self> {
+        let mut writer = OutputFile::create(path)?;
+        if format == TrainFormat::Json {
+            writer.write_all(b"[")?;
         }
-        let prompt_end = (total as f64 * split_ratio).ceil() as usize;
-        let prompt_ids = &tokens[..prompt_end];
-        let completion_ids = &tokens[prompt_end..];
-        let prompt_str = bpe.decode(prompt_ids, true).unwrap_or_default();
-        let completion_str = bpe.decode(completion_ids, true).unwrap_or_default();
-        let sample = TrainingSample {
-            prompt: prompt_str,
-            completion: completion_str,
-            prompt_tokens: prompt_ids.len(),
-            completion_tokens: completion_ids.len(),
-            tokenizer: "deepseek-ai/DeepSeek-R1-Distill-Llama-70B".to_string(),
+        Ok(SampleSink {
+            format,
+            writer,
+            count: 0,
+        })
+    }
+
+    fn push(&mut self, sample: &Sample) -> Result<()> {
+        let sample = Versioned {
+            schema_version: SCHEMA_VERSION,
+            sample,
         };
-        samples.push(sample);
+        match self.format {
+            TrainFormat::Json => {
+                self.writer
+                    .write_all(if self.count == 0 { b"\n" } else { b",\n" })?;
+                serde_json::to_writer_pretty(&mut self.writer, &sample)?;
+            }
+            TrainFormat::Jsonl => {
+                serde_json::to_writer(&mut self.writer, &sample)?;
+                self.writer.write_all(b"\n")?;
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        if self.format == TrainFormat::Json {
+            self.writer.write_all(b"\n]\n")?;
+        }
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+/// `data.jsonl` + `"val"` -> `data.val.jsonl`, and `data.jsonl.gz` -> `data.val.jsonl.gz`
+fn split_file_name(path: &str, split: &str) -> String {
+    let (path, compressed) = split_suffix(path);
+    let file_start = path.rfind(['/', '\\']).map_or(0, |i| i + 1);
+    match path[file_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = file_start + dot;
+            format!("{}.{}{}{}", &path[..dot], split, &path[dot..], compressed)
+        }
+        _ => format!("{}.{}{}", path, split, compressed),
     }
+}
+
+/// splitmix64: tiny and fully specified, so a seed gives the same output on every
+/// platform and release.
+struct SplitMix64(u64);
 
-    let file = File::create(out_path)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &samples)?;
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
 
+    /// Uniform-enough value in `lo..=hi`.
+    fn range(&mut self, lo: usize, hi: usize) -> usize {
+        lo + (self.next() % (hi - lo + 1) as u64) as usize
+    }
+}
+
+/// Seeded Fisher-Yates.
+fn shuffle(items: &mut [usize], seed: u64) {
+    let mut rng = SplitMix64(seed);
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.range(0, i));
+    }
+}
+
+/// The tokenizer behind `--tokenizer`, either a Hugging Face one or a tiktoken encoding.
+enum SampleTokenizer {
+    Hf(Box<Tokenizer>),
+    Tiktoken(CoreBPE),
+}
+
+impl SampleTokenizer {
+    /// `cl100k`/`o200k` (optionally `_base`) use the tiktoken encodings bundled with
+    /// the binary; an existing path loads a local `tokenizer.json`; anything else is
+    /// fetched from the Hugging Face hub.
+    fn load(spec: &str) -> Result<Self> {
+        match spec {
+            "cl100k" | "cl100k_base" => Ok(Self::Tiktoken(tiktoken_rs::cl100k_base()?)),
+            "o200k" | "o200k_base" => Ok(Self::Tiktoken(tiktoken_rs::o200k_base()?)),
+            _ if Path::new(spec).is_file() => Tokenizer::from_file(spec)
+                .map(|t| Self::Hf(Box::new(t)))
+                .map_err(|e| anyhow::anyhow!("Failed to load tokenizer {}: {}", spec, e)),
+            _ => Tokenizer::from_pretrained(spec, None)
+                .map(|t| Self::Hf(Box::new(t)))
+                .map_err(|e| anyhow::anyhow!("Failed to fetch tokenizer {}: {}", spec, e)),
+        }
+    }
+
+    fn encode(&self, text: &str) -> Vec<u32> {
+        match self {
+            Self::Hf(t) => t
+                .encode(text, true)
+                .map(|e| e.get_ids().to_vec())
+                .unwrap_or_default(),
+            Self::Tiktoken(bpe) => bpe.encode_ordinary(text),
+        }
+    }
+
+    fn decode(&self, ids: &[u32]) -> String {
+        match self {
+            Self::Hf(t) => t.decode(ids, true).unwrap_or_default(),
+            // Cuts can land inside a multi-byte character; keep what decodes.
+            Self::Tiktoken(bpe) => bpe
+                .decode_bytes(ids)
+                .map(|b| String::from_utf8_lossy(&b).into_owned())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[test]
+fn test_val_split_is_deterministic() {
+    assert_eq!(
+        split_file_name("out/data.jsonl", "val"),
+        "out/data.val.jsonl"
+    );
+    assert_eq!(split_file_name("data", "train"), "data.train");
+    let mut a: Vec<usize> = (0..20).collect();
+    let mut b = a.clone();
+    shuffle(&mut a, 7);
+    shuffle(&mut b, 7);
+    assert_eq!(a, b);
+    assert_ne!(a, (0..20).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_cl100k_round_trip() -> Result<()> {
+    let bpe = SampleTokenizer::load("cl100k")?;
+    let ids = bpe.encode("fn main() { println!(\"hi\"); }");
+    assert!(ids.len() > 2);
+    assert_eq!(bpe.decode(&ids), "fn main() { println!(\"hi\"); }");
     Ok(())
 }
 
-fn cl100k_base() -> anyhow::Result<Tokenizer> {
-    let tokenizer = Tokenizer::from_pretrained("deepseek-ai/DeepSeek-R1-Distill-Llama-70B", None)
-        .map_err(|e| anyhow::anyhow!("{}", e))?;
-    Ok(tokenizer)
+#[test]
+fn test_fim_span_bounds() {
+    let mut rng = SplitMix64(1);
+    for total in [2, 10, 500] {
+        let (start, end) = fim_span(total, (16, 64), &mut rng);
+        assert!(start < end && end <= total);
+        assert!(end - start < total);
+        assert!(end - start <= 64);
+    }
 }
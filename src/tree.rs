@@ -0,0 +1,121 @@
+use crate::remote::is_remote_url;
+use crate::sanitize::escape_control;
+use crate::types::FileEntry;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Directory tree of one input, built from the files the collection walk actually kept.
+///
+/// Rendering from the collected paths (instead of walking the disk a second time) keeps
+/// the tree and the code sections in lockstep: gitignore, `--exclude`, `--include`,
+/// size limits, `--diff-since` and `--owner` all apply to both.
+pub struct Tree {
+    root: String,
+    paths: Vec<String>,
+}
+
+impl Tree {
+    pub fn new(input: &Path, files: &[FileEntry]) -> Self {
+        Tree {
+            root: input_name(input),
+            paths: files.iter().map(|f| f.rel_path.clone()).collect(),
+        }
+    }
+
+    /// Replace the root name and every path with `f` of it.
+    pub fn map_paths(&mut self, f: impl Fn(&str) -> String) {
+        self.root = f(&self.root);
+        for path in &mut self.paths {
+            *path = f(path);
+        }
+    }
+
+    /// Write the tree as a nested list, depth-first with siblings sorted by name.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "- {}/", escape_control(&self.root))?;
+        // Sort by path components, not raw strings: `/` sorts after `.`, which would put
+        // `src/a.rs` between `src/` and its subdirectory `src/a/`.
+        let mut entries: BTreeSet<(Vec<&str>, bool)> = BTreeSet::new();
+        for path in &self.paths {
+            let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+            for depth in 1..parts.len() {
+                entries.insert((parts[..depth].to_vec(), true));
+            }
+            entries.insert((parts, false));
+        }
+        for (parts, is_dir) in entries {
+            let indent = "  ".repeat(parts.len());
+            let rel_path = escape_control(&parts.join("/"));
+            if is_dir {
+                writeln!(writer, "{}- {}/", indent, rel_path)?;
+            } else {
+                writeln!(writer, "{}- {}", indent, rel_path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Short name for an input: the directory name, archive file name or URL's last segment.
+pub fn input_name(input: &Path) -> String {
+    let text = input.to_string_lossy();
+    if is_remote_url(&text) {
+        let trimmed = text.trim_end_matches('/').trim_end_matches(".git");
+        return trimmed
+            .rsplit(['/', ':'])
+            .next()
+            .unwrap_or(trimmed)
+            .to_string();
+    }
+    let path = input.canonicalize().unwrap_or_else(|_| input.to_path_buf());
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| text.into_owned())
+}
+
+/// The prefix each input's paths get in the output: nothing for a single input, otherwise
+/// `name/`, numbered (`src-2/`) when two inputs share a name, so paths never collide.
+pub fn input_prefixes(inputs: &[PathBuf]) -> Vec<String> {
+    if inputs.len() < 2 {
+        return vec![String::new(); inputs.len()];
+    }
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    inputs
+        .iter()
+        .map(|input| {
+            let name = input_name(input);
+            let count = seen.entry(name.clone()).or_default();
+            *count += 1;
+            if *count == 1 {
+                format!("{}/", name)
+            } else {
+                format!("{}-{}/", name, count)
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_tree_lists_only_collected_files() -> io::Result<()> {
+    let tree = Tree {
+        root: "proj".to_string(),
+        paths: ["src/main.rs", "build.rs", "src/a.rs", "src/a/x.rs"]
+            .map(String::from)
+            .to_vec(),
+    };
+    let mut out = Vec::new();
+    tree.write(&mut out)?;
+    assert_eq!(
+        String::from_utf8_lossy(&out),
+        "- proj/\n  - build.rs\n  - src/\n    - src/a/\n      - src/a/x.rs\n    - src/a.rs\n    - src/main.rs\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_input_prefixes_are_unique() {
+    let inputs = ["a/src", "b/src", "https://github.com/org/tool.git"].map(PathBuf::from);
+    assert_eq!(input_prefixes(&inputs), ["src/", "src-2/", "tool/"]);
+    assert_eq!(input_prefixes(&inputs[..1]), [""]);
+}
@@ -1,6 +1,164 @@
+use crate::chunks::ChunkThresholds;
+use crate::error::R2mdError;
+use crate::filekind::FileKindFilter;
+use crate::hooks::PreprocessHook;
+use crate::i18n::Labels;
+use crate::patterns::PathPatterns;
+use crate::remote::HttpOptions;
+use crate::sanitize::FenceStyle;
+use crate::tokens::Truncation;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
 /// This is what your `r2md` logic uses for final output
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct FileEntry {
     pub rel_path: String,
     pub content: String,
+    /// Where the content's lines are in the file on disk, once parts of it were cut out
+    pub lines: LineMap,
+}
+
+/// The original line number of each line of a file's content after lines were cut out of
+/// it (`--include path:START-END`, `--focus`, `--per-file-max-tokens`, a stripped license
+/// header), so `--line-numbers` and `--flag-chunks` still point at the file on disk.
+/// `None` marks an omission marker. Empty while the content is the whole file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LineMap(Vec<Option<usize>>);
+
+impl LineMap {
+    pub fn is_whole(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The original number of the content's 1-based `line`, `None` for a marker.
+    pub fn original(&self, line: usize) -> Option<usize> {
+        if self.is_whole() {
+            Some(line)
+        } else {
+            self.0.get(line - 1).copied().flatten()
+        }
+    }
+
+    /// This map after the content was cut again: `kept` gives, for each new line, the
+    /// 1-based line of the current content it was, `None` for a new marker.
+    pub fn cut(&self, kept: impl IntoIterator<Item = Option<usize>>) -> LineMap {
+        LineMap(
+            kept.into_iter()
+                .map(|line| line.and_then(|line| self.original(line)))
+                .collect(),
+        )
+    }
+}
+
+/// A path that could not be walked or read (permission denied, stale NFS handle, ...)
+#[derive(Debug, Clone)]
+pub struct InaccessiblePath {
+    pub path: String,
+    pub reason: String,
+}
+
+/// A Git LFS pointer found in place of the real file
+#[derive(Debug, Clone)]
+pub struct LfsAsset {
+    pub path: String,
+    /// e.g. `sha256:4d7a21...`
+    pub oid: String,
+    /// Size of the real object in bytes
+    pub size: u64,
+}
+
+/// Filters and switches that decide which files a collection pass picks up
+#[derive(Debug, Clone, Default)]
+pub struct CollectOptions {
+    /// `ignore_patterns` from `r2md.yml`
+    pub user_ignores: PathPatterns,
+    /// Test and generated files to skip (`--no-tests`, `--no-generated`)
+    pub kind_filter: FileKindFilter,
+    /// `--exclude` paths (relative to the working directory) and patterns
+    pub excludes: Vec<PathBuf>,
+    /// `--include` and `--only` glob patterns
+    pub includes: Vec<String>,
+    /// Leave out every file no include pattern matches (`--only`)
+    pub only_included: bool,
+    /// Also collect docs/config files (`--include-docs`)
+    pub include_docs: bool,
+    /// Extensions that make up the docs/config category
+    pub doc_extensions: Vec<String>,
+    /// Extra extensions to treat as code (`--auto-extensions`)
+    pub extra_extensions: Vec<String>,
+    /// Only collect files of these languages (`--lang`); empty means all
+    pub languages: Vec<String>,
+    /// Annotate chunks over these limits (`--flag-chunks`)
+    pub chunk_flags: Option<ChunkThresholds>,
+    /// Section titles and phrases for the generated document (`--lang-ui`)
+    pub labels: Labels,
+    /// Per-file `preprocess:` hooks from `r2md.yml`
+    pub preprocess: Vec<PreprocessHook>,
+    /// Prefix code lines with their line numbers (`--line-numbers`)
+    pub line_numbers: bool,
+    /// Leave markup and styles out of Vue and Svelte components (`--script-only`)
+    pub script_only: bool,
+    /// Backtick or tilde code fences (`--fence-style`, `fence_style:` in `r2md.yml`)
+    pub fence_style: FenceStyle,
+    /// Extra Markdown shown under a file's heading, keyed by relative path (`--with-authors`)
+    pub file_notes: HashMap<String, String>,
+    /// Files whose content repeats an earlier file's, keyed by relative path, with the
+    /// note shown instead of their content (`--dedup-files`)
+    pub duplicates: HashMap<String, String>,
+    /// Input name to head each input's files with, keyed by the path of its first file;
+    /// only set when there are several inputs
+    pub input_headings: HashMap<String, String>,
+    /// Title of the workspace crate or submodule to head its files with, keyed by the path
+    /// of its first file
+    pub member_headings: HashMap<String, String>,
+    /// Most requests per minute to any one host when downloading (`--rate-limit`)
+    pub rate_limit: Option<u32>,
+    /// Timeout, retries and proxy for downloads (`--http-timeout`, `--retries`, `--proxy`)
+    pub http: HttpOptions,
+    /// Skip non-UTF-8 files instead of transcoding them (`--strict-utf8`)
+    pub strict_utf8: bool,
+    /// Checked-out submodules to walk into even where a directory on the way is skipped by
+    /// default, like `vendor` (`--with-submodules`)
+    pub submodule_dirs: Vec<String>,
+    /// Follow symlinks out of the input directory (`--follow-symlinks`)
+    pub follow_symlinks: bool,
+    /// Strip comments, docstrings and insignificant whitespace before output (`--minify`)
+    pub minify: bool,
+    /// Trim files over a token limit instead of skipping large ones (`--per-file-max-tokens`)
+    pub truncate: Option<Truncation>,
+    /// Only collect files at most this many directories deep, 1 being the input's top level
+    /// (`--max-depth`)
+    pub max_depth: Option<usize>,
+    /// Stop collecting an input after this many files (`--max-files`)
+    pub max_files: Option<usize>,
+}
+
+/// What a collection pass left out, for warnings and the end-of-run summary
+#[derive(Debug, Default)]
+pub struct CollectReport {
+    /// Paths that could not be walked or read
+    pub inaccessible: Vec<InaccessiblePath>,
+    /// Files seen but filtered out (extension, ignore patterns, size, binary content)
+    pub skipped: usize,
+    /// Every per-path failure (inaccessible, unreadable, too large, not text), for `--report`
+    pub errors: Vec<R2mdError>,
+    /// LFS pointers, listed as assets instead of being exported as source
+    pub lfs_assets: Vec<LfsAsset>,
+    /// Files left out to stay within `--max-output-bytes`/`--max-output-tokens`
+    pub over_budget: Vec<String>,
+    /// An input had more files than `--max-files`, and collection stopped there
+    pub file_limit_hit: bool,
+}
+
+impl CollectReport {
+    /// Record a failure, mirroring access problems into `inaccessible` for the Markdown section.
+    pub fn record(&mut self, error: R2mdError) {
+        match error.as_inaccessible() {
+            Some(path) => self.inaccessible.push(path),
+            None => self.skipped += 1,
+        }
+        self.errors.push(error);
+    }
 }
@@ -0,0 +1,334 @@
+//! `--update FILE`: bring an existing export up to date by rewriting only the file
+//! sections that changed, so notes added to the document by hand survive.
+//!
+//! The manifest written with the export says how long each section is; the section
+//! itself is found again by its `### ` heading, so text added between sections (which
+//! moves every offset after it) doesn't matter. Edits inside a section do: that section
+//! can't be told apart from the text around it any more.
+//!
+//! The parts that list or count the files are rendered again too: the directory trees
+//! (found by their root line), the `--metadata` header at the top and the footer at the
+//! end. Other sections, such as the inaccessible paths, keep their old content.
+
+use crate::compress;
+use crate::manifest::{read_manifest_spans, SectionSpan};
+use crate::render_file_section;
+use crate::sanitize::inline_code;
+use crate::types::{CollectOptions, FileEntry};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// What `--update` did to the document's file sections.
+#[derive(Debug, Default, PartialEq)]
+pub struct UpdateCounts {
+    pub updated: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// The parts of the document around the file sections, as they render now.
+pub struct Frame<'a> {
+    /// `--metadata` block, empty without it
+    pub header: &'a str,
+    /// One fenced directory tree per input
+    pub trees: &'a [String],
+    /// Closing line, empty with `--no-footer`
+    pub footer: &'a str,
+}
+
+/// Rewrite the sections of `doc_path` that differ from `files` as they render now, drop
+/// those of files that are gone and add new files after the file that precedes them,
+/// then refresh the trees, header and footer from `frame`. Returns the counts and where
+/// every section now is, for the new manifest.
+pub fn update_markdown(
+    doc_path: &str,
+    manifest_path: &str,
+    frame: &Frame,
+    files: &[FileEntry],
+    opts: &CollectOptions,
+) -> Result<(UpdateCounts, Vec<SectionSpan>), Box<dyn Error>> {
+    let doc = compress::read_to_string(doc_path)
+        .map_err(|e| format!("Cannot read {} to update: {}", doc_path, e))?;
+    let spans = read_manifest_spans(manifest_path, doc_path).map_err(|e| {
+        format!(
+            "Cannot read manifest {} (write one with --manifest first): {}",
+            manifest_path, e
+        )
+    })?;
+    let doc = replace_trees(&replace_header(&doc, frame.header), frame.trees, &spans)?;
+    let (text, counts, spans) = patch_sections(&doc, &spans, files, opts)?;
+    let sections_end = spans.last().map_or(0, |span| span.offset + span.length);
+    let text = replace_footer(&text, frame.footer, sections_end, &opts.labels.footer);
+    compress::write_file(doc_path, text)?;
+    Ok((counts, spans))
+}
+
+/// `doc` with the `--metadata` block it starts with replaced by `header`, or `header`
+/// added on top if it starts with none. An empty `header` leaves `doc` as it is.
+fn replace_header(doc: &str, header: &str) -> String {
+    if header.is_empty() {
+        return doc.to_string();
+    }
+    let old_end = [("---\n", "\n---\n"), ("<!-- r2md\n", "\n-->\n")]
+        .iter()
+        .filter(|(open, _)| doc.starts_with(open))
+        .find_map(|(_, close)| doc.find(close).map(|at| at + close.len()))
+        .map_or(0, |end| end + usize::from(doc[end..].starts_with('\n')));
+    format!("{}{}", header, &doc[old_end..])
+}
+
+/// `doc` with each directory tree replaced by the one in `trees` with the same root line
+/// (`- repo/`). Only the text before the first file section is searched.
+fn replace_trees(doc: &str, trees: &[String], spans: &[SectionSpan]) -> Result<String, String> {
+    let mut doc = doc.to_string();
+    for tree in trees {
+        let root = tree.lines().nth(1).unwrap_or_default();
+        let limit = spans
+            .first()
+            .and_then(|span| doc.find(&format!("### {}", inline_code(&span.rel_path))))
+            .unwrap_or(doc.len());
+        let starts: Vec<usize> = line_starts(&doc, 0).take_while(|&at| at < limit).collect();
+        // The fence line right before the root line, and the same fence closing the tree.
+        let found = starts.windows(2).find_map(|pair| {
+            let (fence_at, root_at) = (pair[0], pair[1]);
+            let fence = &doc[fence_at..root_at - 1];
+            let is_fence = fence.starts_with("```") || fence.starts_with("~~~");
+            if !is_fence || doc[root_at..].lines().next() != Some(root) {
+                return None;
+            }
+            let close = format!("\n{}\n", fence);
+            let end = root_at + doc[root_at..].find(&close)? + close.len();
+            Some((fence_at, end + usize::from(doc[end..].starts_with('\n'))))
+        });
+        let (start, end) = found.ok_or_else(|| {
+            format!(
+                "Directory tree for {} not found in the document; re-export without --update",
+                root.trim_start_matches("- ")
+            )
+        })?;
+        doc.replace_range(start..end, tree);
+    }
+    Ok(doc)
+}
+
+/// `doc` with its footer replaced by `footer`: the last `---` rule after `sections_end`
+/// followed by a line that starts like the footer label does.
+fn replace_footer(doc: &str, footer: &str, sections_end: usize, label: &str) -> String {
+    let rule = "\n---\n\n";
+    let lead = label.split('{').next().unwrap_or_default();
+    let found = doc[sections_end..].rfind(rule).and_then(|at| {
+        let text_at = sections_end + at + rule.len();
+        let line_end = doc[text_at..]
+            .find('\n')
+            .map_or(doc.len(), |i| text_at + i + 1);
+        doc[text_at..]
+            .starts_with(lead)
+            .then_some((sections_end + at, line_end))
+    });
+    match found {
+        Some((start, end)) => format!("{}{}{}", &doc[..start], footer, &doc[end..]),
+        None => doc.to_string(),
+    }
+}
+
+fn patch_sections(
+    doc: &str,
+    old_spans: &[SectionSpan],
+    files: &[FileEntry],
+    opts: &CollectOptions,
+) -> Result<(String, UpdateCounts, Vec<SectionSpan>), String> {
+    let located = locate_sections(doc, old_spans)?;
+    if located.is_empty() {
+        return Err("No file sections to update; the manifest lists none for this file".into());
+    }
+
+    // New files go after the section of the file before them, or before the first section.
+    let old_index: HashMap<&str, usize> = located
+        .iter()
+        .enumerate()
+        .map(|(i, (_, _, path))| (*path, i))
+        .collect();
+    let mut inserts: Vec<Vec<&FileEntry>> = vec![Vec::new(); located.len() + 1];
+    let mut current: HashMap<&str, &FileEntry> = HashMap::new();
+    let mut slot = 0;
+    for file in files {
+        match old_index.get(file.rel_path.as_str()) {
+            Some(&i) => slot = i + 1,
+            None => inserts[slot].push(file),
+        }
+        current.insert(&file.rel_path, file);
+    }
+
+    let mut out = String::with_capacity(doc.len());
+    let mut spans = Vec::with_capacity(files.len());
+    let mut counts = UpdateCounts::default();
+    let mut push = |out: &mut String, file: &FileEntry| {
+        let section =
+            render_file_section(&file.rel_path, &file.content, None, 1, &file.lines, opts);
+        spans.push(SectionSpan {
+            rel_path: file.rel_path.clone(),
+            offset: out.len(),
+            length: section.len(),
+        });
+        out.push_str(&section);
+    };
+    let mut cursor = 0;
+    for (i, (start, end, path)) in located.iter().enumerate() {
+        out.push_str(&doc[cursor..*start]);
+        if i == 0 {
+            for file in &inserts[0] {
+                push(&mut out, file);
+                counts.added += 1;
+            }
+        }
+        match current.get(path) {
+            Some(file) => {
+                let before = out.len();
+                push(&mut out, file);
+                if out[before..] == doc[*start..*end] {
+                    counts.unchanged += 1;
+                } else {
+                    counts.updated += 1;
+                }
+            }
+            None => counts.removed += 1,
+        }
+        for file in &inserts[i + 1] {
+            push(&mut out, file);
+            counts.added += 1;
+        }
+        cursor = *end;
+    }
+    out.push_str(&doc[cursor..]);
+    Ok((out, counts, spans))
+}
+
+/// `(start, end, path)` of each section in `doc`, found by heading from where the one
+/// before it ended.
+fn locate_sections<'a>(
+    doc: &str,
+    spans: &'a [SectionSpan],
+) -> Result<Vec<(usize, usize, &'a str)>, String> {
+    let mut located = Vec::with_capacity(spans.len());
+    let mut cursor = 0;
+    for span in spans {
+        let heading = format!("### {}", inline_code(&span.rel_path));
+        let start = line_starts(doc, cursor)
+            .find(|&at| {
+                let rest = &doc[at..];
+                rest.strip_prefix(&heading)
+                    .is_some_and(|after| after.starts_with('\n') || after.starts_with(" ("))
+            })
+            .ok_or_else(|| format!("Section for {} not found in the document", span.rel_path))?;
+        let end = start + span.length;
+        if end > doc.len() || !doc.is_char_boundary(end) || !doc[..end].ends_with('\n') {
+            return Err(format!(
+                "Section for {} was edited; re-export without --update",
+                span.rel_path
+            ));
+        }
+        located.push((start, end, span.rel_path.as_str()));
+        cursor = end;
+    }
+    Ok(located)
+}
+
+/// Byte offsets of the lines of `doc` starting at or after `from`, which is a line start.
+fn line_starts(doc: &str, from: usize) -> impl Iterator<Item = usize> + '_ {
+    std::iter::once(from).chain(
+        doc[from..]
+            .match_indices('\n')
+            .map(move |(i, _)| from + i + 1)
+            .filter(move |&at| at < doc.len()),
+    )
+}
+
+#[test]
+fn test_patch_keeps_annotations() -> Result<(), Box<dyn Error>> {
+    let opts = CollectOptions::default();
+    let file = |rel_path: &str, content: &str| FileEntry {
+        rel_path: rel_path.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    };
+    let render =
+        |f: &FileEntry| render_file_section(&f.rel_path, &f.content, None, 1, &f.lines, &opts);
+    let old = [
+        file("a.rs", "fn a() {}\n"),
+        file("b.rs", "fn b() {}\n"),
+        file("c.rs", "fn c() {}\n"),
+    ];
+    let mut doc = String::from("# Export\n\n");
+    let mut spans = Vec::new();
+    for f in &old {
+        let section = render(f);
+        spans.push(SectionSpan {
+            rel_path: f.rel_path.clone(),
+            offset: doc.len(),
+            length: section.len(),
+        });
+        doc.push_str(&section);
+    }
+    // A note added by hand between sections.
+    let note = "> Reviewed: b.rs is hot.\n\n";
+    let at = spans[1].offset + spans[1].length;
+    doc.insert_str(at, note);
+
+    let new = [
+        file("a.rs", "fn a() {}\n"),
+        file("b.rs", "fn b() { 2 }\n"),
+        file("b2.rs", "fn b2() {}\n"),
+    ];
+    let (text, counts, new_spans) = patch_sections(&doc, &spans, &new, &opts)?;
+    assert_eq!(
+        counts,
+        UpdateCounts {
+            updated: 1,
+            added: 1,
+            removed: 1,
+            unchanged: 1
+        }
+    );
+    assert_eq!(
+        text,
+        format!(
+            "# Export\n\n{}{}{}{}",
+            render(&new[0]),
+            render(&new[1]),
+            render(&new[2]),
+            note
+        )
+    );
+    for span in &new_spans {
+        assert!(text[span.offset..].starts_with(&format!("### `{}`", span.rel_path)));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_update_refreshes_tree_header_and_footer() -> Result<(), String> {
+    let spans = [SectionSpan {
+        rel_path: "a.rs".to_string(),
+        offset: 0,
+        length: 0,
+    }];
+    let doc = "---\nfiles: 2\n---\n\n> Intro.\n\n```\n- proj/\n  - Makefile\n  - a.rs\n```\n\n## Code\n\n### `a.rs`\n\nbody\n\n> Note.\n\n---\n\nGenerated by r2md 0.4.3. Rerun with: `r2md proj`\n";
+    let doc = replace_header(doc, "---\nfiles: 1\n---\n\n");
+    let tree = "```\n- proj/\n  - a.rs\n```\n\n".to_string();
+    let doc = replace_trees(&doc, &[tree], &spans)?;
+    let end = doc.find("> Note.").unwrap();
+    let doc = replace_footer(
+        &doc,
+        "\n---\n\nGenerated by r2md 0.4.4. Rerun with: `r2md proj --update out.md`\n",
+        end,
+        "Generated by r2md {version}. Rerun with: {command}",
+    );
+    assert_eq!(
+        doc,
+        "---\nfiles: 1\n---\n\n> Intro.\n\n```\n- proj/\n  - a.rs\n```\n\n## Code\n\n### `a.rs`\n\nbody\n\n> Note.\n\n---\n\nGenerated by r2md 0.4.4. Rerun with: `r2md proj --update out.md`\n"
+    );
+    let other = "```\n- other/\n```\n\n".to_string();
+    assert!(replace_trees(&doc, &[other], &spans).is_err());
+    Ok(())
+}
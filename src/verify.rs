@@ -0,0 +1,157 @@
+use crate::tree::Tree;
+use crate::types::{CollectOptions, CollectReport};
+use crate::{collect_files_parallel, write_markdown_document};
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Outcome of checking one fixture against its golden file.
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Pass,
+    Updated,
+    Missing,
+    Mismatch {
+        line: usize,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// Regenerate every fixture under `golden` and compare it with its `expected.md`.
+///
+/// A fixture is any subdirectory containing an `input/` tree. With `update`, the
+/// expected files are rewritten instead (use after an intended output change).
+pub fn run_verify(
+    golden: &Path,
+    opts: &CollectOptions,
+    update: bool,
+) -> Result<(), Box<dyn Error>> {
+    let fixtures = find_fixtures(golden)?;
+    if fixtures.is_empty() {
+        return Err(format!(
+            "No fixtures (subdirectories with input/) in {}",
+            golden.display()
+        )
+        .into());
+    }
+    let mut stdout = io::stdout().lock();
+    let mut failures = 0;
+    for fixture in &fixtures {
+        let name = fixture.file_name().unwrap_or_default().to_string_lossy();
+        match verify_fixture(fixture, opts, update)? {
+            Verdict::Pass => writeln!(stdout, "PASS    {}", name)?,
+            Verdict::Updated => writeln!(stdout, "UPDATED {}", name)?,
+            Verdict::Missing => {
+                failures += 1;
+                writeln!(
+                    stdout,
+                    "MISSING {} (no expected.md; run with --update to create it)",
+                    name
+                )?;
+            }
+            Verdict::Mismatch {
+                line,
+                expected,
+                actual,
+            } => {
+                failures += 1;
+                writeln!(
+                    stdout,
+                    "FAIL    {} (first difference at line {})",
+                    name, line
+                )?;
+                writeln!(stdout, "  - expected: {}", expected)?;
+                writeln!(stdout, "  + actual:   {}", actual)?;
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(format!(
+            "{} of {} fixture(s) differ from golden output",
+            failures,
+            fixtures.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn find_fixtures(golden: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(golden)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.join("input").is_dir())
+        .collect();
+    fixtures.sort();
+    Ok(fixtures)
+}
+
+/// Render a fixture's `input/` with the same collection pass and document writer as an
+/// export and compare it. The rest of the export pipeline is skipped: files stay in
+/// collection order instead of going through `--sort`, `--per-file-max-tokens` doesn't
+/// trim them, and there is no footer, `--metadata` header, prepended or appended text, or
+/// output budget.
+pub fn verify_fixture(
+    fixture: &Path,
+    opts: &CollectOptions,
+    update: bool,
+) -> Result<Verdict, Box<dyn Error>> {
+    let input = fixture.join("input");
+    let mut report = CollectReport::default();
+    let files = collect_files_parallel(&input, opts, &mut report, None)?;
+    let mut doc = Vec::new();
+    write_markdown_document(
+        &mut doc,
+        &[Tree::new(&input, &files)],
+        opts,
+        &[],
+        &files,
+        &report,
+    )?;
+    let actual = String::from_utf8_lossy(&doc).into_owned();
+
+    let expected_path = fixture.join("expected.md");
+    if update {
+        fs::write(&expected_path, &actual)?;
+        return Ok(Verdict::Updated);
+    }
+    let expected = match fs::read_to_string(&expected_path) {
+        Ok(text) => text,
+        Err(_) => return Ok(Verdict::Missing),
+    };
+    Ok(compare(&expected, &actual))
+}
+
+fn compare(expected: &str, actual: &str) -> Verdict {
+    // Golden files may be checked out with CRLF line endings on Windows.
+    let expected = expected.replace("\r\n", "\n");
+    if expected == actual {
+        return Verdict::Pass;
+    }
+    let mut exp_lines = expected.lines();
+    let mut act_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (exp_lines.next(), act_lines.next()) {
+            (Some(e), Some(a)) if e == a => line += 1,
+            (e, a) => {
+                return Verdict::Mismatch {
+                    line,
+                    expected: e.unwrap_or("<end of file>").to_string(),
+                    actual: a.unwrap_or("<end of file>").to_string(),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_golden_fixtures() -> Result<(), Box<dyn Error>> {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("ext_tests/golden");
+    for fixture in find_fixtures(&golden)? {
+        let verdict = verify_fixture(&fixture, &CollectOptions::default(), false)?;
+        assert_eq!(verdict, Verdict::Pass, "fixture {}", fixture.display());
+    }
+    Ok(())
+}